@@ -13,7 +13,9 @@
 // limitations under the License.
 
 pub mod mqtt;
+pub mod output;
 pub mod placement;
+pub mod pubsub;
 
 pub(crate) fn error_info(err: String) {
     println!("Exception:{}", err);
@@ -14,28 +14,111 @@
 
 use std::sync::Arc;
 
-use grpc_clients::mqtt::admin::call::cluster_status;
+use common_base::error::common::CommonError;
+use grpc_clients::mqtt::admin::call::{
+    cluster_status, create_acl, create_blacklist, create_user, delete_acl, delete_blacklist,
+    delete_session, delete_user, disconnect_client, get_cluster_config, list_acl, list_blacklist,
+    list_connection, list_session, list_user, set_cluster_config,
+};
 use grpc_clients::poll::ClientPool;
-use protocol::broker_mqtt::broker_mqtt_admin::ClusterStatusRequest;
+use metadata_struct::acl::mqtt_acl::MqttAcl;
+use metadata_struct::acl::mqtt_blacklist::MqttAclBlackList;
+use metadata_struct::mqtt::session::MqttSession;
+use metadata_struct::mqtt::user::MqttUser;
+use protocol::broker_mqtt::broker_mqtt_admin::{
+    ClusterStatusRequest, CreateAclRequest, CreateBlacklistRequest, CreateUserRequest,
+    DeleteAclRequest, DeleteBlacklistRequest, DeleteSessionRequest, DeleteUserRequest,
+    DisconnectClientRequest, GetClusterConfigRequest, ListAclRequest, ListBlacklistRequest,
+    ListConnectionRequest, ListSessionRequest, ListUserRequest, SetClusterConfigRequest,
+};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
 
-use crate::{error_info, grpc_addr};
+use crate::grpc_addr;
+use crate::output::{
+    flatten_object, merge_object, print_json, print_records, print_success, OutputFormat,
+};
+use crate::pubsub::{publish, subscribe, PublishParam, SubscribeParam};
 
 #[derive(Clone)]
 pub struct MqttCliCommandParam {
     pub server: String,
-    pub action: String,
+    pub output: OutputFormat,
+    pub action: MqttActionType,
 }
 
+#[derive(Clone)]
 pub enum MqttActionType {
-    STATUS,
+    Status,
+
+    ListUser,
+    CreateUser(CreateUserRequest),
+    DeleteUser(DeleteUserRequest),
+
+    ListAcl,
+    CreateAcl(AclParam),
+    DeleteAcl(AclParam),
+
+    ListBlacklist,
+    CreateBlacklist(BlacklistParam),
+    DeleteBlacklist(DeleteBlacklistRequest),
+
+    ListSession,
+    DeleteSession(DeleteSessionRequest),
+
+    ListClient,
+    KickClient(DisconnectClientRequest),
+
+    GetConfig,
+    // JSON object merged into the current cluster config
+    SetConfig(String),
+
+    Publish(PublishParam),
+    Subscribe(SubscribeParam),
 }
 
-impl From<String> for MqttActionType {
-    fn from(s: String) -> Self {
-        match s.as_str() {
-            "status" => MqttActionType::STATUS,
-            _ => panic!("Invalid action type {}", s),
-        }
+#[derive(Clone, Debug, Default)]
+pub struct AclParam {
+    pub resource_type: String,
+    pub resource_name: String,
+    pub topic: String,
+    pub ip: String,
+    pub action: String,
+    pub permission: String,
+    pub priority: u32,
+}
+
+impl AclParam {
+    fn to_acl(&self) -> Result<MqttAcl, CommonError> {
+        Ok(MqttAcl {
+            resource_type: self.resource_type.parse()?,
+            resource_name: self.resource_name.clone(),
+            topic: self.topic.clone(),
+            ip: self.ip.clone(),
+            action: self.action.parse()?,
+            permission: self.permission.parse()?,
+            priority: self.priority,
+        })
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct BlacklistParam {
+    pub blacklist_type: String,
+    pub resource_name: String,
+    // Unix timestamp in seconds
+    pub end_time: u64,
+    pub desc: String,
+}
+
+impl BlacklistParam {
+    fn to_blacklist(&self) -> Result<MqttAclBlackList, CommonError> {
+        Ok(MqttAclBlackList {
+            blacklist_type: self.blacklist_type.parse()?,
+            resource_name: self.resource_name.clone(),
+            end_time: self.end_time,
+            desc: self.desc.clone(),
+        })
     }
 }
 
@@ -52,20 +135,99 @@ impl MqttBrokerCommand {
         MqttBrokerCommand {}
     }
 
-    pub async fn start(&self, params: MqttCliCommandParam) {
-        let action_type = MqttActionType::from(params.action.clone());
+    pub async fn start(&self, params: MqttCliCommandParam) -> Result<(), CommonError> {
         let client_poll = Arc::new(ClientPool::new(100));
-        match action_type {
-            MqttActionType::STATUS => {
-                self.status(client_poll.clone(), params.clone()).await;
+        let addrs = grpc_addr(params.server);
+        let output = params.output;
+        match params.action {
+            MqttActionType::Status => self.status(client_poll, addrs, output).await,
+
+            MqttActionType::ListUser => self.list_user(client_poll, addrs, output).await,
+            MqttActionType::CreateUser(request) => {
+                let username = request.username.clone();
+                create_user(client_poll, addrs, request).await?;
+                print_success(output, format!("Created user {}", username));
+                Ok(())
+            }
+            MqttActionType::DeleteUser(request) => {
+                let username = request.username.clone();
+                delete_user(client_poll, addrs, request).await?;
+                print_success(output, format!("Deleted user {}", username));
+                Ok(())
+            }
+
+            MqttActionType::ListAcl => self.list_acl(client_poll, addrs, output).await,
+            MqttActionType::CreateAcl(param) => {
+                let acl = param.to_acl()?.encode()?;
+                create_acl(client_poll, addrs, CreateAclRequest { acl }).await?;
+                print_success(
+                    output,
+                    format!("Created acl rule for {}", param.resource_name),
+                );
+                Ok(())
+            }
+            MqttActionType::DeleteAcl(param) => {
+                let acl = param.to_acl()?.encode()?;
+                delete_acl(client_poll, addrs, DeleteAclRequest { acl }).await?;
+                print_success(
+                    output,
+                    format!("Deleted acl rule for {}", param.resource_name),
+                );
+                Ok(())
+            }
+
+            MqttActionType::ListBlacklist => self.list_blacklist(client_poll, addrs, output).await,
+            MqttActionType::CreateBlacklist(param) => {
+                let blacklist = param.to_blacklist()?.encode()?;
+                create_blacklist(client_poll, addrs, CreateBlacklistRequest { blacklist }).await?;
+                print_success(output, format!("Blacklisted {}", param.resource_name));
+                Ok(())
             }
+            MqttActionType::DeleteBlacklist(request) => {
+                let resource_name = request.resource_name.clone();
+                delete_blacklist(client_poll, addrs, request).await?;
+                print_success(
+                    output,
+                    format!("Removed {} from the blacklist", resource_name),
+                );
+                Ok(())
+            }
+
+            MqttActionType::ListSession => self.list_session(client_poll, addrs, output).await,
+            MqttActionType::DeleteSession(request) => {
+                let client_id = request.client_id.clone();
+                delete_session(client_poll, addrs, request).await?;
+                print_success(output, format!("Deleted session {}", client_id));
+                Ok(())
+            }
+
+            MqttActionType::ListClient => self.list_client(client_poll, addrs, output).await,
+            MqttActionType::KickClient(request) => {
+                let client_id = request.client_id.clone();
+                disconnect_client(client_poll, addrs, request).await?;
+                print_success(output, format!("Disconnected client {}", client_id));
+                Ok(())
+            }
+
+            MqttActionType::GetConfig => self.get_config(client_poll, addrs, output).await,
+            MqttActionType::SetConfig(config) => {
+                self.set_config(client_poll, addrs, output, config).await
+            }
+
+            MqttActionType::Publish(param) => publish(param, output).await,
+            MqttActionType::Subscribe(param) => subscribe(param, output).await,
         }
     }
 
-    async fn status(&self, client_poll: Arc<ClientPool>, params: MqttCliCommandParam) {
-        let request = ClusterStatusRequest {};
-        match cluster_status(client_poll, grpc_addr(params.server), request).await {
-            Ok(data) => {
+    async fn status(
+        &self,
+        client_poll: Arc<ClientPool>,
+        addrs: Vec<String>,
+        output: OutputFormat,
+    ) -> Result<(), CommonError> {
+        let data = cluster_status(client_poll, addrs, ClusterStatusRequest {}).await?;
+        match output {
+            OutputFormat::Table => {
                 println!("cluster name: {}", data.cluster_name);
                 println!("node list:");
                 for node in data.nodes {
@@ -73,10 +235,253 @@ impl MqttBrokerCommand {
                 }
                 println!("MQTT broker cluster up and running")
             }
-            Err(e) => {
-                println!("MQTT broker cluster normal exception");
-                error_info(e.to_string());
+            OutputFormat::Json => print_json(&json!({
+                "cluster_name": data.cluster_name,
+                "nodes": data.nodes,
+            })),
+        }
+        Ok(())
+    }
+
+    async fn list_user(
+        &self,
+        client_poll: Arc<ClientPool>,
+        addrs: Vec<String>,
+        output: OutputFormat,
+    ) -> Result<(), CommonError> {
+        let reply = list_user(client_poll, addrs, ListUserRequest {}).await?;
+        let mut records = Vec::new();
+        for raw in reply.users {
+            // never print the stored password
+            let user = decode::<MqttUser>(&raw)?;
+            records.push(json!({
+                "username": user.username,
+                "is_superuser": user.is_superuser,
+            }));
+        }
+        print_records(output, &["username", "is_superuser"], records);
+        Ok(())
+    }
+
+    async fn list_acl(
+        &self,
+        client_poll: Arc<ClientPool>,
+        addrs: Vec<String>,
+        output: OutputFormat,
+    ) -> Result<(), CommonError> {
+        let reply = list_acl(client_poll, addrs, ListAclRequest {}).await?;
+        let records = decode_records::<MqttAcl>(&reply.acls)?;
+        print_records(
+            output,
+            &[
+                "resource_type",
+                "resource_name",
+                "topic",
+                "ip",
+                "action",
+                "permission",
+                "priority",
+            ],
+            records,
+        );
+        Ok(())
+    }
+
+    async fn list_blacklist(
+        &self,
+        client_poll: Arc<ClientPool>,
+        addrs: Vec<String>,
+        output: OutputFormat,
+    ) -> Result<(), CommonError> {
+        let reply = list_blacklist(client_poll, addrs, ListBlacklistRequest {}).await?;
+        let records = decode_records::<MqttAclBlackList>(&reply.blacklists)?;
+        print_records(
+            output,
+            &["blacklist_type", "resource_name", "end_time", "desc"],
+            records,
+        );
+        Ok(())
+    }
+
+    async fn list_session(
+        &self,
+        client_poll: Arc<ClientPool>,
+        addrs: Vec<String>,
+        output: OutputFormat,
+    ) -> Result<(), CommonError> {
+        let reply = list_session(client_poll, addrs, ListSessionRequest {}).await?;
+        let records = decode_records::<MqttSession>(&reply.sessions)?;
+        print_records(
+            output,
+            &[
+                "client_id",
+                "session_expiry",
+                "is_contain_last_will",
+                "connection_id",
+                "broker_id",
+                "create_time",
+                "reconnect_time",
+                "distinct_time",
+            ],
+            records,
+        );
+        Ok(())
+    }
+
+    async fn list_client(
+        &self,
+        client_poll: Arc<ClientPool>,
+        addrs: Vec<String>,
+        output: OutputFormat,
+    ) -> Result<(), CommonError> {
+        let reply = list_connection(client_poll, addrs, ListConnectionRequest {}).await?;
+        let records = reply
+            .connections
+            .into_iter()
+            .map(|connection| {
+                json!({
+                    "connect_id": connection.connect_id,
+                    "client_id": connection.client_id,
+                    "username": connection.username,
+                    "source_ip_addr": connection.source_ip_addr,
+                    "network": connection.network,
+                    "protocol_version": connection.protocol_version,
+                    "keep_alive": connection.keep_alive,
+                    "inflight": connection.inflight,
+                    "create_time": connection.create_time,
+                })
+            })
+            .collect();
+        print_records(
+            output,
+            &[
+                "connect_id",
+                "client_id",
+                "username",
+                "source_ip_addr",
+                "network",
+                "protocol_version",
+                "keep_alive",
+                "inflight",
+                "create_time",
+            ],
+            records,
+        );
+        Ok(())
+    }
+
+    async fn get_config(
+        &self,
+        client_poll: Arc<ClientPool>,
+        addrs: Vec<String>,
+        output: OutputFormat,
+    ) -> Result<(), CommonError> {
+        let reply = get_cluster_config(client_poll, addrs, GetClusterConfigRequest {}).await?;
+        let config = decode::<Value>(&reply.config)?;
+        match output {
+            OutputFormat::Table => {
+                let mut records = Vec::new();
+                flatten_object("", &config, &mut records);
+                print_records(output, &["name", "value"], records);
             }
+            OutputFormat::Json => print_json(&config),
+        }
+        Ok(())
+    }
+
+    // Only the fields present in `patch` are changed, the rest of the config is kept
+    async fn set_config(
+        &self,
+        client_poll: Arc<ClientPool>,
+        addrs: Vec<String>,
+        output: OutputFormat,
+        patch: String,
+    ) -> Result<(), CommonError> {
+        let patch = serde_json::from_str::<Value>(&patch)?;
+        if !patch.is_object() {
+            return Err(CommonError::CommmonError(
+                "cluster config must be a JSON object".to_string(),
+            ));
         }
+
+        let reply = get_cluster_config(
+            client_poll.clone(),
+            addrs.clone(),
+            GetClusterConfigRequest {},
+        )
+        .await?;
+        let mut config = decode::<Value>(&reply.config)?;
+        merge_object(&mut config, patch);
+        let request = SetClusterConfigRequest {
+            config: serde_json::to_vec(&config)?,
+        };
+        set_cluster_config(client_poll, addrs, request).await?;
+        print_success(output, "Updated cluster config".to_string());
+        Ok(())
+    }
+}
+
+fn decode<T: DeserializeOwned>(raw: &[u8]) -> Result<T, CommonError> {
+    Ok(serde_json::from_slice::<T>(raw)?)
+}
+
+// Decodes the JSON encoded records of a reply so their fields can be printed as columns
+fn decode_records<T: DeserializeOwned + serde::Serialize>(
+    raws: &[Vec<u8>],
+) -> Result<Vec<Value>, CommonError> {
+    let mut records = Vec::new();
+    for raw in raws {
+        records.push(serde_json::to_value(decode::<T>(raw)?)?);
+    }
+    Ok(records)
+}
+
+#[cfg(test)]
+mod tests {
+    use metadata_struct::acl::mqtt_acl::{MqttAclAction, MqttAclPermission, MqttAclResourceType};
+    use metadata_struct::acl::mqtt_blacklist::MqttAclBlackListType;
+
+    use super::{AclParam, BlacklistParam};
+
+    #[test]
+    fn acl_param_test() {
+        let param = AclParam {
+            resource_type: "user".to_string(),
+            resource_name: "admin".to_string(),
+            topic: "sensor/#".to_string(),
+            ip: "*".to_string(),
+            action: "Publish".to_string(),
+            permission: "deny".to_string(),
+            priority: 1,
+        };
+        let acl = param.to_acl().unwrap();
+        assert_eq!(acl.resource_type, MqttAclResourceType::User);
+        assert_eq!(acl.action, MqttAclAction::Publish);
+        assert_eq!(acl.permission, MqttAclPermission::Deny);
+        assert_eq!(acl.priority, 1);
+
+        let param = AclParam {
+            action: "read".to_string(),
+            ..param
+        };
+        assert!(param.to_acl().is_err());
+    }
+
+    #[test]
+    fn blacklist_param_test() {
+        let param = BlacklistParam {
+            blacklist_type: "ipcidr".to_string(),
+            resource_name: "10.0.0.0/8".to_string(),
+            end_time: 1_700_000_000,
+            desc: String::new(),
+        };
+        let blacklist = param.to_blacklist().unwrap();
+        assert_eq!(blacklist.blacklist_type, MqttAclBlackListType::IPCIDR);
+
+        let param = BlacklistParam {
+            blacklist_type: "host".to_string(),
+            ..param
+        };
+        assert!(param.to_blacklist().is_err());
     }
 }
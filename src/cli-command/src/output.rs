@@ -0,0 +1,163 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde_json::{json, Value};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OutputFormat {
+    #[default]
+    Table,
+    Json,
+}
+
+// Prints the records as a table with the given columns, or as a JSON array
+pub(crate) fn print_records(output: OutputFormat, columns: &[&str], records: Vec<Value>) {
+    match output {
+        OutputFormat::Table => print!("{}", render_table(columns, &records)),
+        OutputFormat::Json => print_json(&Value::Array(records)),
+    }
+}
+
+pub(crate) fn print_success(output: OutputFormat, message: String) {
+    match output {
+        OutputFormat::Table => println!("{}", message),
+        OutputFormat::Json => print_json(&json!({ "success": true, "message": message })),
+    }
+}
+
+pub(crate) fn print_json(value: &Value) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(value).unwrap_or_default()
+    );
+}
+
+pub(crate) fn render_table(columns: &[&str], records: &[Value]) -> String {
+    let header: Vec<String> = columns.iter().map(|column| column.to_uppercase()).collect();
+    let rows: Vec<Vec<String>> = records
+        .iter()
+        .map(|record| {
+            columns
+                .iter()
+                .map(|column| table_cell(record.get(column)))
+                .collect()
+        })
+        .collect();
+
+    let mut widths: Vec<usize> = header.iter().map(|name| name.chars().count()).collect();
+    for row in rows.iter() {
+        for (i, cell) in row.iter().enumerate() {
+            widths[i] = widths[i].max(cell.chars().count());
+        }
+    }
+
+    let mut table = String::new();
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(widths.iter())
+            .map(|(cell, width)| format!("{:<width$}", cell, width = width))
+            .collect();
+        table.push_str(line.join("  ").trim_end());
+        table.push('\n');
+    }
+    table
+}
+
+fn table_cell(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => "-".to_string(),
+        Some(Value::String(value)) => value.clone(),
+        Some(value) => value.to_string(),
+    }
+}
+
+// Turns nested objects into {"name": "a.b", "value": ..} records so they fit in a table
+pub(crate) fn flatten_object(prefix: &str, value: &Value, records: &mut Vec<Value>) {
+    match value {
+        Value::Object(object) if !object.is_empty() => {
+            for (key, value) in object.iter() {
+                let name = if prefix.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", prefix, key)
+                };
+                flatten_object(&name, value, records);
+            }
+        }
+        _ => records.push(json!({ "name": prefix, "value": value })),
+    }
+}
+
+// Overwrites the fields of `target` present in `patch`, nested objects are merged key by key
+pub(crate) fn merge_object(target: &mut Value, patch: Value) {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (key, value) in patch {
+                merge_object(target.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (target, patch) => *target = patch,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{flatten_object, merge_object, render_table};
+
+    #[test]
+    fn render_table_test() {
+        let records = vec![
+            json!({"client_id": "sensor-001", "qos": 1, "retain": false}),
+            json!({"client_id": "c2", "qos": 2, "extra": "ignored"}),
+        ];
+        let table = render_table(&["client_id", "qos", "retain"], &records);
+        assert_eq!(
+            table,
+            "CLIENT_ID   QOS  RETAIN\nsensor-001  1    false\nc2          2    -\n"
+        );
+
+        assert_eq!(render_table(&["name"], &[]), "NAME\n");
+    }
+
+    #[test]
+    fn flatten_object_test() {
+        let mut records = Vec::new();
+        let config = json!({"share_sub": {"group_strategy": {}}, "slow": {"enable": true, "response_ms": 100}});
+        flatten_object("", &config, &mut records);
+        assert_eq!(
+            records,
+            vec![
+                json!({"name": "share_sub.group_strategy", "value": {}}),
+                json!({"name": "slow.enable", "value": true}),
+                json!({"name": "slow.response_ms", "value": 100}),
+            ]
+        );
+    }
+
+    #[test]
+    fn merge_object_test() {
+        let mut config = json!({"slow": {"enable": false, "response_ms": 100}, "name": "a"});
+        merge_object(&mut config, json!({"slow": {"enable": true}, "extra": [1]}));
+        assert_eq!(
+            config,
+            json!({"slow": {"enable": true, "response_ms": 100}, "name": "a", "extra": [1]})
+        );
+
+        merge_object(&mut config, json!({"slow": 1}));
+        assert_eq!(config["slow"], json!(1));
+    }
+}
@@ -0,0 +1,262 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::time::Duration;
+
+use common_base::error::common::CommonError;
+use common_base::tools::unique_id;
+use rumqttc::{
+    AsyncClient, Event, EventLoop, MqttOptions, Outgoing, Packet, QoS, SubscribeReasonCode,
+};
+use serde_json::json;
+use tokio::time::timeout;
+
+use crate::output::{print_success, OutputFormat};
+
+// How long a publish waits for the broker to acknowledge the message
+const PUBLISH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Clone, Debug, Default)]
+pub struct MqttClientParam {
+    // MQTT listener of the broker, host:port
+    pub server: String,
+    // A random client ID is used when empty
+    pub client_id: String,
+    pub username: Option<String>,
+    pub password: Option<String>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PublishParam {
+    pub client: MqttClientParam,
+    pub topic: String,
+    pub qos: u8,
+    pub retain: bool,
+    pub payload: String,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct SubscribeParam {
+    pub client: MqttClientParam,
+    pub topic: String,
+    pub qos: u8,
+    // Exit after receiving this many messages, 0 keeps the subscription open
+    pub count: usize,
+}
+
+pub(crate) async fn publish(param: PublishParam, output: OutputFormat) -> Result<(), CommonError> {
+    let qos = mqtt_qos(param.qos)?;
+    let (client, mut eventloop) = AsyncClient::new(mqtt_options(&param.client)?, 10);
+    client
+        .publish(param.topic.clone(), qos, param.retain, param.payload)
+        .await
+        .map_err(|e| CommonError::CommmonError(e.to_string()))?;
+
+    match timeout(PUBLISH_TIMEOUT, wait_publish_complete(&mut eventloop, qos)).await {
+        Ok(result) => result?,
+        Err(_) => {
+            return Err(CommonError::CommmonError(format!(
+                "publish to topic {} was not acknowledged within {}s",
+                param.topic,
+                PUBLISH_TIMEOUT.as_secs()
+            )));
+        }
+    }
+
+    disconnect(&client, &mut eventloop).await;
+    print_success(
+        output,
+        format!("Published a message to topic {}", param.topic),
+    );
+    Ok(())
+}
+
+pub(crate) async fn subscribe(
+    param: SubscribeParam,
+    output: OutputFormat,
+) -> Result<(), CommonError> {
+    let qos = mqtt_qos(param.qos)?;
+    let (client, mut eventloop) = AsyncClient::new(mqtt_options(&param.client)?, 10);
+    client
+        .subscribe(param.topic.clone(), qos)
+        .await
+        .map_err(|e| CommonError::CommmonError(e.to_string()))?;
+
+    let mut received = 0;
+    loop {
+        let event = eventloop
+            .poll()
+            .await
+            .map_err(|e| CommonError::CommmonError(e.to_string()))?;
+        match event {
+            Event::Incoming(Packet::SubAck(ack))
+                if ack.return_codes.contains(&SubscribeReasonCode::Failure) =>
+            {
+                return Err(CommonError::CommmonError(format!(
+                    "subscribe to topic {} was rejected by the broker",
+                    param.topic
+                )));
+            }
+            Event::Incoming(Packet::Publish(publish)) => {
+                let payload = String::from_utf8_lossy(&publish.payload).to_string();
+                match output {
+                    OutputFormat::Table => println!("[{}] {}", publish.topic, payload),
+                    // One object per line so the stream can be piped
+                    OutputFormat::Json => println!(
+                        "{}",
+                        json!({
+                            "topic": publish.topic,
+                            "qos": publish.qos as u8,
+                            "retain": publish.retain,
+                            "payload": payload,
+                        })
+                    ),
+                }
+                received += 1;
+                if param.count > 0 && received >= param.count {
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    disconnect(&client, &mut eventloop).await;
+    Ok(())
+}
+
+fn mqtt_options(param: &MqttClientParam) -> Result<MqttOptions, CommonError> {
+    let Some((host, port)) = param.server.rsplit_once(':') else {
+        return Err(CommonError::CommmonError(format!(
+            "invalid mqtt server address {}, expected host:port",
+            param.server
+        )));
+    };
+    let port = port.parse::<u16>().map_err(|e| {
+        CommonError::CommmonError(format!(
+            "invalid mqtt server address {}, {}",
+            param.server, e
+        ))
+    })?;
+    let client_id = if param.client_id.is_empty() {
+        format!("robust-ctl-{}", unique_id())
+    } else {
+        param.client_id.clone()
+    };
+
+    let mut options = MqttOptions::new(client_id, host, port);
+    options.set_keep_alive(Duration::from_secs(30));
+    if let Some(username) = &param.username {
+        options.set_credentials(username, param.password.clone().unwrap_or_default());
+    }
+    Ok(options)
+}
+
+fn mqtt_qos(qos: u8) -> Result<QoS, CommonError> {
+    match qos {
+        0 => Ok(QoS::AtMostOnce),
+        1 => Ok(QoS::AtLeastOnce),
+        2 => Ok(QoS::ExactlyOnce),
+        _ => Err(CommonError::CommmonError(format!(
+            "invalid qos {}, expected 0, 1 or 2",
+            qos
+        ))),
+    }
+}
+
+async fn wait_publish_complete(eventloop: &mut EventLoop, qos: QoS) -> Result<(), CommonError> {
+    loop {
+        let event = eventloop
+            .poll()
+            .await
+            .map_err(|e| CommonError::CommmonError(e.to_string()))?;
+        if is_publish_complete(qos, &event) {
+            return Ok(());
+        }
+    }
+}
+
+// QoS0 is done once the message is written, QoS1 and QoS2 wait for the broker
+fn is_publish_complete(qos: QoS, event: &Event) -> bool {
+    matches!(
+        (qos, event),
+        (QoS::AtMostOnce, Event::Outgoing(Outgoing::Publish(_)))
+            | (QoS::AtLeastOnce, Event::Incoming(Packet::PubAck(_)))
+            | (QoS::ExactlyOnce, Event::Incoming(Packet::PubComp(_)))
+    )
+}
+
+async fn disconnect(client: &AsyncClient, eventloop: &mut EventLoop) {
+    if client.disconnect().await.is_ok() {
+        // Drive the event loop until the DISCONNECT packet has been sent
+        while let Ok(event) = eventloop.poll().await {
+            if matches!(event, Event::Outgoing(Outgoing::Disconnect)) {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rumqttc::{Event, Outgoing, Packet, PubAck, QoS};
+
+    use super::{is_publish_complete, mqtt_options, mqtt_qos, MqttClientParam};
+
+    #[test]
+    fn mqtt_options_test() {
+        let param = MqttClientParam {
+            server: "127.0.0.1:1883".to_string(),
+            client_id: "c1".to_string(),
+            username: Some("admin".to_string()),
+            password: Some("pwd".to_string()),
+        };
+        let options = mqtt_options(&param).unwrap();
+        assert_eq!(options.client_id(), "c1");
+        assert_eq!(options.broker_address(), ("127.0.0.1".to_string(), 1883));
+        assert_eq!(
+            options.credentials(),
+            Some(("admin".to_string(), "pwd".to_string()))
+        );
+
+        let param = MqttClientParam {
+            server: "127.0.0.1:1883".to_string(),
+            ..Default::default()
+        };
+        let options = mqtt_options(&param).unwrap();
+        assert!(options.client_id().starts_with("robust-ctl-"));
+        assert!(options.credentials().is_none());
+
+        for server in ["127.0.0.1", "127.0.0.1:port"] {
+            let param = MqttClientParam {
+                server: server.to_string(),
+                ..Default::default()
+            };
+            assert!(mqtt_options(&param).is_err());
+        }
+    }
+
+    #[test]
+    fn publish_complete_test() {
+        assert!(mqtt_qos(3).is_err());
+        assert_eq!(mqtt_qos(1).unwrap(), QoS::AtLeastOnce);
+
+        let sent = Event::Outgoing(Outgoing::Publish(0));
+        let acked = Event::Incoming(Packet::PubAck(PubAck::new(1)));
+        assert!(is_publish_complete(QoS::AtMostOnce, &sent));
+        assert!(!is_publish_complete(QoS::AtLeastOnce, &sent));
+        assert!(is_publish_complete(QoS::AtLeastOnce, &acked));
+        assert!(!is_publish_complete(QoS::ExactlyOnce, &acked));
+    }
+}
@@ -13,8 +13,16 @@
 // limitations under the License.
 
 use clap::Parser;
-use cli_command::mqtt::{MqttBrokerCommand, MqttCliCommandParam};
+use cli_command::mqtt::{
+    AclParam, BlacklistParam, MqttActionType, MqttBrokerCommand, MqttCliCommandParam,
+};
+use cli_command::output::OutputFormat;
 use cli_command::placement::{PlacementCenterCommand, PlacementCliCommandParam};
+use cli_command::pubsub::{MqttClientParam, PublishParam, SubscribeParam};
+use protocol::broker_mqtt::broker_mqtt_admin::{
+    CreateUserRequest, DeleteBlacklistRequest, DeleteSessionRequest, DeleteUserRequest,
+    DisconnectClientRequest,
+};
 
 #[derive(Parser)] // requires `derive` feature
 #[command(name = "robust-ctl")]
@@ -41,11 +49,181 @@ pub const CLAP_STYLING: clap::builder::styling::Styles = clap::builder::styling:
 #[command(author="RobustMQ", about="Command line tool for mqtt broker", long_about = None)]
 #[command(next_line_help = true)]
 struct MQTTArgs {
-    #[arg(short, long,default_value_t =String::from("127.0.0.1:9981"))]
+    /// gRPC address of the broker
+    #[arg(short, long, global = true, default_value_t = String::from("127.0.0.1:9981"))]
     server: String,
 
-    #[arg(short, long,default_value_t =String::from("status"))]
+    /// Print the result as JSON instead of a table
+    #[arg(long, global = true)]
+    json: bool,
+
+    #[command(subcommand)]
+    action: Option<MqttAction>,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum MqttAction {
+    /// Show the nodes of the cluster
+    Status,
+    /// Manage users
+    #[command(subcommand)]
+    User(UserAction),
+    /// Manage ACL rules
+    #[command(subcommand)]
+    Acl(AclAction),
+    /// Manage the blacklist
+    #[command(subcommand)]
+    Blacklist(BlacklistAction),
+    /// Manage sessions
+    #[command(subcommand)]
+    Session(SessionAction),
+    /// Manage connected clients
+    #[command(subcommand)]
+    Client(ClientAction),
+    /// Show or change the cluster config
+    #[command(subcommand)]
+    Config(ConfigAction),
+    /// Publish a message
+    Publish(PublishArgs),
+    /// Subscribe to a topic and print the received messages
+    Subscribe(SubscribeArgs),
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum UserAction {
+    Create {
+        #[arg(short, long)]
+        username: String,
+        #[arg(short, long)]
+        password: String,
+        #[arg(long)]
+        is_superuser: bool,
+    },
+    Delete {
+        #[arg(short, long)]
+        username: String,
+    },
+    List,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum AclAction {
+    Create(AclArgs),
+    Delete(AclArgs),
+    List,
+}
+
+#[derive(clap::Args, Debug)]
+struct AclArgs {
+    /// ClientId or User
+    #[arg(long)]
+    resource_type: String,
+    #[arg(long)]
+    resource_name: String,
+    #[arg(long)]
+    topic: String,
+    #[arg(long, default_value_t = String::from("*"))]
+    ip: String,
+    /// All, Subscribe, Publish, PubSub, Retain or Qos
+    #[arg(long)]
     action: String,
+    /// Allow or Deny
+    #[arg(long)]
+    permission: String,
+    /// Rules with a lower value are evaluated first
+    #[arg(long, default_value_t = 0)]
+    priority: u32,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum BlacklistAction {
+    Create {
+        /// ClientId, User, Ip, ClientIdMatch, UserMatch or IPCIDR
+        #[arg(long)]
+        blacklist_type: String,
+        #[arg(long)]
+        resource_name: String,
+        /// Unix timestamp in seconds when the entry expires
+        #[arg(long)]
+        end_time: u64,
+        #[arg(long, default_value_t = String::new())]
+        desc: String,
+    },
+    Delete {
+        #[arg(long)]
+        blacklist_type: String,
+        #[arg(long)]
+        resource_name: String,
+    },
+    List,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum SessionAction {
+    List,
+    Delete {
+        #[arg(short, long)]
+        client_id: String,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum ClientAction {
+    List,
+    Kick {
+        #[arg(short, long)]
+        client_id: String,
+    },
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum ConfigAction {
+    Get,
+    Set {
+        /// JSON object merged into the current config, e.g. '{"slow":{"enable":true}}'
+        config: String,
+    },
+}
+
+#[derive(clap::Args, Debug)]
+struct MqttClientArgs {
+    /// MQTT listener of the broker
+    #[arg(long, default_value_t = String::from("127.0.0.1:1883"))]
+    broker: String,
+    /// A random client ID is used when not set
+    #[arg(long)]
+    client_id: Option<String>,
+    #[arg(short, long)]
+    username: Option<String>,
+    #[arg(short, long)]
+    password: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct PublishArgs {
+    #[command(flatten)]
+    client: MqttClientArgs,
+    #[arg(short, long)]
+    topic: String,
+    #[arg(short, long, default_value_t = 0)]
+    qos: u8,
+    #[arg(short, long)]
+    retain: bool,
+    #[arg(short = 'm', long)]
+    payload: String,
+}
+
+#[derive(clap::Args, Debug)]
+struct SubscribeArgs {
+    #[command(flatten)]
+    client: MqttClientArgs,
+    #[arg(short, long)]
+    topic: String,
+    #[arg(short, long, default_value_t = 0)]
+    qos: u8,
+    /// Exit after receiving this many messages, 0 keeps the subscription open
+    #[arg(short, long, default_value_t = 0)]
+    count: usize,
 }
 
 #[derive(clap::Args, Debug)]
@@ -78,9 +256,17 @@ async fn main() {
             let cmd = MqttBrokerCommand::new();
             let params = MqttCliCommandParam {
                 server: args.server,
-                action: args.action,
+                output: if args.json {
+                    OutputFormat::Json
+                } else {
+                    OutputFormat::Table
+                },
+                action: mqtt_action(args.action.unwrap_or(MqttAction::Status)),
             };
-            cmd.start(params).await;
+            if let Err(e) = cmd.start(params).await {
+                println!("Exception:{}", e);
+                std::process::exit(1);
+            }
         }
         RobustMQCli::Place(args) => {
             let cmd = PlacementCenterCommand::new();
@@ -95,3 +281,100 @@ async fn main() {
         }
     }
 }
+
+fn mqtt_action(action: MqttAction) -> MqttActionType {
+    match action {
+        MqttAction::Status => MqttActionType::Status,
+        MqttAction::User(action) => match action {
+            UserAction::Create {
+                username,
+                password,
+                is_superuser,
+            } => MqttActionType::CreateUser(CreateUserRequest {
+                username,
+                password,
+                is_superuser,
+            }),
+            UserAction::Delete { username } => {
+                MqttActionType::DeleteUser(DeleteUserRequest { username })
+            }
+            UserAction::List => MqttActionType::ListUser,
+        },
+        MqttAction::Acl(action) => match action {
+            AclAction::Create(args) => MqttActionType::CreateAcl(acl_param(args)),
+            AclAction::Delete(args) => MqttActionType::DeleteAcl(acl_param(args)),
+            AclAction::List => MqttActionType::ListAcl,
+        },
+        MqttAction::Blacklist(action) => match action {
+            BlacklistAction::Create {
+                blacklist_type,
+                resource_name,
+                end_time,
+                desc,
+            } => MqttActionType::CreateBlacklist(BlacklistParam {
+                blacklist_type,
+                resource_name,
+                end_time,
+                desc,
+            }),
+            BlacklistAction::Delete {
+                blacklist_type,
+                resource_name,
+            } => MqttActionType::DeleteBlacklist(DeleteBlacklistRequest {
+                blacklist_type,
+                resource_name,
+            }),
+            BlacklistAction::List => MqttActionType::ListBlacklist,
+        },
+        MqttAction::Session(action) => match action {
+            SessionAction::List => MqttActionType::ListSession,
+            SessionAction::Delete { client_id } => {
+                MqttActionType::DeleteSession(DeleteSessionRequest { client_id })
+            }
+        },
+        MqttAction::Client(action) => match action {
+            ClientAction::List => MqttActionType::ListClient,
+            ClientAction::Kick { client_id } => {
+                MqttActionType::KickClient(DisconnectClientRequest { client_id })
+            }
+        },
+        MqttAction::Config(action) => match action {
+            ConfigAction::Get => MqttActionType::GetConfig,
+            ConfigAction::Set { config } => MqttActionType::SetConfig(config),
+        },
+        MqttAction::Publish(args) => MqttActionType::Publish(PublishParam {
+            client: mqtt_client_param(args.client),
+            topic: args.topic,
+            qos: args.qos,
+            retain: args.retain,
+            payload: args.payload,
+        }),
+        MqttAction::Subscribe(args) => MqttActionType::Subscribe(SubscribeParam {
+            client: mqtt_client_param(args.client),
+            topic: args.topic,
+            qos: args.qos,
+            count: args.count,
+        }),
+    }
+}
+
+fn acl_param(args: AclArgs) -> AclParam {
+    AclParam {
+        resource_type: args.resource_type,
+        resource_name: args.resource_name,
+        topic: args.topic,
+        ip: args.ip,
+        action: args.action,
+        permission: args.permission,
+        priority: args.priority,
+    }
+}
+
+fn mqtt_client_param(args: MqttClientArgs) -> MqttClientParam {
+    MqttClientParam {
+        server: args.broker,
+        client_id: args.client_id.unwrap_or_default(),
+        username: args.username,
+        password: args.password,
+    }
+}
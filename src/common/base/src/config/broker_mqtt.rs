@@ -63,6 +63,29 @@ pub struct BrokerMQTTConfig {
     pub auth: Auth,
     #[serde(default = "default_log")]
     pub log: Log,
+    #[serde(default)]
+    pub http_api: HttpApi,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct HttpApi {
+    // Keys accepted in the "Authorization: Bearer <key>" header, the API is closed when empty
+    #[serde(default)]
+    pub api_keys: Vec<HttpApiKey>,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default)]
+pub struct HttpApiKey {
+    // Identifies the key, HTTP publishes use "http_api_<name>" as their client id
+    pub name: String,
+    pub key: String,
+    // Username the requests made with this key act as, e.g. in ACL rules,
+    // a key without one cannot publish
+    #[serde(default)]
+    pub principal: String,
+    // Route groups the key may call, "publish" and/or "admin", a key without scopes is rejected
+    #[serde(default)]
+    pub scopes: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
@@ -81,6 +104,30 @@ pub struct Network {
     pub tls_cert: String,
     #[serde(default)]
     pub tls_key: String,
+    // CA bundle that signs the client certificates of mutual TLS
+    #[serde(default)]
+    pub tls_ca_cert: String,
+    // "none", "optional" or "required", client certificates are only requested with a tls_ca_cert
+    #[serde(default)]
+    pub tls_client_auth: String,
+    // CRL file checked against the client certificates
+    #[serde(default)]
+    pub tls_crl: String,
+    // "cn" or "san", the client certificate field used as the username
+    #[serde(default)]
+    pub tls_cert_as_username: String,
+    // "cn" or "san", the client certificate field used as the client ID
+    #[serde(default)]
+    pub tls_cert_as_client_id: String,
+    // TLS-PSK listener port, the listener is off when 0
+    #[serde(default)]
+    pub tcps_psk_port: u32,
+    // File of "identity:hex-key" lines for the TLS-PSK listener
+    #[serde(default)]
+    pub tls_psk_file: String,
+    // Also read the PSK list from the placement center KV key /mqtt/psk/{cluster_name}
+    #[serde(default)]
+    pub tls_psk_from_placement: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
@@ -22,6 +22,8 @@ pub struct Storage {
     #[serde(default)]
     pub mysql_addr: String,
     #[serde(default)]
+    pub postgres_addr: String,
+    #[serde(default)]
     pub rocksdb_data_path: String,
     pub rocksdb_max_open_files: Option<i32>,
 }
@@ -33,6 +35,101 @@ pub struct Auth {
     pub journal_addr: String,
     #[serde(default)]
     pub mysql_addr: String,
+    #[serde(default)]
+    pub postgres_addr: String,
+    #[serde(default)]
+    pub jwt: AuthJwt,
+    #[serde(default)]
+    pub http: AuthHttp,
+    #[serde(default)]
+    pub redis: AuthRedis,
+    #[serde(default)]
+    pub password_hash: AuthPasswordHash,
+    #[serde(default)]
+    pub chain: AuthChain,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct AuthJwt {
+    pub enable: bool,
+    // CONNECT field that carries the token, "password" or "username"
+    pub from: String,
+    // HS256, RS256 or ES256
+    pub algorithm: String,
+    // HS256 shared secret
+    pub secret: String,
+    // PEM file of the RS256/ES256 public key
+    pub public_key: String,
+    // JWKS file on disk, takes precedence over public_key
+    pub jwks_file: String,
+    // Checked against the iss claim when not empty
+    pub issuer: String,
+    // Checked against the aud claim when not empty
+    pub audience: String,
+    // Claim that must equal the MQTT username when the token is sent as the password, "sub" when empty
+    pub username_claim: String,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct AuthHttp {
+    pub enable: bool,
+    // Endpoint that decides CONNECT, authentication callout is off when empty
+    pub authn_url: String,
+    // Endpoint that decides PUBLISH and SUBSCRIBE, authorization callout is off when empty
+    pub authz_url: String,
+    // Request timeout in milliseconds, 0 uses 500
+    pub timeout_ms: u64,
+    // Idle connections kept per host, 0 uses 16
+    pub pool_max_idle: usize,
+    // Seconds a result of the service is cached, failed calls are cached for at most 1 second,
+    // 0 disables the cache
+    pub cache_ttl: u64,
+    // Result of a failed call (transport error, timeout, unexpected status or body),
+    // "deny" or "ignore", empty is deny
+    pub failure_action: String,
+}
+
+// How passwords read from MySQL, PostgreSQL or Redis are hashed, placement users carry their own
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct AuthPasswordHash {
+    // plain, sha256, sha512, pbkdf2 or bcrypt, empty is plain
+    pub algorithm: String,
+    // Where the per-user salt is joined to the password, "prefix" or "suffix"
+    pub salt_position: String,
+    // pbkdf2 only, 0 uses 4096
+    pub iterations: u32,
+}
+
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct AuthRedis {
+    // redis://[:password@]host:port/db
+    pub addr: String,
+    // Hash of one user, ${username} is replaced, empty uses "mqtt_user:${username}"
+    pub user_key: String,
+    // Hash of topic => rule, empty uses "mqtt_acl:${resource_type}:${resource_name}"
+    pub acl_key: String,
+    // Blacklist entry expiring with the key TTL,
+    // empty uses "mqtt_blacklist:${blacklist_type}:${resource_name}"
+    pub blacklist_key: String,
+}
+
+// Authenticators tried in order on CONNECT: "anonymous", "x509", "psk", "http", "jwt" or
+// "password". The first allow or deny decides, a client no authenticator accepts is rejected.
+#[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
+#[serde(default)]
+pub struct AuthChain {
+    // Used by listeners without their own chain, empty follows the jwt and http settings
+    pub default: Vec<String>,
+    pub tcp: Vec<String>,
+    pub tls: Vec<String>,
+    pub tlspsk: Vec<String>,
+    pub websocket: Vec<String>,
+    pub websockets: Vec<String>,
+    pub quic: Vec<String>,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default, PartialEq, Eq)]
@@ -13,7 +13,9 @@
 // limitations under the License.
 
 use super::broker_mqtt::{Network, System, TcpThread};
-use super::common::{Auth, Log, Storage};
+use super::common::{
+    Auth, AuthChain, AuthHttp, AuthJwt, AuthPasswordHash, AuthRedis, Log, Storage,
+};
 
 pub fn default_grpc_port() -> u32 {
     9981
@@ -32,6 +34,14 @@ pub fn default_network() -> Network {
         quic_port: default_network_quic_port(),
         tls_cert: "".to_string(),
         tls_key: "".to_string(),
+        tls_ca_cert: "".to_string(),
+        tls_client_auth: "".to_string(),
+        tls_crl: "".to_string(),
+        tls_cert_as_username: "".to_string(),
+        tls_cert_as_client_id: "".to_string(),
+        tcps_psk_port: 0,
+        tls_psk_file: "".to_string(),
+        tls_psk_from_placement: false,
     }
 }
 pub fn default_network_tcp_port() -> u32 {
@@ -76,6 +86,7 @@ pub fn default_storage() -> Storage {
         storage_type: "memory".to_string(),
         journal_addr: "".to_string(),
         mysql_addr: "".to_string(),
+        postgres_addr: "".to_string(),
         rocksdb_data_path: "".to_string(),
         rocksdb_max_open_files: None,
     }
@@ -93,5 +104,11 @@ pub fn default_auth() -> Auth {
         storage_type: "memory".to_string(),
         journal_addr: "".to_string(),
         mysql_addr: "".to_string(),
+        postgres_addr: "".to_string(),
+        jwt: AuthJwt::default(),
+        http: AuthHttp::default(),
+        redis: AuthRedis::default(),
+        password_hash: AuthPasswordHash::default(),
+        chain: AuthChain::default(),
     }
 }
@@ -14,7 +14,7 @@
 
 use toml::Table;
 
-use super::common::Log;
+use super::common::{AuthPasswordHash, Log};
 use super::placement_center::{Heartbeat, Network, Node, Rocksdb, System};
 
 pub fn default_cluster_name() -> String {
@@ -102,3 +102,12 @@ pub fn default_heartbeat_timeout_ms() -> u64 {
 pub fn default_heartbeat_check_time_ms() -> u64 {
     1000
 }
+
+// pbkdf2 users can also log in with SCRAM-SHA-256, the stored key is its SaltedPassword
+pub fn default_password_hash() -> AuthPasswordHash {
+    AuthPasswordHash {
+        algorithm: "pbkdf2".to_string(),
+        salt_position: "".to_string(),
+        iterations: 4096,
+    }
+}
@@ -34,12 +34,12 @@ use serde::{Deserialize, Serialize};
 use toml::map::Map;
 use toml::{Table, Value};
 
-use super::common::Log;
+use super::common::{AuthPasswordHash, Log};
 use super::default_placement_center::{
     default_cluster_name, default_data_path, default_grpc_port, default_heartbeat,
     default_heartbeat_check_time_ms, default_heartbeat_timeout_ms, default_http_port, default_log,
     default_max_open_files, default_network, default_node, default_node_id, default_nodes,
-    default_rocksdb, default_runtime_work_threads, default_system,
+    default_password_hash, default_rocksdb, default_runtime_work_threads, default_system,
 };
 use crate::tools::{read_file, try_create_fold, unique_id};
 
@@ -59,6 +59,9 @@ pub struct PlacementCenterConfig {
     pub rocksdb: Rocksdb,
     #[serde(default = "default_log")]
     pub log: Log,
+    // How the passwords of new MQTT users are hashed before they are stored
+    #[serde(default = "default_password_hash")]
+    pub password_hash: AuthPasswordHash,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone, Default)]
@@ -173,6 +176,7 @@ pub fn placement_center_test_conf() -> PlacementCenterConfig {
     let config = PlacementCenterConfig {
         rocksdb,
         node,
+        password_hash: default_password_hash(),
         ..Default::default()
     };
     init_placement_center_conf_by_config(config.clone());
@@ -183,7 +187,7 @@ pub fn placement_center_test_conf() -> PlacementCenterConfig {
 mod tests {
     use toml::Table;
 
-    use super::{placement_center_conf, Log, PlacementCenterConfig};
+    use super::{placement_center_conf, AuthPasswordHash, Log, PlacementCenterConfig};
     use crate::config::placement_center::init_placement_center_conf_by_path;
 
     #[test]
@@ -221,5 +225,13 @@ mod tests {
         assert_eq!(config.rocksdb.max_open_files, Some(10000_i32));
         assert_eq!(config.heartbeat.heartbeat_timeout_ms, 5000);
         assert_eq!(config.heartbeat.heartbeat_check_time_ms, 1000);
+        assert_eq!(
+            config.password_hash,
+            AuthPasswordHash {
+                algorithm: "pbkdf2".to_string(),
+                salt_position: "".to_string(),
+                iterations: 4096,
+            }
+        );
     }
 }
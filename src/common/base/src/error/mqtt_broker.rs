@@ -58,4 +58,37 @@ pub enum MQTTBrokerError {
 
     #[error("Topic [{0}] does not exist")]
     TopicDoesNotExist(String),
+
+    #[error("Authentication method [{0}] is not supported")]
+    AuthenticationMethodNotSupported(String),
+
+    #[error("Enhanced authentication failed, {0}")]
+    EnhancedAuthenticationFailed(String),
+
+    #[error("Delay interval {0}s exceeds the maximum delay interval {1}s")]
+    DelayPublishIntervalExceeded(u64, u64),
+
+    #[error("The number of pending delayed messages has reached the upper limit {0}")]
+    DelayPublishPendingExceeded(u64),
+
+    #[error("Client [{0}] is temporarily banned for flapping")]
+    ClientFlappingBanned(String),
+
+    #[error("JWT authentication failed, {0}")]
+    JwtAuthenticationFailed(String),
+
+    #[error("HTTP authentication failed, {0}")]
+    HttpAuthenticationFailed(String),
+
+    #[error("X.509 authentication failed, {0}")]
+    X509AuthenticationFailed(String),
+
+    #[error("PSK authentication failed, {0}")]
+    PskAuthenticationFailed(String),
+
+    #[error("Password verification failed, {0}")]
+    PasswordVerificationFailed(String),
+
+    #[error("HTTP publish failed, {0}")]
+    HttpPublishFailed(String),
 }
@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use std::fmt;
+use std::str::FromStr;
 
 use common_base::error::common::CommonError;
 use serde::{Deserialize, Serialize};
@@ -25,6 +26,9 @@ pub struct MqttAcl {
     pub ip: String,
     pub action: MqttAclAction,
     pub permission: MqttAclPermission,
+    // Rules are evaluated from the lowest priority value up, the first matching rule decides
+    #[serde(default)]
+    pub priority: u32,
 }
 
 impl MqttAcl {
@@ -52,6 +56,21 @@ impl fmt::Display for MqttAclResourceType {
     }
 }
 
+impl FromStr for MqttAclResourceType {
+    type Err = CommonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "clientid" => Ok(MqttAclResourceType::ClientId),
+            "user" => Ok(MqttAclResourceType::User),
+            _ => Err(CommonError::CommmonError(format!(
+                "unsupported acl resource type {}",
+                s
+            ))),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Debug, PartialEq, PartialOrd, Clone)]
 pub enum MqttAclAction {
     All,
@@ -62,8 +81,43 @@ pub enum MqttAclAction {
     Qos,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, PartialOrd, Clone)]
+#[derive(Serialize, Deserialize, Debug, PartialEq, PartialOrd, Clone, Default)]
 pub enum MqttAclPermission {
+    #[default]
     Allow,
     Deny,
 }
+
+impl FromStr for MqttAclAction {
+    type Err = CommonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "all" => Ok(MqttAclAction::All),
+            "subscribe" => Ok(MqttAclAction::Subscribe),
+            "publish" => Ok(MqttAclAction::Publish),
+            "pubsub" => Ok(MqttAclAction::PubSub),
+            "retain" => Ok(MqttAclAction::Retain),
+            "qos" => Ok(MqttAclAction::Qos),
+            _ => Err(CommonError::CommmonError(format!(
+                "unsupported acl action {}",
+                s
+            ))),
+        }
+    }
+}
+
+impl FromStr for MqttAclPermission {
+    type Err = CommonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "allow" => Ok(MqttAclPermission::Allow),
+            "deny" => Ok(MqttAclPermission::Deny),
+            _ => Err(CommonError::CommmonError(format!(
+                "unsupported acl permission {}",
+                s
+            ))),
+        }
+    }
+}
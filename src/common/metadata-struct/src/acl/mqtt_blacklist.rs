@@ -13,6 +13,7 @@
 // limitations under the License.
 
 use std::fmt;
+use std::str::FromStr;
 
 use common_base::error::common::CommonError;
 use serde::{Deserialize, Serialize};
@@ -57,3 +58,22 @@ impl fmt::Display for MqttAclBlackListType {
         )
     }
 }
+
+impl FromStr for MqttAclBlackListType {
+    type Err = CommonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "clientid" => Ok(MqttAclBlackListType::ClientId),
+            "user" => Ok(MqttAclBlackListType::User),
+            "ip" => Ok(MqttAclBlackListType::Ip),
+            "clientidmatch" => Ok(MqttAclBlackListType::ClientIdMatch),
+            "usermatch" => Ok(MqttAclBlackListType::UserMatch),
+            "ipcidr" => Ok(MqttAclBlackListType::IPCIDR),
+            _ => Err(CommonError::CommmonError(format!(
+                "unsupported blacklist type {}",
+                s
+            ))),
+        }
+    }
+}
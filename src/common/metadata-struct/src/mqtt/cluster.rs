@@ -12,9 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+
+use common_base::error::common::CommonError;
 use protocol::mqtt::common::QoS;
 use serde::{Deserialize, Serialize};
 
+use crate::acl::mqtt_acl::MqttAclPermission;
+
 // Dynamic configuration of MQTT cluster latitude
 #[derive(Serialize, Deserialize, Default, Clone)]
 pub struct MqttClusterDynamicConfig {
@@ -23,6 +28,17 @@ pub struct MqttClusterDynamicConfig {
     pub security: MqttClusterDynamicConfigSecurity,
     pub network: MqttClusterDynamicConfigNetwork,
     pub slow: MqttClusterDynamicSlowSub,
+    #[serde(default)]
+    pub delay_publish: MqttClusterDynamicDelayPublish,
+    #[serde(default)]
+    pub share_sub: MqttClusterDynamicShareSub,
+    #[serde(default)]
+    pub rate_limit: MqttClusterDynamicRateLimit,
+    #[serde(default)]
+    pub flapping_detect: MqttClusterDynamicFlappingDetect,
+    // Bumped on every change made through the admin API, a change must carry the version it read
+    #[serde(default)]
+    pub version: u64,
 }
 
 // MQTT cluster protocol related dynamic configuration
@@ -37,6 +53,23 @@ pub struct MqttClusterDynamicConfigProtocol {
     pub receive_max: u16,
     pub max_message_expiry_interval: u64,
     pub client_pkid_persistent: bool,
+    // Offline message queue of clients with a persistent session, 0 means no limit
+    #[serde(default)]
+    pub offline_message_max_num: u64,
+    #[serde(default)]
+    pub offline_message_max_bytes: u64,
+    #[serde(default)]
+    pub offline_message_drop_policy: OfflineMessageDropPolicy,
+    // Whether QoS0 messages are also queued while the client is offline
+    #[serde(default)]
+    pub offline_message_qos0: bool,
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+pub enum OfflineMessageDropPolicy {
+    #[default]
+    DropOldest,
+    DropNewest,
 }
 
 // MQTT cluster security related dynamic configuration
@@ -44,6 +77,9 @@ pub struct MqttClusterDynamicConfigProtocol {
 pub struct MqttClusterDynamicConfigSecurity {
     pub is_self_protection_status: bool,
     pub secret_free_login: bool,
+    // Decision when no ACL rule matches a publish or subscribe
+    #[serde(default)]
+    pub acl_no_match: MqttAclPermission,
 }
 
 // MQTT cluster network related dynamic configuration
@@ -74,6 +110,82 @@ pub struct MqttClusterDynamicSlowSub {
     pub response_ms: u32,
 }
 
+// MQTT cluster delay publish related dynamic configuration
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct MqttClusterDynamicDelayPublish {
+    pub enable: bool,
+    pub max_delay_interval: u64,
+    pub max_pending_num: u64,
+}
+
+// MQTT cluster shared subscription related dynamic configuration
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct MqttClusterDynamicShareSub {
+    // Cluster-wide dispatch strategy
+    pub strategy: ShareSubStrategy,
+    // Per-group dispatch strategy, (group_name, strategy)
+    pub group_strategy: HashMap<String, ShareSubStrategy>,
+}
+
+impl MqttClusterDynamicShareSub {
+    pub fn get_strategy(&self, group_name: &str) -> ShareSubStrategy {
+        if let Some(strategy) = self.group_strategy.get(group_name) {
+            return strategy.clone();
+        }
+        self.strategy.clone()
+    }
+}
+
+// MQTT cluster rate limit related dynamic configuration, 0 means no limit
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct MqttClusterDynamicRateLimit {
+    // New connections accepted per second by each listener
+    pub listener_connection_rate: u64,
+    // Messages published per second by each client
+    pub client_publish_rate: u64,
+    // Payload bytes published per second by each client
+    pub client_publish_bytes_rate: u64,
+    // Subscribe requests per second by each client
+    pub client_subscribe_rate: u64,
+    // Messages published per second in the whole cluster
+    pub cluster_publish_rate: u64,
+}
+
+// MQTT cluster flapping detection related dynamic configuration, disabled unless turned on
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+pub struct MqttClusterDynamicFlappingDetect {
+    pub enable: bool,
+    // Length of the sliding window, in seconds
+    pub window_time: u64,
+    // Connections of a client within the window that trigger the ban
+    pub max_client_connections: u64,
+    // How long the client stays banned, in seconds
+    pub ban_time: u64,
+}
+
+// Also used for the cluster configs stored before flapping detection existed
+impl Default for MqttClusterDynamicFlappingDetect {
+    fn default() -> Self {
+        MqttClusterDynamicFlappingDetect {
+            enable: false,
+            window_time: 60,
+            max_client_connections: 15,
+            ban_time: 300,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, PartialEq, Debug)]
+pub enum ShareSubStrategy {
+    #[default]
+    RoundRobin,
+    Random,
+    HashClientId,
+    HashTopic,
+    Sticky,
+    LocalFirst,
+}
+
 impl MqttClusterDynamicConfig {
     pub fn new() -> Self {
         MqttClusterDynamicConfig {
@@ -87,6 +199,10 @@ impl MqttClusterDynamicConfig {
                 receive_max: 65535,
                 client_pkid_persistent: false,
                 max_message_expiry_interval: 3600,
+                offline_message_max_num: 1000,
+                offline_message_max_bytes: 1024 * 1024 * 10,
+                offline_message_drop_policy: OfflineMessageDropPolicy::DropOldest,
+                offline_message_qos0: false,
             },
             feature: MqttClusterDynamicConfigFeature {
                 retain_available: AvailableFlag::Enable,
@@ -97,6 +213,7 @@ impl MqttClusterDynamicConfig {
             security: MqttClusterDynamicConfigSecurity {
                 secret_free_login: false,
                 is_self_protection_status: false,
+                acl_no_match: MqttAclPermission::Allow,
             },
             network: MqttClusterDynamicConfigNetwork {
                 tcp_max_connection_num: 1000,
@@ -112,12 +229,70 @@ impl MqttClusterDynamicConfig {
                 internal_ms: 0,
                 response_ms: 0,
             },
+            delay_publish: MqttClusterDynamicDelayPublish {
+                enable: true,
+                max_delay_interval: 4294967,
+                max_pending_num: 100000,
+            },
+            share_sub: MqttClusterDynamicShareSub {
+                strategy: ShareSubStrategy::RoundRobin,
+                group_strategy: HashMap::new(),
+            },
+            rate_limit: MqttClusterDynamicRateLimit {
+                listener_connection_rate: 0,
+                client_publish_rate: 0,
+                client_publish_bytes_rate: 0,
+                client_subscribe_rate: 0,
+                cluster_publish_rate: 0,
+            },
+            flapping_detect: MqttClusterDynamicFlappingDetect::default(),
+            version: 0,
         }
     }
 
     pub fn encode(&self) -> Vec<u8> {
         serde_json::to_vec(&self).unwrap()
     }
+
+    // Rejects values the broker cannot work with before they are stored
+    pub fn validate(&self) -> Result<(), CommonError> {
+        let protocol = &self.protocol;
+        if protocol.max_packet_size == 0 {
+            return Err(invalid_config(
+                "protocol.max_packet_size must be greater than 0",
+            ));
+        }
+        if protocol.receive_max == 0 {
+            return Err(invalid_config(
+                "protocol.receive_max must be greater than 0",
+            ));
+        }
+        if protocol.default_server_keep_alive > protocol.max_server_keep_alive {
+            return Err(invalid_config(
+                "protocol.default_server_keep_alive must not exceed protocol.max_server_keep_alive",
+            ));
+        }
+        if self.delay_publish.enable && self.delay_publish.max_delay_interval == 0 {
+            return Err(invalid_config(
+                "delay_publish.max_delay_interval must be greater than 0",
+            ));
+        }
+        let flapping = &self.flapping_detect;
+        if flapping.enable
+            && (flapping.window_time == 0
+                || flapping.max_client_connections == 0
+                || flapping.ban_time == 0)
+        {
+            return Err(invalid_config(
+                "flapping_detect.window_time, max_client_connections and ban_time must be greater than 0",
+            ));
+        }
+        Ok(())
+    }
+}
+
+fn invalid_config(reason: &str) -> CommonError {
+    CommonError::CommmonError(format!("invalid cluster config, {}", reason))
 }
 
 #[derive(Serialize, Deserialize, Default, Clone)]
@@ -129,11 +304,72 @@ pub enum AvailableFlag {
 
 #[cfg(test)]
 mod tests {
-    use crate::mqtt::cluster::AvailableFlag;
+    use crate::mqtt::cluster::{AvailableFlag, MqttClusterDynamicConfig, ShareSubStrategy};
 
     #[test]
     fn client34_connect_test() {
         assert_eq!(AvailableFlag::Disable as u8, 0);
         assert_eq!(AvailableFlag::Enable as u8, 1);
     }
+
+    #[test]
+    fn share_sub_strategy_test() {
+        let mut config = MqttClusterDynamicConfig::new();
+        assert_eq!(
+            config.share_sub.get_strategy("g1"),
+            ShareSubStrategy::RoundRobin
+        );
+
+        config
+            .share_sub
+            .group_strategy
+            .insert("g1".to_string(), ShareSubStrategy::HashTopic);
+        assert_eq!(
+            config.share_sub.get_strategy("g1"),
+            ShareSubStrategy::HashTopic
+        );
+        assert_eq!(
+            config.share_sub.get_strategy("g2"),
+            ShareSubStrategy::RoundRobin
+        );
+    }
+
+    #[test]
+    fn flapping_detect_default_test() {
+        let config = MqttClusterDynamicConfig::new();
+        assert!(!config.flapping_detect.enable);
+
+        // a stored config without the section gets the same defaults
+        let mut value = serde_json::to_value(&config).unwrap();
+        value.as_object_mut().unwrap().remove("flapping_detect");
+        let stored: MqttClusterDynamicConfig = serde_json::from_value(value).unwrap();
+        assert_eq!(stored.flapping_detect, config.flapping_detect);
+    }
+
+    #[test]
+    fn validate_test() {
+        let config = MqttClusterDynamicConfig::new();
+        assert!(config.validate().is_ok());
+
+        let mut bad = config.clone();
+        bad.protocol.receive_max = 0;
+        assert!(bad.validate().is_err());
+
+        let mut bad = config.clone();
+        bad.protocol.default_server_keep_alive = bad.protocol.max_server_keep_alive + 1;
+        assert!(bad.validate().is_err());
+
+        let mut bad = config.clone();
+        bad.flapping_detect.enable = true;
+        bad.flapping_detect.window_time = 0;
+        assert!(bad.validate().is_err());
+        bad.flapping_detect.enable = false;
+        assert!(bad.validate().is_ok());
+
+        // configs stored before versioning start at 0
+        let mut value = serde_json::to_value(&config).unwrap();
+        value.as_object_mut().unwrap().remove("version");
+        let stored: MqttClusterDynamicConfig = serde_json::from_value(value).unwrap();
+        assert_eq!(stored.version, 0);
+    }
 }
@@ -12,17 +12,335 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::str::FromStr;
+
+use common_base::config::common::AuthPasswordHash;
+use common_base::error::common::CommonError;
+use pbkdf2::pbkdf2_hmac;
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
+use subtle::ConstantTimeEq;
+
+// Iterations used when a pbkdf2 user does not set them
+pub const DEFAULT_PBKDF2_ITERATIONS: u32 = 4096;
 
 #[derive(Clone, Serialize, Deserialize, Debug, PartialEq)]
 pub struct MqttUser {
     pub username: String,
     pub password: String,
     pub is_superuser: bool,
+    // How `password` is stored, users written before hashing was added are plain
+    #[serde(default)]
+    pub hash: MqttUserHash,
 }
 
 impl MqttUser {
     pub fn encode(&self) -> Vec<u8> {
         serde_json::to_vec(&self).unwrap()
     }
+
+    // Replace a plain password with its hash under `template` and a fresh random salt
+    pub fn hash_password(&mut self, template: &MqttUserHash) -> Result<(), CommonError> {
+        if self.hash.algorithm != HashAlgorithm::Plain {
+            return Ok(());
+        }
+        let password = self.hash.salted(&self.password);
+        let mut hash = MqttUserHash {
+            algorithm: template.algorithm.clone(),
+            salt_position: template.salt_position.clone(),
+            ..Default::default()
+        };
+        if !matches!(hash.algorithm, HashAlgorithm::Plain | HashAlgorithm::Bcrypt) {
+            let mut salt = [0u8; 16];
+            rand::thread_rng().fill_bytes(&mut salt);
+            hash.salt = to_hex(&salt);
+        }
+        if hash.algorithm == HashAlgorithm::Pbkdf2 {
+            hash.iterations = template.iterations();
+        }
+        self.password = hash.hash(&password)?;
+        self.hash = hash;
+        Ok(())
+    }
+
+    // CPU bound for pbkdf2 and bcrypt, async callers should run it on a blocking thread
+    pub fn verify_password(&self, password: &str) -> bool {
+        if self.hash.algorithm == HashAlgorithm::Bcrypt {
+            return bcrypt::verify(self.hash.salted(password), &self.password).unwrap_or(false);
+        }
+        match self.hash.hash(password) {
+            Ok(hashed) => {
+                if self.hash.algorithm == HashAlgorithm::Plain {
+                    hashed.as_bytes().ct_eq(self.password.as_bytes()).into()
+                } else {
+                    // digests are stored as hex in either case
+                    let stored = self.password.to_ascii_lowercase();
+                    hashed.as_bytes().ct_eq(stored.as_bytes()).into()
+                }
+            }
+            Err(_) => false,
+        }
+    }
+
+    // (SaltedPassword, salt, iterations) of SCRAM-SHA-256 for a pbkdf2 user
+    pub fn scram_credentials(&self) -> Option<(Vec<u8>, Vec<u8>, u32)> {
+        if self.hash.algorithm != HashAlgorithm::Pbkdf2 {
+            return None;
+        }
+        let salted_password = from_hex(&self.password)?;
+        if salted_password.len() != 32 {
+            return None;
+        }
+        Some((
+            salted_password,
+            self.hash.salt.as_bytes().to_vec(),
+            self.hash.iterations(),
+        ))
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+#[serde(default)]
+pub struct MqttUserHash {
+    pub algorithm: HashAlgorithm,
+    // Mixed into the password before hashing, pbkdf2 uses it as the KDF salt
+    pub salt: String,
+    pub salt_position: SaltPosition,
+    // pbkdf2 only, 0 uses DEFAULT_PBKDF2_ITERATIONS
+    pub iterations: u32,
+}
+
+impl MqttUserHash {
+    // The configured hash settings, the salt is filled in per user
+    pub fn from_conf(conf: &AuthPasswordHash) -> Result<Self, CommonError> {
+        Ok(MqttUserHash {
+            algorithm: HashAlgorithm::from_str(&conf.algorithm)?,
+            salt: "".to_string(),
+            salt_position: SaltPosition::from_str(&conf.salt_position)?,
+            iterations: conf.iterations,
+        })
+    }
+
+    // The stored form of `password`, hex encoded for the digest algorithms
+    pub fn hash(&self, password: &str) -> Result<String, CommonError> {
+        match self.algorithm {
+            HashAlgorithm::Plain => Ok(self.salted(password)),
+            HashAlgorithm::Sha256 => Ok(to_hex(&Sha256::digest(self.salted(password)))),
+            HashAlgorithm::Sha512 => Ok(to_hex(&Sha512::digest(self.salted(password)))),
+            HashAlgorithm::Pbkdf2 => {
+                let mut key = [0u8; 32];
+                pbkdf2_hmac::<Sha256>(
+                    password.as_bytes(),
+                    self.salt.as_bytes(),
+                    self.iterations(),
+                    &mut key,
+                );
+                Ok(to_hex(&key))
+            }
+            HashAlgorithm::Bcrypt => bcrypt::hash(self.salted(password), bcrypt::DEFAULT_COST)
+                .map_err(|e| CommonError::CommmonError(e.to_string())),
+        }
+    }
+
+    fn iterations(&self) -> u32 {
+        if self.iterations == 0 {
+            DEFAULT_PBKDF2_ITERATIONS
+        } else {
+            self.iterations
+        }
+    }
+
+    fn salted(&self, password: &str) -> String {
+        if self.algorithm == HashAlgorithm::Pbkdf2 {
+            return password.to_string();
+        }
+        match self.salt_position {
+            SaltPosition::Prefix => format!("{}{}", self.salt, password),
+            SaltPosition::Suffix => format!("{}{}", password, self.salt),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum HashAlgorithm {
+    #[default]
+    Plain,
+    Sha256,
+    Sha512,
+    Pbkdf2,
+    Bcrypt,
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = CommonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "" | "plain" => Ok(HashAlgorithm::Plain),
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            "sha512" => Ok(HashAlgorithm::Sha512),
+            "pbkdf2" => Ok(HashAlgorithm::Pbkdf2),
+            "bcrypt" => Ok(HashAlgorithm::Bcrypt),
+            _ => Err(CommonError::CommmonError(format!(
+                "unsupported password hash algorithm {}",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize, Debug, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum SaltPosition {
+    #[default]
+    Prefix,
+    Suffix,
+}
+
+impl FromStr for SaltPosition {
+    type Err = CommonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "" | "prefix" => Ok(SaltPosition::Prefix),
+            "suffix" => Ok(SaltPosition::Suffix),
+            _ => Err(CommonError::CommmonError(format!(
+                "unsupported salt position {}",
+                s
+            ))),
+        }
+    }
+}
+
+fn to_hex(data: &[u8]) -> String {
+    data.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex(data: &str) -> Option<Vec<u8>> {
+    data.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            let pair = std::str::from_utf8(pair)
+                .ok()
+                .filter(|pair| pair.len() == 2)?;
+            u8::from_str_radix(pair, 16).ok()
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use common_base::config::common::AuthPasswordHash;
+
+    use super::{HashAlgorithm, MqttUser, MqttUserHash, SaltPosition};
+
+    fn hash_of(algorithm: HashAlgorithm) -> MqttUserHash {
+        MqttUserHash {
+            algorithm,
+            ..Default::default()
+        }
+    }
+
+    fn user(password: &str, hash: MqttUserHash) -> MqttUser {
+        MqttUser {
+            username: "robustmq".to_string(),
+            password: password.to_string(),
+            is_superuser: false,
+            hash,
+        }
+    }
+
+    #[test]
+    fn verify_password_test() {
+        let plain = user("pwd123", MqttUserHash::default());
+        assert!(plain.verify_password("pwd123"));
+        assert!(!plain.verify_password("pwd1234"));
+
+        // echo -n "saltpwd123" | sha256sum
+        let sha256 = user(
+            "1C76576362C925B9522842884F30DCB824EA61CE9BE4BD104331352911D5C4A2",
+            MqttUserHash {
+                algorithm: HashAlgorithm::Sha256,
+                salt: "salt".to_string(),
+                ..Default::default()
+            },
+        );
+        assert!(sha256.verify_password("pwd123"));
+        assert!(!sha256.verify_password("pwd"));
+
+        let suffix = MqttUserHash {
+            algorithm: HashAlgorithm::Sha512,
+            salt: "salt".to_string(),
+            salt_position: SaltPosition::Suffix,
+            ..Default::default()
+        };
+        let prefix = MqttUserHash {
+            salt_position: SaltPosition::Prefix,
+            ..suffix.clone()
+        };
+        assert_ne!(suffix.hash("pwd").unwrap(), prefix.hash("pwd").unwrap());
+        assert_eq!(suffix.hash("pwd").unwrap().len(), 128);
+    }
+
+    #[test]
+    fn hash_password_test() {
+        for algorithm in [
+            HashAlgorithm::Sha256,
+            HashAlgorithm::Sha512,
+            HashAlgorithm::Pbkdf2,
+            HashAlgorithm::Bcrypt,
+        ] {
+            let mut mqtt_user = user("pwd123", MqttUserHash::default());
+            mqtt_user
+                .hash_password(&hash_of(algorithm.clone()))
+                .unwrap();
+            assert_eq!(mqtt_user.hash.algorithm, algorithm);
+            assert_ne!(mqtt_user.password, "pwd123");
+            assert!(mqtt_user.verify_password("pwd123"));
+            assert!(!mqtt_user.verify_password("pwd1234"));
+
+            // already hashed users are left alone
+            let password = mqtt_user.password.clone();
+            mqtt_user
+                .hash_password(&hash_of(HashAlgorithm::Sha256))
+                .unwrap();
+            assert_eq!(mqtt_user.password, password);
+        }
+
+        let mut mqtt_user = user("pwd123", MqttUserHash::default());
+        assert!(mqtt_user.scram_credentials().is_none());
+        mqtt_user
+            .hash_password(&hash_of(HashAlgorithm::Pbkdf2))
+            .unwrap();
+        let (salted_password, salt, iterations) = mqtt_user.scram_credentials().unwrap();
+        assert_eq!(salted_password.len(), 32);
+        assert_eq!(salt, mqtt_user.hash.salt.as_bytes());
+        assert_eq!(iterations, 4096);
+
+        // the configured settings are kept, only the salt is generated
+        let conf = AuthPasswordHash {
+            algorithm: "pbkdf2".to_string(),
+            salt_position: "suffix".to_string(),
+            iterations: 1000,
+        };
+        let mut mqtt_user = user("pwd123", MqttUserHash::default());
+        mqtt_user
+            .hash_password(&MqttUserHash::from_conf(&conf).unwrap())
+            .unwrap();
+        assert_eq!(mqtt_user.hash.iterations, 1000);
+        assert_eq!(mqtt_user.hash.salt_position, SaltPosition::Suffix);
+        assert!(mqtt_user.verify_password("pwd123"));
+        assert!(MqttUserHash::from_conf(&AuthPasswordHash {
+            algorithm: "md5".to_string(),
+            ..Default::default()
+        })
+        .is_err());
+
+        let raw = r#"{"username":"u","password":"p","is_superuser":false}"#;
+        let mqtt_user = serde_json::from_str::<MqttUser>(raw).unwrap();
+        assert_eq!(mqtt_user.hash.algorithm, HashAlgorithm::Plain);
+        assert!(mqtt_user.verify_password("p"));
+    }
 }
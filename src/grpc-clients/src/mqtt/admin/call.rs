@@ -16,7 +16,18 @@ use std::sync::Arc;
 
 use common_base::error::common::CommonError;
 use prost::Message as _;
-use protocol::broker_mqtt::broker_mqtt_admin::{ClusterStatusReply, ClusterStatusRequest};
+use protocol::broker_mqtt::broker_mqtt_admin::{
+    ClusterStatusReply, ClusterStatusRequest, CreateAclReply, CreateAclRequest,
+    CreateBlacklistReply, CreateBlacklistRequest, CreateUserReply, CreateUserRequest,
+    DeleteAclReply, DeleteAclRequest, DeleteBlacklistReply, DeleteBlacklistRequest,
+    DeleteSessionReply, DeleteSessionRequest, DeleteUserReply, DeleteUserRequest,
+    DisconnectClientReply, DisconnectClientRequest, GetClusterConfigReply, GetClusterConfigRequest,
+    ListAclReply, ListAclRequest, ListAlarmReply, ListAlarmRequest, ListBlacklistReply,
+    ListBlacklistRequest, ListConnectionReply, ListConnectionRequest, ListSessionReply,
+    ListSessionRequest, ListSlowSubscribeReply, ListSlowSubscribeRequest, ListSubscribeReply,
+    ListSubscribeRequest, ListUserReply, ListUserRequest, SetClusterConfigReply,
+    SetClusterConfigRequest,
+};
 
 use crate::mqtt::{retry_call, MQTTBrokerPlacementInterface, MQTTBrokerService};
 use crate::poll::ClientPool;
@@ -43,3 +54,417 @@ pub async fn cluster_status(
         Err(e) => Err(e),
     }
 }
+
+pub async fn list_connection(
+    client_poll: Arc<ClientPool>,
+    addrs: Vec<String>,
+    request: ListConnectionRequest,
+) -> Result<ListConnectionReply, CommonError> {
+    let request_data = ListConnectionRequest::encode_to_vec(&request);
+    match retry_call(
+        MQTTBrokerService::Admin,
+        MQTTBrokerPlacementInterface::ListConnection,
+        client_poll,
+        addrs,
+        request_data,
+    )
+    .await
+    {
+        Ok(data) => match ListConnectionReply::decode(data.as_ref()) {
+            Ok(da) => Ok(da),
+            Err(e) => Err(CommonError::CommmonError(e.to_string())),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn list_subscribe(
+    client_poll: Arc<ClientPool>,
+    addrs: Vec<String>,
+    request: ListSubscribeRequest,
+) -> Result<ListSubscribeReply, CommonError> {
+    let request_data = ListSubscribeRequest::encode_to_vec(&request);
+    match retry_call(
+        MQTTBrokerService::Admin,
+        MQTTBrokerPlacementInterface::ListSubscribe,
+        client_poll,
+        addrs,
+        request_data,
+    )
+    .await
+    {
+        Ok(data) => match ListSubscribeReply::decode(data.as_ref()) {
+            Ok(da) => Ok(da),
+            Err(e) => Err(CommonError::CommmonError(e.to_string())),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn disconnect_client(
+    client_poll: Arc<ClientPool>,
+    addrs: Vec<String>,
+    request: DisconnectClientRequest,
+) -> Result<DisconnectClientReply, CommonError> {
+    let request_data = DisconnectClientRequest::encode_to_vec(&request);
+    match retry_call(
+        MQTTBrokerService::Admin,
+        MQTTBrokerPlacementInterface::DisconnectClient,
+        client_poll,
+        addrs,
+        request_data,
+    )
+    .await
+    {
+        Ok(data) => match DisconnectClientReply::decode(data.as_ref()) {
+            Ok(da) => Ok(da),
+            Err(e) => Err(CommonError::CommmonError(e.to_string())),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn list_session(
+    client_poll: Arc<ClientPool>,
+    addrs: Vec<String>,
+    request: ListSessionRequest,
+) -> Result<ListSessionReply, CommonError> {
+    let request_data = ListSessionRequest::encode_to_vec(&request);
+    match retry_call(
+        MQTTBrokerService::Admin,
+        MQTTBrokerPlacementInterface::ListSession,
+        client_poll,
+        addrs,
+        request_data,
+    )
+    .await
+    {
+        Ok(data) => match ListSessionReply::decode(data.as_ref()) {
+            Ok(da) => Ok(da),
+            Err(e) => Err(CommonError::CommmonError(e.to_string())),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn delete_session(
+    client_poll: Arc<ClientPool>,
+    addrs: Vec<String>,
+    request: DeleteSessionRequest,
+) -> Result<DeleteSessionReply, CommonError> {
+    let request_data = DeleteSessionRequest::encode_to_vec(&request);
+    match retry_call(
+        MQTTBrokerService::Admin,
+        MQTTBrokerPlacementInterface::DeleteSession,
+        client_poll,
+        addrs,
+        request_data,
+    )
+    .await
+    {
+        Ok(data) => match DeleteSessionReply::decode(data.as_ref()) {
+            Ok(da) => Ok(da),
+            Err(e) => Err(CommonError::CommmonError(e.to_string())),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn list_user(
+    client_poll: Arc<ClientPool>,
+    addrs: Vec<String>,
+    request: ListUserRequest,
+) -> Result<ListUserReply, CommonError> {
+    let request_data = ListUserRequest::encode_to_vec(&request);
+    match retry_call(
+        MQTTBrokerService::Admin,
+        MQTTBrokerPlacementInterface::ListUser,
+        client_poll,
+        addrs,
+        request_data,
+    )
+    .await
+    {
+        Ok(data) => match ListUserReply::decode(data.as_ref()) {
+            Ok(da) => Ok(da),
+            Err(e) => Err(CommonError::CommmonError(e.to_string())),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn create_user(
+    client_poll: Arc<ClientPool>,
+    addrs: Vec<String>,
+    request: CreateUserRequest,
+) -> Result<CreateUserReply, CommonError> {
+    let request_data = CreateUserRequest::encode_to_vec(&request);
+    match retry_call(
+        MQTTBrokerService::Admin,
+        MQTTBrokerPlacementInterface::CreateUser,
+        client_poll,
+        addrs,
+        request_data,
+    )
+    .await
+    {
+        Ok(data) => match CreateUserReply::decode(data.as_ref()) {
+            Ok(da) => Ok(da),
+            Err(e) => Err(CommonError::CommmonError(e.to_string())),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn delete_user(
+    client_poll: Arc<ClientPool>,
+    addrs: Vec<String>,
+    request: DeleteUserRequest,
+) -> Result<DeleteUserReply, CommonError> {
+    let request_data = DeleteUserRequest::encode_to_vec(&request);
+    match retry_call(
+        MQTTBrokerService::Admin,
+        MQTTBrokerPlacementInterface::DeleteUser,
+        client_poll,
+        addrs,
+        request_data,
+    )
+    .await
+    {
+        Ok(data) => match DeleteUserReply::decode(data.as_ref()) {
+            Ok(da) => Ok(da),
+            Err(e) => Err(CommonError::CommmonError(e.to_string())),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn list_acl(
+    client_poll: Arc<ClientPool>,
+    addrs: Vec<String>,
+    request: ListAclRequest,
+) -> Result<ListAclReply, CommonError> {
+    let request_data = ListAclRequest::encode_to_vec(&request);
+    match retry_call(
+        MQTTBrokerService::Admin,
+        MQTTBrokerPlacementInterface::ListAcl,
+        client_poll,
+        addrs,
+        request_data,
+    )
+    .await
+    {
+        Ok(data) => match ListAclReply::decode(data.as_ref()) {
+            Ok(da) => Ok(da),
+            Err(e) => Err(CommonError::CommmonError(e.to_string())),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn create_acl(
+    client_poll: Arc<ClientPool>,
+    addrs: Vec<String>,
+    request: CreateAclRequest,
+) -> Result<CreateAclReply, CommonError> {
+    let request_data = CreateAclRequest::encode_to_vec(&request);
+    match retry_call(
+        MQTTBrokerService::Admin,
+        MQTTBrokerPlacementInterface::CreateAcl,
+        client_poll,
+        addrs,
+        request_data,
+    )
+    .await
+    {
+        Ok(data) => match CreateAclReply::decode(data.as_ref()) {
+            Ok(da) => Ok(da),
+            Err(e) => Err(CommonError::CommmonError(e.to_string())),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn delete_acl(
+    client_poll: Arc<ClientPool>,
+    addrs: Vec<String>,
+    request: DeleteAclRequest,
+) -> Result<DeleteAclReply, CommonError> {
+    let request_data = DeleteAclRequest::encode_to_vec(&request);
+    match retry_call(
+        MQTTBrokerService::Admin,
+        MQTTBrokerPlacementInterface::DeleteAcl,
+        client_poll,
+        addrs,
+        request_data,
+    )
+    .await
+    {
+        Ok(data) => match DeleteAclReply::decode(data.as_ref()) {
+            Ok(da) => Ok(da),
+            Err(e) => Err(CommonError::CommmonError(e.to_string())),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn list_blacklist(
+    client_poll: Arc<ClientPool>,
+    addrs: Vec<String>,
+    request: ListBlacklistRequest,
+) -> Result<ListBlacklistReply, CommonError> {
+    let request_data = ListBlacklistRequest::encode_to_vec(&request);
+    match retry_call(
+        MQTTBrokerService::Admin,
+        MQTTBrokerPlacementInterface::ListBlacklist,
+        client_poll,
+        addrs,
+        request_data,
+    )
+    .await
+    {
+        Ok(data) => match ListBlacklistReply::decode(data.as_ref()) {
+            Ok(da) => Ok(da),
+            Err(e) => Err(CommonError::CommmonError(e.to_string())),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn create_blacklist(
+    client_poll: Arc<ClientPool>,
+    addrs: Vec<String>,
+    request: CreateBlacklistRequest,
+) -> Result<CreateBlacklistReply, CommonError> {
+    let request_data = CreateBlacklistRequest::encode_to_vec(&request);
+    match retry_call(
+        MQTTBrokerService::Admin,
+        MQTTBrokerPlacementInterface::CreateBlacklist,
+        client_poll,
+        addrs,
+        request_data,
+    )
+    .await
+    {
+        Ok(data) => match CreateBlacklistReply::decode(data.as_ref()) {
+            Ok(da) => Ok(da),
+            Err(e) => Err(CommonError::CommmonError(e.to_string())),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn delete_blacklist(
+    client_poll: Arc<ClientPool>,
+    addrs: Vec<String>,
+    request: DeleteBlacklistRequest,
+) -> Result<DeleteBlacklistReply, CommonError> {
+    let request_data = DeleteBlacklistRequest::encode_to_vec(&request);
+    match retry_call(
+        MQTTBrokerService::Admin,
+        MQTTBrokerPlacementInterface::DeleteBlacklist,
+        client_poll,
+        addrs,
+        request_data,
+    )
+    .await
+    {
+        Ok(data) => match DeleteBlacklistReply::decode(data.as_ref()) {
+            Ok(da) => Ok(da),
+            Err(e) => Err(CommonError::CommmonError(e.to_string())),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn get_cluster_config(
+    client_poll: Arc<ClientPool>,
+    addrs: Vec<String>,
+    request: GetClusterConfigRequest,
+) -> Result<GetClusterConfigReply, CommonError> {
+    let request_data = GetClusterConfigRequest::encode_to_vec(&request);
+    match retry_call(
+        MQTTBrokerService::Admin,
+        MQTTBrokerPlacementInterface::GetClusterConfig,
+        client_poll,
+        addrs,
+        request_data,
+    )
+    .await
+    {
+        Ok(data) => match GetClusterConfigReply::decode(data.as_ref()) {
+            Ok(da) => Ok(da),
+            Err(e) => Err(CommonError::CommmonError(e.to_string())),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn set_cluster_config(
+    client_poll: Arc<ClientPool>,
+    addrs: Vec<String>,
+    request: SetClusterConfigRequest,
+) -> Result<SetClusterConfigReply, CommonError> {
+    let request_data = SetClusterConfigRequest::encode_to_vec(&request);
+    match retry_call(
+        MQTTBrokerService::Admin,
+        MQTTBrokerPlacementInterface::SetClusterConfig,
+        client_poll,
+        addrs,
+        request_data,
+    )
+    .await
+    {
+        Ok(data) => match SetClusterConfigReply::decode(data.as_ref()) {
+            Ok(da) => Ok(da),
+            Err(e) => Err(CommonError::CommmonError(e.to_string())),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn list_slow_subscribe(
+    client_poll: Arc<ClientPool>,
+    addrs: Vec<String>,
+    request: ListSlowSubscribeRequest,
+) -> Result<ListSlowSubscribeReply, CommonError> {
+    let request_data = ListSlowSubscribeRequest::encode_to_vec(&request);
+    match retry_call(
+        MQTTBrokerService::Admin,
+        MQTTBrokerPlacementInterface::ListSlowSubscribe,
+        client_poll,
+        addrs,
+        request_data,
+    )
+    .await
+    {
+        Ok(data) => match ListSlowSubscribeReply::decode(data.as_ref()) {
+            Ok(da) => Ok(da),
+            Err(e) => Err(CommonError::CommmonError(e.to_string())),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn list_alarm(
+    client_poll: Arc<ClientPool>,
+    addrs: Vec<String>,
+    request: ListAlarmRequest,
+) -> Result<ListAlarmReply, CommonError> {
+    let request_data = ListAlarmRequest::encode_to_vec(&request);
+    match retry_call(
+        MQTTBrokerService::Admin,
+        MQTTBrokerPlacementInterface::ListAlarm,
+        client_poll,
+        addrs,
+        request_data,
+    )
+    .await
+    {
+        Ok(data) => match ListAlarmReply::decode(data.as_ref()) {
+            Ok(da) => Ok(da),
+            Err(e) => Err(CommonError::CommmonError(e.to_string())),
+        },
+        Err(e) => Err(e),
+    }
+}
@@ -15,7 +15,18 @@
 use common_base::error::common::CommonError;
 use mobc::Connection;
 use prost::Message;
-use protocol::broker_mqtt::broker_mqtt_admin::{ClusterStatusReply, ClusterStatusRequest};
+use protocol::broker_mqtt::broker_mqtt_admin::{
+    ClusterStatusReply, ClusterStatusRequest, CreateAclReply, CreateAclRequest,
+    CreateBlacklistReply, CreateBlacklistRequest, CreateUserReply, CreateUserRequest,
+    DeleteAclReply, DeleteAclRequest, DeleteBlacklistReply, DeleteBlacklistRequest,
+    DeleteSessionReply, DeleteSessionRequest, DeleteUserReply, DeleteUserRequest,
+    DisconnectClientReply, DisconnectClientRequest, GetClusterConfigReply, GetClusterConfigRequest,
+    ListAclReply, ListAclRequest, ListAlarmReply, ListAlarmRequest, ListBlacklistReply,
+    ListBlacklistRequest, ListConnectionReply, ListConnectionRequest, ListSessionReply,
+    ListSessionRequest, ListSlowSubscribeReply, ListSlowSubscribeRequest, ListSubscribeReply,
+    ListSubscribeRequest, ListUserReply, ListUserRequest, SetClusterConfigReply,
+    SetClusterConfigRequest,
+};
 
 use super::MqttBrokerAdminServiceManager;
 
@@ -31,3 +42,237 @@ pub(crate) async fn inner_cluster_status(
         Err(e) => Err(CommonError::CommmonError(e.to_string())),
     }
 }
+
+pub(crate) async fn inner_list_connection(
+    mut client: Connection<MqttBrokerAdminServiceManager>,
+    request: Vec<u8>,
+) -> Result<Vec<u8>, CommonError> {
+    match ListConnectionRequest::decode(request.as_ref()) {
+        Ok(request) => match client.list_connection(request).await {
+            Ok(result) => Ok(ListConnectionReply::encode_to_vec(&result.into_inner())),
+            Err(e) => Err(CommonError::GrpcServerStatus(e)),
+        },
+        Err(e) => Err(CommonError::CommmonError(e.to_string())),
+    }
+}
+
+pub(crate) async fn inner_list_subscribe(
+    mut client: Connection<MqttBrokerAdminServiceManager>,
+    request: Vec<u8>,
+) -> Result<Vec<u8>, CommonError> {
+    match ListSubscribeRequest::decode(request.as_ref()) {
+        Ok(request) => match client.list_subscribe(request).await {
+            Ok(result) => Ok(ListSubscribeReply::encode_to_vec(&result.into_inner())),
+            Err(e) => Err(CommonError::GrpcServerStatus(e)),
+        },
+        Err(e) => Err(CommonError::CommmonError(e.to_string())),
+    }
+}
+
+pub(crate) async fn inner_disconnect_client(
+    mut client: Connection<MqttBrokerAdminServiceManager>,
+    request: Vec<u8>,
+) -> Result<Vec<u8>, CommonError> {
+    match DisconnectClientRequest::decode(request.as_ref()) {
+        Ok(request) => match client.disconnect_client(request).await {
+            Ok(result) => Ok(DisconnectClientReply::encode_to_vec(&result.into_inner())),
+            Err(e) => Err(CommonError::GrpcServerStatus(e)),
+        },
+        Err(e) => Err(CommonError::CommmonError(e.to_string())),
+    }
+}
+
+pub(crate) async fn inner_list_session(
+    mut client: Connection<MqttBrokerAdminServiceManager>,
+    request: Vec<u8>,
+) -> Result<Vec<u8>, CommonError> {
+    match ListSessionRequest::decode(request.as_ref()) {
+        Ok(request) => match client.list_session(request).await {
+            Ok(result) => Ok(ListSessionReply::encode_to_vec(&result.into_inner())),
+            Err(e) => Err(CommonError::GrpcServerStatus(e)),
+        },
+        Err(e) => Err(CommonError::CommmonError(e.to_string())),
+    }
+}
+
+pub(crate) async fn inner_delete_session(
+    mut client: Connection<MqttBrokerAdminServiceManager>,
+    request: Vec<u8>,
+) -> Result<Vec<u8>, CommonError> {
+    match DeleteSessionRequest::decode(request.as_ref()) {
+        Ok(request) => match client.delete_session(request).await {
+            Ok(result) => Ok(DeleteSessionReply::encode_to_vec(&result.into_inner())),
+            Err(e) => Err(CommonError::GrpcServerStatus(e)),
+        },
+        Err(e) => Err(CommonError::CommmonError(e.to_string())),
+    }
+}
+
+pub(crate) async fn inner_list_user(
+    mut client: Connection<MqttBrokerAdminServiceManager>,
+    request: Vec<u8>,
+) -> Result<Vec<u8>, CommonError> {
+    match ListUserRequest::decode(request.as_ref()) {
+        Ok(request) => match client.list_user(request).await {
+            Ok(result) => Ok(ListUserReply::encode_to_vec(&result.into_inner())),
+            Err(e) => Err(CommonError::GrpcServerStatus(e)),
+        },
+        Err(e) => Err(CommonError::CommmonError(e.to_string())),
+    }
+}
+
+pub(crate) async fn inner_create_user(
+    mut client: Connection<MqttBrokerAdminServiceManager>,
+    request: Vec<u8>,
+) -> Result<Vec<u8>, CommonError> {
+    match CreateUserRequest::decode(request.as_ref()) {
+        Ok(request) => match client.create_user(request).await {
+            Ok(result) => Ok(CreateUserReply::encode_to_vec(&result.into_inner())),
+            Err(e) => Err(CommonError::GrpcServerStatus(e)),
+        },
+        Err(e) => Err(CommonError::CommmonError(e.to_string())),
+    }
+}
+
+pub(crate) async fn inner_delete_user(
+    mut client: Connection<MqttBrokerAdminServiceManager>,
+    request: Vec<u8>,
+) -> Result<Vec<u8>, CommonError> {
+    match DeleteUserRequest::decode(request.as_ref()) {
+        Ok(request) => match client.delete_user(request).await {
+            Ok(result) => Ok(DeleteUserReply::encode_to_vec(&result.into_inner())),
+            Err(e) => Err(CommonError::GrpcServerStatus(e)),
+        },
+        Err(e) => Err(CommonError::CommmonError(e.to_string())),
+    }
+}
+
+pub(crate) async fn inner_list_acl(
+    mut client: Connection<MqttBrokerAdminServiceManager>,
+    request: Vec<u8>,
+) -> Result<Vec<u8>, CommonError> {
+    match ListAclRequest::decode(request.as_ref()) {
+        Ok(request) => match client.list_acl(request).await {
+            Ok(result) => Ok(ListAclReply::encode_to_vec(&result.into_inner())),
+            Err(e) => Err(CommonError::GrpcServerStatus(e)),
+        },
+        Err(e) => Err(CommonError::CommmonError(e.to_string())),
+    }
+}
+
+pub(crate) async fn inner_create_acl(
+    mut client: Connection<MqttBrokerAdminServiceManager>,
+    request: Vec<u8>,
+) -> Result<Vec<u8>, CommonError> {
+    match CreateAclRequest::decode(request.as_ref()) {
+        Ok(request) => match client.create_acl(request).await {
+            Ok(result) => Ok(CreateAclReply::encode_to_vec(&result.into_inner())),
+            Err(e) => Err(CommonError::GrpcServerStatus(e)),
+        },
+        Err(e) => Err(CommonError::CommmonError(e.to_string())),
+    }
+}
+
+pub(crate) async fn inner_delete_acl(
+    mut client: Connection<MqttBrokerAdminServiceManager>,
+    request: Vec<u8>,
+) -> Result<Vec<u8>, CommonError> {
+    match DeleteAclRequest::decode(request.as_ref()) {
+        Ok(request) => match client.delete_acl(request).await {
+            Ok(result) => Ok(DeleteAclReply::encode_to_vec(&result.into_inner())),
+            Err(e) => Err(CommonError::GrpcServerStatus(e)),
+        },
+        Err(e) => Err(CommonError::CommmonError(e.to_string())),
+    }
+}
+
+pub(crate) async fn inner_list_blacklist(
+    mut client: Connection<MqttBrokerAdminServiceManager>,
+    request: Vec<u8>,
+) -> Result<Vec<u8>, CommonError> {
+    match ListBlacklistRequest::decode(request.as_ref()) {
+        Ok(request) => match client.list_blacklist(request).await {
+            Ok(result) => Ok(ListBlacklistReply::encode_to_vec(&result.into_inner())),
+            Err(e) => Err(CommonError::GrpcServerStatus(e)),
+        },
+        Err(e) => Err(CommonError::CommmonError(e.to_string())),
+    }
+}
+
+pub(crate) async fn inner_create_blacklist(
+    mut client: Connection<MqttBrokerAdminServiceManager>,
+    request: Vec<u8>,
+) -> Result<Vec<u8>, CommonError> {
+    match CreateBlacklistRequest::decode(request.as_ref()) {
+        Ok(request) => match client.create_blacklist(request).await {
+            Ok(result) => Ok(CreateBlacklistReply::encode_to_vec(&result.into_inner())),
+            Err(e) => Err(CommonError::GrpcServerStatus(e)),
+        },
+        Err(e) => Err(CommonError::CommmonError(e.to_string())),
+    }
+}
+
+pub(crate) async fn inner_delete_blacklist(
+    mut client: Connection<MqttBrokerAdminServiceManager>,
+    request: Vec<u8>,
+) -> Result<Vec<u8>, CommonError> {
+    match DeleteBlacklistRequest::decode(request.as_ref()) {
+        Ok(request) => match client.delete_blacklist(request).await {
+            Ok(result) => Ok(DeleteBlacklistReply::encode_to_vec(&result.into_inner())),
+            Err(e) => Err(CommonError::GrpcServerStatus(e)),
+        },
+        Err(e) => Err(CommonError::CommmonError(e.to_string())),
+    }
+}
+
+pub(crate) async fn inner_get_cluster_config(
+    mut client: Connection<MqttBrokerAdminServiceManager>,
+    request: Vec<u8>,
+) -> Result<Vec<u8>, CommonError> {
+    match GetClusterConfigRequest::decode(request.as_ref()) {
+        Ok(request) => match client.get_cluster_config(request).await {
+            Ok(result) => Ok(GetClusterConfigReply::encode_to_vec(&result.into_inner())),
+            Err(e) => Err(CommonError::GrpcServerStatus(e)),
+        },
+        Err(e) => Err(CommonError::CommmonError(e.to_string())),
+    }
+}
+
+pub(crate) async fn inner_set_cluster_config(
+    mut client: Connection<MqttBrokerAdminServiceManager>,
+    request: Vec<u8>,
+) -> Result<Vec<u8>, CommonError> {
+    match SetClusterConfigRequest::decode(request.as_ref()) {
+        Ok(request) => match client.set_cluster_config(request).await {
+            Ok(result) => Ok(SetClusterConfigReply::encode_to_vec(&result.into_inner())),
+            Err(e) => Err(CommonError::GrpcServerStatus(e)),
+        },
+        Err(e) => Err(CommonError::CommmonError(e.to_string())),
+    }
+}
+
+pub(crate) async fn inner_list_slow_subscribe(
+    mut client: Connection<MqttBrokerAdminServiceManager>,
+    request: Vec<u8>,
+) -> Result<Vec<u8>, CommonError> {
+    match ListSlowSubscribeRequest::decode(request.as_ref()) {
+        Ok(request) => match client.list_slow_subscribe(request).await {
+            Ok(result) => Ok(ListSlowSubscribeReply::encode_to_vec(&result.into_inner())),
+            Err(e) => Err(CommonError::GrpcServerStatus(e)),
+        },
+        Err(e) => Err(CommonError::CommmonError(e.to_string())),
+    }
+}
+
+pub(crate) async fn inner_list_alarm(
+    mut client: Connection<MqttBrokerAdminServiceManager>,
+    request: Vec<u8>,
+) -> Result<Vec<u8>, CommonError> {
+    match ListAlarmRequest::decode(request.as_ref()) {
+        Ok(request) => match client.list_alarm(request).await {
+            Ok(result) => Ok(ListAlarmReply::encode_to_vec(&result.into_inner())),
+            Err(e) => Err(CommonError::GrpcServerStatus(e)),
+        },
+        Err(e) => Err(CommonError::CommmonError(e.to_string())),
+    }
+}
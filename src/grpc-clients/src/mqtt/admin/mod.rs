@@ -15,7 +15,7 @@
 use std::sync::Arc;
 
 use common_base::error::common::CommonError;
-use inner::inner_cluster_status;
+use inner::*;
 use mobc::{Connection, Manager};
 use protocol::broker_mqtt::broker_mqtt_admin::mqtt_broker_admin_service_client::MqttBrokerAdminServiceClient;
 use tonic::transport::Channel;
@@ -48,6 +48,50 @@ pub(crate) async fn admin_interface_call(
                 MQTTBrokerPlacementInterface::ClusterStatus => {
                     inner_cluster_status(client, request).await
                 }
+                MQTTBrokerPlacementInterface::ListConnection => {
+                    inner_list_connection(client, request).await
+                }
+                MQTTBrokerPlacementInterface::ListSubscribe => {
+                    inner_list_subscribe(client, request).await
+                }
+                MQTTBrokerPlacementInterface::DisconnectClient => {
+                    inner_disconnect_client(client, request).await
+                }
+                MQTTBrokerPlacementInterface::ListSession => {
+                    inner_list_session(client, request).await
+                }
+                MQTTBrokerPlacementInterface::DeleteSession => {
+                    inner_delete_session(client, request).await
+                }
+                MQTTBrokerPlacementInterface::ListUser => inner_list_user(client, request).await,
+                MQTTBrokerPlacementInterface::CreateUser => {
+                    inner_create_user(client, request).await
+                }
+                MQTTBrokerPlacementInterface::DeleteUser => {
+                    inner_delete_user(client, request).await
+                }
+                MQTTBrokerPlacementInterface::ListAcl => inner_list_acl(client, request).await,
+                MQTTBrokerPlacementInterface::CreateAcl => inner_create_acl(client, request).await,
+                MQTTBrokerPlacementInterface::DeleteAcl => inner_delete_acl(client, request).await,
+                MQTTBrokerPlacementInterface::ListBlacklist => {
+                    inner_list_blacklist(client, request).await
+                }
+                MQTTBrokerPlacementInterface::CreateBlacklist => {
+                    inner_create_blacklist(client, request).await
+                }
+                MQTTBrokerPlacementInterface::DeleteBlacklist => {
+                    inner_delete_blacklist(client, request).await
+                }
+                MQTTBrokerPlacementInterface::GetClusterConfig => {
+                    inner_get_cluster_config(client, request).await
+                }
+                MQTTBrokerPlacementInterface::SetClusterConfig => {
+                    inner_set_cluster_config(client, request).await
+                }
+                MQTTBrokerPlacementInterface::ListSlowSubscribe => {
+                    inner_list_slow_subscribe(client, request).await
+                }
+                MQTTBrokerPlacementInterface::ListAlarm => inner_list_alarm(client, request).await,
                 _ => {
                     return Err(CommonError::CommmonError(format!(
                         "admin service does not support service interfaces [{:?}]",
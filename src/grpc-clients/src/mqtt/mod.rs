@@ -36,9 +36,29 @@ pub enum MQTTBrokerPlacementInterface {
     DeleteSession,
     UpdateCache,
     SendLastWillMessage,
+    NotifyTopicMessage,
+    TakeoverSession,
 
     // admin
     ClusterStatus,
+    ListConnection,
+    ListSubscribe,
+    DisconnectClient,
+    ListSession,
+    DeleteSession,
+    ListUser,
+    CreateUser,
+    DeleteUser,
+    ListAcl,
+    CreateAcl,
+    DeleteAcl,
+    ListBlacklist,
+    CreateBlacklist,
+    DeleteBlacklist,
+    GetClusterConfig,
+    SetClusterConfig,
+    ListSlowSubscribe,
+    ListAlarm,
 }
 
 pub mod admin;
@@ -17,8 +17,9 @@ use std::sync::Arc;
 use common_base::error::common::CommonError;
 use prost::Message as _;
 use protocol::broker_mqtt::broker_mqtt_placement::{
-    DeleteSessionReply, DeleteSessionRequest, SendLastWillMessageReply, SendLastWillMessageRequest,
-    UpdateCacheReply, UpdateCacheRequest,
+    DeleteSessionReply, DeleteSessionRequest, NotifyTopicMessageReply, NotifyTopicMessageRequest,
+    SendLastWillMessageReply, SendLastWillMessageRequest, TakeoverSessionReply,
+    TakeoverSessionRequest, UpdateCacheReply, UpdateCacheRequest,
 };
 
 use crate::mqtt::{retry_call, MQTTBrokerPlacementInterface, MQTTBrokerService};
@@ -92,3 +93,49 @@ pub async fn send_last_will_message(
         Err(e) => Err(e),
     }
 }
+
+pub async fn broker_mqtt_notify_topic_message(
+    client_poll: Arc<ClientPool>,
+    addrs: Vec<String>,
+    request: NotifyTopicMessageRequest,
+) -> Result<NotifyTopicMessageReply, CommonError> {
+    let request_data = NotifyTopicMessageRequest::encode_to_vec(&request);
+    match retry_call(
+        MQTTBrokerService::Placement,
+        MQTTBrokerPlacementInterface::NotifyTopicMessage,
+        client_poll,
+        addrs,
+        request_data,
+    )
+    .await
+    {
+        Ok(data) => match NotifyTopicMessageReply::decode(data.as_ref()) {
+            Ok(da) => Ok(da),
+            Err(e) => Err(CommonError::CommmonError(e.to_string())),
+        },
+        Err(e) => Err(e),
+    }
+}
+
+pub async fn broker_mqtt_takeover_session(
+    client_poll: Arc<ClientPool>,
+    addrs: Vec<String>,
+    request: TakeoverSessionRequest,
+) -> Result<TakeoverSessionReply, CommonError> {
+    let request_data = TakeoverSessionRequest::encode_to_vec(&request);
+    match retry_call(
+        MQTTBrokerService::Placement,
+        MQTTBrokerPlacementInterface::TakeoverSession,
+        client_poll,
+        addrs,
+        request_data,
+    )
+    .await
+    {
+        Ok(data) => match TakeoverSessionReply::decode(data.as_ref()) {
+            Ok(da) => Ok(da),
+            Err(e) => Err(CommonError::CommmonError(e.to_string())),
+        },
+        Err(e) => Err(e),
+    }
+}
@@ -16,8 +16,9 @@ use common_base::error::common::CommonError;
 use mobc::Connection;
 use prost::Message;
 use protocol::broker_mqtt::broker_mqtt_placement::{
-    DeleteSessionReply, DeleteSessionRequest, SendLastWillMessageReply, SendLastWillMessageRequest,
-    UpdateCacheReply, UpdateCacheRequest,
+    DeleteSessionReply, DeleteSessionRequest, NotifyTopicMessageReply, NotifyTopicMessageRequest,
+    SendLastWillMessageReply, SendLastWillMessageRequest, TakeoverSessionReply,
+    TakeoverSessionRequest, UpdateCacheReply, UpdateCacheRequest,
 };
 
 use super::MqttBrokerPlacementServiceManager;
@@ -62,3 +63,29 @@ pub(crate) async fn inner_send_last_will_message(
         Err(e) => Err(CommonError::CommmonError(e.to_string())),
     }
 }
+
+pub(crate) async fn inner_notify_topic_message(
+    mut client: Connection<MqttBrokerPlacementServiceManager>,
+    request: Vec<u8>,
+) -> Result<Vec<u8>, CommonError> {
+    match NotifyTopicMessageRequest::decode(request.as_ref()) {
+        Ok(request) => match client.notify_topic_message(request).await {
+            Ok(result) => Ok(NotifyTopicMessageReply::encode_to_vec(&result.into_inner())),
+            Err(e) => Err(CommonError::GrpcServerStatus(e)),
+        },
+        Err(e) => Err(CommonError::CommmonError(e.to_string())),
+    }
+}
+
+pub(crate) async fn inner_takeover_session(
+    mut client: Connection<MqttBrokerPlacementServiceManager>,
+    request: Vec<u8>,
+) -> Result<Vec<u8>, CommonError> {
+    match TakeoverSessionRequest::decode(request.as_ref()) {
+        Ok(request) => match client.takeover_session(request).await {
+            Ok(result) => Ok(TakeoverSessionReply::encode_to_vec(&result.into_inner())),
+            Err(e) => Err(CommonError::GrpcServerStatus(e)),
+        },
+        Err(e) => Err(CommonError::CommmonError(e.to_string())),
+    }
+}
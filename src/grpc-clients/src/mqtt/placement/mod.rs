@@ -15,7 +15,10 @@
 use std::sync::Arc;
 
 use common_base::error::common::CommonError;
-use inner::{inner_delete_session, inner_send_last_will_message, inner_update_cache};
+use inner::{
+    inner_delete_session, inner_notify_topic_message, inner_send_last_will_message,
+    inner_takeover_session, inner_update_cache,
+};
 use mobc::{Connection, Manager};
 use protocol::broker_mqtt::broker_mqtt_placement::mqtt_broker_placement_service_client::MqttBrokerPlacementServiceClient;
 use tonic::transport::Channel;
@@ -54,6 +57,12 @@ pub(crate) async fn placement_interface_call(
                 MQTTBrokerPlacementInterface::SendLastWillMessage => {
                     inner_send_last_will_message(client, request).await
                 }
+                MQTTBrokerPlacementInterface::NotifyTopicMessage => {
+                    inner_notify_topic_message(client, request).await
+                }
+                MQTTBrokerPlacementInterface::TakeoverSession => {
+                    inner_takeover_session(client, request).await
+                }
                 _ => {
                     return Err(CommonError::CommmonError(format!(
                         "kv service does not support service interfaces [{:?}]",
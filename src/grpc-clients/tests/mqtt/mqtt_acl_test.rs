@@ -41,6 +41,7 @@ mod tests {
             ip: "*".to_string(),
             action: MqttAclAction::All,
             permission: MqttAclPermission::Deny,
+            priority: 0,
         };
 
         let request = CreateAclRequest {
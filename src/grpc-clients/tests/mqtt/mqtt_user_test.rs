@@ -39,6 +39,7 @@ mod tests {
             username: user_name.clone(),
             password: password.clone(),
             is_superuser: false,
+            hash: Default::default(),
         };
 
         let request: CreateUserRequest = CreateUserRequest {
@@ -63,7 +64,7 @@ mod tests {
                 let mut flag: bool = false;
                 for raw in data.users {
                     let user = serde_json::from_slice::<MqttUser>(raw.as_slice()).unwrap();
-                    if mqtt_user == user {
+                    if mqtt_user.username == user.username && user.verify_password(&password) {
                         flag = true;
                     }
                 }
@@ -96,7 +97,7 @@ mod tests {
                 let mut flag: bool = false;
                 for raw in data.users {
                     let user = serde_json::from_slice::<MqttUser>(raw.as_slice()).unwrap();
-                    if mqtt_user == user {
+                    if mqtt_user.username == user.username && user.verify_password(&password) {
                         flag = true;
                     }
                 }
@@ -0,0 +1,86 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use mqtt_broker::path_match;
+use mqtt_broker::TopicTrie;
+
+const TOPIC_NUM: usize = 10000;
+const FILTER_NUM: usize = 10000;
+
+fn topic_list() -> Vec<String> {
+    let mut list = Vec::with_capacity(TOPIC_NUM);
+    for i in 0..TOPIC_NUM {
+        list.push(format!("device/{}/sensor/{}/temperature", i % 100, i));
+    }
+    list
+}
+
+fn filter_list() -> Vec<String> {
+    let mut list = Vec::with_capacity(FILTER_NUM);
+    for i in 0..FILTER_NUM {
+        let filter = match i % 3 {
+            0 => format!("device/{}/sensor/+/temperature", i % 100),
+            1 => format!("device/{}/#", i % 100),
+            _ => format!("device/{}/sensor/{}/temperature", i % 100, i),
+        };
+        list.push(filter);
+    }
+    list
+}
+
+fn match_filter_benchmark(c: &mut Criterion) {
+    let topics = topic_list();
+    let trie = TopicTrie::new();
+    for topic in topics.iter() {
+        trie.insert(topic, topic.clone(), topic.clone());
+    }
+    let filter = "device/10/sensor/+/temperature".to_string();
+
+    let mut group = c.benchmark_group("match_filter");
+    group.bench_function("trie", |b| b.iter(|| trie.match_filter(black_box(&filter))));
+    group.bench_function("scan", |b| {
+        b.iter(|| {
+            topics
+                .iter()
+                .filter(|topic| path_match(topic.to_string(), black_box(filter.clone())))
+                .count()
+        })
+    });
+    group.finish();
+}
+
+fn match_topic_benchmark(c: &mut Criterion) {
+    let filters = filter_list();
+    let trie = TopicTrie::new();
+    for filter in filters.iter() {
+        trie.insert(filter, filter.clone(), filter.clone());
+    }
+    let topic = "device/10/sensor/110/temperature".to_string();
+
+    let mut group = c.benchmark_group("match_topic");
+    group.bench_function("trie", |b| b.iter(|| trie.match_topic(black_box(&topic))));
+    group.bench_function("scan", |b| {
+        b.iter(|| {
+            filters
+                .iter()
+                .filter(|filter| path_match(black_box(topic.clone()), filter.to_string()))
+                .count()
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, match_filter_benchmark, match_topic_benchmark);
+criterion_main!(benches);
@@ -12,14 +12,16 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 
 use common_base::config::broker_mqtt::broker_mqtt_conf;
 use common_base::tools::now_second;
 use dashmap::DashMap;
+use grpc_clients::mqtt::placement::call::broker_mqtt_update_cache;
 use grpc_clients::poll::ClientPool;
-use log::warn;
+use log::{error, warn};
 use metadata_struct::acl::mqtt_acl::MqttAcl;
 use metadata_struct::acl::mqtt_blacklist::MqttAclBlackList;
 use metadata_struct::mqtt::cluster::MqttClusterDynamicConfig;
@@ -29,18 +31,34 @@ use metadata_struct::mqtt::user::MqttUser;
 use protocol::broker_mqtt::broker_mqtt_placement::{
     MqttBrokerUpdateCacheActionType, MqttBrokerUpdateCacheResourceType, UpdateCacheRequest,
 };
-use protocol::mqtt::common::{MQTTProtocol, PublishProperties, Subscribe, SubscribeProperties};
+use protocol::mqtt::common::{
+    Connect, ConnectProperties, LastWill, LastWillProperties, MQTTProtocol, PublishProperties,
+    Subscribe, SubscribeProperties,
+};
 use serde::{Deserialize, Serialize};
-use tokio::sync::broadcast::Sender;
+use tokio::sync::broadcast::{self, Sender};
+use tokio::sync::Mutex;
 use tokio::time::sleep;
 
 use crate::handler::connection::Connection;
+use crate::handler::flapping_detect::FlappingDetector;
+use crate::handler::flow_control::{refresh_broker_num, RateLimiter};
+use crate::observability::records::RecordBuffer;
+use crate::observability::slow::qos::{SlowMessage, SLOW_SUBSCRIBE_RECORD_CAPACITY};
+use crate::observability::warn::{AlarmRecord, ALARM_RECORD_CAPACITY};
 use crate::security::acl::metadata::AclMetadata;
+use crate::security::login::EnhancedAuthentication;
 use crate::security::AuthDriver;
 use crate::storage::cluster::ClusterStorage;
 use crate::storage::topic::TopicStorage;
 use crate::storage::user::UserStorage;
+use crate::subscribe::push_notify::{remote_node_addrs, PushNotify};
+use crate::subscribe::sub_common::{decode_share_info, is_share_sub};
 use crate::subscribe::subscriber::SubscribeData;
+use crate::subscribe::topic_trie::TopicTrie;
+
+// New topics buffered for the subscribe manager, it rescans all topics if it falls behind
+const NEW_TOPIC_CHANNEL_CAPACITY: usize = 1024;
 
 #[derive(Clone, Serialize, Deserialize)]
 pub enum MetadataCacheAction {
@@ -95,7 +113,16 @@ pub struct ClientPkidData {
     pub create_time: u64,
 }
 
+// CONNECT packet waiting for the enhanced authentication exchange to complete
 #[derive(Clone)]
+pub struct ConnectPackage {
+    pub connect: Connect,
+    pub connect_properties: Option<ConnectProperties>,
+    pub last_will: Option<LastWill>,
+    pub last_will_properties: Option<LastWillProperties>,
+    pub addr: SocketAddr,
+}
+
 pub struct CacheManager {
     pub client_poll: Arc<ClientPool>,
 
@@ -117,6 +144,9 @@ pub struct CacheManager {
     // (client_id, vec<pkid>)
     pub publish_pkid_info: DashMap<String, Vec<u16>>,
 
+    // (client_id, Mutex), serializes the updates of the persisted inflight messages of the client
+    pub inflight_message_lock: DashMap<String, Arc<Mutex<()>>>,
+
     // (connect_id, Connection)
     pub connection_info: DashMap<u64, Connection>,
 
@@ -126,6 +156,18 @@ pub struct CacheManager {
     // (topic_id, topic_name)
     pub topic_id_name: DashMap<String, String>,
 
+    // topic name trie, value is topic_id
+    pub topic_tree: TopicTrie<String>,
+
+    // announces the name of every topic added to this cache, consumed by the subscribe manager
+    pub new_topic_sx: Sender<String>,
+
+    // subscription filter trie, value is (client_id, path)
+    pub subscribe_tree: TopicTrie<(String, String)>,
+
+    // wakes up the push tasks parked on a topic when new messages are written
+    pub push_notify: PushNotify,
+
     // (client_id, HeartbeatShard)
     pub heartbeat_data: DashMap<String, ConnectionLiveTime>,
 
@@ -135,8 +177,26 @@ pub struct CacheManager {
     // (client_id_pkid, QosPkidData)
     pub client_pkid_data: DashMap<String, ClientPkidData>,
 
+    // (connect_id, ConnectPackage)
+    pub pending_connect: DashMap<u64, ConnectPackage>,
+
+    // (connect_id, in-progress enhanced authentication exchange)
+    pub enhanced_auth_info: DashMap<u64, Box<dyn EnhancedAuthentication + Send + Sync>>,
+
     // acl metadata
     pub acl_metadata: AclMetadata,
+
+    // token buckets of the connection and publish rate limits
+    pub rate_limiter: RateLimiter,
+
+    // connect history of the clients, used to detect flapping
+    pub flapping_detector: FlappingDetector,
+
+    // latest slow subscriptions of this broker
+    pub slow_subscribe_records: RecordBuffer<SlowMessage>,
+
+    // latest alarms raised by this broker
+    pub alarm_records: RecordBuffer<AlarmRecord>,
 }
 
 impl CacheManager {
@@ -149,13 +209,24 @@ impl CacheManager {
             session_info: DashMap::with_capacity(8),
             topic_info: DashMap::with_capacity(8),
             topic_id_name: DashMap::with_capacity(8),
+            topic_tree: TopicTrie::new(),
+            new_topic_sx: broadcast::channel(NEW_TOPIC_CHANNEL_CAPACITY).0,
+            subscribe_tree: TopicTrie::new(),
+            push_notify: PushNotify::new(),
             connection_info: DashMap::with_capacity(8),
             subscribe_filter: DashMap::with_capacity(8),
             publish_pkid_info: DashMap::with_capacity(8),
+            inflight_message_lock: DashMap::with_capacity(8),
             heartbeat_data: DashMap::with_capacity(8),
             qos_ack_packet: DashMap::with_capacity(8),
             client_pkid_data: DashMap::with_capacity(8),
+            pending_connect: DashMap::with_capacity(8),
+            enhanced_auth_info: DashMap::with_capacity(8),
             acl_metadata: AclMetadata::new(),
+            rate_limiter: RateLimiter::new(),
+            flapping_detector: FlappingDetector::new(),
+            slow_subscribe_records: RecordBuffer::new(SLOW_SUBSCRIBE_RECORD_CAPACITY),
+            alarm_records: RecordBuffer::new(ALARM_RECORD_CAPACITY),
         }
     }
 
@@ -167,6 +238,11 @@ impl CacheManager {
         subscribe_properties: Option<SubscribeProperties>,
     ) {
         for filter in subscribe.filters {
+            self.subscribe_tree.insert(
+                &subscribe_tree_path(&filter.path),
+                subscribe_tree_key(&client_id, &filter.path),
+                (client_id.clone(), filter.path.clone()),
+            );
             if let Some(data) = self.subscribe_filter.get_mut(&client_id) {
                 data.insert(
                     filter.path.clone(),
@@ -193,6 +269,10 @@ impl CacheManager {
 
     pub fn remove_filter_by_pkid(&self, client_id: &str, filters: &[String]) {
         for path in filters {
+            self.subscribe_tree.remove(
+                &subscribe_tree_path(path),
+                &subscribe_tree_key(client_id, path),
+            );
             if let Some(sub_list) = self.subscribe_filter.get_mut(client_id) {
                 if sub_list.contains_key(path) {
                     sub_list.remove(path);
@@ -202,9 +282,21 @@ impl CacheManager {
     }
 
     pub fn remove_filter_by_client_id(&self, client_id: String) {
+        self.remove_client_subscribe_tree(&client_id);
         self.subscribe_filter.remove(&client_id);
     }
 
+    fn remove_client_subscribe_tree(&self, client_id: &str) {
+        if let Some(sub_list) = self.subscribe_filter.get(client_id) {
+            for path in sub_list.iter() {
+                self.subscribe_tree.remove(
+                    &subscribe_tree_path(path.key()),
+                    &subscribe_tree_key(client_id, path.key()),
+                );
+            }
+        }
+    }
+
     pub fn get_session_info(&self, client_id: &str) -> Option<MqttSession> {
         if let Some(session) = self.session_info.get(client_id) {
             return Some(session.clone());
@@ -275,8 +367,17 @@ impl CacheManager {
 
     pub fn add_topic(&self, topic_name: &str, topic: &MqttTopic) {
         let t = topic.clone();
-        self.topic_info.insert(topic_name.to_owned(), t.clone());
+        let is_new = self
+            .topic_info
+            .insert(topic_name.to_owned(), t.clone())
+            .is_none();
+        self.topic_tree
+            .insert(topic_name, t.topic_id.clone(), t.topic_id.clone());
         self.topic_id_name.insert(t.topic_id, topic_name.to_owned());
+        if is_new {
+            // no receiver only means the subscribe manager is not running yet
+            let _ = self.new_topic_sx.send(topic_name.to_owned());
+        }
     }
 
     pub fn update_topic_retain_message(&self, topic_name: &str, retain_message: Option<Vec<u8>>) {
@@ -318,9 +419,12 @@ impl CacheManager {
 
     pub fn remove_session(&self, client_id: &str) {
         self.session_info.remove(client_id);
+        self.remove_client_subscribe_tree(client_id);
         self.subscribe_filter.remove(client_id);
         self.publish_pkid_info.remove(client_id);
+        self.inflight_message_lock.remove(client_id);
         self.heartbeat_data.remove(client_id);
+        self.rate_limiter.remove_client(client_id);
 
         for (key, _) in self.qos_ack_packet.clone() {
             if key.starts_with(client_id) {
@@ -337,6 +441,20 @@ impl CacheManager {
 
     pub fn remove_connection(&self, connect_id: u64) {
         self.connection_info.remove(&connect_id);
+        self.pending_connect.remove(&connect_id);
+        self.enhanced_auth_info.remove(&connect_id);
+        self.rate_limiter.remove_connection(connect_id);
+        self.acl_metadata.remove_connection_acl(connect_id);
+    }
+
+    pub fn add_pending_connect(&self, connect_id: u64, pending: ConnectPackage) {
+        self.pending_connect.insert(connect_id, pending);
+    }
+
+    pub fn remove_pending_connect(&self, connect_id: u64) -> Option<ConnectPackage> {
+        self.pending_connect
+            .remove(&connect_id)
+            .map(|(_, pending)| pending)
     }
 
     pub fn get_topic_alias(&self, connect_id: u64, topic_alias: u16) -> Option<String> {
@@ -419,10 +537,28 @@ impl CacheManager {
 
     pub fn remove_pkid_info(&self, client_id: &str, pkid: u16) {
         if let Some(mut pkid_list) = self.publish_pkid_info.get_mut(client_id) {
-            pkid_list.retain(|x| *x == pkid);
+            pkid_list.retain(|x| *x != pkid);
+        }
+    }
+
+    // Marks the pkid of a resumed inflight message as in use
+    pub fn reserve_pkid(&self, client_id: &str, pkid: u16) {
+        let mut pkid_list = self
+            .publish_pkid_info
+            .entry(client_id.to_owned())
+            .or_default();
+        if !pkid_list.contains(&pkid) {
+            pkid_list.push(pkid);
         }
     }
 
+    pub fn get_inflight_message_lock(&self, client_id: &str) -> Arc<Mutex<()>> {
+        self.inflight_message_lock
+            .entry(client_id.to_owned())
+            .or_insert_with(|| Arc::new(Mutex::new(())))
+            .clone()
+    }
+
     pub fn is_new_sub(&self, client_id: &str, path: &str) -> bool {
         if let Some(sub) = self.subscribe_filter.get(client_id) {
             return !sub.contains_key(path);
@@ -504,6 +640,7 @@ impl CacheManager {
             username: conf.system.default_user.clone(),
             password: conf.system.default_password.clone(),
             is_superuser: true,
+            hash: Default::default(),
         };
         let user_storage = UserStorage::new(self.client_poll.clone());
         match user_storage.save_user(system_user_info.clone()).await {
@@ -533,10 +670,18 @@ impl CacheManager {
         self.acl_metadata.parse_mqtt_acl(acl);
     }
 
+    pub fn remove_acl(&self, acl: &MqttAcl) {
+        self.acl_metadata.remove_mqtt_acl(acl);
+    }
+
     pub fn add_blacklist(&self, blacklist: MqttAclBlackList) {
         self.acl_metadata.parse_mqtt_blacklist(blacklist);
     }
 
+    pub fn remove_blacklist(&self, blacklist: &MqttAclBlackList) {
+        self.acl_metadata.remove_mqtt_blacklist(blacklist);
+    }
+
     pub fn remove_ack_packet(&self, client_id: &str, pkid: u16) {
         let key = self.key(client_id, pkid);
         self.qos_ack_packet.remove(&key);
@@ -579,15 +724,95 @@ impl CacheManager {
     }
 }
 
-pub fn update_cache_metadata(request: UpdateCacheRequest) {
+pub fn update_cache_metadata(cache_manager: &Arc<CacheManager>, request: UpdateCacheRequest) {
     match request.resource_type() {
         MqttBrokerUpdateCacheResourceType::Session => match request.action_type() {
             MqttBrokerUpdateCacheActionType::Add => {}
             MqttBrokerUpdateCacheActionType::Delete => {}
         },
-        MqttBrokerUpdateCacheResourceType::User => match request.action_type() {
-            MqttBrokerUpdateCacheActionType::Add => {}
+        MqttBrokerUpdateCacheResourceType::User => {
+            match serde_json::from_slice::<MqttUser>(&request.data) {
+                Ok(user) => match request.action_type() {
+                    MqttBrokerUpdateCacheActionType::Add => cache_manager.add_user(user),
+                    MqttBrokerUpdateCacheActionType::Delete => {
+                        cache_manager.user_info.remove(&user.username);
+                    }
+                },
+                Err(e) => warn!("Failed to parse the user, error message :{}", e),
+            }
+        }
+        MqttBrokerUpdateCacheResourceType::Blacklist => {
+            match serde_json::from_slice::<MqttAclBlackList>(&request.data) {
+                Ok(blacklist) => match request.action_type() {
+                    MqttBrokerUpdateCacheActionType::Add => cache_manager.add_blacklist(blacklist),
+                    MqttBrokerUpdateCacheActionType::Delete => {
+                        cache_manager.remove_blacklist(&blacklist)
+                    }
+                },
+                Err(e) => warn!("Failed to parse the blacklist, error message :{}", e),
+            }
+        }
+        MqttBrokerUpdateCacheResourceType::Acl => {
+            match serde_json::from_slice::<MqttAcl>(&request.data) {
+                Ok(acl) => match request.action_type() {
+                    MqttBrokerUpdateCacheActionType::Add => cache_manager.add_acl(acl),
+                    MqttBrokerUpdateCacheActionType::Delete => cache_manager.remove_acl(&acl),
+                },
+                Err(e) => warn!("Failed to parse the acl, error message :{}", e),
+            }
+        }
+        MqttBrokerUpdateCacheResourceType::ClusterConfig => match request.action_type() {
+            MqttBrokerUpdateCacheActionType::Add => {
+                match serde_json::from_slice::<MqttClusterDynamicConfig>(&request.data) {
+                    Ok(cluster) => cache_manager.set_cluster_info(cluster),
+                    Err(e) => warn!("Failed to parse the cluster config, error message :{}", e),
+                }
+            }
             MqttBrokerUpdateCacheActionType::Delete => {}
         },
+        MqttBrokerUpdateCacheResourceType::Node => {
+            let cache_manager = cache_manager.clone();
+            tokio::spawn(async move {
+                refresh_broker_num(&cache_manager, &cache_manager.client_poll).await;
+            });
+        }
+    }
+}
+
+// Pushes a metadata change to the other brokers of the cluster, the caller updates the local cache
+pub async fn update_remote_cache(
+    client_poll: &Arc<ClientPool>,
+    action_type: MqttBrokerUpdateCacheActionType,
+    resource_type: MqttBrokerUpdateCacheResourceType,
+    data: Vec<u8>,
+) {
+    let request = UpdateCacheRequest {
+        cluster_name: broker_mqtt_conf().cluster_name.clone(),
+        action_type: action_type.into(),
+        resource_type: resource_type.into(),
+        data,
+    };
+    for addr in remote_node_addrs(client_poll).await {
+        if let Err(e) =
+            broker_mqtt_update_cache(client_poll.clone(), vec![addr.clone()], request.clone()).await
+        {
+            error!(
+                "Failed to push the {:?} update to broker node {}, error message :{}",
+                resource_type, addr, e
+            );
+        }
     }
 }
+
+// Shared subscriptions are indexed by the filter without the $share/{group} prefix
+fn subscribe_tree_path(path: &str) -> String {
+    if is_share_sub(path.to_owned()) {
+        let (_, sub_name) = decode_share_info(path.to_owned());
+        return sub_name;
+    }
+    path.to_owned()
+}
+
+fn subscribe_tree_key(client_id: &str, path: &str) -> String {
+    format!("{}_{}", client_id, path)
+}
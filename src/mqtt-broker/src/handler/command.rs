@@ -16,7 +16,6 @@ use std::net::SocketAddr;
 use std::sync::Arc;
 
 use grpc_clients::poll::ClientPool;
-use log::info;
 use protocol::mqtt::common::{
     is_mqtt3, is_mqtt4, is_mqtt5, ConnectReturnCode, DisconnectReasonCode, MQTTPacket, MQTTProtocol,
 };
@@ -95,8 +94,9 @@ where
         addr: SocketAddr,
         packet: MQTTPacket,
     ) -> Option<MQTTPacket> {
+        // AUTH packets may continue an enhanced authentication exchange started by CONNECT
         let mut is_conect_pkg = false;
-        if let MQTTPacket::Connect(_, _, _, _, _, _) = packet {
+        if let MQTTPacket::Connect(_, _, _, _, _, _) | MQTTPacket::Auth(_, _) = packet {
             is_conect_pkg = true;
         }
 
@@ -170,20 +170,21 @@ where
                     ));
                 };
 
-                let ack_pkg = resp_pkg.unwrap();
-                if let MQTTPacket::ConnAck(conn_ack, _) = ack_pkg.clone() {
-                    if conn_ack.code == ConnectReturnCode::Success {
-                        let username = if let Some(user) = login {
-                            user.username
-                        } else {
-                            "".to_string()
-                        };
-                        self.metadata_cache
-                            .login_success(tcp_connection.connection_id, username);
-                        info!("connect [{}] login success", tcp_connection.connection_id);
-                    }
+                return resp_pkg;
+            }
+
+            MQTTPacket::Auth(auth, auth_properties) => {
+                if tcp_connection.is_mqtt5() {
+                    return Some(
+                        self.mqtt5_service
+                            .auth(tcp_connection.connection_id, auth, auth_properties)
+                            .await,
+                    );
                 }
-                return Some(ack_pkg);
+                return Some(response_packet_mqtt_distinct_by_reason(
+                    &MQTTProtocol::MQTT5,
+                    Some(DisconnectReasonCode::ProtocolError),
+                ));
             }
 
             MQTTPacket::Publish(publish, publish_properties) => {
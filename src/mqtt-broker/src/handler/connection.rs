@@ -20,13 +20,17 @@ use common_base::error::common::CommonError;
 use common_base::tools::{now_second, unique_id};
 use dashmap::DashMap;
 use grpc_clients::poll::ClientPool;
+use log::warn;
 use metadata_struct::mqtt::cluster::MqttClusterDynamicConfig;
-use protocol::mqtt::common::{Connect, ConnectProperties};
+use protocol::mqtt::common::{Connect, ConnectProperties, DisconnectReasonCode};
 
 use super::cache::CacheManager;
 use super::keep_alive::client_keep_live_time;
+use super::response::response_packet_mqtt_distinct_by_reason;
 use crate::server::connection_manager::ConnectionManager;
+use crate::server::packet::ResponsePackage;
 use crate::storage::session::SessionStorage;
+use crate::subscribe::sub_common::publish_message_to_client;
 use crate::subscribe::subscribe_manager::SubscribeManager;
 
 pub const REQUEST_RESPONSE_PREFIX_NAME: &str = "/sys/request_response/";
@@ -61,6 +65,10 @@ pub struct Connection {
     pub sender_qos_message: Arc<AtomicIsize>,
     // Time when the connection was created
     pub create_time: u64,
+    // MQTT 5 enhanced authentication method used in CONNECT, re-authentication must use the same method
+    pub auth_method: Option<String>,
+    // Common name of the verified client certificate, the ${cn} ACL placeholder
+    pub cert_common_name: Option<String>,
 }
 
 pub struct ConnectionConfig {
@@ -126,6 +134,20 @@ impl Connection {
     pub fn send_qos_message_decr(&self) {
         self.sender_qos_message.fetch_add(-1, Ordering::Relaxed);
     }
+
+    // Takes a slot of the client's Receive Maximum window, returns false when the window is full
+    pub fn try_send_qos_message_incr(&self) -> bool {
+        let max = self.client_max_receive_maximum as isize;
+        self.sender_qos_message
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |num| {
+                if num < max {
+                    Some(num + 1)
+                } else {
+                    None
+                }
+            })
+            .is_ok()
+    }
 }
 
 pub fn build_connection(
@@ -185,7 +207,9 @@ pub fn build_connection(
         keep_alive,
         source_ip_addr: addr.to_string(),
     };
-    Connection::new(config)
+    let mut connection = Connection::new(config);
+    connection.auth_method = authentication_method(connect_properties);
+    connection
 }
 
 pub fn get_client_id(client_id: &str) -> (String, bool) {
@@ -196,6 +220,13 @@ pub fn get_client_id(client_id: &str) -> (String, bool) {
     }
 }
 
+pub fn authentication_method(connect_properties: &Option<ConnectProperties>) -> Option<String> {
+    if let Some(properties) = connect_properties {
+        return properties.authentication_method.clone();
+    }
+    None
+}
+
 pub fn response_information(connect_properties: &Option<ConnectProperties>) -> Option<String> {
     if let Some(properties) = connect_properties {
         if let Some(request_response_info) = properties.request_response_info {
@@ -219,8 +250,16 @@ pub async fn disconnect_connection(
     cache_manager.remove_connection(connect_id);
     // Remove the client id bound connection information
     cache_manager.update_session_connect_id(client_id, None);
-    // Once the connection is dropped, the push thread for the Client ID dimension is paused
-    subscribe_manager.stop_push_by_client_id(client_id);
+    // Once the connection is dropped, the push thread for the Client ID dimension is paused.
+    // The subscriptions of a persistent session stay bound, their messages are kept in the offline queue
+    let is_persistent = cache_manager
+        .get_session_info(client_id)
+        .is_some_and(|session| session.session_expiry > 0);
+    if is_persistent {
+        subscribe_manager.stop_share_follower_by_client_id(client_id);
+    } else {
+        subscribe_manager.stop_push_by_client_id(client_id);
+    }
 
     // Remove the Connect id of the Session in the Placement Center
     let session_storage = SessionStorage::new(client_poll.clone());
@@ -239,6 +278,59 @@ pub async fn disconnect_connection(
     Ok(())
 }
 
+// The client closed the network stream. Logged in connections are left to the keep alive check,
+// which also updates the session and sends the last will, so only connections that never
+// completed CONNECT (e.g. in the middle of an enhanced authentication exchange) are released here
+pub async fn release_unlogged_connection(
+    connect_id: u64,
+    cache_manager: &Arc<CacheManager>,
+    connnection_manager: &Arc<ConnectionManager>,
+) {
+    if cache_manager.get_connection(connect_id).is_some() {
+        return;
+    }
+    cache_manager.remove_connection(connect_id);
+    connnection_manager.close_connect(connect_id).await;
+}
+
+// Closes a connection on request of an administrator, MQTT 5 clients are sent DISCONNECT 0x98 first
+pub async fn kick_connection(
+    client_id: &str,
+    connect_id: u64,
+    cache_manager: &Arc<CacheManager>,
+    client_poll: &Arc<ClientPool>,
+    connnection_manager: &Arc<ConnectionManager>,
+    subscribe_manager: &Arc<SubscribeManager>,
+) -> Result<(), CommonError> {
+    if let Some(protocol) = connnection_manager.get_connect_protocol(connect_id) {
+        if protocol.is_mqtt5() {
+            let resp = ResponsePackage {
+                connection_id: connect_id,
+                packet: response_packet_mqtt_distinct_by_reason(
+                    &protocol,
+                    Some(DisconnectReasonCode::AdministrativeAction),
+                ),
+            };
+            if let Err(e) = publish_message_to_client(resp, connnection_manager).await {
+                warn!(
+                    "Failed to send DISCONNECT to the kicked connection [{}], error message :{}",
+                    connect_id, e
+                );
+            }
+        }
+    }
+
+    disconnect_connection(
+        client_id,
+        connect_id,
+        cache_manager,
+        client_poll,
+        connnection_manager,
+        subscribe_manager,
+    )
+    .await
+}
+
 #[cfg(test)]
 mod test {
     use metadata_struct::mqtt::cluster::MqttClusterDynamicConfig;
@@ -344,4 +436,18 @@ mod test {
         conn.send_qos_message_decr();
         assert_eq!(conn.get_send_qos_message(), 0);
     }
+
+    #[tokio::test]
+    pub async fn send_qos_message_window_test() {
+        let conn = Connection {
+            client_max_receive_maximum: 2,
+            ..Default::default()
+        };
+        assert!(conn.try_send_qos_message_incr());
+        assert!(conn.try_send_qos_message_incr());
+        assert!(!conn.try_send_qos_message_incr());
+        assert_eq!(conn.get_send_qos_message(), 2);
+        conn.send_qos_message_decr();
+        assert!(conn.try_send_qos_message_incr());
+    }
 }
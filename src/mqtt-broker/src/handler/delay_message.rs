@@ -0,0 +1,788 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use bytes::Bytes;
+use common_base::error::common::CommonError;
+use common_base::error::mqtt_broker::MQTTBrokerError;
+use common_base::tools::now_second;
+use grpc_clients::poll::ClientPool;
+use log::{debug, error, info};
+use metadata_struct::adapter::record::Record;
+use metadata_struct::mqtt::message::MqttMessage;
+use protocol::mqtt::common::{Publish, PublishProperties};
+use serde::{Deserialize, Serialize};
+use storage_adapter::storage::{ShardConfig, StorageAdapter};
+use tokio::select;
+use tokio::sync::broadcast;
+use tokio::time::sleep;
+
+use super::cache::CacheManager;
+use super::message::build_message_expire;
+use super::topic::{topic_name_validator, try_init_topic};
+use crate::storage::message::MessageStorage;
+use crate::storage::topic::TopicStorage;
+use crate::subscribe::sub_common::get_share_sub_leader;
+
+pub const DELAY_PUBLISH_TOPIC_PREFIX: &str = "$delay/";
+
+// Delayed messages are appended to this shard by the node that receives the publish
+const DELAY_MESSAGE_SHARD_NAME: &str = "$delay-message";
+// Consumer group of the delay shard, and the group used to elect the node that fires delayed messages
+const DELAY_MESSAGE_GROUP_NAME: &str = "$delay-message-scheduler";
+const DELAY_MESSAGE_SHARD_INIT_KEY: &str = "/delay_message/shard_init";
+const DELAY_MESSAGE_RECORD_KEY_PREFIX: &str = "/delay_message/record/";
+const DELAY_MESSAGE_INDEX_KEY: &str = "/delay_message/index";
+const DELAY_MESSAGE_PENDING_NUM_KEY: &str = "/delay_message/pending_num";
+const DELAY_MESSAGE_LEASE_KEY: &str = "/delay_message/lease";
+const DELAY_MESSAGE_PULL_BATCH_NUM: u128 = 100;
+const DELAY_MESSAGE_LEADER_CHECK_INTERVAL: u64 = 10;
+// Longer than the leader check interval, so a node that lost the election stops renewing
+// the lease long before the new leader can take it
+const DELAY_MESSAGE_LEASE_TIME: u64 = 20;
+// The lease holder stops firing this long before its lease runs out
+const DELAY_MESSAGE_LEASE_MARGIN: u64 = 5;
+
+#[derive(Clone, Serialize, Deserialize, Debug)]
+pub struct DelayMessage {
+    pub target_topic: String,
+    pub deliver_time: u64,
+    pub message: MqttMessage,
+}
+
+// The node that fires delayed messages, only the elected leader takes or renews it
+#[derive(Clone, Serialize, Deserialize, Debug)]
+struct DelayMessageLease {
+    broker_id: u64,
+    expire_time: u64,
+}
+
+pub fn is_delay_publish_topic(topic_name: &str) -> bool {
+    topic_name.starts_with(DELAY_PUBLISH_TOPIC_PREFIX)
+}
+
+// Parse $delay/{seconds}/{topic}, returns None when the topic is not a delayed publish
+pub fn parse_delay_publish_topic(
+    cache_manager: &Arc<CacheManager>,
+    topic_name: &str,
+) -> Result<Option<(u64, String)>, MQTTBrokerError> {
+    let cluster = cache_manager.get_cluster_info();
+    if !cluster.delay_publish.enable || !is_delay_publish_topic(topic_name) {
+        return Ok(None);
+    }
+
+    let (interval, target_topic) =
+        match topic_name[DELAY_PUBLISH_TOPIC_PREFIX.len()..].split_once("/") {
+            Some(data) => data,
+            None => {
+                return Err(MQTTBrokerError::TopicNameIncorrectlyFormatted(
+                    topic_name.to_owned(),
+                ))
+            }
+        };
+
+    let delay_interval = match interval.parse::<u64>() {
+        Ok(data) => data,
+        Err(_) => {
+            return Err(MQTTBrokerError::TopicNameIncorrectlyFormatted(
+                topic_name.to_owned(),
+            ))
+        }
+    };
+
+    if delay_interval > cluster.delay_publish.max_delay_interval {
+        return Err(MQTTBrokerError::DelayPublishIntervalExceeded(
+            delay_interval,
+            cluster.delay_publish.max_delay_interval,
+        ));
+    }
+
+    if target_topic.is_empty() || is_delay_publish_topic(target_topic) {
+        return Err(MQTTBrokerError::TopicNameIncorrectlyFormatted(
+            topic_name.to_owned(),
+        ));
+    }
+    topic_name_validator(target_topic)?;
+
+    Ok(Some((delay_interval, target_topic.to_owned())))
+}
+
+pub async fn save_delay_message<S>(
+    cache_manager: &Arc<CacheManager>,
+    message_storage_adapter: &Arc<S>,
+    delay_interval: u64,
+    target_topic: &str,
+    client_id: &str,
+    publish: &Publish,
+    publish_properties: &Option<PublishProperties>,
+) -> Result<Vec<usize>, CommonError>
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    // The pending number is maintained by the scheduler node, so the limit is a soft limit
+    let cluster = cache_manager.get_cluster_info();
+    let pending_num = get_pending_num(message_storage_adapter).await?;
+    if pending_num >= cluster.delay_publish.max_pending_num {
+        return Err(MQTTBrokerError::DelayPublishPendingExceeded(
+            cluster.delay_publish.max_pending_num,
+        )
+        .into());
+    }
+
+    let message_expire = build_message_expire(cache_manager, publish_properties);
+    let mut message =
+        MqttMessage::build_message(client_id, publish, publish_properties, message_expire);
+    message.topic = Bytes::from(target_topic.to_owned());
+
+    let delay_message = DelayMessage {
+        target_topic: target_topic.to_owned(),
+        deliver_time: now_second() + delay_interval,
+        message,
+    };
+    let data = serde_json::to_vec(&delay_message)?;
+    message_storage_adapter
+        .stream_write(
+            DELAY_MESSAGE_SHARD_NAME.to_string(),
+            vec![Record::build_b(data)],
+        )
+        .await
+}
+
+async fn get_pending_num<S>(message_storage_adapter: &Arc<S>) -> Result<u64, CommonError>
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    match message_storage_adapter
+        .get(DELAY_MESSAGE_PENDING_NUM_KEY.to_string())
+        .await?
+    {
+        Some(record) => Ok(String::from_utf8(record.data)?
+            .parse::<u64>()
+            .unwrap_or_default()),
+        None => Ok(0),
+    }
+}
+
+// Only the node elected by the placement center fires delayed messages. Pending messages
+// are persisted through the storage adapter, so a new leader or a restarted node picks them up.
+// The elected node also has to hold the lease in the storage before it pulls, fires or writes
+// the index. A new leader can only take the lease once the previous holder stopped renewing
+// it, so the two never fire at the same time even while their view of the election differs.
+// A message is published before its record is deleted, delivery is at least once: a node that
+// stops between the two steps leaves the message to be fired again by the next lease holder.
+pub struct DelayMessageManager<S> {
+    cache_manager: Arc<CacheManager>,
+    message_storage_adapter: Arc<S>,
+    client_poll: Arc<ClientPool>,
+    broker_id: u64,
+    // (deliver_time, offset) -> message
+    delay_queue: BTreeMap<(u64, u128), DelayMessage>,
+    is_leader: bool,
+    // Expiry of the lease held by this node, 0 when it does not hold it
+    lease_expire_time: u64,
+    last_leader_check_time: u64,
+}
+
+impl<S> DelayMessageManager<S>
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    pub fn new(
+        cache_manager: Arc<CacheManager>,
+        message_storage_adapter: Arc<S>,
+        client_poll: Arc<ClientPool>,
+        broker_id: u64,
+    ) -> Self {
+        DelayMessageManager {
+            cache_manager,
+            message_storage_adapter,
+            client_poll,
+            broker_id,
+            delay_queue: BTreeMap::new(),
+            is_leader: false,
+            lease_expire_time: 0,
+            last_leader_check_time: 0,
+        }
+    }
+
+    pub async fn start(&mut self, stop_send: broadcast::Sender<bool>) {
+        if let Err(e) = self.try_init_delay_shard().await {
+            error!(
+                "Failed to initialize the delay message shard, error message: {}",
+                e
+            );
+        }
+
+        let mut stop_rx = stop_send.subscribe();
+        loop {
+            select! {
+                val = stop_rx.recv() =>{
+                    if let Ok(flag) = val {
+                        if flag {
+                            debug!("Delay message thread stopped successfully");
+                            break;
+                        }
+                    }
+                }
+                _ = self.delay_message_process()=>{}
+            }
+            sleep(Duration::from_secs(1)).await;
+        }
+    }
+
+    async fn delay_message_process(&mut self) {
+        if now_second() - self.last_leader_check_time >= DELAY_MESSAGE_LEADER_CHECK_INTERVAL {
+            self.refresh_leader().await;
+        }
+
+        if !self.is_leader {
+            return;
+        }
+
+        match self.renew_lease().await {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(e) => {
+                error!("Failed to renew delay message lease, error message: {}", e);
+                return;
+            }
+        }
+
+        if let Err(e) = self.pull_delay_message().await {
+            error!("Failed to pull delay message, error message: {}", e);
+        }
+
+        if let Err(e) = self.send_expired_delay_message().await {
+            error!("Failed to send delay message, error message: {}", e);
+        }
+    }
+
+    async fn refresh_leader(&mut self) {
+        let reply = match get_share_sub_leader(
+            self.client_poll.clone(),
+            DELAY_MESSAGE_GROUP_NAME.to_string(),
+        )
+        .await
+        {
+            Ok(reply) => reply,
+            Err(e) => {
+                // Stop renewing the lease until the leadership is confirmed again
+                error!(
+                    "Failed to get Leader for delay message, error message: {}",
+                    e
+                );
+                self.give_up_leader();
+                return;
+            }
+        };
+        self.last_leader_check_time = now_second();
+
+        if reply.broker_id == self.broker_id {
+            self.is_leader = true;
+        } else {
+            self.give_up_leader();
+        }
+    }
+
+    fn give_up_leader(&mut self) {
+        self.is_leader = false;
+        if self.lease_expire_time > 0 {
+            self.lease_expire_time = 0;
+            self.delay_queue.clear();
+            info!("Node {} gives up delay message", self.broker_id);
+        }
+    }
+
+    // Takes the lease when it is free or expired and extends it when this node holds it,
+    // returns whether this node may fire delayed messages
+    async fn renew_lease(&mut self) -> Result<bool, CommonError> {
+        let now = now_second();
+        if let Some(record) = self
+            .message_storage_adapter
+            .get(DELAY_MESSAGE_LEASE_KEY.to_string())
+            .await?
+        {
+            let lease: DelayMessageLease = serde_json::from_slice(&record.data)?;
+            if lease.broker_id != self.broker_id && lease.expire_time > now {
+                self.lease_expire_time = 0;
+                return Ok(false);
+            }
+        }
+
+        let lease = DelayMessageLease {
+            broker_id: self.broker_id,
+            expire_time: now + DELAY_MESSAGE_LEASE_TIME,
+        };
+        self.message_storage_adapter
+            .set(
+                DELAY_MESSAGE_LEASE_KEY.to_string(),
+                Record::build_b(serde_json::to_vec(&lease)?),
+            )
+            .await?;
+
+        // Another node may have held the lease in between, rebuild the queue from storage
+        if self.lease_expire_time <= now {
+            self.load_delay_message().await?;
+            info!(
+                "Node {} takes over delay message, pending message num: {}",
+                self.broker_id,
+                self.delay_queue.len()
+            );
+        }
+        self.lease_expire_time = lease.expire_time;
+        Ok(true)
+    }
+
+    fn hold_lease(&self) -> bool {
+        now_second() + DELAY_MESSAGE_LEASE_MARGIN < self.lease_expire_time
+    }
+
+    async fn try_init_delay_shard(&self) -> Result<(), CommonError> {
+        if self
+            .message_storage_adapter
+            .exists(DELAY_MESSAGE_SHARD_INIT_KEY.to_string())
+            .await?
+        {
+            return Ok(());
+        }
+        self.message_storage_adapter
+            .create_shard(DELAY_MESSAGE_SHARD_NAME.to_string(), ShardConfig::default())
+            .await?;
+        self.message_storage_adapter
+            .set(
+                DELAY_MESSAGE_SHARD_INIT_KEY.to_string(),
+                Record::build_b(now_second().to_string().into_bytes()),
+            )
+            .await
+    }
+
+    // Rebuild the delay queue from the persisted index
+    async fn load_delay_message(&mut self) -> Result<(), CommonError> {
+        self.delay_queue.clear();
+        let offsets: Vec<u128> = match self
+            .message_storage_adapter
+            .get(DELAY_MESSAGE_INDEX_KEY.to_string())
+            .await?
+        {
+            Some(record) => serde_json::from_slice(&record.data)?,
+            None => Vec::new(),
+        };
+
+        for offset in offsets {
+            if let Some(record) = self
+                .message_storage_adapter
+                .get(delay_message_record_key(offset))
+                .await?
+            {
+                let delay_message: DelayMessage = serde_json::from_slice(&record.data)?;
+                self.delay_queue
+                    .insert((delay_message.deliver_time, offset), delay_message);
+            }
+        }
+        Ok(())
+    }
+
+    // Move newly published delay messages from the shard into the delay queue
+    async fn pull_delay_message(&mut self) -> Result<(), CommonError> {
+        loop {
+            let records = match self
+                .message_storage_adapter
+                .stream_read(
+                    DELAY_MESSAGE_SHARD_NAME.to_string(),
+                    DELAY_MESSAGE_GROUP_NAME.to_string(),
+                    Some(DELAY_MESSAGE_PULL_BATCH_NUM),
+                    None,
+                )
+                .await?
+            {
+                Some(records) => records,
+                None => return Ok(()),
+            };
+
+            if records.is_empty() {
+                return Ok(());
+            }
+
+            let mut last_offset = 0;
+            for record in records.iter() {
+                last_offset = record.offset;
+                let delay_message: DelayMessage = match serde_json::from_slice(&record.data) {
+                    Ok(data) => data,
+                    Err(e) => {
+                        error!(
+                            "Delay message with offset {} decode failed, error message: {}",
+                            record.offset, e
+                        );
+                        continue;
+                    }
+                };
+                self.message_storage_adapter
+                    .set(
+                        delay_message_record_key(record.offset),
+                        Record::build_b(record.data.clone()),
+                    )
+                    .await?;
+                self.delay_queue
+                    .insert((delay_message.deliver_time, record.offset), delay_message);
+            }
+
+            // The index must be persisted before the offset is committed, otherwise a crash loses messages
+            self.save_delay_message_index().await?;
+            self.message_storage_adapter
+                .stream_commit_offset(
+                    DELAY_MESSAGE_SHARD_NAME.to_string(),
+                    DELAY_MESSAGE_GROUP_NAME.to_string(),
+                    last_offset,
+                )
+                .await?;
+
+            if (records.len() as u128) < DELAY_MESSAGE_PULL_BATCH_NUM {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn send_expired_delay_message(&mut self) -> Result<(), CommonError> {
+        let now = now_second();
+        let expired: Vec<(u64, u128)> = self
+            .delay_queue
+            .range(..(now + 1, 0))
+            .map(|(key, _)| *key)
+            .collect();
+        if expired.is_empty() {
+            return Ok(());
+        }
+
+        let mut result = Ok(());
+        for key in expired {
+            // a node that stalls past its lease must not fire next to the new holder
+            if !self.hold_lease() {
+                return Err(CommonError::CommmonError(
+                    "the delay message lease expired".to_string(),
+                ));
+            }
+
+            let record_key = delay_message_record_key(key.1);
+            if !self
+                .message_storage_adapter
+                .exists(record_key.clone())
+                .await?
+            {
+                // already fired by the previous lease holder
+                self.delay_queue.remove(&key);
+                continue;
+            }
+
+            let delay_message = self.delay_queue.get(&key).unwrap().clone();
+            if let Err(e) = self.send_delay_message(&delay_message).await {
+                result = Err(e);
+                break;
+            }
+            self.delay_queue.remove(&key);
+            self.message_storage_adapter.delete(record_key).await?;
+        }
+
+        self.save_delay_message_index().await?;
+        result
+    }
+
+    async fn send_delay_message(&self, delay_message: &DelayMessage) -> Result<(), CommonError> {
+        let topic = try_init_topic(
+            &delay_message.target_topic,
+            &self.cache_manager,
+            &self.message_storage_adapter,
+            &self.client_poll,
+        )
+        .await?;
+
+        // The message expiry interval starts from the actual publish time
+        let now = now_second();
+        let mut message = delay_message.message.clone();
+        message.expiry_interval = now + message.expiry_interval.saturating_sub(message.create_time);
+        message.create_time = now;
+
+        if message.retain {
+            self.save_retain_message(&delay_message.target_topic, &message)
+                .await?;
+        }
+
+        let message_storage = MessageStorage::new(self.message_storage_adapter.clone());
+        message_storage
+            .append_topic_message(
+                topic.topic_id.clone(),
+                vec![Record::build_b(message.encode())],
+            )
+            .await?;
+        self.cache_manager.push_notify.notify(&topic.topic_id);
+        Ok(())
+    }
+
+    async fn save_retain_message(
+        &self,
+        topic_name: &str,
+        message: &MqttMessage,
+    ) -> Result<(), CommonError> {
+        let topic_storage = TopicStorage::new(self.client_poll.clone());
+        if message.payload.is_empty() {
+            topic_storage
+                .delete_retain_message(topic_name.to_owned())
+                .await?;
+            self.cache_manager
+                .update_topic_retain_message(topic_name, Some(Vec::new()));
+        } else {
+            topic_storage
+                .set_retain_message(topic_name.to_owned(), message, message.expiry_interval)
+                .await?;
+            self.cache_manager
+                .update_topic_retain_message(topic_name, Some(message.encode()));
+        }
+        Ok(())
+    }
+
+    // The index is rebuilt from the local queue, only the lease holder may write it
+    async fn save_delay_message_index(&self) -> Result<(), CommonError> {
+        if !self.hold_lease() {
+            return Err(CommonError::CommmonError(
+                "the delay message lease expired".to_string(),
+            ));
+        }
+        let offsets: Vec<u128> = self.delay_queue.keys().map(|(_, offset)| *offset).collect();
+        self.message_storage_adapter
+            .set(
+                DELAY_MESSAGE_INDEX_KEY.to_string(),
+                Record::build_b(serde_json::to_vec(&offsets)?),
+            )
+            .await?;
+        self.message_storage_adapter
+            .set(
+                DELAY_MESSAGE_PENDING_NUM_KEY.to_string(),
+                Record::build_b(offsets.len().to_string().into_bytes()),
+            )
+            .await
+    }
+}
+
+fn delay_message_record_key(offset: u128) -> String {
+    format!("{}{}", DELAY_MESSAGE_RECORD_KEY_PREFIX, offset)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use common_base::error::mqtt_broker::MQTTBrokerError;
+    use common_base::tools::now_second;
+    use grpc_clients::poll::ClientPool;
+    use metadata_struct::adapter::record::Record;
+    use metadata_struct::mqtt::cluster::MqttClusterDynamicConfig;
+    use metadata_struct::mqtt::topic::MqttTopic;
+    use protocol::mqtt::common::Publish;
+    use storage_adapter::memory::MemoryStorageAdapter;
+    use storage_adapter::storage::StorageAdapter;
+
+    use super::{
+        parse_delay_publish_topic, save_delay_message, DelayMessageLease, DelayMessageManager,
+        DELAY_MESSAGE_LEASE_KEY,
+    };
+    use crate::handler::cache::CacheManager;
+
+    fn build_cache_manager() -> Arc<CacheManager> {
+        let client_poll = Arc::new(ClientPool::new(1));
+        let cache_manager = Arc::new(CacheManager::new(client_poll, "test".to_string()));
+        let mut cluster = MqttClusterDynamicConfig::new();
+        cluster.delay_publish.max_delay_interval = 3600;
+        cluster.delay_publish.max_pending_num = 2;
+        cache_manager.set_cluster_info(cluster);
+        cache_manager
+    }
+
+    #[test]
+    fn parse_delay_publish_topic_test() {
+        let cache_manager = build_cache_manager();
+
+        let res = parse_delay_publish_topic(&cache_manager, "a/b").unwrap();
+        assert!(res.is_none());
+
+        let res = parse_delay_publish_topic(&cache_manager, "$delay/60/a/b").unwrap();
+        assert_eq!(res, Some((60, "a/b".to_string())));
+
+        let res = parse_delay_publish_topic(&cache_manager, "$delay/a/b");
+        assert!(res.is_err());
+
+        let res = parse_delay_publish_topic(&cache_manager, "$delay/60");
+        assert!(res.is_err());
+
+        let res = parse_delay_publish_topic(&cache_manager, "$delay/60/$delay/10/a");
+        assert!(res.is_err());
+
+        let res = parse_delay_publish_topic(&cache_manager, "$delay/3601/a/b");
+        assert_eq!(
+            res,
+            Err(MQTTBrokerError::DelayPublishIntervalExceeded(3601, 3600))
+        );
+
+        let mut cluster = cache_manager.get_cluster_info();
+        cluster.delay_publish.enable = false;
+        cache_manager.set_cluster_info(cluster);
+        let res = parse_delay_publish_topic(&cache_manager, "$delay/60/a/b").unwrap();
+        assert!(res.is_none());
+    }
+
+    #[tokio::test]
+    async fn delay_message_persist_test() {
+        let cache_manager = build_cache_manager();
+        let storage_adapter = Arc::new(MemoryStorageAdapter::new());
+        let client_poll = Arc::new(ClientPool::new(1));
+
+        let publish = Publish {
+            topic: Bytes::from("$delay/60/a/b"),
+            payload: Bytes::from("test"),
+            ..Default::default()
+        };
+        save_delay_message(
+            &cache_manager,
+            &storage_adapter,
+            60,
+            "a/b",
+            "client-1",
+            &publish,
+            &None,
+        )
+        .await
+        .unwrap();
+        save_delay_message(
+            &cache_manager,
+            &storage_adapter,
+            120,
+            "a/c",
+            "client-1",
+            &publish,
+            &None,
+        )
+        .await
+        .unwrap();
+
+        let mut manager = DelayMessageManager::new(
+            cache_manager.clone(),
+            storage_adapter.clone(),
+            client_poll.clone(),
+            1,
+        );
+        assert!(manager.renew_lease().await.unwrap());
+        manager.pull_delay_message().await.unwrap();
+        assert_eq!(manager.delay_queue.len(), 2);
+        let (_, first) = manager.delay_queue.first_key_value().unwrap();
+        assert_eq!(first.target_topic, "a/b");
+        assert_eq!(first.message.topic, Bytes::from("a/b"));
+        assert!(first.deliver_time >= now_second() + 59);
+
+        // pending limit reached
+        let res = save_delay_message(
+            &cache_manager,
+            &storage_adapter,
+            60,
+            "a/d",
+            "client-1",
+            &publish,
+            &None,
+        )
+        .await;
+        assert!(res.is_err());
+
+        // a restarted node rebuilds the queue from storage when it takes the lease again
+        let mut manager = DelayMessageManager::new(cache_manager, storage_adapter, client_poll, 1);
+        assert!(manager.renew_lease().await.unwrap());
+        assert_eq!(manager.delay_queue.len(), 2);
+        let (_, last) = manager.delay_queue.last_key_value().unwrap();
+        assert_eq!(last.target_topic, "a/c");
+    }
+
+    #[tokio::test]
+    async fn delay_message_lease_test() {
+        let cache_manager = build_cache_manager();
+        let storage_adapter = Arc::new(MemoryStorageAdapter::new());
+        let client_poll = Arc::new(ClientPool::new(1));
+        let topic = MqttTopic::new("topic-1".to_string(), "a/b".to_string());
+        cache_manager.add_topic("a/b", &topic);
+
+        let publish = Publish {
+            topic: Bytes::from("$delay/0/a/b"),
+            payload: Bytes::from("test"),
+            ..Default::default()
+        };
+        save_delay_message(
+            &cache_manager,
+            &storage_adapter,
+            0,
+            "a/b",
+            "client-1",
+            &publish,
+            &None,
+        )
+        .await
+        .unwrap();
+
+        let mut leader = DelayMessageManager::new(
+            cache_manager.clone(),
+            storage_adapter.clone(),
+            client_poll.clone(),
+            1,
+        );
+        assert!(leader.renew_lease().await.unwrap());
+        leader.pull_delay_message().await.unwrap();
+
+        // a node that believes it was elected cannot fire while another one holds the lease
+        let mut other =
+            DelayMessageManager::new(cache_manager, storage_adapter.clone(), client_poll, 2);
+        assert!(!other.renew_lease().await.unwrap());
+        other.load_delay_message().await.unwrap();
+        assert_eq!(other.delay_queue.len(), 1);
+        assert!(other.send_expired_delay_message().await.is_err());
+        assert_eq!(other.delay_queue.len(), 1);
+
+        leader.send_expired_delay_message().await.unwrap();
+        assert!(leader.delay_queue.is_empty());
+
+        // the lease of the previous holder ran out, the new holder finds nothing left to fire
+        let lease = DelayMessageLease {
+            broker_id: 1,
+            expire_time: 0,
+        };
+        storage_adapter
+            .set(
+                DELAY_MESSAGE_LEASE_KEY.to_string(),
+                Record::build_b(serde_json::to_vec(&lease).unwrap()),
+            )
+            .await
+            .unwrap();
+        assert!(other.renew_lease().await.unwrap());
+        assert!(other.delay_queue.is_empty());
+        other.send_expired_delay_message().await.unwrap();
+
+        let first = storage_adapter
+            .stream_read_by_offset("topic-1".to_string(), 0)
+            .await
+            .unwrap();
+        assert!(first.is_some());
+        let second = storage_adapter
+            .stream_read_by_offset("topic-1".to_string(), 1)
+            .await
+            .unwrap();
+        assert!(second.is_none());
+    }
+}
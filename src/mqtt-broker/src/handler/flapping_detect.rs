@@ -0,0 +1,209 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use common_base::error::common::CommonError;
+use common_base::tools::{now_mills, now_second};
+use dashmap::DashMap;
+use grpc_clients::poll::ClientPool;
+use log::{error, info};
+use metadata_struct::acl::mqtt_blacklist::{MqttAclBlackList, MqttAclBlackListType};
+use protocol::broker_mqtt::broker_mqtt_placement::{
+    MqttBrokerUpdateCacheActionType, MqttBrokerUpdateCacheResourceType,
+};
+use storage_adapter::storage::StorageAdapter;
+
+use super::cache::{update_remote_cache, CacheManager};
+use crate::observability::system_topic::event::{
+    st_report_flapping_event, SystemTopicFlappingEventMessge,
+};
+use crate::observability::warn::{record_alarm, ALARM_CLIENT_FLAPPING};
+use crate::storage::blacklist::BlackListStorage;
+
+// Clients that stopped connecting are cleaned up once every this many connects
+const FLAPPING_GC_INTERVAL: u64 = 1000;
+
+#[derive(Default)]
+pub struct FlappingDetector {
+    // (client_id, connect times in seconds within the window)
+    connect_times: DashMap<String, VecDeque<u64>>,
+    record_num: AtomicU64,
+}
+
+impl FlappingDetector {
+    pub fn new() -> Self {
+        FlappingDetector {
+            connect_times: DashMap::with_capacity(8),
+            record_num: AtomicU64::new(0),
+        }
+    }
+
+    // Every connect after the first one follows a disconnect, so counting the connects of
+    // a client within the sliding window is enough to tell whether it is flapping.
+    pub fn record_connect(
+        &self,
+        client_id: &str,
+        now: u64,
+        window_time: u64,
+        max_client_connections: u64,
+    ) -> bool {
+        if self.record_num.fetch_add(1, Ordering::Relaxed) >= FLAPPING_GC_INTERVAL {
+            self.record_num.store(0, Ordering::Relaxed);
+            self.try_gc(now, window_time);
+        }
+
+        let mut times = self.connect_times.entry(client_id.to_owned()).or_default();
+        times.push_back(now);
+        while let Some(time) = times.front() {
+            if *time + window_time > now {
+                break;
+            }
+            times.pop_front();
+        }
+
+        if times.len() as u64 >= max_client_connections {
+            times.clear();
+            return true;
+        }
+        false
+    }
+
+    fn try_gc(&self, now: u64, window_time: u64) {
+        self.connect_times
+            .retain(|_, times| times.back().is_some_and(|time| *time + window_time > now));
+    }
+}
+
+// Returns true when the client has just been banned for flapping
+pub async fn check_flapping_detect<S>(
+    client_id: &str,
+    source_ip_addr: &str,
+    cache_manager: &Arc<CacheManager>,
+    client_poll: &Arc<ClientPool>,
+    message_storage_adapter: &Arc<S>,
+) -> bool
+where
+    S: StorageAdapter + Clone + Send + Sync + 'static,
+{
+    let config = cache_manager.get_cluster_info().flapping_detect;
+    if !config.enable || config.max_client_connections == 0 {
+        return false;
+    }
+
+    if !cache_manager.flapping_detector.record_connect(
+        client_id,
+        now_second(),
+        config.window_time,
+        config.max_client_connections,
+    ) {
+        return false;
+    }
+
+    let blacklist = MqttAclBlackList {
+        blacklist_type: MqttAclBlackListType::ClientId,
+        resource_name: client_id.to_owned(),
+        end_time: now_second() + config.ban_time,
+        desc: format!(
+            "Flapping detected, more than {} connections in {} seconds",
+            config.max_client_connections, config.window_time
+        ),
+    };
+    info!(
+        "Client [{}] is banned until {} for flapping",
+        client_id, blacklist.end_time
+    );
+    cache_manager.add_blacklist(blacklist.clone());
+    record_alarm(
+        cache_manager,
+        ALARM_CLIENT_FLAPPING,
+        format!(
+            "client {} from {} is banned until {}, {}",
+            client_id, source_ip_addr, blacklist.end_time, blacklist.desc
+        ),
+    );
+
+    if let Err(e) = save_flapping_blacklist(client_poll, &blacklist).await {
+        error!(
+            "Failed to save the flapping blacklist of client [{}], error message :{}",
+            client_id, e
+        );
+    }
+
+    let event_data = SystemTopicFlappingEventMessge {
+        ts: now_mills(),
+        clientid: client_id.to_owned(),
+        ipaddress: source_ip_addr.to_owned(),
+        window_time: config.window_time,
+        max_client_connections: config.max_client_connections,
+        ban_until: blacklist.end_time,
+    };
+    st_report_flapping_event(
+        message_storage_adapter,
+        cache_manager,
+        client_poll,
+        event_data,
+    )
+    .await;
+    true
+}
+
+// The blacklist is written to the placement center so that brokers started later load it,
+// and pushed to the running brokers so that the ban applies to the whole cluster right away.
+async fn save_flapping_blacklist(
+    client_poll: &Arc<ClientPool>,
+    blacklist: &MqttAclBlackList,
+) -> Result<(), CommonError> {
+    let blacklist_storage = BlackListStorage::new(client_poll.clone());
+    blacklist_storage.save_blacklist(blacklist.clone()).await?;
+    update_remote_cache(
+        client_poll,
+        MqttBrokerUpdateCacheActionType::Add,
+        MqttBrokerUpdateCacheResourceType::Blacklist,
+        blacklist.encode()?,
+    )
+    .await;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FlappingDetector;
+
+    #[test]
+    fn record_connect_test() {
+        let detector = FlappingDetector::new();
+        let client_id = "client-1";
+        assert!(!detector.record_connect(client_id, 100, 10, 3));
+        assert!(!detector.record_connect(client_id, 101, 10, 3));
+        assert!(detector.record_connect(client_id, 102, 10, 3));
+
+        // the window restarts after a ban
+        assert!(!detector.record_connect(client_id, 103, 10, 3));
+
+        // connects older than the window are not counted
+        let detector = FlappingDetector::new();
+        assert!(!detector.record_connect(client_id, 100, 10, 3));
+        assert!(!detector.record_connect(client_id, 105, 10, 3));
+        assert!(!detector.record_connect(client_id, 110, 10, 3));
+        assert!(detector.record_connect(client_id, 114, 10, 3));
+
+        // clients are counted separately
+        assert!(!detector.record_connect("client-2", 114, 10, 3));
+
+        detector.try_gc(130, 10);
+        assert!(detector.connect_times.is_empty());
+    }
+}
@@ -11,17 +11,432 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+use std::cmp::max;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
+use dashmap::DashMap;
+use grpc_clients::poll::ClientPool;
+use log::error;
+use metadata_struct::mqtt::cluster::MqttClusterDynamicRateLimit;
 use protocol::mqtt::common::{MQTTProtocol, QoS};
 
+use super::cache::CacheManager;
+use crate::server::connection::NetworkConnectionType;
+use crate::storage::cluster::ClusterStorage;
+
 pub fn is_flow_control(protocol: &MQTTProtocol, qos: QoS) -> bool {
     protocol.is_mqtt5() && (qos == QoS::AtLeastOnce || qos == QoS::ExactlyOnce)
 }
 
-pub fn is_connection_rate_exceeded() -> bool {
-    false
+pub fn is_connection_rate_exceeded(
+    cache_manager: &Arc<CacheManager>,
+    network_type: &NetworkConnectionType,
+) -> bool {
+    let rate = cache_manager
+        .get_cluster_info()
+        .rate_limit
+        .listener_connection_rate;
+    !cache_manager
+        .rate_limiter
+        .try_acquire_connection(&network_type.to_string(), rate)
+}
+
+pub fn is_subscribe_rate_exceeded(cache_manager: &Arc<CacheManager>, client_id: &str) -> bool {
+    let rate = cache_manager
+        .get_cluster_info()
+        .rate_limit
+        .client_subscribe_rate;
+    !cache_manager
+        .rate_limiter
+        .try_acquire_subscribe(client_id, rate)
+}
+
+// The cluster-wide publish rate is shared between the registered broker nodes. Called when a node
+// joins or leaves the cluster, and periodically for the nodes that are lost without leaving.
+pub async fn refresh_broker_num(cache_manager: &Arc<CacheManager>, client_poll: &Arc<ClientPool>) {
+    let cluster_storage = ClusterStorage::new(client_poll.clone());
+    match cluster_storage.node_list().await {
+        Ok(nodes) => cache_manager
+            .rate_limiter
+            .set_broker_num(nodes.len() as u64),
+        Err(e) => error!(
+            "Failed to refresh the broker node number, error message :{}",
+            e
+        ),
+    }
+}
+
+#[derive(Clone, PartialEq, Debug)]
+pub enum PublishRateResult {
+    Pass,
+    // The client publishes too many messages per second
+    MessageRateTooHigh,
+    // The client publishes too many bytes per second, or the cluster-wide limit is reached
+    QuotaExceeded,
+}
+
+// MQTT 5 clients are told about the exceeded limit with a reason code. MQTT 3 has no such reason codes,
+// so the message is accepted and the reads of the connection are paused until the limits recover.
+pub fn publish_rate_check(
+    protocol: &MQTTProtocol,
+    cache_manager: &Arc<CacheManager>,
+    client_id: &str,
+    connect_id: u64,
+    payload_len: usize,
+) -> PublishRateResult {
+    let limit = cache_manager.get_cluster_info().rate_limit;
+    if protocol.is_mqtt5() {
+        cache_manager
+            .rate_limiter
+            .try_acquire_publish(&limit, client_id, payload_len as u64)
+    } else {
+        cache_manager.rate_limiter.acquire_publish_or_pause(
+            &limit,
+            client_id,
+            connect_id,
+            payload_len as u64,
+        );
+        PublishRateResult::Pass
+    }
+}
+
+// Token bucket that refills at `rate` tokens per second and holds at most one second of tokens
+pub struct TokenBucket {
+    rate: u64,
+    tokens: f64,
+    last_time: Instant,
+}
+
+impl TokenBucket {
+    pub fn new(rate: u64) -> Self {
+        TokenBucket {
+            rate,
+            tokens: rate as f64,
+            last_time: Instant::now(),
+        }
+    }
+
+    fn refill(&mut self, rate: u64) {
+        if rate != self.rate {
+            // a bucket that had no limit before starts full
+            self.tokens = if self.rate == 0 {
+                rate as f64
+            } else {
+                self.tokens.min(rate as f64)
+            };
+            self.rate = rate;
+        }
+        let now = Instant::now();
+        let elapsed = now.duration_since(self.last_time).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rate as f64).min(rate as f64);
+        self.last_time = now;
+    }
+
+    // Takes the tokens only when they are available. A request larger than the bucket
+    // is allowed when the bucket is full, and puts the bucket in debt.
+    pub fn try_acquire(&mut self, rate: u64, num: u64) -> bool {
+        self.refill(rate);
+        if self.tokens >= num.min(rate) as f64 {
+            self.tokens -= num as f64;
+            return true;
+        }
+        false
+    }
+
+    // Gives back tokens taken by try_acquire
+    pub fn refund(&mut self, num: u64) {
+        self.tokens = (self.tokens + num as f64).min(self.rate as f64);
+    }
+
+    // Always takes the tokens, returns how long it takes the bucket to get out of debt
+    pub fn acquire(&mut self, rate: u64, num: u64) -> Duration {
+        self.refill(rate);
+        self.tokens -= num as f64;
+        if self.tokens >= 0.0 {
+            return Duration::ZERO;
+        }
+        Duration::from_secs_f64(-self.tokens / rate as f64)
+    }
+}
+
+pub struct RateLimiter {
+    // (listener, TokenBucket)
+    connection_bucket: DashMap<String, TokenBucket>,
+    // (client_id, TokenBucket)
+    publish_bucket: DashMap<String, TokenBucket>,
+    // (client_id, TokenBucket)
+    publish_bytes_bucket: DashMap<String, TokenBucket>,
+    // (client_id, TokenBucket)
+    subscribe_bucket: DashMap<String, TokenBucket>,
+    cluster_publish_bucket: Mutex<TokenBucket>,
+    // The cluster-wide limit is shared evenly between the broker nodes
+    broker_num: AtomicU64,
+    // (connect_id, time to resume reading), MQTT 3 connections that are throttled
+    read_pause: DashMap<u64, Instant>,
+}
+
+impl Default for RateLimiter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        RateLimiter {
+            connection_bucket: DashMap::with_capacity(4),
+            publish_bucket: DashMap::with_capacity(8),
+            publish_bytes_bucket: DashMap::with_capacity(8),
+            subscribe_bucket: DashMap::with_capacity(8),
+            cluster_publish_bucket: Mutex::new(TokenBucket::new(0)),
+            broker_num: AtomicU64::new(1),
+            read_pause: DashMap::with_capacity(8),
+        }
+    }
+
+    pub fn set_broker_num(&self, num: u64) {
+        self.broker_num.store(max(num, 1), Ordering::Relaxed);
+    }
+
+    pub fn try_acquire_connection(&self, listener: &str, rate: u64) -> bool {
+        try_acquire(&self.connection_bucket, listener, rate, 1)
+    }
+
+    pub fn try_acquire_subscribe(&self, client_id: &str, rate: u64) -> bool {
+        try_acquire(&self.subscribe_bucket, client_id, rate, 1)
+    }
+
+    pub fn try_acquire_publish(
+        &self,
+        limit: &MqttClusterDynamicRateLimit,
+        client_id: &str,
+        bytes: u64,
+    ) -> PublishRateResult {
+        if !try_acquire(
+            &self.publish_bucket,
+            client_id,
+            limit.client_publish_rate,
+            1,
+        ) {
+            return PublishRateResult::MessageRateTooHigh;
+        }
+        // A rejected message does not use up the tokens of the limits that passed
+        if !try_acquire(
+            &self.publish_bytes_bucket,
+            client_id,
+            limit.client_publish_bytes_rate,
+            bytes,
+        ) {
+            refund(&self.publish_bucket, client_id, 1);
+            return PublishRateResult::QuotaExceeded;
+        }
+
+        let cluster_rate = self.node_publish_rate(limit);
+        if cluster_rate > 0
+            && !self
+                .cluster_publish_bucket
+                .lock()
+                .unwrap()
+                .try_acquire(cluster_rate, 1)
+        {
+            refund(&self.publish_bucket, client_id, 1);
+            refund(&self.publish_bytes_bucket, client_id, bytes);
+            return PublishRateResult::QuotaExceeded;
+        }
+        PublishRateResult::Pass
+    }
+
+    pub fn acquire_publish_or_pause(
+        &self,
+        limit: &MqttClusterDynamicRateLimit,
+        client_id: &str,
+        connect_id: u64,
+        bytes: u64,
+    ) {
+        let mut wait = acquire(
+            &self.publish_bucket,
+            client_id,
+            limit.client_publish_rate,
+            1,
+        );
+        wait = max(
+            wait,
+            acquire(
+                &self.publish_bytes_bucket,
+                client_id,
+                limit.client_publish_bytes_rate,
+                bytes,
+            ),
+        );
+        let cluster_rate = self.node_publish_rate(limit);
+        if cluster_rate > 0 {
+            wait = max(
+                wait,
+                self.cluster_publish_bucket
+                    .lock()
+                    .unwrap()
+                    .acquire(cluster_rate, 1),
+            );
+        }
+
+        if !wait.is_zero() {
+            self.read_pause.insert(connect_id, Instant::now() + wait);
+        }
+    }
+
+    // How long the reads of the connection stay paused
+    pub fn read_pause_time(&self, connect_id: u64) -> Option<Duration> {
+        let resume_time = *self.read_pause.get(&connect_id)?;
+        let now = Instant::now();
+        if resume_time <= now {
+            self.read_pause.remove(&connect_id);
+            return None;
+        }
+        Some(resume_time - now)
+    }
+
+    pub fn remove_connection(&self, connect_id: u64) {
+        self.read_pause.remove(&connect_id);
+    }
+
+    pub fn remove_client(&self, client_id: &str) {
+        self.publish_bucket.remove(client_id);
+        self.publish_bytes_bucket.remove(client_id);
+        self.subscribe_bucket.remove(client_id);
+    }
+
+    fn node_publish_rate(&self, limit: &MqttClusterDynamicRateLimit) -> u64 {
+        if limit.cluster_publish_rate == 0 {
+            return 0;
+        }
+        max(
+            limit.cluster_publish_rate / self.broker_num.load(Ordering::Relaxed),
+            1,
+        )
+    }
+}
+
+fn try_acquire(buckets: &DashMap<String, TokenBucket>, key: &str, rate: u64, num: u64) -> bool {
+    if rate == 0 {
+        return true;
+    }
+    buckets
+        .entry(key.to_owned())
+        .or_insert_with(|| TokenBucket::new(rate))
+        .try_acquire(rate, num)
+}
+
+fn refund(buckets: &DashMap<String, TokenBucket>, key: &str, num: u64) {
+    if let Some(mut bucket) = buckets.get_mut(key) {
+        bucket.refund(num);
+    }
 }
 
-pub fn is_subscribe_rate_exceeded() -> bool {
-    false
+fn acquire(buckets: &DashMap<String, TokenBucket>, key: &str, rate: u64, num: u64) -> Duration {
+    if rate == 0 {
+        return Duration::ZERO;
+    }
+    buckets
+        .entry(key.to_owned())
+        .or_insert_with(|| TokenBucket::new(rate))
+        .acquire(rate, num)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use metadata_struct::mqtt::cluster::MqttClusterDynamicRateLimit;
+
+    use super::{PublishRateResult, RateLimiter, TokenBucket};
+
+    #[test]
+    fn token_bucket_test() {
+        let mut bucket = TokenBucket::new(2);
+        assert!(bucket.try_acquire(2, 1));
+        assert!(bucket.try_acquire(2, 1));
+        assert!(!bucket.try_acquire(2, 1));
+
+        // a request larger than the bucket is allowed once the bucket is full
+        let mut bucket = TokenBucket::new(10);
+        assert!(bucket.try_acquire(10, 25));
+        assert!(!bucket.try_acquire(10, 1));
+
+        let mut bucket = TokenBucket::new(10);
+        assert_eq!(bucket.acquire(10, 10), Duration::ZERO);
+        let wait = bucket.acquire(10, 5);
+        assert!(wait > Duration::from_millis(400) && wait <= Duration::from_millis(500));
+    }
+
+    #[test]
+    fn publish_rate_test() {
+        let limiter = RateLimiter::new();
+        let limit = MqttClusterDynamicRateLimit {
+            client_publish_rate: 2,
+            client_publish_bytes_rate: 100,
+            ..Default::default()
+        };
+        assert_eq!(
+            limiter.try_acquire_publish(&limit, "c1", 10),
+            PublishRateResult::Pass
+        );
+        assert_eq!(
+            limiter.try_acquire_publish(&limit, "c1", 200),
+            PublishRateResult::QuotaExceeded
+        );
+        // the message rejected by the bytes limit did not use up a message token
+        assert_eq!(
+            limiter.try_acquire_publish(&limit, "c1", 10),
+            PublishRateResult::Pass
+        );
+        assert_eq!(
+            limiter.try_acquire_publish(&limit, "c1", 10),
+            PublishRateResult::MessageRateTooHigh
+        );
+        // limits are per client
+        assert_eq!(
+            limiter.try_acquire_publish(&limit, "c2", 10),
+            PublishRateResult::Pass
+        );
+
+        // the cluster-wide limit is shared between the broker nodes
+        let limiter = RateLimiter::new();
+        limiter.set_broker_num(2);
+        let limit = MqttClusterDynamicRateLimit {
+            cluster_publish_rate: 4,
+            ..Default::default()
+        };
+        assert_eq!(
+            limiter.try_acquire_publish(&limit, "c1", 10),
+            PublishRateResult::Pass
+        );
+        assert_eq!(
+            limiter.try_acquire_publish(&limit, "c2", 10),
+            PublishRateResult::Pass
+        );
+        assert_eq!(
+            limiter.try_acquire_publish(&limit, "c3", 10),
+            PublishRateResult::QuotaExceeded
+        );
+    }
+
+    #[test]
+    fn read_pause_test() {
+        let limiter = RateLimiter::new();
+        let limit = MqttClusterDynamicRateLimit {
+            client_publish_rate: 1,
+            ..Default::default()
+        };
+        limiter.acquire_publish_or_pause(&limit, "c1", 1, 10);
+        assert!(limiter.read_pause_time(1).is_none());
+
+        limiter.acquire_publish_or_pause(&limit, "c1", 1, 10);
+        let wait = limiter.read_pause_time(1).unwrap();
+        assert!(wait <= Duration::from_secs(1));
+
+        limiter.remove_connection(1);
+        assert!(limiter.read_pause_time(1).is_none());
+    }
 }
@@ -94,7 +94,7 @@ impl ClientKeepAlive {
                         packet: resp,
                     };
 
-                    if network.is_tcp() {
+                    if network.is_stream() {
                         match self
                             .connnection_manager
                             .write_tcp_frame(connection.connect_id, wrap)
@@ -85,6 +85,7 @@ where
                     .await
                 {
                     Ok(_) => {
+                        cache_manager.push_notify.notify(&topic.topic_id);
                         return Ok(());
                     }
                     Err(e) => {
@@ -16,6 +16,8 @@ pub mod cache;
 pub mod command;
 pub mod connection;
 pub mod constant;
+pub mod delay_message;
+pub mod flapping_detect;
 pub mod flow_control;
 pub mod heartbreat;
 pub mod keep_alive;
@@ -23,8 +25,10 @@ pub mod lastwill;
 pub mod message;
 pub mod mqtt;
 pub mod pkid;
+pub mod publish;
 pub mod response;
 pub mod retain;
 pub mod session;
+pub mod takeover;
 pub mod topic;
 pub mod validator;
@@ -15,17 +15,21 @@
 use std::net::SocketAddr;
 use std::sync::Arc;
 
+use bytes::Bytes;
+use common_base::config::broker_mqtt::broker_mqtt_conf;
+use common_base::error::common::CommonError;
+use common_base::error::mqtt_broker::MQTTBrokerError;
 use common_base::tools::now_second;
 use grpc_clients::poll::ClientPool;
-use log::{error, warn};
+use log::{error, info, warn};
 use metadata_struct::mqtt::message::MqttMessage;
 use protocol::mqtt::common::{
-    Connect, ConnectProperties, ConnectReturnCode, Disconnect, DisconnectProperties,
-    DisconnectReasonCode, LastWill, LastWillProperties, Login, MQTTPacket, MQTTProtocol, PingReq,
-    PubAck, PubAckProperties, PubAckReason, PubComp, PubCompProperties, PubCompReason, PubRec,
-    PubRecProperties, PubRecReason, PubRel, PubRelProperties, PubRelReason, Publish,
-    PublishProperties, QoS, Subscribe, SubscribeProperties, SubscribeReasonCode, UnsubAckReason,
-    Unsubscribe, UnsubscribeProperties,
+    Auth, AuthProperties, AuthReason, Connect, ConnectProperties, ConnectReturnCode, Disconnect,
+    DisconnectProperties, DisconnectReasonCode, LastWill, LastWillProperties, Login, MQTTPacket,
+    MQTTProtocol, PingReq, PubAck, PubAckProperties, PubAckReason, PubComp, PubCompProperties,
+    PubCompReason, PubRec, PubRecProperties, PubRecReason, PubRel, PubRelProperties, PubRelReason,
+    Publish, PublishProperties, QoS, Subscribe, SubscribeProperties, SubscribeReasonCode,
+    UnsubAckReason, Unsubscribe, UnsubscribeProperties,
 };
 use storage_adapter::storage::StorageAdapter;
 
@@ -33,22 +37,25 @@ use super::connection::disconnect_connection;
 use super::flow_control::is_flow_control;
 use super::message::build_message_expire;
 use crate::handler::cache::{
-    CacheManager, ConnectionLiveTime, QosAckPackageData, QosAckPackageType,
+    CacheManager, ConnectPackage, ConnectionLiveTime, QosAckPackageData, QosAckPackageType,
 };
-use crate::handler::connection::{build_connection, get_client_id};
+use crate::handler::connection::{authentication_method, build_connection, get_client_id};
+use crate::handler::delay_message::{parse_delay_publish_topic, save_delay_message};
+use crate::handler::flapping_detect::check_flapping_detect;
 use crate::handler::lastwill::save_last_will_message;
 use crate::handler::pkid::{pkid_delete, pkid_exists, pkid_save};
 use crate::handler::response::{
-    response_packet_mqtt_connect_fail, response_packet_mqtt_connect_success,
-    response_packet_mqtt_distinct_by_reason, response_packet_mqtt_ping_resp,
-    response_packet_mqtt_puback_fail, response_packet_mqtt_puback_success,
-    response_packet_mqtt_pubcomp_fail, response_packet_mqtt_pubcomp_success,
-    response_packet_mqtt_pubrec_fail, response_packet_mqtt_pubrec_success,
-    response_packet_mqtt_pubrel_success, response_packet_mqtt_suback,
-    response_packet_mqtt_unsuback,
+    response_packet_mqtt_auth, response_packet_mqtt_connect_fail,
+    response_packet_mqtt_connect_success, response_packet_mqtt_distinct_by_reason,
+    response_packet_mqtt_ping_resp, response_packet_mqtt_puback_fail,
+    response_packet_mqtt_puback_success, response_packet_mqtt_pubcomp_fail,
+    response_packet_mqtt_pubcomp_success, response_packet_mqtt_pubrec_fail,
+    response_packet_mqtt_pubrec_success, response_packet_mqtt_pubrel_success,
+    response_packet_mqtt_suback, response_packet_mqtt_unsuback,
 };
 use crate::handler::retain::save_topic_retain_message;
 use crate::handler::session::{build_session, save_session};
+use crate::handler::takeover::takeover_session;
 use crate::handler::topic::{get_topic_name, try_init_topic};
 use crate::handler::validator::{
     connect_validator, publish_validator, subscribe_validator, un_subscribe_validator,
@@ -57,9 +64,18 @@ use crate::observability::system_topic::event::{
     st_report_connected_event, st_report_disconnected_event, st_report_subscribed_event,
     st_report_unsubscribed_event,
 };
+use crate::security::acl::{is_client_id_blacklist, AclPlaceholders};
+use crate::security::login::psk::apply_psk_identity;
+use crate::security::login::x509::apply_cert_identity;
+use crate::security::login::EnhancedAuthStep;
 use crate::security::AuthDriver;
+use crate::server::connection::NetworkConnection;
 use crate::server::connection_manager::ConnectionManager;
 use crate::storage::message::MessageStorage;
+use crate::subscribe::inflight::InflightStore;
+use crate::subscribe::offline_queue::{
+    clear_offline_message, mark_offline_message_replay, start_offline_message_replay,
+};
 use crate::subscribe::sub_common::{min_qos, path_contain_sub};
 use crate::subscribe::subscribe_manager::SubscribeManager;
 
@@ -102,7 +118,7 @@ where
     pub async fn connect(
         &mut self,
         connect_id: u64,
-        connnect: Connect,
+        mut connnect: Connect,
         connect_properties: Option<ConnectProperties>,
         last_will: Option<LastWill>,
         last_will_properties: Option<LastWillProperties>,
@@ -112,8 +128,26 @@ where
         let cluster: metadata_struct::mqtt::cluster::MqttClusterDynamicConfig =
             self.cache_manager.get_cluster_info();
 
+        // Clients authenticated by the TLS layer take their identity from the TLS client
+        // certificate or the TLS-PSK identity
+        let network_connection = self.connnection_manager.get_connect(connect_id);
+        let login = &if let Some(identity) = network_connection
+            .as_ref()
+            .and_then(|connection| connection.peer_cert.as_ref())
+        {
+            apply_cert_identity(identity, &broker_mqtt_conf().network, &mut connnect, login)
+        } else if let Some(identity) = network_connection
+            .as_ref()
+            .and_then(|connection| connection.psk_identity.as_ref())
+        {
+            apply_psk_identity(identity, login)
+        } else {
+            login.clone()
+        };
+
         if let Some(res) = connect_validator(
             &self.protocol,
+            &self.cache_manager,
             &cluster,
             &connnect,
             &connect_properties,
@@ -125,9 +159,50 @@ where
             return res;
         }
 
+        let username = if let Some(info) = login {
+            info.username.clone()
+        } else {
+            "".to_string()
+        };
+
+        // Banned and flapping clients are rejected before any authentication work is done
+        if let Some(res) = self
+            .connect_precheck(
+                connect_id,
+                &connnect.client_id,
+                &username,
+                &addr,
+                &connect_properties,
+            )
+            .await
+        {
+            return res;
+        }
+
+        if let Some(method) = authentication_method(&connect_properties) {
+            let package = ConnectPackage {
+                connect: connnect,
+                connect_properties,
+                last_will,
+                last_will_properties,
+                addr,
+            };
+            return self
+                .connect_enhanced_auth(connect_id, method, package, network_connection.as_ref())
+                .await;
+        }
+
         match self
             .auth_driver
-            .check_login_auth(login, &connect_properties, &addr)
+            .check_login_auth(
+                connect_id,
+                &connnect.client_id,
+                &self.protocol,
+                network_connection.as_ref(),
+                login,
+                &connect_properties,
+                &addr,
+            )
             .await
         {
             Ok(flag) => {
@@ -150,9 +225,168 @@ where
             }
         }
 
+        let package = ConnectPackage {
+            connect: connnect,
+            connect_properties,
+            last_will,
+            last_will_properties,
+            addr,
+        };
+        self.connect_process(connect_id, package, username, None, None)
+            .await
+    }
+
+    async fn connect_precheck(
+        &self,
+        connect_id: u64,
+        client_id: &str,
+        username: &str,
+        addr: &SocketAddr,
+        connect_properties: &Option<ConnectProperties>,
+    ) -> Option<MQTTPacket> {
+        if self.is_client_banned(connect_id, client_id, username) {
+            return Some(response_packet_mqtt_connect_fail(
+                &self.protocol,
+                ConnectReturnCode::Banned,
+                connect_properties,
+                None,
+            ));
+        }
+
+        // a client without a client ID gets a new one on every connect, so it cannot flap
+        if !client_id.is_empty()
+            && check_flapping_detect(
+                client_id,
+                &addr.ip().to_string(),
+                &self.cache_manager,
+                &self.client_poll,
+                &self.message_storage_adapter,
+            )
+            .await
+        {
+            return Some(response_packet_mqtt_connect_fail(
+                &self.protocol,
+                ConnectReturnCode::Banned,
+                connect_properties,
+                Some(MQTTBrokerError::ClientFlappingBanned(client_id.to_owned()).to_string()),
+            ));
+        }
+        None
+    }
+
+    fn is_client_banned(&self, connect_id: u64, client_id: &str, username: &str) -> bool {
+        let cert_common_name = self
+            .connnection_manager
+            .get_connect(connect_id)
+            .and_then(|network_connection| network_connection.peer_cert)
+            .and_then(|identity| identity.common_name);
+
+        let placeholders = AclPlaceholders {
+            username,
+            client_id,
+            cn: cert_common_name.as_deref(),
+        };
+        is_client_id_blacklist(&self.cache_manager, &placeholders)
+    }
+
+    async fn connect_enhanced_auth(
+        &mut self,
+        connect_id: u64,
+        method: String,
+        package: ConnectPackage,
+        network_connection: Option<&NetworkConnection>,
+    ) -> MQTTPacket {
+        if !self
+            .auth_driver
+            .is_support_auth_method(&method, network_connection)
+        {
+            return response_packet_mqtt_connect_fail(
+                &self.protocol,
+                ConnectReturnCode::BadAuthenticationMethod,
+                &package.connect_properties,
+                Some(MQTTBrokerError::AuthenticationMethodNotSupported(method).to_string()),
+            );
+        }
+
+        let data = if let Some(properties) = &package.connect_properties {
+            properties.authentication_data.clone()
+        } else {
+            None
+        };
+
+        match self
+            .auth_driver
+            .enhanced_auth(connect_id, &method, data, true)
+            .await
+        {
+            Ok(EnhancedAuthStep::Continue(data)) => {
+                // CONNACK is deferred until the client completes the exchange with AUTH packets
+                self.cache_manager.add_pending_connect(connect_id, package);
+                response_packet_mqtt_auth(AuthReason::ContinueAuthentication, method, Some(data))
+            }
+            Ok(EnhancedAuthStep::Success { username, data }) => {
+                self.connect_process(connect_id, package, username, Some(method), data)
+                    .await
+            }
+            Err(e) => response_packet_mqtt_connect_fail(
+                &self.protocol,
+                ConnectReturnCode::NotAuthorized,
+                &package.connect_properties,
+                Some(e.to_string()),
+            ),
+        }
+    }
+
+    async fn connect_process(
+        &mut self,
+        connect_id: u64,
+        package: ConnectPackage,
+        username: String,
+        authentication_method: Option<String>,
+        authentication_data: Option<Bytes>,
+    ) -> MQTTPacket {
+        let cluster = self.cache_manager.get_cluster_info();
+        let ConnectPackage {
+            connect: connnect,
+            connect_properties,
+            last_will,
+            last_will_properties,
+            addr,
+            ..
+        } = package;
+
         let (client_id, new_client_id) = get_client_id(&connnect.client_id);
 
-        let connection = build_connection(
+        // The username of an enhanced authentication is only known once the exchange completes
+        if authentication_method.is_some()
+            && self.is_client_banned(connect_id, &client_id, &username)
+        {
+            return response_packet_mqtt_connect_fail(
+                &self.protocol,
+                ConnectReturnCode::Banned,
+                &connect_properties,
+                None,
+            );
+        }
+
+        // Close the connection that still holds the session on this or another broker
+        if let Err(e) = takeover_session(
+            &client_id,
+            connect_id,
+            &self.cache_manager,
+            &self.client_poll,
+            &self.connnection_manager,
+            &self.sucscribe_manager,
+        )
+        .await
+        {
+            warn!(
+                "Session takeover of client [{}] failed, error message :{}",
+                client_id, e
+            );
+        }
+
+        let mut connection = build_connection(
             connect_id,
             client_id.clone(),
             &cluster,
@@ -160,6 +394,7 @@ where
             &connect_properties,
             &addr,
         );
+        connection.cert_common_name = cert_common_name;
 
         let (session, new_session) = match build_session(
             connect_id,
@@ -231,10 +466,54 @@ where
         self.cache_manager
             .report_heartbeat(client_id.clone(), live_time);
 
+        // Inflight and offline messages of a resumed session are sent before new messages are pushed
+        if new_session {
+            if let Err(e) = clear_offline_message(
+                &self.sucscribe_manager,
+                &self.message_storage_adapter,
+                &client_id,
+            )
+            .await
+            {
+                warn!(
+                    "Failed to clear offline message of client [{}], error message :{}",
+                    client_id, e
+                );
+            }
+            let inflight_store = InflightStore::new(
+                &self.cache_manager,
+                &client_id,
+                self.message_storage_adapter.clone(),
+            );
+            if let Err(e) = inflight_store.clear().await {
+                warn!(
+                    "Failed to clear inflight message of client [{}], error message :{}",
+                    client_id, e
+                );
+            }
+        } else {
+            mark_offline_message_replay(&self.sucscribe_manager, &client_id).await;
+        }
+
         self.cache_manager
             .add_session(client_id.clone(), session.clone());
         self.cache_manager
             .add_connection(connect_id, connection.clone());
+        self.cache_manager.login_success(connect_id, username);
+        info!("connect [{}] login success", connect_id);
+
+        if !new_session {
+            self.sucscribe_manager
+                .restore_client_subscribe(&client_id)
+                .await;
+            start_offline_message_replay(
+                client_id.clone(),
+                self.cache_manager.clone(),
+                self.sucscribe_manager.clone(),
+                self.connnection_manager.clone(),
+                self.message_storage_adapter.clone(),
+            );
+        }
 
         st_report_connected_event(
             &self.message_storage_adapter,
@@ -256,6 +535,8 @@ where
             new_session,
             connection.keep_alive,
             &connect_properties,
+            authentication_method,
+            authentication_data,
         )
     }
 
@@ -278,91 +559,63 @@ where
             connection.recv_qos_message_incr();
         }
 
-        if let Some(pkg) = publish_validator(
+        let is_puback = publish.qos != QoS::ExactlyOnce;
+
+        let result = match publish_message(
             &self.protocol,
             &self.cache_manager,
             &self.client_poll,
+            &self.message_storage_adapter,
+            &self.auth_driver,
             &connection,
             &publish,
             &publish_properties,
         )
         .await
         {
-            if is_flow_control(&self.protocol, publish.qos) {
-                connection.recv_qos_message_decr();
-            }
-            if publish.qos == QoS::AtMostOnce {
-                return None;
-            } else {
-                return Some(pkg);
-            }
-        }
-
-        let is_puback = publish.qos != QoS::ExactlyOnce;
-
-        let topic_name = match get_topic_name(
-            connect_id,
-            &self.cache_manager,
-            &publish,
-            &publish_properties,
-        ) {
-            Ok(da) => da,
+            Ok(result) => result,
             Err(e) => {
                 if is_flow_control(&self.protocol, publish.qos) {
                     connection.recv_qos_message_decr();
                 }
 
-                if is_puback {
-                    return Some(response_packet_mqtt_puback_fail(
-                        &self.protocol,
-                        &connection,
-                        publish.pkid,
+                let (puback_reason, pubrec_reason, e) = match e {
+                    PublishError::Rejected(pkg) => {
+                        // QoS0 publishes get no ack, only the disconnect is sent back
+                        if publish.qos == QoS::AtMostOnce
+                            && !matches!(pkg, MQTTPacket::Disconnect(_, _))
+                        {
+                            return None;
+                        }
+                        return Some(pkg);
+                    }
+                    PublishError::NotAuthorized(_) => {
+                        return Some(response_packet_mqtt_distinct_by_reason(
+                            &self.protocol,
+                            Some(DisconnectReasonCode::NotAuthorized),
+                        ));
+                    }
+                    PublishError::TopicNameInvalid(e) => (
+                        PubAckReason::TopicNameInvalid,
+                        PubRecReason::TopicNameInvalid,
+                        e,
+                    ),
+                    PublishError::QuotaExceeded(e) => {
+                        (PubAckReason::QuotaExceeded, PubRecReason::QuotaExceeded, e)
+                    }
+                    PublishError::Failed(e) => (
                         PubAckReason::UnspecifiedError,
-                        Some(e.to_string()),
-                    ));
-                } else {
-                    return Some(response_packet_mqtt_pubrec_fail(
-                        &self.protocol,
-                        &connection,
-                        publish.pkid,
                         PubRecReason::UnspecifiedError,
-                        Some(e.to_string()),
-                    ));
-                }
-            }
-        };
-
-        if !self
-            .auth_driver
-            .allow_publish(&connection, &topic_name, publish.retain, publish.qos)
-            .await
-        {
-            return Some(response_packet_mqtt_distinct_by_reason(
-                &self.protocol,
-                Some(DisconnectReasonCode::NotAuthorized),
-            ));
-        }
-
-        let topic = match try_init_topic(
-            &topic_name,
-            &self.cache_manager,
-            &self.message_storage_adapter,
-            &self.client_poll,
-        )
-        .await
-        {
-            Ok(tp) => tp,
-            Err(e) => {
-                if is_flow_control(&self.protocol, publish.qos) {
-                    connection.recv_qos_message_decr();
-                }
+                        e,
+                    ),
+                };
 
                 if is_puback {
                     return Some(response_packet_mqtt_puback_fail(
                         &self.protocol,
                         &connection,
                         publish.pkid,
-                        PubAckReason::UnspecifiedError,
+                        puback_reason,
                         Some(e.to_string()),
                     ));
                 } else {
@@ -370,97 +623,19 @@ where
                         &self.protocol,
                         &connection,
                         publish.pkid,
-                        PubRecReason::UnspecifiedError,
+                        pubrec_reason,
                         Some(e.to_string()),
                     ));
                 }
             }
         };
 
+        let topic_name = result.topic_name;
         let client_id = connection.client_id.clone();
-
-        // Persisting retain message data
-        match save_topic_retain_message(
-            &self.cache_manager,
-            &self.client_poll,
-            topic_name.clone(),
-            &client_id,
-            &publish,
-            &publish_properties,
-        )
-        .await
-        {
-            Ok(()) => {}
-            Err(e) => {
-                if is_flow_control(&self.protocol, publish.qos) {
-                    connection.recv_qos_message_decr();
-                }
-
-                if is_puback {
-                    return Some(response_packet_mqtt_puback_fail(
-                        &self.protocol,
-                        &connection,
-                        publish.pkid,
-                        PubAckReason::UnspecifiedError,
-                        Some(e.to_string()),
-                    ));
-                } else {
-                    return Some(response_packet_mqtt_pubrec_fail(
-                        &self.protocol,
-                        &connection,
-                        publish.pkid,
-                        PubRecReason::UnspecifiedError,
-                        Some(e.to_string()),
-                    ));
-                }
-            }
-        }
-
-        // Persisting stores message data
-        let message_storage = MessageStorage::new(self.message_storage_adapter.clone());
-
-        let message_expire = build_message_expire(&self.cache_manager, &publish_properties);
-        let offset = if let Some(record) =
-            MqttMessage::build_record(&client_id, &publish, &publish_properties, message_expire)
-        {
-            match message_storage
-                .append_topic_message(topic.topic_id.clone(), vec![record])
-                .await
-            {
-                Ok(da) => {
-                    format!("{:?}", da)
-                }
-                Err(e) => {
-                    if is_flow_control(&self.protocol, publish.qos) {
-                        connection.recv_qos_message_decr();
-                    }
-
-                    if is_puback {
-                        return Some(response_packet_mqtt_puback_fail(
-                            &self.protocol,
-                            &connection,
-                            publish.pkid,
-                            PubAckReason::UnspecifiedError,
-                            Some(e.to_string()),
-                        ));
-                    } else {
-                        return Some(response_packet_mqtt_pubrec_fail(
-                            &self.protocol,
-                            &connection,
-                            publish.pkid,
-                            PubRecReason::UnspecifiedError,
-                            Some(e.to_string()),
-                        ));
-                    }
-                }
-            }
-        } else {
-            "-1".to_string()
-        };
-        let user_properties: Vec<(String, String)> = vec![("offset".to_string(), offset)];
+        let user_properties: Vec<(String, String)> = vec![("offset".to_string(), result.offset)];
 
         self.cache_manager
-            .add_topic_alias(connect_id, &topic_name, &publish_properties);
+            .add_topic_alias(connect_id, &result.raw_topic_name, &publish_properties);
 
         match publish.qos {
             QoS::AtMostOnce => None,
@@ -889,12 +1064,138 @@ where
         )
     }
 
+    pub async fn auth(
+        &mut self,
+        connect_id: u64,
+        auth: Auth,
+        auth_properties: Option<AuthProperties>,
+    ) -> MQTTPacket {
+        let (method, data) = if let Some(properties) = auth_properties {
+            (
+                properties.authentication_method,
+                properties.authentication_data,
+            )
+        } else {
+            (None, None)
+        };
+
+        let method = if let Some(method) = method {
+            method
+        } else {
+            return response_packet_mqtt_distinct_by_reason(
+                &self.protocol,
+                Some(DisconnectReasonCode::ProtocolError),
+            );
+        };
+
+        // The authentication exchange was started by CONNECT
+        if let Some(package) = self.cache_manager.remove_pending_connect(connect_id) {
+            if auth.reason != Some(AuthReason::ContinueAuthentication)
+                || authentication_method(&package.connect_properties) != Some(method.clone())
+            {
+                self.auth_driver.remove_enhanced_auth(connect_id);
+                return response_packet_mqtt_connect_fail(
+                    &self.protocol,
+                    ConnectReturnCode::ProtocolError,
+                    &package.connect_properties,
+                    None,
+                );
+            }
+
+            return match self
+                .auth_driver
+                .enhanced_auth(connect_id, &method, data, false)
+                .await
+            {
+                Ok(EnhancedAuthStep::Continue(data)) => {
+                    self.cache_manager.add_pending_connect(connect_id, package);
+                    response_packet_mqtt_auth(
+                        AuthReason::ContinueAuthentication,
+                        method,
+                        Some(data),
+                    )
+                }
+                Ok(EnhancedAuthStep::Success { username, data }) => {
+                    self.connect_process(connect_id, package, username, Some(method), data)
+                        .await
+                }
+                Err(e) => response_packet_mqtt_connect_fail(
+                    &self.protocol,
+                    ConnectReturnCode::NotAuthorized,
+                    &package.connect_properties,
+                    Some(e.to_string()),
+                ),
+            };
+        }
+
+        // Re-authentication, it must use the same method as CONNECT
+        let connection = if let Some(connection) = self.cache_manager.get_connection(connect_id) {
+            connection
+        } else {
+            return response_packet_mqtt_distinct_by_reason(
+                &self.protocol,
+                Some(DisconnectReasonCode::ProtocolError),
+            );
+        };
+
+        if connection.auth_method != Some(method.clone()) {
+            return response_packet_mqtt_distinct_by_reason(
+                &self.protocol,
+                Some(DisconnectReasonCode::ProtocolError),
+            );
+        }
+
+        let restart = match auth.reason {
+            Some(AuthReason::ReAuthenticate) => true,
+            Some(AuthReason::ContinueAuthentication) => false,
+            _ => {
+                return response_packet_mqtt_distinct_by_reason(
+                    &self.protocol,
+                    Some(DisconnectReasonCode::ProtocolError),
+                );
+            }
+        };
+
+        match self
+            .auth_driver
+            .enhanced_auth(connect_id, &method, data, restart)
+            .await
+        {
+            Ok(EnhancedAuthStep::Continue(data)) => {
+                response_packet_mqtt_auth(AuthReason::ContinueAuthentication, method, Some(data))
+            }
+            Ok(EnhancedAuthStep::Success { username, data }) => {
+                // re-authentication refreshes the credentials, it cannot switch to another user
+                if username != connection.login_user {
+                    warn!(
+                        "connect [{}] re-authenticated as {} instead of {}",
+                        connect_id, username, connection.login_user
+                    );
+                    return response_packet_mqtt_distinct_by_reason(
+                        &self.protocol,
+                        Some(DisconnectReasonCode::NotAuthorized),
+                    );
+                }
+                response_packet_mqtt_auth(AuthReason::Success, method, data)
+            }
+            Err(e) => {
+                warn!("connect [{}] re-authentication failed, {}", connect_id, e);
+                response_packet_mqtt_distinct_by_reason(
+                    &self.protocol,
+                    Some(DisconnectReasonCode::NotAuthorized),
+                )
+            }
+        }
+    }
+
     pub async fn disconnect(
         &self,
         connect_id: u64,
         disconnect: Disconnect,
         _: Option<DisconnectProperties>,
     ) -> Option<MQTTPacket> {
+        self.auth_driver.remove_enhanced_auth(connect_id);
+
         let connection = if let Some(se) = self.cache_manager.connection_info.get(&connect_id) {
             se.clone()
         } else {
@@ -0,0 +1,176 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use common_base::error::common::CommonError;
+use common_base::error::mqtt_broker::MQTTBrokerError;
+use grpc_clients::poll::ClientPool;
+use metadata_struct::mqtt::message::MqttMessage;
+use protocol::mqtt::common::{MQTTPacket, MQTTProtocol, Publish, PublishProperties};
+use storage_adapter::storage::StorageAdapter;
+
+use super::cache::CacheManager;
+use super::connection::Connection;
+use super::delay_message::{parse_delay_publish_topic, save_delay_message};
+use super::message::build_message_expire;
+use super::retain::save_topic_retain_message;
+use super::topic::{get_topic_name, try_init_topic};
+use super::validator::publish_validator;
+use crate::security::AuthDriver;
+use crate::storage::message::MessageStorage;
+
+// Why a publish was rejected, MQTT answers with a PUBACK/PUBREC or DISCONNECT, HTTP with an error
+pub enum PublishError {
+    // The validator already built the packet to send back
+    Rejected(MQTTPacket),
+    NotAuthorized(String),
+    TopicNameInvalid(CommonError),
+    QuotaExceeded(CommonError),
+    Failed(CommonError),
+}
+
+pub struct PublishResult {
+    // The topic the message was written to, the target topic of a delayed message
+    pub topic_name: String,
+    // The topic name of the packet, before the alias and $delay prefix were resolved
+    pub raw_topic_name: String,
+    pub offset: String,
+}
+
+// Validates, authorizes and stores one message, shared by MQTT clients and the HTTP publish API
+#[allow(clippy::too_many_arguments)]
+pub async fn publish_message<S>(
+    protocol: &MQTTProtocol,
+    cache_manager: &Arc<CacheManager>,
+    client_poll: &Arc<ClientPool>,
+    message_storage_adapter: &Arc<S>,
+    auth_driver: &Arc<AuthDriver>,
+    connection: &Connection,
+    publish: &Publish,
+    publish_properties: &Option<PublishProperties>,
+) -> Result<PublishResult, PublishError>
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    if let Some(pkg) = publish_validator(
+        protocol,
+        cache_manager,
+        client_poll,
+        connection,
+        publish,
+        publish_properties,
+    )
+    .await
+    {
+        return Err(PublishError::Rejected(pkg));
+    }
+
+    let topic_name = get_topic_name(
+        connection.connect_id,
+        cache_manager,
+        publish,
+        publish_properties,
+    )
+    .map_err(|e| PublishError::Failed(e.into()))?;
+
+    // $delay/{seconds}/{topic}: the message is published to the target topic after the delay
+    let raw_topic_name = topic_name.clone();
+    let (topic_name, delay_interval) = match parse_delay_publish_topic(cache_manager, &topic_name)
+        .map_err(|e| {
+        PublishError::TopicNameInvalid(e.into())
+    })? {
+        Some((delay_interval, target_topic)) => (target_topic, Some(delay_interval)),
+        None => (topic_name, None),
+    };
+
+    if !auth_driver
+        .allow_publish(connection, &topic_name, publish.retain, publish.qos)
+        .await
+    {
+        return Err(PublishError::NotAuthorized(topic_name));
+    }
+
+    let topic = try_init_topic(
+        &topic_name,
+        cache_manager,
+        message_storage_adapter,
+        client_poll,
+    )
+    .await
+    .map_err(PublishError::Failed)?;
+
+    let client_id = &connection.client_id;
+
+    // Delayed messages persist their retain message when they are published
+    if let Some(delay_interval) = delay_interval {
+        let offset = save_delay_message(
+            cache_manager,
+            message_storage_adapter,
+            delay_interval,
+            &topic_name,
+            client_id,
+            publish,
+            publish_properties,
+        )
+        .await
+        .map_err(|e| {
+            if matches!(
+                e,
+                CommonError::MQTTBrokerError(MQTTBrokerError::DelayPublishPendingExceeded(_))
+            ) {
+                PublishError::QuotaExceeded(e)
+            } else {
+                PublishError::Failed(e)
+            }
+        })?;
+        return Ok(PublishResult {
+            topic_name,
+            raw_topic_name,
+            offset: format!("{:?}", offset),
+        });
+    }
+
+    save_topic_retain_message(
+        cache_manager,
+        client_poll,
+        topic_name.clone(),
+        client_id,
+        publish,
+        publish_properties,
+    )
+    .await
+    .map_err(PublishError::Failed)?;
+
+    let message_expire = build_message_expire(cache_manager, publish_properties);
+    let offset = if let Some(record) =
+        MqttMessage::build_record(client_id, publish, publish_properties, message_expire)
+    {
+        let message_storage = MessageStorage::new(message_storage_adapter.clone());
+        let offset = message_storage
+            .append_topic_message(topic.topic_id.clone(), vec![record])
+            .await
+            .map_err(PublishError::Failed)?;
+        cache_manager.push_notify.notify(&topic.topic_id);
+        format!("{:?}", offset)
+    } else {
+        "-1".to_string()
+    };
+
+    Ok(PublishResult {
+        topic_name,
+        raw_topic_name,
+        offset,
+    })
+}
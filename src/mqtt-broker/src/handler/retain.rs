@@ -14,16 +14,15 @@
 
 use std::sync::Arc;
 
-use bytes::Bytes;
 use common_base::error::common::CommonError;
-use common_base::tools::now_second;
 use grpc_clients::poll::ClientPool;
 use log::error;
 use metadata_struct::mqtt::message::MqttMessage;
-use protocol::mqtt::common::{Publish, PublishProperties, QoS, RetainForwardRule};
+use protocol::mqtt::common::{Publish, PublishProperties, RetainForwardRule};
+use storage_adapter::storage::StorageAdapter;
 use tokio::sync::broadcast::{self};
 
-use super::cache::{CacheManager, QosAckPacketInfo};
+use super::cache::CacheManager;
 use super::constant::{SUB_RETAIN_MESSAGE_PUSH_FLAG, SUB_RETAIN_MESSAGE_PUSH_FLAG_VALUE};
 use super::message::build_message_expire;
 use crate::observability::metrics::packets::{
@@ -31,10 +30,8 @@ use crate::observability::metrics::packets::{
 };
 use crate::server::connection_manager::ConnectionManager;
 use crate::storage::topic::TopicStorage;
-use crate::subscribe::sub_common::{get_sub_topic_id_list, min_qos, publish_message_qos0};
-use crate::subscribe::sub_exclusive::{
-    exclusive_publish_message_qos1, exclusive_publish_message_qos2,
-};
+use crate::subscribe::sub_common::{get_sub_topic_id_list, min_qos};
+use crate::subscribe::sub_exclusive::exclusive_push_message;
 use crate::subscribe::subscriber::Subscriber;
 
 pub async fn save_topic_retain_message(
@@ -86,14 +83,17 @@ pub async fn save_topic_retain_message(
 }
 
 // Reservation messages are processed when a subscription is created
-pub async fn try_send_retain_message(
+pub async fn try_send_retain_message<S>(
     client_id: String,
     subscriber: Subscriber,
     client_poll: Arc<ClientPool>,
     cache_manager: Arc<CacheManager>,
     connection_manager: Arc<ConnectionManager>,
+    storage_adapter: Arc<S>,
     stop_sx: broadcast::Sender<bool>,
-) {
+) where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
     if subscriber.retain_forward_rule == RetainForwardRule::Never {
         return;
     }
@@ -105,140 +105,45 @@ pub async fn try_send_retain_message(
     }
 
     tokio::spawn(async move {
-        let topic_id_list = get_sub_topic_id_list(cache_manager.clone(), subscriber.sub_path).await;
+        let topic_id_list =
+            get_sub_topic_id_list(cache_manager.clone(), subscriber.sub_path.clone()).await;
         let topic_storage = TopicStorage::new(client_poll.clone());
         let cluster = cache_manager.get_cluster_info();
         for topic_id in topic_id_list {
-            if let Some(topic_name) = cache_manager.topic_name_by_id(topic_id) {
+            if let Some(topic_name) = cache_manager.topic_name_by_id(topic_id.clone()) {
                 match topic_storage.get_retain_message(topic_name.clone()).await {
                     Ok(Some(msg)) => {
                         if subscriber.nolocal && client_id == msg.client_id {
                             continue;
                         }
 
-                        let retain = if subscriber.preserve_retain {
-                            msg.retain
-                        } else {
-                            false
-                        };
-
                         let qos = min_qos(cluster.protocol.max_qos, subscriber.qos);
-                        let pkid = 1;
-                        let mut publish = Publish {
-                            dup: false,
-                            qos,
-                            pkid,
-                            retain,
-                            topic: Bytes::from(topic_name),
-                            payload: msg.payload.clone(),
-                        };
-                        let mut user_properties = msg.user_properties.clone();
-                        user_properties.push((
+                        let mut message = msg;
+                        message.user_properties.push((
                             SUB_RETAIN_MESSAGE_PUSH_FLAG.to_string(),
                             SUB_RETAIN_MESSAGE_PUSH_FLAG_VALUE.to_string(),
                         ));
-
-                        let mut sub_ids = Vec::new();
-                        if let Some(id) = subscriber.subscription_identifier {
-                            sub_ids.push(id);
+                        let mut retain_subscriber = subscriber.clone();
+                        retain_subscriber.topic_name = topic_name;
+                        retain_subscriber.topic_id = topic_id;
+
+                        record_retain_sent_metrics(qos);
+                        if let Err(e) = exclusive_push_message(
+                            &cache_manager,
+                            &connection_manager,
+                            &storage_adapter,
+                            &retain_subscriber,
+                            qos,
+                            message,
+                            &stop_sx,
+                        )
+                        .await
+                        {
+                            error!(
+                                "Failed to push retain message to client [{}], error message :{}",
+                                client_id, e
+                            );
                         }
-
-                        let properties = PublishProperties {
-                            payload_format_indicator: msg.format_indicator,
-                            message_expiry_interval: Some(msg.expiry_interval as u32),
-                            topic_alias: None,
-                            response_topic: msg.response_topic,
-                            correlation_data: msg.correlation_data,
-                            user_properties,
-                            subscription_identifiers: sub_ids,
-                            content_type: msg.content_type,
-                        };
-
-                        record_retain_sent_metrics(publish.qos);
-                        match qos {
-                            QoS::AtMostOnce => {
-                                publish_message_qos0(
-                                    &cache_manager,
-                                    &client_id,
-                                    &publish,
-                                    &Some(properties),
-                                    &connection_manager,
-                                    &stop_sx,
-                                )
-                                .await;
-                            }
-
-                            QoS::AtLeastOnce => {
-                                let pkid: u16 = cache_manager.get_pkid(&client_id).await;
-                                publish.pkid = pkid;
-
-                                let (wait_puback_sx, _) = broadcast::channel(1);
-                                cache_manager.add_ack_packet(
-                                    &client_id,
-                                    pkid,
-                                    QosAckPacketInfo {
-                                        sx: wait_puback_sx.clone(),
-                                        create_time: now_second(),
-                                    },
-                                );
-
-                                match exclusive_publish_message_qos1(
-                                    &cache_manager,
-                                    &client_id,
-                                    publish,
-                                    &properties,
-                                    pkid,
-                                    &connection_manager,
-                                    &stop_sx,
-                                    &wait_puback_sx,
-                                )
-                                .await
-                                {
-                                    Ok(()) => {
-                                        cache_manager.remove_pkid_info(&client_id, pkid);
-                                        cache_manager.remove_ack_packet(&client_id, pkid);
-                                    }
-                                    Err(e) => {
-                                        error!("{}", e);
-                                    }
-                                }
-                            }
-
-                            QoS::ExactlyOnce => {
-                                let pkid: u16 = cache_manager.get_pkid(&client_id).await;
-                                publish.pkid = pkid;
-
-                                let (wait_ack_sx, _) = broadcast::channel(1);
-                                cache_manager.add_ack_packet(
-                                    &client_id,
-                                    pkid,
-                                    QosAckPacketInfo {
-                                        sx: wait_ack_sx.clone(),
-                                        create_time: now_second(),
-                                    },
-                                );
-                                match exclusive_publish_message_qos2(
-                                    &cache_manager,
-                                    &client_id,
-                                    &publish,
-                                    &properties,
-                                    pkid,
-                                    &connection_manager,
-                                    &stop_sx,
-                                    &wait_ack_sx,
-                                )
-                                .await
-                                {
-                                    Ok(()) => {
-                                        cache_manager.remove_pkid_info(&client_id, pkid);
-                                        cache_manager.remove_ack_packet(&client_id, pkid);
-                                    }
-                                    Err(e) => {
-                                        error!("{}", e);
-                                    }
-                                }
-                            }
-                        };
                     }
                     Ok(None) => {
                         continue;
@@ -18,12 +18,17 @@ use common_base::config::broker_mqtt::broker_mqtt_conf;
 use common_base::error::common::CommonError;
 use common_base::tools::now_second;
 use grpc_clients::poll::ClientPool;
+use log::warn;
 use metadata_struct::mqtt::session::MqttSession;
 use protocol::mqtt::common::{Connect, ConnectProperties, LastWill, LastWillProperties};
+use storage_adapter::storage::StorageAdapter;
 
 use super::cache::CacheManager;
 use super::lastwill::last_will_delay_interval;
 use crate::storage::session::SessionStorage;
+use crate::subscribe::inflight::InflightStore;
+use crate::subscribe::offline_queue::clear_offline_message;
+use crate::subscribe::subscribe_manager::SubscribeManager;
 
 #[allow(clippy::too_many_arguments)]
 pub async fn build_session(
@@ -107,6 +112,37 @@ pub async fn save_session(
     Ok(())
 }
 
+// Drops what this broker keeps for a deleted session: its inflight and offline messages,
+// the cached session and its push threads
+pub async fn clear_local_session<S>(
+    cache_manager: &Arc<CacheManager>,
+    subscribe_manager: &Arc<SubscribeManager>,
+    message_storage_adapter: &Arc<S>,
+    client_id: &str,
+) where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    let inflight_store =
+        InflightStore::new(cache_manager, client_id, message_storage_adapter.clone());
+    if let Err(e) = inflight_store.clear().await {
+        warn!(
+            "Failed to clear inflight message of client [{}], error message :{}",
+            client_id, e
+        );
+    }
+    cache_manager.remove_session(client_id);
+    subscribe_manager.stop_push_by_client_id(client_id);
+    if let Err(e) =
+        clear_offline_message(subscribe_manager, message_storage_adapter, client_id).await
+    {
+        warn!(
+            "Failed to clear offline message of client [{}], error message :{}",
+            client_id, e
+        );
+    }
+    subscribe_manager.offline_queue_lock.remove(client_id);
+}
+
 fn session_expiry_interval(
     cache_manager: &Arc<CacheManager>,
     connect_properties: &Option<ConnectProperties>,
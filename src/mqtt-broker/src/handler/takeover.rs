@@ -0,0 +1,243 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use common_base::config::broker_mqtt::broker_mqtt_conf;
+use common_base::error::common::CommonError;
+use grpc_clients::mqtt::placement::call::broker_mqtt_takeover_session;
+use grpc_clients::poll::ClientPool;
+use log::{info, warn};
+use protocol::broker_mqtt::broker_mqtt_placement::TakeoverSessionRequest;
+use protocol::mqtt::common::DisconnectReasonCode;
+use serde::{Deserialize, Serialize};
+
+use super::cache::CacheManager;
+use super::response::response_packet_mqtt_distinct_by_reason;
+use crate::server::connection_manager::ConnectionManager;
+use crate::server::packet::ResponsePackage;
+use crate::storage::cluster::ClusterStorage;
+use crate::storage::session::SessionStorage;
+use crate::subscribe::sub_common::publish_message_to_client;
+use crate::subscribe::subscribe_manager::SubscribeManager;
+
+// Inflight state that moves with the session when it is taken over by another broker.
+// Messages pushed to the client but not yet acknowledged are not part of it, their offsets are left
+// uncommitted by the stopped push threads and they are delivered again by the new broker.
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+pub struct SessionInflight {
+    // Packet ids of the QoS2 messages received from the client that are still waiting for PUBREL
+    pub client_pkid: Vec<u16>,
+}
+
+// Called before a new connection of the client is established on this broker.
+// If the client is still connected to this broker or to another broker in the cluster,
+// the old connection is closed with DISCONNECT 0x8E and its inflight state is moved here.
+pub async fn takeover_session(
+    client_id: &str,
+    connect_id: u64,
+    cache_manager: &Arc<CacheManager>,
+    client_poll: &Arc<ClientPool>,
+    connection_manager: &Arc<ConnectionManager>,
+    subscribe_manager: &Arc<SubscribeManager>,
+) -> Result<(), CommonError> {
+    if let Some(old_connect_id) = cache_manager.get_connect_id(client_id) {
+        if old_connect_id != connect_id && cache_manager.get_connection(old_connect_id).is_some() {
+            close_taken_over_connection(
+                client_id,
+                old_connect_id,
+                cache_manager,
+                connection_manager,
+                subscribe_manager,
+            )
+            .await;
+            return Ok(());
+        }
+    }
+
+    let session_storage = SessionStorage::new(client_poll.clone());
+    let session = if let Some(session) = session_storage.get_session(client_id.to_owned()).await? {
+        session
+    } else {
+        return Ok(());
+    };
+
+    let (old_connect_id, broker_id) = match (session.connection_id, session.broker_id) {
+        (Some(old_connect_id), Some(broker_id)) => (old_connect_id, broker_id),
+        _ => return Ok(()),
+    };
+
+    // A disconnected session has its connection cleared, it is only owned by the placement center
+    if old_connect_id == 0 || broker_id == broker_mqtt_conf().broker_id {
+        return Ok(());
+    }
+
+    let cluster_storage = ClusterStorage::new(client_poll.clone());
+    let addr = if let Some(node) = cluster_storage
+        .node_list()
+        .await?
+        .into_iter()
+        .find(|node| node.node_id == broker_id)
+    {
+        node.node_inner_addr
+    } else {
+        // The owning broker is no longer alive, there is no connection to close
+        return Ok(());
+    };
+
+    let request = TakeoverSessionRequest {
+        cluster_name: cache_manager.cluster_name.clone(),
+        client_id: client_id.to_owned(),
+        connect_id: old_connect_id,
+    };
+    let reply = broker_mqtt_takeover_session(client_poll.clone(), vec![addr], request).await?;
+    if !reply.inflight.is_empty() {
+        let inflight = serde_json::from_slice::<SessionInflight>(&reply.inflight)?;
+        import_session_inflight(cache_manager, client_id, &inflight);
+    }
+
+    info!(
+        "Session of client [{}] was taken over from broker {}",
+        client_id, broker_id
+    );
+    Ok(())
+}
+
+// Called on the broker that owns the session when another broker takes it over.
+pub async fn release_session(
+    client_id: &str,
+    connect_id: u64,
+    cache_manager: &Arc<CacheManager>,
+    connection_manager: &Arc<ConnectionManager>,
+    subscribe_manager: &Arc<SubscribeManager>,
+) -> SessionInflight {
+    if cache_manager.get_connect_id(client_id) != Some(connect_id) {
+        return SessionInflight::default();
+    }
+
+    close_taken_over_connection(
+        client_id,
+        connect_id,
+        cache_manager,
+        connection_manager,
+        subscribe_manager,
+    )
+    .await;
+
+    let inflight = export_session_inflight(cache_manager, client_id);
+    for pkid in inflight.client_pkid.iter() {
+        cache_manager.delete_client_pkid(client_id, *pkid);
+    }
+    cache_manager.remove_session(client_id);
+    inflight
+}
+
+async fn close_taken_over_connection(
+    client_id: &str,
+    connect_id: u64,
+    cache_manager: &Arc<CacheManager>,
+    connection_manager: &Arc<ConnectionManager>,
+    subscribe_manager: &Arc<SubscribeManager>,
+) {
+    if let Some(protocol) = connection_manager.get_connect_protocol(connect_id) {
+        if protocol.is_mqtt5() {
+            let resp = ResponsePackage {
+                connection_id: connect_id,
+                packet: response_packet_mqtt_distinct_by_reason(
+                    &protocol,
+                    Some(DisconnectReasonCode::SessionTakenOver),
+                ),
+            };
+            if let Err(e) = publish_message_to_client(resp, connection_manager).await {
+                warn!(
+                    "Failed to send DISCONNECT to the taken over connection [{}], error message :{}",
+                    connect_id, e
+                );
+            }
+        }
+    }
+
+    cache_manager.remove_connection(connect_id);
+    subscribe_manager.stop_push_by_client_id(client_id);
+    connection_manager.close_connect(connect_id).await;
+    info!(
+        "Connection [{}] of client [{}] was closed, session taken over",
+        connect_id, client_id
+    );
+}
+
+fn export_session_inflight(cache_manager: &Arc<CacheManager>, client_id: &str) -> SessionInflight {
+    let prefix = format!("{}_", client_id);
+    let mut client_pkid = Vec::new();
+    for raw in cache_manager.client_pkid_data.iter() {
+        if raw.value().client_id != client_id {
+            continue;
+        }
+        if let Some(pkid) = raw
+            .key()
+            .strip_prefix(&prefix)
+            .and_then(|pkid| pkid.parse::<u16>().ok())
+        {
+            client_pkid.push(pkid);
+        }
+    }
+    client_pkid.sort();
+    SessionInflight { client_pkid }
+}
+
+fn import_session_inflight(
+    cache_manager: &Arc<CacheManager>,
+    client_id: &str,
+    inflight: &SessionInflight,
+) {
+    for pkid in inflight.client_pkid.iter() {
+        cache_manager.add_client_pkid(client_id, *pkid);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use grpc_clients::poll::ClientPool;
+
+    use super::{export_session_inflight, import_session_inflight, SessionInflight};
+    use crate::handler::cache::CacheManager;
+
+    #[tokio::test]
+    async fn session_inflight_test() {
+        let client_poll = Arc::new(ClientPool::new(1));
+        let old_cache = Arc::new(CacheManager::new(client_poll.clone(), "test".to_string()));
+        old_cache.add_client_pkid("c1", 3);
+        old_cache.add_client_pkid("c1", 1);
+        old_cache.add_client_pkid("c11", 2);
+
+        let inflight = export_session_inflight(&old_cache, "c1");
+        assert_eq!(
+            inflight,
+            SessionInflight {
+                client_pkid: vec![1, 3]
+            }
+        );
+
+        let data = serde_json::to_vec(&inflight).unwrap();
+        let inflight = serde_json::from_slice::<SessionInflight>(&data).unwrap();
+
+        let new_cache = Arc::new(CacheManager::new(client_poll, "test".to_string()));
+        import_session_inflight(&new_cache, "c1", &inflight);
+        assert!(new_cache.get_client_pkid("c1", 1).is_some());
+        assert!(new_cache.get_client_pkid("c1", 3).is_some());
+        assert!(new_cache.get_client_pkid("c1", 2).is_none());
+    }
+}
@@ -27,12 +27,14 @@ use protocol::mqtt::common::{
     LastWillProperties, Login, MQTTPacket, MQTTProtocol, PubAckReason, PubRecReason, Publish,
     PublishProperties, QoS, Subscribe, SubscribeReasonCode, UnsubAckReason, Unsubscribe,
 };
+use tokio::io::AsyncWrite;
 use tokio_util::codec::FramedWrite;
 
 use super::cache::CacheManager;
 use super::connection::Connection;
 use super::flow_control::{
-    is_connection_rate_exceeded, is_flow_control, is_subscribe_rate_exceeded,
+    is_connection_rate_exceeded, is_flow_control, is_subscribe_rate_exceeded, publish_rate_check,
+    PublishRateResult,
 };
 use super::pkid::pkid_exists;
 use super::response::{
@@ -43,129 +45,71 @@ use super::response::{
 use super::topic::topic_name_validator;
 use crate::security::authentication_acl;
 use crate::security::login::is_ip_blacklist;
+use crate::server::connection::NetworkConnectionType;
 use crate::server::connection_manager::ConnectionManager;
 use crate::subscribe::sub_common::sub_path_validator;
 
 pub async fn tcp_establish_connection_check(
     addr: &SocketAddr,
     connection_manager: &Arc<ConnectionManager>,
+    cache_manager: &Arc<CacheManager>,
     write_frame_stream: &mut FramedWrite<tokio::io::WriteHalf<tokio::net::TcpStream>, MqttCodec>,
 ) -> bool {
-    if connection_manager.tcp_connect_num_check() {
-        let packet_wrapper = MqttPacketWrapper {
-            protocol_version: MQTTProtocol::MQTT5.into(),
-            packet: response_packet_mqtt_distinct_by_reason(
-                &MQTTProtocol::MQTT5,
-                Some(DisconnectReasonCode::QuotaExceeded),
-            ),
-        };
-        match write_frame_stream.send(packet_wrapper).await {
-            Ok(_) => {}
-            Err(e) => error!("{}", e),
-        }
-
-        match write_frame_stream.close().await {
-            Ok(_) => {
-                error!(
-                    "tcp connection failed to establish from IP: {}",
-                    addr.to_string()
-                );
-            }
-            Err(e) => error!("{}", e),
-        }
-        return false;
-    }
-
-    if is_connection_rate_exceeded() {
-        let packet_wrapper = MqttPacketWrapper {
-            protocol_version: MQTTProtocol::MQTT5.into(),
-            packet: response_packet_mqtt_distinct_by_reason(
-                &MQTTProtocol::MQTT5,
-                Some(DisconnectReasonCode::ConnectionRateExceeded),
-            ),
-        };
-        match write_frame_stream.send(packet_wrapper).await {
-            Ok(_) => {}
-            Err(e) => error!("{}", e),
-        }
-
-        match write_frame_stream.close().await {
-            Ok(_) => {
-                error!(
-                    "tcp connection failed to establish from IP: {}",
-                    addr.to_string()
-                );
-            }
-            Err(e) => error!("{}", e),
-        }
-        return false;
-    }
-    true
+    establish_connection_check(
+        addr,
+        connection_manager,
+        cache_manager,
+        &NetworkConnectionType::Tcp,
+        write_frame_stream,
+    )
+    .await
 }
 
-pub async fn tcp_tls_establish_connection_check(
+// Shared by the TCP, TLS, TLS-PSK and QUIC listeners
+pub async fn establish_connection_check<W>(
     addr: &SocketAddr,
     connection_manager: &Arc<ConnectionManager>,
-    write_frame_stream: &mut FramedWrite<
-        tokio::io::WriteHalf<tokio_rustls::server::TlsStream<tokio::net::TcpStream>>,
-        MqttCodec,
-    >,
-) -> bool {
-    if connection_manager.tcp_connect_num_check() {
-        let packet_wrapper = MqttPacketWrapper {
-            protocol_version: MQTTProtocol::MQTT5.into(),
-            packet: response_packet_mqtt_distinct_by_reason(
-                &MQTTProtocol::MQTT5,
-                Some(DisconnectReasonCode::QuotaExceeded),
-            ),
-        };
-        match write_frame_stream.send(packet_wrapper).await {
-            Ok(_) => {}
-            Err(e) => error!("{}", e),
-        }
+    cache_manager: &Arc<CacheManager>,
+    network_type: &NetworkConnectionType,
+    write_frame_stream: &mut FramedWrite<W, MqttCodec>,
+) -> bool
+where
+    W: AsyncWrite + Unpin,
+{
+    let reason = if connection_manager.tcp_connect_num_check() {
+        DisconnectReasonCode::QuotaExceeded
+    } else if is_connection_rate_exceeded(cache_manager, network_type) {
+        DisconnectReasonCode::ConnectionRateExceeded
+    } else {
+        return true;
+    };
 
-        match write_frame_stream.close().await {
-            Ok(_) => {
-                error!(
-                    "tcp connection failed to establish from IP: {}",
-                    addr.to_string()
-                );
-            }
-            Err(e) => error!("{}", e),
-        }
-        return false;
+    let packet_wrapper = MqttPacketWrapper {
+        protocol_version: MQTTProtocol::MQTT5.into(),
+        packet: response_packet_mqtt_distinct_by_reason(&MQTTProtocol::MQTT5, Some(reason)),
+    };
+    match write_frame_stream.send(packet_wrapper).await {
+        Ok(_) => {}
+        Err(e) => error!("{}", e),
     }
 
-    if is_connection_rate_exceeded() {
-        let packet_wrapper = MqttPacketWrapper {
-            protocol_version: MQTTProtocol::MQTT5.into(),
-            packet: response_packet_mqtt_distinct_by_reason(
-                &MQTTProtocol::MQTT5,
-                Some(DisconnectReasonCode::ConnectionRateExceeded),
-            ),
-        };
-        match write_frame_stream.send(packet_wrapper).await {
-            Ok(_) => {}
-            Err(e) => error!("{}", e),
-        }
-
-        match write_frame_stream.close().await {
-            Ok(_) => {
-                error!(
-                    "tcp connection failed to establish from IP: {}",
-                    addr.to_string()
-                );
-            }
-            Err(e) => error!("{}", e),
+    match write_frame_stream.close().await {
+        Ok(_) => {
+            error!(
+                "{} connection failed to establish from IP: {}",
+                network_type,
+                addr.to_string()
+            );
         }
-        return false;
+        Err(e) => error!("{}", e),
     }
-    true
+    false
 }
 
 #[allow(clippy::too_many_arguments)]
 pub fn connect_validator(
     protocol: &MQTTProtocol,
+    cache_manager: &Arc<CacheManager>,
     cluster: &MqttClusterDynamicConfig,
     connect: &Connect,
     connect_properties: &Option<ConnectProperties>,
@@ -183,7 +127,7 @@ pub fn connect_validator(
         ));
     }
 
-    if is_ip_blacklist(addr) {
+    if is_ip_blacklist(cache_manager, addr) {
         return Some(response_packet_mqtt_connect_fail(
             protocol,
             ConnectReturnCode::Banned,
@@ -415,6 +359,41 @@ pub async fn publish_validator(
         }
     }
 
+    match publish_rate_check(
+        protocol,
+        cache_manager,
+        &connection.client_id,
+        connection.connect_id,
+        publish.payload.len(),
+    ) {
+        PublishRateResult::Pass => {}
+        PublishRateResult::MessageRateTooHigh => {
+            return Some(response_packet_mqtt_distinct_by_reason(
+                protocol,
+                Some(DisconnectReasonCode::MessageRateTooHigh),
+            ));
+        }
+        PublishRateResult::QuotaExceeded => {
+            if is_puback {
+                return Some(response_packet_mqtt_puback_fail(
+                    protocol,
+                    connection,
+                    publish.pkid,
+                    PubAckReason::QuotaExceeded,
+                    None,
+                ));
+            } else {
+                return Some(response_packet_mqtt_pubrec_fail(
+                    protocol,
+                    connection,
+                    publish.pkid,
+                    PubRecReason::QuotaExceeded,
+                    None,
+                ));
+            }
+        }
+    }
+
     if let Some(properties) = publish_properties {
         if let Some(alias) = properties.topic_alias {
             let cluster = cache_manager.get_cluster_info();
@@ -507,7 +486,7 @@ pub async fn subscribe_validator(
         ));
     }
 
-    if is_subscribe_rate_exceeded() {
+    if is_subscribe_rate_exceeded(cache_manager, &connection.client_id) {
         return Some(response_packet_mqtt_suback(
             protocol,
             connection,
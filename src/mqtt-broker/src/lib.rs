@@ -20,29 +20,38 @@ use common_base::config::broker_mqtt::broker_mqtt_conf;
 use common_base::runtime::create_runtime;
 use common_base::tools::now_second;
 use grpc_clients::poll::ClientPool;
-use handler::cache::CacheManager;
+use handler::cache::{update_remote_cache, CacheManager};
+use handler::delay_message::DelayMessageManager;
+use handler::flow_control::refresh_broker_num;
 use handler::heartbreat::report_heartbeat;
 use handler::keep_alive::ClientKeepAlive;
 use lazy_static::lazy_static;
 use log::{error, info};
 use observability::start_opservability;
+use protocol::broker_mqtt::broker_mqtt_placement::{
+    MqttBrokerUpdateCacheActionType, MqttBrokerUpdateCacheResourceType,
+};
 use security::AuthDriver;
 use server::connection_manager::ConnectionManager;
 use server::grpc::server::GrpcServer;
 use server::http::server::{start_http_server, HttpServerState};
+use server::quic::server::start_quic_server;
 use server::tcp::server::start_tcp_server;
 use server::websocket::server::{websocket_server, websockets_server, WebSocketServerState};
 use storage::cluster::ClusterStorage;
 use storage_adapter::local_rocksdb::RocksDBStorageAdapter;
 use storage_adapter::memory::MemoryStorageAdapter;
 use storage_adapter::mysql::MySQLStorageAdapter;
+use storage_adapter::postgres::PostgresStorageAdapter;
 use storage_adapter::storage::StorageAdapter;
 use storage_adapter::StorageType;
+use subscribe::push_notify::start_remote_notify_thread;
 use subscribe::sub_exclusive::SubscribeExclusive;
 use subscribe::sub_share_follower::SubscribeShareFollower;
 use subscribe::sub_share_leader::SubscribeShareLeader;
 use subscribe::subscribe_manager::SubscribeManager;
 use third_driver::mysql::build_mysql_conn_pool;
+use third_driver::postgres::build_postgres_conn_pool;
 use tokio::runtime::Runtime;
 use tokio::signal;
 use tokio::sync::broadcast::{self};
@@ -59,6 +68,10 @@ mod server;
 pub mod storage;
 mod subscribe;
 
+// Topic matching, exported for the benchmarks
+pub use subscribe::sub_common::path_match;
+pub use subscribe::topic_trie::TopicTrie;
+
 pub fn start_mqtt_broker_server(stop_send: broadcast::Sender<bool>) {
     let conf = broker_mqtt_conf();
     let client_poll: Arc<ClientPool> = Arc::new(ClientPool::new(5));
@@ -85,6 +98,16 @@ pub fn start_mqtt_broker_server(stop_send: broadcast::Sender<bool>) {
                 MqttBroker::new(client_poll, message_storage_adapter, metadata_cache);
             server.start(stop_send);
         }
+        StorageType::Postgres => {
+            if conf.storage.postgres_addr.is_empty() {
+                panic!("storaget type is [postgres],[storage.postgres_addr] cannot be empty");
+            }
+            let pool = build_postgres_conn_pool(&conf.storage.postgres_addr).unwrap();
+            let message_storage_adapter = Arc::new(PostgresStorageAdapter::new(pool));
+            let server: MqttBroker<PostgresStorageAdapter> =
+                MqttBroker::new(client_poll, message_storage_adapter, metadata_cache);
+            server.start(stop_send);
+        }
         StorageType::RocksDB => {
             if conf.storage.rocksdb_data_path.is_empty() {
                 panic!("storaget type is [rocksdb],[storage.rocksdb_path] cannot be empty");
@@ -97,7 +120,9 @@ pub fn start_mqtt_broker_server(stop_send: broadcast::Sender<bool>) {
             server.start(stop_send);
         }
         _ => {
-            panic!("Message data storage type configuration error, optional :mysql, memory");
+            panic!(
+                "Message data storage type configuration error, optional :mysql, postgres, memory"
+            );
         }
     }
 }
@@ -150,12 +175,15 @@ where
         self.register_node();
         self.start_grpc_server();
         self.start_mqtt_server(stop_send.clone());
+        self.start_quic_server(stop_send.clone());
         self.start_http_server();
         self.start_websocket_server(stop_send.clone());
         self.start_keep_alive_thread(stop_send.clone());
         self.start_cluster_heartbeat_report(stop_send.clone());
         self.start_push_server();
         self.start_system_topic_thread(stop_send.clone());
+        self.start_delay_message_thread(stop_send.clone());
+        self.start_push_notify_thread(stop_send.clone());
         self.awaiting_stop(stop_send);
     }
 
@@ -181,12 +209,39 @@ where
         });
     }
 
+    fn start_quic_server(&self, stop_send: broadcast::Sender<bool>) {
+        let cache = self.cache_manager.clone();
+        let message_storage_adapter = self.message_storage_adapter.clone();
+        let subscribe_manager = self.subscribe_manager.clone();
+        let client_poll = self.client_poll.clone();
+        let connection_manager = self.connection_manager.clone();
+        let auth_driver = self.auth_driver.clone();
+
+        self.runtime.spawn(async move {
+            // A QUIC listener without a usable certificate must not take the broker down
+            if let Err(e) = start_quic_server(
+                subscribe_manager,
+                cache,
+                connection_manager,
+                message_storage_adapter,
+                client_poll,
+                stop_send,
+                auth_driver,
+            )
+            .await
+            {
+                error!("MQTT QUIC Server failed to start, error message: {}", e);
+            }
+        });
+    }
+
     fn start_grpc_server(&self) {
         let conf = broker_mqtt_conf();
         let server = GrpcServer::new(
             conf.grpc_port,
             self.cache_manager.clone(),
             self.subscribe_manager.clone(),
+            self.connection_manager.clone(),
             self.client_poll.clone(),
             self.message_storage_adapter.clone(),
         );
@@ -201,7 +256,14 @@ where
     }
 
     fn start_http_server(&self) {
-        let http_state = HttpServerState::new();
+        let http_state = HttpServerState::new(
+            self.cache_manager.clone(),
+            self.client_poll.clone(),
+            self.message_storage_adapter.clone(),
+            self.auth_driver.clone(),
+            self.connection_manager.clone(),
+            self.subscribe_manager.clone(),
+        );
         self.runtime.spawn(async move {
             match start_http_server(http_state).await {
                 Ok(_) => {}
@@ -316,6 +378,26 @@ where
         });
     }
 
+    fn start_delay_message_thread(&self, stop_send: broadcast::Sender<bool>) {
+        let mut delay_message_manager = DelayMessageManager::new(
+            self.cache_manager.clone(),
+            self.message_storage_adapter.clone(),
+            self.client_poll.clone(),
+            broker_mqtt_conf().broker_id,
+        );
+        self.runtime.spawn(async move {
+            delay_message_manager.start(stop_send).await;
+        });
+    }
+
+    fn start_push_notify_thread(&self, stop_send: broadcast::Sender<bool>) {
+        let cache_manager = self.cache_manager.clone();
+        let client_poll = self.client_poll.clone();
+        self.runtime.spawn(async move {
+            start_remote_notify_thread(cache_manager, client_poll, stop_send).await;
+        });
+    }
+
     pub fn awaiting_stop(&self, stop_send: broadcast::Sender<bool>) {
         self.runtime.spawn(async move {
             sleep(Duration::from_millis(5)).await;
@@ -360,6 +442,15 @@ where
                     panic!("{}", e.to_string());
                 }
             }
+
+            refresh_broker_num(&metadata_cache, &client_poll).await;
+            update_remote_cache(
+                &client_poll,
+                MqttBrokerUpdateCacheActionType::Add,
+                MqttBrokerUpdateCacheResourceType::Node,
+                config.broker_id.to_string().into_bytes(),
+            )
+            .await;
         });
     }
 
@@ -369,6 +460,13 @@ where
         match cluster_storage.unregister_node(config).await {
             Ok(()) => {
                 info!("Node {} exits successfully", config.broker_id);
+                update_remote_cache(
+                    &self.client_poll,
+                    MqttBrokerUpdateCacheActionType::Delete,
+                    MqttBrokerUpdateCacheResourceType::Node,
+                    config.broker_id.to_string().into_bytes(),
+                )
+                .await;
             }
             Err(e) => {
                 error!("{}", e.to_string());
@@ -22,6 +22,7 @@ use tokio::sync::broadcast;
 use crate::handler::cache::CacheManager;
 
 pub mod metrics;
+pub mod records;
 pub mod slow;
 pub mod system_topic;
 pub mod warn;
@@ -0,0 +1,67 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+// Latest records kept in memory, the oldest one is dropped once the capacity is reached
+pub struct RecordBuffer<T> {
+    capacity: usize,
+    records: Mutex<VecDeque<T>>,
+}
+
+impl<T: Clone> RecordBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        RecordBuffer {
+            capacity,
+            records: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub fn push(&self, record: T) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut records = self.records.lock().unwrap();
+        if records.len() >= self.capacity {
+            records.pop_front();
+        }
+        records.push_back(record);
+    }
+
+    // Newest first
+    pub fn list(&self) -> Vec<T> {
+        self.records.lock().unwrap().iter().rev().cloned().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RecordBuffer;
+
+    #[test]
+    fn record_buffer_test() {
+        let buffer = RecordBuffer::new(3);
+        assert!(buffer.list().is_empty());
+
+        for i in 0..5 {
+            buffer.push(i);
+        }
+        assert_eq!(buffer.list(), vec![4, 3, 2]);
+
+        let buffer = RecordBuffer::new(0);
+        buffer.push(1);
+        assert!(buffer.list().is_empty());
+    }
+}
@@ -20,26 +20,33 @@ use serde::{Deserialize, Serialize};
 
 use crate::handler::cache::CacheManager;
 
+pub const SLOW_SUBSCRIBE_RECORD_CAPACITY: usize = 1000;
+
 #[derive(Serialize, Deserialize, Default, Clone)]
 pub struct SlowMessage {
-    client_id: String,
-    topic: String,
-    time_ms: u16,
-    node_info: String,
-    create_time: u128,
+    pub client_id: String,
+    pub topic: String,
+    pub time_ms: u128,
+    pub node_info: String,
+    pub create_time: u128,
 }
 
-#[allow(dead_code)]
+// time_ms is the time taken to deliver the message, up to the acknowledgement for QoS 1 and 2
 pub fn try_record_slow_message(
-    _: Arc<CacheManager>,
-    client_id: String,
-    topic: String,
-    time_ms: u16,
+    cache_manager: &Arc<CacheManager>,
+    client_id: &str,
+    topic: &str,
+    time_ms: u128,
 ) {
+    let slow_config = cache_manager.get_cluster_info().slow;
+    if !slow_config.enable || time_ms < slow_config.response_ms as u128 {
+        return;
+    }
+
     let ip = get_local_ip();
     let slow = SlowMessage {
-        client_id,
-        topic,
+        client_id: client_id.to_owned(),
+        topic: topic.to_owned(),
         time_ms,
         node_info: format!("RobustMQ-MQTT@{}", ip),
         create_time: now_mills(),
@@ -52,4 +59,5 @@ pub fn try_record_slow_message(
             e.to_string()
         ),
     }
+    cache_manager.slow_subscribe_records.push(slow);
 }
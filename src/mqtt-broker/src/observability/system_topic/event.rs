@@ -26,7 +26,8 @@ use storage_adapter::storage::StorageAdapter;
 
 use super::{
     write_topic_data, SYSTEM_TOPIC_BROKERS_CONNECTED, SYSTEM_TOPIC_BROKERS_DISCONNECTED,
-    SYSTEM_TOPIC_BROKERS_SUBSCRIBED, SYSTEM_TOPIC_BROKERS_UNSUBSCRIBED,
+    SYSTEM_TOPIC_BROKERS_FLAPPING, SYSTEM_TOPIC_BROKERS_SUBSCRIBED,
+    SYSTEM_TOPIC_BROKERS_UNSUBSCRIBED,
 };
 use crate::handler::cache::CacheManager;
 use crate::handler::connection::Connection;
@@ -89,6 +90,16 @@ pub struct SystemTopicUnSubscribedEventMessge {
     pub clientid: String,
 }
 
+#[derive(Default, Serialize, Deserialize)]
+pub struct SystemTopicFlappingEventMessge {
+    pub ts: u128,
+    pub clientid: String,
+    pub ipaddress: String,
+    pub window_time: u64,
+    pub max_client_connections: u64,
+    pub ban_until: u64,
+}
+
 // Go live event. When any client comes online, messages for that topic will be published
 pub async fn st_report_connected_event<S>(
     message_storage_adapter: &Arc<S>,
@@ -304,6 +315,40 @@ pub async fn st_report_unsubscribed_event<S>(
     }
 }
 
+// Flapping event. When a client is banned for connecting too often, a message for that topic is published
+pub async fn st_report_flapping_event<S>(
+    message_storage_adapter: &Arc<S>,
+    metadata_cache: &Arc<CacheManager>,
+    client_poll: &Arc<ClientPool>,
+    event_data: SystemTopicFlappingEventMessge,
+) where
+    S: StorageAdapter + Clone + Send + Sync + 'static,
+{
+    match serde_json::to_string(&event_data) {
+        Ok(data) => {
+            let topic_name = replace_name(
+                SYSTEM_TOPIC_BROKERS_FLAPPING.to_string(),
+                event_data.clientid.clone(),
+            );
+
+            if let Some(record) = MqttMessage::build_system_topic_message(topic_name.clone(), data)
+            {
+                write_topic_data(
+                    message_storage_adapter,
+                    metadata_cache,
+                    client_poll,
+                    topic_name,
+                    record,
+                )
+                .await;
+            }
+        }
+        Err(e) => {
+            error!("{}", e.to_string());
+        }
+    }
+}
+
 fn replace_name(mut topic_name: String, client_id: String) -> String {
     if topic_name.contains("${node}") {
         let local_ip = get_local_ip();
@@ -45,6 +45,7 @@ pub const SYSTEM_TOPIC_BROKERS_SUBSCRIBED: &str =
     "$SYS/brokers/${node}/clients/${clientid}/subscribed";
 pub const SYSTEM_TOPIC_BROKERS_UNSUBSCRIBED: &str =
     "$SYS/brokers/${node}/clients/${clientid}/unsubscribed";
+pub const SYSTEM_TOPIC_BROKERS_FLAPPING: &str = "$SYS/brokers/${node}/clients/${clientid}/flapping";
 
 pub mod broker;
 pub mod event;
@@ -165,10 +166,12 @@ pub(crate) async fn write_topic_data<S>(
         Ok(topic) => {
             let message_storage = MessageStorage::new(message_storage_adapter.clone());
             match message_storage
-                .append_topic_message(topic.topic_id, vec![record])
+                .append_topic_message(topic.topic_id.clone(), vec![record])
                 .await
             {
-                Ok(_) => {}
+                Ok(_) => {
+                    metadata_cache.push_notify.notify(&topic.topic_id);
+                }
                 Err(e) => {
                     error!(
                         "Message written to system subject {} Error, error message :{}",
@@ -11,3 +11,34 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+
+use std::sync::Arc;
+
+use common_base::tools::{get_local_ip, now_mills};
+use log::warn;
+use serde::{Deserialize, Serialize};
+
+use crate::handler::cache::CacheManager;
+
+pub const ALARM_RECORD_CAPACITY: usize = 1000;
+
+pub const ALARM_CLIENT_FLAPPING: &str = "client_flapping";
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug, PartialEq)]
+pub struct AlarmRecord {
+    pub name: String,
+    pub message: String,
+    pub node_info: String,
+    pub create_time: u128,
+}
+
+pub fn record_alarm(cache_manager: &Arc<CacheManager>, name: &str, message: String) {
+    let alarm = AlarmRecord {
+        name: name.to_owned(),
+        message,
+        node_info: format!("RobustMQ-MQTT@{}", get_local_ip()),
+        create_time: now_mills(),
+    };
+    warn!("Alarm [{}] raised, {}", alarm.name, alarm.message);
+    cache_manager.alarm_records.push(alarm);
+}
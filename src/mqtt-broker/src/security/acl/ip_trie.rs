@@ -0,0 +1,178 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::net::IpAddr;
+use std::sync::RwLock;
+
+use ipnet::IpNet;
+
+// Binary prefix tree over the bits of the IP address. A lookup walks at most 32 (IPv4) or
+// 128 (IPv6) nodes and collects the values of every network on the path that contains the address.
+pub struct IpPrefixTrie<T> {
+    v4: RwLock<TrieNode<T>>,
+    v6: RwLock<TrieNode<T>>,
+}
+
+struct TrieNode<T> {
+    children: [Option<Box<TrieNode<T>>>; 2],
+    values: Vec<T>,
+}
+
+impl<T> TrieNode<T> {
+    fn new() -> Self {
+        TrieNode {
+            children: [None, None],
+            values: Vec::new(),
+        }
+    }
+}
+
+impl<T: Clone> Default for IpPrefixTrie<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone> IpPrefixTrie<T> {
+    pub fn new() -> Self {
+        IpPrefixTrie {
+            v4: RwLock::new(TrieNode::new()),
+            v6: RwLock::new(TrieNode::new()),
+        }
+    }
+
+    pub fn insert(&self, net: &IpNet, value: T) {
+        let (bits, prefix_len) = net_bits(net);
+        let mut root = self.root(net.network()).write().unwrap();
+        let mut node = &mut *root;
+        for i in 0..prefix_len {
+            node = node.children[bit_at(bits, i)].get_or_insert_with(|| Box::new(TrieNode::new()));
+        }
+        node.values.push(value);
+    }
+
+    pub fn remove<F>(&self, net: &IpNet, f: F)
+    where
+        F: Fn(&T) -> bool,
+    {
+        let (bits, prefix_len) = net_bits(net);
+        let mut root = self.root(net.network()).write().unwrap();
+        let mut node = &mut *root;
+        for i in 0..prefix_len {
+            match node.children[bit_at(bits, i)].as_mut() {
+                Some(child) => node = child,
+                None => return,
+            }
+        }
+        node.values.retain(|value| !f(value));
+    }
+
+    // Values of all the networks that contain the address
+    pub fn matches(&self, ip: &IpAddr) -> Vec<T> {
+        let ip = canonical_ip(ip);
+        let (bits, len) = ip_bits(&ip);
+        let root = self.root(ip).read().unwrap();
+        let mut node = &*root;
+        let mut results = node.values.clone();
+        for i in 0..len {
+            match node.children[bit_at(bits, i)].as_ref() {
+                Some(child) => {
+                    node = child;
+                    results.extend(node.values.iter().cloned());
+                }
+                None => break,
+            }
+        }
+        results
+    }
+
+    fn root(&self, ip: IpAddr) -> &RwLock<TrieNode<T>> {
+        match ip {
+            IpAddr::V4(_) => &self.v4,
+            IpAddr::V6(_) => &self.v6,
+        }
+    }
+}
+
+// IPv4 clients of a dual-stack listener show up as IPv4-mapped IPv6 addresses
+fn canonical_ip(ip: &IpAddr) -> IpAddr {
+    if let IpAddr::V6(v6) = ip {
+        if let Some(v4) = v6.to_ipv4_mapped() {
+            return IpAddr::V4(v4);
+        }
+    }
+    *ip
+}
+
+// The address bits are left aligned in a u128 so that IPv4 and IPv6 are walked the same way
+fn ip_bits(ip: &IpAddr) -> (u128, u8) {
+    match ip {
+        IpAddr::V4(v4) => ((u32::from(*v4) as u128) << 96, 32),
+        IpAddr::V6(v6) => (u128::from(*v6), 128),
+    }
+}
+
+fn net_bits(net: &IpNet) -> (u128, u8) {
+    let (bits, _) = ip_bits(&net.network());
+    (bits, net.prefix_len())
+}
+
+fn bit_at(bits: u128, index: u8) -> usize {
+    ((bits >> (127 - index)) & 1) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::IpAddr;
+
+    use ipnet::IpNet;
+
+    use super::IpPrefixTrie;
+
+    #[test]
+    fn ip_prefix_trie_test() {
+        let trie = IpPrefixTrie::new();
+        trie.insert(&"192.168.1.0/24".parse::<IpNet>().unwrap(), 1);
+        trie.insert(&"10.0.0.1/32".parse::<IpNet>().unwrap(), 2);
+        trie.insert(&"2001:db8::/32".parse::<IpNet>().unwrap(), 3);
+
+        let ip: IpAddr = "192.168.1.20".parse().unwrap();
+        assert_eq!(trie.matches(&ip), vec![1]);
+        let ip: IpAddr = "192.168.2.20".parse().unwrap();
+        assert!(trie.matches(&ip).is_empty());
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        assert_eq!(trie.matches(&ip), vec![2]);
+        let ip: IpAddr = "10.0.0.2".parse().unwrap();
+        assert!(trie.matches(&ip).is_empty());
+
+        let ip: IpAddr = "2001:db8:1::1".parse().unwrap();
+        assert_eq!(trie.matches(&ip), vec![3]);
+        let ip: IpAddr = "2001:db9::1".parse().unwrap();
+        assert!(trie.matches(&ip).is_empty());
+
+        // IPv4-mapped IPv6 addresses match the IPv4 networks
+        let ip: IpAddr = "::ffff:192.168.1.20".parse().unwrap();
+        assert_eq!(trie.matches(&ip), vec![1]);
+
+        // nested networks all match
+        trie.insert(&"192.168.0.0/16".parse::<IpNet>().unwrap(), 4);
+        let ip: IpAddr = "192.168.1.20".parse().unwrap();
+        assert_eq!(trie.matches(&ip), vec![4, 1]);
+
+        trie.remove(&"192.168.1.0/24".parse::<IpNet>().unwrap(), |v| *v == 1);
+        assert_eq!(trie.matches(&ip), vec![4]);
+
+        trie.insert(&"0.0.0.0/0".parse::<IpNet>().unwrap(), 5);
+        assert_eq!(trie.matches(&ip), vec![5, 4]);
+    }
+}
@@ -12,11 +12,22 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::net::IpAddr;
+
 use dashmap::DashMap;
+use ipnet::IpNet;
+use log::warn;
 use metadata_struct::acl::mqtt_acl::{MqttAcl, MqttAclResourceType};
 use metadata_struct::acl::mqtt_blacklist::{MqttAclBlackList, MqttAclBlackListType};
+use regex::Regex;
+
+use super::ip_trie::IpPrefixTrie;
+use super::ConnectionAcl;
+
+// Patterns with placeholders are expanded per connection, so the compiled cache is
+// dropped once it holds this many entries
+const BLACKLIST_REGEX_CACHE_CAPACITY: usize = 1024;
 
-#[derive(Clone)]
 pub struct AclMetadata {
     // blacklist
     pub blacklist_user: DashMap<String, MqttAclBlackList>,
@@ -25,6 +36,13 @@ pub struct AclMetadata {
     pub blacklist_user_match: DashMap<String, Vec<MqttAclBlackList>>,
     pub blacklist_client_id_match: DashMap<String, Vec<MqttAclBlackList>>,
     pub blacklist_ip_match: DashMap<String, Vec<MqttAclBlackList>>,
+    // Ip and IPCIDR blacklists indexed by network prefix
+    pub blacklist_ip_trie: IpPrefixTrie<MqttAclBlackList>,
+    // (expanded match pattern, compiled regex), None for a pattern that does not compile
+    pub blacklist_regex: DashMap<String, Option<Regex>>,
+
+    // (connect_id, ConnectionAcl)
+    pub connection_acl: DashMap<u64, ConnectionAcl>,
 
     // acl
     pub acl_user: DashMap<String, Vec<MqttAcl>>,
@@ -46,6 +64,9 @@ impl AclMetadata {
             blacklist_user_match: DashMap::with_capacity(2),
             blacklist_client_id_match: DashMap::with_capacity(2),
             blacklist_ip_match: DashMap::with_capacity(2),
+            blacklist_ip_trie: IpPrefixTrie::new(),
+            blacklist_regex: DashMap::with_capacity(2),
+            connection_acl: DashMap::with_capacity(2),
 
             acl_user: DashMap::with_capacity(2),
             acl_client_id: DashMap::with_capacity(2),
@@ -83,6 +104,7 @@ impl AclMetadata {
                     .insert(blacklist.resource_name.clone(), blacklist);
             }
             MqttAclBlackListType::Ip => {
+                self.add_blacklist_ip_trie(&blacklist);
                 self.blacklist_ip
                     .insert(blacklist.resource_name.clone(), blacklist);
             }
@@ -103,6 +125,7 @@ impl AclMetadata {
                 }
             }
             MqttAclBlackListType::IPCIDR => {
+                self.add_blacklist_ip_trie(&blacklist);
                 let key = self.get_ip_cidr_key();
                 if let Some(mut data) = self.blacklist_ip_match.get_mut(&key) {
                     data.push(blacklist)
@@ -113,6 +136,99 @@ impl AclMetadata {
         }
     }
 
+    pub fn remove_mqtt_acl(&self, acl: &MqttAcl) {
+        let acls = match acl.resource_type {
+            MqttAclResourceType::ClientId => &self.acl_client_id,
+            MqttAclResourceType::User => &self.acl_user,
+        };
+        if let Some(mut raw) = acls.get_mut(&acl.resource_name) {
+            raw.retain(|rule| rule != acl);
+        }
+        acls.remove_if(&acl.resource_name, |_, raw| raw.is_empty());
+    }
+
+    // Blacklists are identified by their type and resource name
+    pub fn remove_mqtt_blacklist(&self, blacklist: &MqttAclBlackList) {
+        let resource_name = &blacklist.resource_name;
+        match blacklist.blacklist_type {
+            MqttAclBlackListType::ClientId => {
+                self.blacklist_client_id.remove(resource_name);
+            }
+            MqttAclBlackListType::User => {
+                self.blacklist_user.remove(resource_name);
+            }
+            MqttAclBlackListType::Ip => {
+                self.remove_blacklist_ip_trie(resource_name);
+                self.blacklist_ip.remove(resource_name);
+            }
+            MqttAclBlackListType::ClientIdMatch => {
+                let key = self.get_client_id_match_key();
+                if let Some(mut data) = self.blacklist_client_id_match.get_mut(&key) {
+                    data.retain(|raw| raw.resource_name != *resource_name);
+                }
+            }
+            MqttAclBlackListType::UserMatch => {
+                let key = self.get_user_match_key();
+                if let Some(mut data) = self.blacklist_user_match.get_mut(&key) {
+                    data.retain(|raw| raw.resource_name != *resource_name);
+                }
+            }
+            MqttAclBlackListType::IPCIDR => {
+                self.remove_blacklist_ip_trie(resource_name);
+                let key = self.get_ip_cidr_key();
+                if let Some(mut data) = self.blacklist_ip_match.get_mut(&key) {
+                    data.retain(|raw| raw.resource_name != *resource_name);
+                }
+            }
+        }
+    }
+
+    pub fn add_connection_acl(&self, connect_id: u64, acl: ConnectionAcl) {
+        self.connection_acl.insert(connect_id, acl);
+    }
+
+    pub fn remove_connection_acl(&self, connect_id: u64) {
+        self.connection_acl.remove(&connect_id);
+    }
+
+    pub fn get_blacklist_ip(&self, ip: &IpAddr) -> Vec<MqttAclBlackList> {
+        self.blacklist_ip_trie.matches(ip)
+    }
+
+    fn add_blacklist_ip_trie(&self, blacklist: &MqttAclBlackList) {
+        let Some(net) = blacklist_ip_net(&blacklist.resource_name) else {
+            warn!(
+                "Blacklist resource {} is not a valid IP address or CIDR",
+                blacklist.resource_name
+            );
+            return;
+        };
+        // the same resource added again replaces the previous entry
+        self.blacklist_ip_trie
+            .remove(&net, |raw| raw.resource_name == blacklist.resource_name);
+        self.blacklist_ip_trie.insert(&net, blacklist.clone());
+    }
+
+    fn remove_blacklist_ip_trie(&self, resource_name: &str) {
+        if let Some(net) = blacklist_ip_net(resource_name) {
+            self.blacklist_ip_trie
+                .remove(&net, |raw| raw.resource_name == resource_name);
+        }
+    }
+
+    // The anchored regex of a user or client id match pattern, compiled once
+    pub fn get_blacklist_regex(&self, pattern: &str) -> Option<Regex> {
+        if let Some(re) = self.blacklist_regex.get(pattern) {
+            return re.clone();
+        }
+        let re = Regex::new(&format!("^{}$", pattern)).ok();
+        if self.blacklist_regex.len() >= BLACKLIST_REGEX_CACHE_CAPACITY {
+            self.blacklist_regex.clear();
+        }
+        self.blacklist_regex.insert(pattern.to_string(), re.clone());
+        re
+    }
+
     pub fn get_blacklist_user_match(&self) -> Option<Vec<MqttAclBlackList>> {
         let key = self.get_user_match_key();
         if let Some(data) = self.blacklist_user_match.get(&key) {
@@ -150,6 +266,13 @@ impl AclMetadata {
     }
 }
 
+fn blacklist_ip_net(resource_name: &str) -> Option<IpNet> {
+    if let Ok(net) = resource_name.parse::<IpNet>() {
+        return Some(net);
+    }
+    resource_name.parse::<IpAddr>().ok().map(IpNet::from)
+}
+
 #[cfg(test)]
 mod test {
     use common_base::tools::now_second;
@@ -171,6 +294,7 @@ mod test {
             ip: "".to_string(),
             action: MqttAclAction::All,
             permission: MqttAclPermission::Allow,
+            priority: 0,
         };
         acl_metadata.parse_mqtt_acl(client_id_acl.clone());
 
@@ -192,6 +316,7 @@ mod test {
             ip: "".to_string(),
             action: MqttAclAction::All,
             permission: MqttAclPermission::Allow,
+            priority: 0,
         };
         acl_metadata.parse_mqtt_acl(user_acl.clone());
 
@@ -325,4 +450,69 @@ mod test {
             2
         );
     }
+
+    #[tokio::test]
+    pub async fn remove_mqtt_acl_test() {
+        let acl_metadata = AclMetadata::new();
+        let acl = MqttAcl {
+            resource_type: MqttAclResourceType::User,
+            resource_name: "test_user".to_string(),
+            topic: "a/b".to_string(),
+            ip: "".to_string(),
+            action: MqttAclAction::Publish,
+            permission: MqttAclPermission::Deny,
+            priority: 0,
+        };
+        let other_acl = MqttAcl {
+            topic: "a/c".to_string(),
+            ..acl.clone()
+        };
+        acl_metadata.parse_mqtt_acl(acl.clone());
+        acl_metadata.parse_mqtt_acl(other_acl.clone());
+
+        acl_metadata.remove_mqtt_acl(&acl);
+        assert_eq!(
+            acl_metadata.acl_user.get("test_user").unwrap().clone(),
+            vec![other_acl.clone()]
+        );
+
+        acl_metadata.remove_mqtt_acl(&other_acl);
+        assert!(!acl_metadata.acl_user.contains_key("test_user"));
+    }
+
+    #[tokio::test]
+    pub async fn remove_mqtt_blacklist_test() {
+        let acl_metadata = AclMetadata::new();
+        let blacklist = |blacklist_type, resource_name: &str| MqttAclBlackList {
+            blacklist_type,
+            resource_name: resource_name.to_string(),
+            end_time: now_second() + 100,
+            desc: "".to_string(),
+        };
+        let client_id = blacklist(MqttAclBlackListType::ClientId, "test_client");
+        let ip = blacklist(MqttAclBlackListType::Ip, "192.168.1.1");
+        let ip_cidr = blacklist(MqttAclBlackListType::IPCIDR, "10.0.0.0/8");
+        let user_match = blacklist(MqttAclBlackListType::UserMatch, "test_user_*");
+        for raw in [&client_id, &ip, &ip_cidr, &user_match] {
+            acl_metadata.parse_mqtt_blacklist(raw.clone());
+        }
+
+        acl_metadata.remove_mqtt_blacklist(&client_id);
+        assert!(!acl_metadata.blacklist_client_id.contains_key("test_client"));
+
+        acl_metadata.remove_mqtt_blacklist(&ip);
+        assert!(!acl_metadata.blacklist_ip.contains_key("192.168.1.1"));
+        assert!(acl_metadata
+            .get_blacklist_ip(&"192.168.1.1".parse().unwrap())
+            .is_empty());
+
+        acl_metadata.remove_mqtt_blacklist(&ip_cidr);
+        assert!(acl_metadata.get_blacklist_ip_match().unwrap().is_empty());
+        assert!(acl_metadata
+            .get_blacklist_ip(&"10.1.2.3".parse().unwrap())
+            .is_empty());
+
+        acl_metadata.remove_mqtt_blacklist(&user_match);
+        assert!(acl_metadata.get_blacklist_user_match().unwrap().is_empty());
+    }
 }
@@ -18,18 +18,141 @@ use std::sync::Arc;
 
 use common_base::tools::now_second;
 use ipnet::IpNet;
-use metadata_struct::acl::mqtt_acl::{MqttAclAction, MqttAclPermission};
+use metadata_struct::acl::mqtt_acl::{MqttAcl, MqttAclAction, MqttAclPermission};
 use protocol::mqtt::common::QoS;
-use regex::Regex;
 
 use crate::handler::cache::CacheManager;
 use crate::handler::connection::Connection;
 use crate::handler::constant::WILDCARD_RESOURCE;
+use crate::security::acl::metadata::AclMetadata;
+use crate::security::login::http::{HttpAuthClient, HttpAuthResult};
 
+pub mod ip_trie;
 pub mod metadata;
 
-pub fn is_allow_acl(
+// Topic permissions that only apply to one connection, e.g. the ACL claims of a JWT.
+// When present, the connection can only publish and subscribe to the listed topic filters.
+#[derive(Clone, Default, Debug, PartialEq)]
+pub struct ConnectionAcl {
+    pub publish: Vec<String>,
+    pub subscribe: Vec<String>,
+}
+
+// Values substituted for ${username}, ${clientid} and ${cn} in ACL topics and blacklist patterns
+pub struct AclPlaceholders<'a> {
+    pub username: &'a str,
+    pub client_id: &'a str,
+    pub cn: Option<&'a str>,
+}
+
+impl<'a> AclPlaceholders<'a> {
+    pub fn from_connection(connection: &'a Connection) -> Self {
+        AclPlaceholders {
+            username: &connection.login_user,
+            client_id: &connection.client_id,
+            cn: connection.cert_common_name.as_deref(),
+        }
+    }
+
+    // None when the template uses a value that is missing or rejected by `accept`
+    fn expand(
+        &self,
+        template: &str,
+        accept: fn(&str) -> bool,
+        escape: fn(&str) -> String,
+    ) -> Option<String> {
+        let mut result = String::with_capacity(template.len());
+        let mut rest = template;
+        while let Some(start) = rest.find("${") {
+            let Some(end) = rest[start..].find('}') else {
+                break;
+            };
+            result.push_str(&rest[..start]);
+            let name = &rest[start + 2..start + end];
+            let value = match name {
+                "username" => Some(self.username),
+                "clientid" => Some(self.client_id),
+                "cn" => Some(self.cn.unwrap_or_default()),
+                _ => None,
+            };
+            match value {
+                Some(value) => {
+                    if value.is_empty() || !accept(value) {
+                        return None;
+                    }
+                    result.push_str(&escape(value));
+                }
+                // unknown placeholders are kept as they are
+                None => result.push_str(&rest[start..start + end + 1]),
+            }
+            rest = &rest[start + end + 1..];
+        }
+        result.push_str(rest);
+        Some(result)
+    }
+
+    // A value must fill exactly one topic level, "+", "#" or "/" would widen the rule
+    fn expand_topic(&self, topic: &str) -> Option<String> {
+        self.expand(
+            topic,
+            |value| !value.contains(['/', '+', '#']),
+            |value| value.to_string(),
+        )
+    }
+
+    fn expand_regex(&self, pattern: &str) -> Option<String> {
+        self.expand(pattern, |_| true, regex::escape)
+    }
+}
+
+pub fn is_allow_connection_acl(
+    cache_manager: &Arc<CacheManager>,
+    connection: &Connection,
+    topic_filter: &str,
+    action: MqttAclAction,
+) -> bool {
+    let acl = if let Some(acl) = cache_manager
+        .acl_metadata
+        .connection_acl
+        .get(&connection.connect_id)
+    {
+        acl.clone()
+    } else {
+        return true;
+    };
+
+    let allow_list = match action {
+        MqttAclAction::Publish => &acl.publish,
+        MqttAclAction::Subscribe => &acl.subscribe,
+        _ => return true,
+    };
+    allow_list
+        .iter()
+        .any(|allow| topic_filter_covered(topic_filter, allow))
+}
+
+// Whether every topic matched by `topic_filter` is also matched by `allow_filter`
+fn topic_filter_covered(topic_filter: &str, allow_filter: &str) -> bool {
+    let mut levels = topic_filter.split('/');
+    for allow_level in allow_filter.split('/') {
+        if allow_level == "#" {
+            return true;
+        }
+        match levels.next() {
+            Some(level) => {
+                if level == "#" || (allow_level != "+" && allow_level != level) {
+                    return false;
+                }
+            }
+            None => return false,
+        }
+    }
+    levels.next().is_none()
+}
+
+pub async fn is_allow_acl(
     cache_mamanger: &Arc<CacheManager>,
+    http_authz: Option<&HttpAuthClient>,
     connection: &Connection,
     topic_name: &str,
     action: MqttAclAction,
@@ -46,9 +169,25 @@ pub fn is_allow_acl(
         return false;
     }
 
-    // chack acl
-    if is_acl_deny(cache_mamanger, connection, topic_name, action) {
-        return false;
+    // check the external authorization service, its decision replaces the local acl
+    if let Some(http) = http_authz {
+        match http.authorize(connection, topic_name, &action).await {
+            HttpAuthResult::Allow => return true,
+            HttpAuthResult::Deny => return false,
+            HttpAuthResult::Ignore => {}
+        }
+    }
+
+    // chack acl, the cluster decides when no rule matches
+    match acl_permission(cache_mamanger, connection, topic_name, action) {
+        Some(MqttAclPermission::Allow) => {}
+        Some(MqttAclPermission::Deny) => return false,
+        None => {
+            let cluster = cache_mamanger.get_cluster_info();
+            if cluster.security.acl_no_match == MqttAclPermission::Deny {
+                return false;
+            }
+        }
     }
 
     // check retain acl
@@ -77,6 +216,8 @@ fn is_super_user(cache_manager: &Arc<CacheManager>, username: &str) -> bool {
 }
 
 fn is_blacklist(cache_manager: &Arc<CacheManager>, connection: &Connection) -> bool {
+    let placeholders = AclPlaceholders::from_connection(connection);
+
     // check user blacklist
     if let Some(data) = cache_manager
         .acl_metadata
@@ -90,103 +231,174 @@ fn is_blacklist(cache_manager: &Arc<CacheManager>, connection: &Connection) -> b
 
     if let Some(data) = cache_manager.acl_metadata.get_blacklist_user_match() {
         for raw in data {
-            let re = Regex::new(&format!("^{}$", raw.resource_name)).unwrap();
-            if re.is_match(&connection.login_user) && raw.end_time > now_second() {
+            if raw.end_time > now_second()
+                && is_regex_match(
+                    &cache_manager.acl_metadata,
+                    &placeholders,
+                    &raw.resource_name,
+                    &connection.login_user,
+                )
+            {
                 return true;
             }
         }
     }
 
     // check client_id blacklist
-    if let Some(data) = cache_manager
-        .acl_metadata
-        .blacklist_client_id
-        .get(&connection.client_id)
-    {
-        if data.end_time > now_second() {
-            return true;
-        }
+    if is_client_id_blacklist(cache_manager, &placeholders) {
+        return true;
     }
 
-    if let Some(data) = cache_manager.acl_metadata.get_blacklist_client_id_match() {
-        for raw in data {
-            let re = Regex::new(&format!("^{}$", raw.resource_name)).unwrap();
-            if re.is_match(&connection.client_id) && raw.end_time > now_second() {
-                return true;
-            }
+    // check ip blacklist
+    if let Ok(ip) = connection.source_ip_addr.parse::<IpAddr>() {
+        let now = now_second();
+        if cache_manager
+            .acl_metadata
+            .get_blacklist_ip(&ip)
+            .iter()
+            .any(|raw| raw.end_time > now)
+        {
+            return true;
         }
     }
 
-    // check ip blacklist
+    false
+}
+
+pub fn is_client_id_blacklist(
+    cache_manager: &Arc<CacheManager>,
+    placeholders: &AclPlaceholders,
+) -> bool {
     if let Some(data) = cache_manager
         .acl_metadata
-        .blacklist_ip
-        .get(&connection.source_ip_addr)
+        .blacklist_client_id
+        .get(placeholders.client_id)
     {
-        if data.end_time < now_second() {
+        if data.end_time > now_second() {
             return true;
         }
     }
 
-    if let Some(data) = cache_manager.acl_metadata.get_blacklist_ip_match() {
+    if let Some(data) = cache_manager.acl_metadata.get_blacklist_client_id_match() {
         for raw in data {
-            if ip_match(&connection.source_ip_addr, &raw.resource_name)
-                && raw.end_time < now_second()
+            if raw.end_time > now_second()
+                && is_regex_match(
+                    &cache_manager.acl_metadata,
+                    placeholders,
+                    &raw.resource_name,
+                    placeholders.client_id,
+                )
             {
                 return true;
             }
         }
     }
-
     false
 }
 
+// Blacklist match types are anchored regular expressions that may use placeholders
+fn is_regex_match(
+    acl_metadata: &AclMetadata,
+    placeholders: &AclPlaceholders,
+    pattern: &str,
+    value: &str,
+) -> bool {
+    let Some(pattern) = placeholders.expand_regex(pattern) else {
+        return false;
+    };
+    acl_metadata
+        .get_blacklist_regex(&pattern)
+        .is_some_and(|re| re.is_match(value))
+}
+
+// Only an explicit deny rule counts, used for the retain check
 fn is_acl_deny(
     cache_mamanger: &Arc<CacheManager>,
     connection: &Connection,
     topic_name: &str,
     action: MqttAclAction,
 ) -> bool {
-    // check user acl
+    acl_permission(cache_mamanger, connection, topic_name, action) == Some(MqttAclPermission::Deny)
+}
+
+// The permission of the first matching user or client id rule, None when no rule matches.
+// Rules are ordered by priority, deny before allow on a tie.
+fn acl_permission(
+    cache_mamanger: &Arc<CacheManager>,
+    connection: &Connection,
+    topic_name: &str,
+    action: MqttAclAction,
+) -> Option<MqttAclPermission> {
+    let mut rules: Vec<MqttAcl> = Vec::new();
     if let Some(acl_list) = cache_mamanger
         .acl_metadata
         .acl_user
         .get(&connection.login_user)
     {
-        for raw in acl_list.clone() {
-            if topic_match(topic_name, &raw.topic)
-                && ip_match(&connection.source_ip_addr, &raw.ip)
-                && raw.action == action
-                && raw.permission == MqttAclPermission::Deny
-            {
-                return true;
-            }
-        }
+        rules.extend(acl_list.iter().cloned());
     }
-    // check client id acl
-    if let Some(client_id_list) = cache_mamanger
+    if let Some(acl_list) = cache_mamanger
         .acl_metadata
         .acl_client_id
         .get(&connection.client_id)
     {
-        for raw in client_id_list.clone() {
-            if topic_match(topic_name, &raw.topic)
+        rules.extend(acl_list.iter().cloned());
+    }
+    rules.sort_by_key(|raw| (raw.priority, raw.permission != MqttAclPermission::Deny));
+
+    let placeholders = AclPlaceholders::from_connection(connection);
+    rules
+        .into_iter()
+        .find(|raw| {
+            action_match(&raw.action, &action)
                 && ip_match(&connection.source_ip_addr, &raw.ip)
-                && raw.action == action
-                && raw.permission == MqttAclPermission::Deny
-            {
-                return true;
-            }
-        }
+                && placeholders
+                    .expand_topic(&raw.topic)
+                    .is_some_and(|rule_topic| topic_match(topic_name, &rule_topic, &raw.permission))
+        })
+        .map(|raw| raw.permission)
+}
+
+fn action_match(rule_action: &MqttAclAction, action: &MqttAclAction) -> bool {
+    match rule_action {
+        MqttAclAction::All => true,
+        MqttAclAction::PubSub => matches!(
+            action,
+            MqttAclAction::Publish | MqttAclAction::Subscribe | MqttAclAction::PubSub
+        ),
+        _ => rule_action == action,
     }
-    false
 }
 
-fn topic_match(topic_name: &str, match_topic_name: &str) -> bool {
+// An allow rule must cover the whole topic name or filter, a deny rule applies as soon as
+// the subscribed filter could receive one of its topics
+fn topic_match(topic_name: &str, match_topic_name: &str, permission: &MqttAclPermission) -> bool {
     if match_topic_name == WILDCARD_RESOURCE {
         return true;
     }
-    topic_name == match_topic_name
+    if *permission == MqttAclPermission::Deny {
+        topic_filters_intersect(topic_name, match_topic_name)
+    } else {
+        topic_filter_covered(topic_name, match_topic_name)
+    }
+}
+
+// Whether at least one topic is matched by both filters
+fn topic_filters_intersect(topic_filter: &str, match_filter: &str) -> bool {
+    let mut levels = topic_filter.split('/');
+    let mut match_levels = match_filter.split('/');
+    loop {
+        match (levels.next(), match_levels.next()) {
+            (Some("#"), _) | (_, Some("#")) => return true,
+            (Some(level), Some(match_level)) => {
+                if level != "+" && match_level != "+" && level != match_level {
+                    return false;
+                }
+            }
+            (None, None) => return true,
+            _ => return false,
+        }
+    }
 }
 
 fn ip_match(source_ip_addr: &str, ip_role: &str) -> bool {
@@ -214,9 +426,15 @@ mod test {
         MqttAcl, MqttAclAction, MqttAclPermission, MqttAclResourceType,
     };
     use metadata_struct::acl::mqtt_blacklist::{MqttAclBlackList, MqttAclBlackListType};
+    use metadata_struct::mqtt::cluster::MqttClusterDynamicConfig;
     use metadata_struct::mqtt::user::MqttUser;
+    use protocol::mqtt::common::QoS;
 
-    use super::{ip_match, is_acl_deny, is_blacklist, is_super_user, topic_match};
+    use super::{
+        acl_permission, ip_match, is_acl_deny, is_allow_acl, is_allow_connection_acl, is_blacklist,
+        is_client_id_blacklist, is_super_user, topic_filter_covered, topic_filters_intersect,
+        topic_match, AclPlaceholders, ConnectionAcl,
+    };
     use crate::handler::cache::CacheManager;
     use crate::handler::connection::{Connection, ConnectionConfig};
     use crate::handler::constant::WILDCARD_RESOURCE;
@@ -231,6 +449,7 @@ mod test {
             username: "loboxu".to_string(),
             password: "lobo_123".to_string(),
             is_superuser: true,
+            hash: Default::default(),
         };
         cache_manager.add_user(user.clone());
 
@@ -246,6 +465,7 @@ mod test {
             username: "loboxu".to_string(),
             password: "lobo_123".to_string(),
             is_superuser: false,
+            hash: Default::default(),
         };
         cache_manager.add_user(user.clone());
         assert!(!is_super_user(&cache_manager, &user.username));
@@ -260,6 +480,7 @@ mod test {
             username: "loboxu".to_string(),
             password: "lobo_123".to_string(),
             is_superuser: true,
+            hash: Default::default(),
         };
 
         cache_manager.add_user(user.clone());
@@ -347,6 +568,7 @@ mod test {
             username: "loboxu".to_string(),
             password: "lobo_123".to_string(),
             is_superuser: true,
+            hash: Default::default(),
         };
 
         cache_manager.add_user(user.clone());
@@ -388,6 +610,7 @@ mod test {
             username: "loboxu".to_string(),
             password: "lobo_123".to_string(),
             is_superuser: true,
+            hash: Default::default(),
         };
 
         cache_manager.add_user(user.clone());
@@ -411,6 +634,7 @@ mod test {
             ip: WILDCARD_RESOURCE.to_string(),
             action: MqttAclAction::Publish,
             permission: MqttAclPermission::Deny,
+            priority: 0,
         };
         cache_manager.add_acl(acl);
         assert!(is_acl_deny(
@@ -434,6 +658,7 @@ mod test {
             ip: WILDCARD_RESOURCE.to_string(),
             action: MqttAclAction::Subscribe,
             permission: MqttAclPermission::Deny,
+            priority: 0,
         };
         cache_manager.add_acl(acl);
         assert!(is_acl_deny(
@@ -454,6 +679,7 @@ mod test {
             username: "loboxu".to_string(),
             password: "lobo_123".to_string(),
             is_superuser: true,
+            hash: Default::default(),
         };
 
         cache_manager.add_user(user.clone());
@@ -477,6 +703,7 @@ mod test {
             ip: WILDCARD_RESOURCE.to_string(),
             action: MqttAclAction::Publish,
             permission: MqttAclPermission::Deny,
+            priority: 0,
         };
         cache_manager.add_acl(acl);
         assert!(is_acl_deny(
@@ -500,6 +727,7 @@ mod test {
             ip: WILDCARD_RESOURCE.to_string(),
             action: MqttAclAction::Subscribe,
             permission: MqttAclPermission::Deny,
+            priority: 0,
         };
         cache_manager.add_acl(acl);
         assert!(is_acl_deny(
@@ -520,6 +748,7 @@ mod test {
             username: "loboxu".to_string(),
             password: "lobo_123".to_string(),
             is_superuser: true,
+            hash: Default::default(),
         };
 
         cache_manager.add_user(user.clone());
@@ -543,6 +772,7 @@ mod test {
             ip: WILDCARD_RESOURCE.to_string(),
             action: MqttAclAction::Publish,
             permission: MqttAclPermission::Deny,
+            priority: 0,
         };
         cache_manager.add_acl(acl);
         assert!(is_acl_deny(
@@ -566,6 +796,7 @@ mod test {
             ip: WILDCARD_RESOURCE.to_string(),
             action: MqttAclAction::Subscribe,
             permission: MqttAclPermission::Deny,
+            priority: 0,
         };
         cache_manager.add_acl(acl);
         assert!(is_acl_deny(
@@ -586,6 +817,7 @@ mod test {
             username: "loboxu".to_string(),
             password: "lobo_123".to_string(),
             is_superuser: true,
+            hash: Default::default(),
         };
 
         cache_manager.add_user(user.clone());
@@ -609,6 +841,7 @@ mod test {
             ip: WILDCARD_RESOURCE.to_string(),
             action: MqttAclAction::Publish,
             permission: MqttAclPermission::Deny,
+            priority: 0,
         };
         cache_manager.add_acl(acl);
         assert!(is_acl_deny(
@@ -632,6 +865,7 @@ mod test {
             ip: WILDCARD_RESOURCE.to_string(),
             action: MqttAclAction::Subscribe,
             permission: MqttAclPermission::Deny,
+            priority: 0,
         };
         cache_manager.add_acl(acl);
         assert!(is_acl_deny(
@@ -646,9 +880,17 @@ mod test {
     pub async fn topic_match_test() {
         let topic_name = "t1";
         let match_topic_name = WILDCARD_RESOURCE.to_string();
-        assert!(topic_match(topic_name, &match_topic_name));
-        assert!(topic_match(topic_name, topic_name));
-        assert!(!topic_match(topic_name, "v1"));
+        let allow = MqttAclPermission::Allow;
+        assert!(topic_match(topic_name, &match_topic_name, &allow));
+        assert!(topic_match(topic_name, topic_name, &allow));
+        assert!(!topic_match(topic_name, "v1", &allow));
+
+        // a deny rule applies to every filter that overlaps it, an allow rule must cover it
+        let deny = MqttAclPermission::Deny;
+        assert!(topic_match("a/#", "a/b", &deny));
+        assert!(topic_match("#", "a/b", &deny));
+        assert!(!topic_match("a/#", "a/b", &allow));
+        assert!(!topic_match("b/#", "a/b", &deny));
     }
 
     #[tokio::test]
@@ -661,4 +903,389 @@ mod test {
         assert!(!ip_match(source_ip, "192.1.1.1"));
         assert!(ip_match(source_ip, "127.0.0.1/24"));
     }
+
+    fn test_connection(client_id: &str, username: &str) -> Connection {
+        let config = ConnectionConfig {
+            connect_id: 1,
+            client_id: client_id.to_string(),
+            receive_maximum: 3,
+            max_packet_size: 3,
+            topic_alias_max: 3,
+            request_problem_info: 1,
+            keep_alive: 2,
+            source_ip_addr: "127.0.0.1".to_string(),
+        };
+        let mut connection = Connection::new(config);
+        connection.login_success(username.to_string());
+        connection
+    }
+
+    fn user_acl(
+        username: &str,
+        topic: &str,
+        action: MqttAclAction,
+        permission: MqttAclPermission,
+        priority: u32,
+    ) -> MqttAcl {
+        MqttAcl {
+            resource_type: MqttAclResourceType::User,
+            resource_name: username.to_string(),
+            topic: topic.to_string(),
+            ip: WILDCARD_RESOURCE.to_string(),
+            action,
+            permission,
+            priority,
+        }
+    }
+
+    #[tokio::test]
+    pub async fn acl_placeholder_test() {
+        let client_poll = Arc::new(ClientPool::new(1));
+        let cache_manager = Arc::new(CacheManager::new(client_poll, "test".to_string()));
+        let mut connection = test_connection("dev-1", "u1");
+        connection.cert_common_name = Some("cn-1".to_string());
+
+        cache_manager.add_acl(user_acl(
+            "u1",
+            "devices/${clientid}/#",
+            MqttAclAction::Publish,
+            MqttAclPermission::Allow,
+            0,
+        ));
+        cache_manager.add_acl(user_acl(
+            "u1",
+            "certs/${cn}/${username}",
+            MqttAclAction::Publish,
+            MqttAclPermission::Allow,
+            0,
+        ));
+        cache_manager.add_acl(user_acl(
+            "u1",
+            WILDCARD_RESOURCE,
+            MqttAclAction::Publish,
+            MqttAclPermission::Deny,
+            10,
+        ));
+
+        let check = |connection: &Connection, topic: &str| {
+            acl_permission(&cache_manager, connection, topic, MqttAclAction::Publish)
+        };
+        assert_eq!(
+            check(&connection, "devices/dev-1/temp"),
+            Some(MqttAclPermission::Allow)
+        );
+        assert_eq!(
+            check(&connection, "devices/dev-2/temp"),
+            Some(MqttAclPermission::Deny)
+        );
+        assert_eq!(
+            check(&connection, "certs/cn-1/u1"),
+            Some(MqttAclPermission::Allow)
+        );
+
+        // without a certificate the ${cn} rule does not apply
+        connection.cert_common_name = None;
+        assert_eq!(
+            check(&connection, "certs/cn-1/u1"),
+            Some(MqttAclPermission::Deny)
+        );
+
+        // a value with topic wildcards or separators cannot widen the rule
+        let connection = test_connection("#", "u1");
+        assert_eq!(
+            check(&connection, "devices/dev-1/temp"),
+            Some(MqttAclPermission::Deny)
+        );
+        let connection = test_connection("dev-1/x", "u1");
+        assert_eq!(
+            check(&connection, "devices/dev-1/x/temp"),
+            Some(MqttAclPermission::Deny)
+        );
+    }
+
+    #[tokio::test]
+    pub async fn acl_priority_test() {
+        let client_poll = Arc::new(ClientPool::new(1));
+        let cache_manager = Arc::new(CacheManager::new(client_poll, "test".to_string()));
+        let connection = test_connection("c1", "u1");
+
+        // the lower priority value wins
+        cache_manager.add_acl(user_acl(
+            "u1",
+            "t/1",
+            MqttAclAction::Publish,
+            MqttAclPermission::Deny,
+            2,
+        ));
+        cache_manager.add_acl(user_acl(
+            "u1",
+            "t/1",
+            MqttAclAction::PubSub,
+            MqttAclPermission::Allow,
+            1,
+        ));
+        assert_eq!(
+            acl_permission(&cache_manager, &connection, "t/1", MqttAclAction::Publish),
+            Some(MqttAclPermission::Allow)
+        );
+
+        // deny wins a tie
+        cache_manager.add_acl(user_acl(
+            "u1",
+            "t/#",
+            MqttAclAction::All,
+            MqttAclPermission::Deny,
+            1,
+        ));
+        assert_eq!(
+            acl_permission(&cache_manager, &connection, "t/1", MqttAclAction::Publish),
+            Some(MqttAclPermission::Deny)
+        );
+
+        // client id rules are ordered together with the user rules
+        cache_manager.add_acl(MqttAcl {
+            resource_type: MqttAclResourceType::ClientId,
+            resource_name: "c1".to_string(),
+            topic: "t/+".to_string(),
+            ip: WILDCARD_RESOURCE.to_string(),
+            action: MqttAclAction::Subscribe,
+            permission: MqttAclPermission::Allow,
+            priority: 0,
+        });
+        assert_eq!(
+            acl_permission(&cache_manager, &connection, "t/+", MqttAclAction::Subscribe),
+            Some(MqttAclPermission::Allow)
+        );
+        assert_eq!(
+            acl_permission(&cache_manager, &connection, "t/#", MqttAclAction::Subscribe),
+            Some(MqttAclPermission::Deny)
+        );
+        assert_eq!(
+            acl_permission(&cache_manager, &connection, "x", MqttAclAction::Subscribe),
+            None
+        );
+    }
+
+    #[tokio::test]
+    pub async fn acl_no_match_test() {
+        let client_poll = Arc::new(ClientPool::new(1));
+        let cache_manager = Arc::new(CacheManager::new(client_poll, "test".to_string()));
+        let connection = test_connection("c1", "u1");
+        let allow = |topic: &'static str| {
+            let cache_manager = cache_manager.clone();
+            let connection = connection.clone();
+            async move {
+                is_allow_acl(
+                    &cache_manager,
+                    None,
+                    &connection,
+                    topic,
+                    MqttAclAction::Publish,
+                    false,
+                    QoS::AtMostOnce,
+                )
+                .await
+            }
+        };
+        assert!(allow("t/1").await);
+
+        let mut cluster = MqttClusterDynamicConfig::new();
+        cluster.security.acl_no_match = MqttAclPermission::Deny;
+        cache_manager.set_cluster_info(cluster);
+        assert!(!allow("t/1").await);
+
+        cache_manager.add_acl(user_acl(
+            "u1",
+            "t/${username}",
+            MqttAclAction::Publish,
+            MqttAclPermission::Allow,
+            0,
+        ));
+        assert!(!allow("t/1").await);
+        assert!(allow("t/u1").await);
+    }
+
+    #[tokio::test]
+    pub async fn blacklist_placeholder_test() {
+        let client_poll = Arc::new(ClientPool::new(1));
+        let cache_manager = Arc::new(CacheManager::new(client_poll, "test".to_string()));
+
+        // an invalid pattern never matches
+        cache_manager.add_blacklist(MqttAclBlackList {
+            blacklist_type: MqttAclBlackListType::ClientIdMatch,
+            resource_name: "${username}(".to_string(),
+            end_time: now_second() + 100,
+            desc: "".to_string(),
+        });
+        assert!(!is_blacklist(&cache_manager, &test_connection("x-1", "u1")));
+
+        cache_manager.add_blacklist(MqttAclBlackList {
+            blacklist_type: MqttAclBlackListType::UserMatch,
+            resource_name: "${clientid}".to_string(),
+            end_time: now_second() + 100,
+            desc: "".to_string(),
+        });
+        assert!(is_blacklist(
+            &cache_manager,
+            &test_connection("same", "same")
+        ));
+        assert!(!is_blacklist(&cache_manager, &test_connection("c1", "u1")));
+
+        // placeholder values are matched literally
+        assert!(!is_blacklist(&cache_manager, &test_connection("u.", "u1")));
+
+        cache_manager.add_blacklist(MqttAclBlackList {
+            blacklist_type: MqttAclBlackListType::ClientIdMatch,
+            resource_name: "${username}-test-.*".to_string(),
+            end_time: now_second() + 100,
+            desc: "".to_string(),
+        });
+        let placeholders = AclPlaceholders {
+            username: "u1",
+            client_id: "u1-test-1",
+            cn: None,
+        };
+        assert!(is_client_id_blacklist(&cache_manager, &placeholders));
+        let placeholders = AclPlaceholders {
+            username: "u2",
+            client_id: "u1-test-1",
+            cn: None,
+        };
+        assert!(!is_client_id_blacklist(&cache_manager, &placeholders));
+
+        // expanded patterns are compiled once, invalid ones are remembered as well
+        let metadata = &cache_manager.acl_metadata;
+        assert!(metadata.blacklist_regex.contains_key("u1-test-.*"));
+        assert!(metadata.blacklist_regex.get("u1(").unwrap().is_none());
+    }
+
+    #[tokio::test]
+    pub async fn connection_acl_test() {
+        let client_poll = Arc::new(ClientPool::new(1));
+        let cache_manager = Arc::new(CacheManager::new(client_poll, "test".to_string()));
+        let config = ConnectionConfig {
+            connect_id: 1,
+            client_id: "client_id-1".to_string(),
+            receive_maximum: 3,
+            max_packet_size: 3,
+            topic_alias_max: 3,
+            request_problem_info: 1,
+            keep_alive: 2,
+            source_ip_addr: "127.0.0.1".to_string(),
+        };
+        let connection = Connection::new(config);
+
+        // connections without their own acl are not restricted
+        assert!(is_allow_connection_acl(
+            &cache_manager,
+            &connection,
+            "t1",
+            MqttAclAction::Publish
+        ));
+
+        cache_manager.acl_metadata.add_connection_acl(
+            connection.connect_id,
+            ConnectionAcl {
+                publish: vec!["device/1/up".to_string()],
+                subscribe: vec!["device/1/down/#".to_string()],
+            },
+        );
+        assert!(is_allow_connection_acl(
+            &cache_manager,
+            &connection,
+            "device/1/up",
+            MqttAclAction::Publish
+        ));
+        assert!(!is_allow_connection_acl(
+            &cache_manager,
+            &connection,
+            "device/2/up",
+            MqttAclAction::Publish
+        ));
+        assert!(is_allow_connection_acl(
+            &cache_manager,
+            &connection,
+            "device/1/down/+",
+            MqttAclAction::Subscribe
+        ));
+        assert!(!is_allow_connection_acl(
+            &cache_manager,
+            &connection,
+            "device/1/up",
+            MqttAclAction::Subscribe
+        ));
+
+        cache_manager.remove_connection(connection.connect_id);
+        assert!(is_allow_connection_acl(
+            &cache_manager,
+            &connection,
+            "device/2/up",
+            MqttAclAction::Publish
+        ));
+    }
+
+    #[tokio::test]
+    pub async fn acl_deny_filter_test() {
+        let client_poll = Arc::new(ClientPool::new(1));
+        let cache_manager = Arc::new(CacheManager::new(client_poll, "test".to_string()));
+        let connection = test_connection("c1", "u1");
+        cache_manager.add_acl(user_acl(
+            "u1",
+            "a/b",
+            MqttAclAction::Subscribe,
+            MqttAclPermission::Deny,
+            0,
+        ));
+        cache_manager.add_acl(user_acl(
+            "u1",
+            "#",
+            MqttAclAction::Subscribe,
+            MqttAclPermission::Allow,
+            1,
+        ));
+
+        let check = |filter: &str| {
+            acl_permission(
+                &cache_manager,
+                &connection,
+                filter,
+                MqttAclAction::Subscribe,
+            )
+        };
+        // wildcard filters that would also receive a/b are denied
+        assert_eq!(check("a/#"), Some(MqttAclPermission::Deny));
+        assert_eq!(check("#"), Some(MqttAclPermission::Deny));
+        assert_eq!(check("+/b"), Some(MqttAclPermission::Deny));
+        assert_eq!(check("a/c"), Some(MqttAclPermission::Allow));
+        assert_eq!(check("b/#"), Some(MqttAclPermission::Allow));
+    }
+
+    #[tokio::test]
+    pub async fn topic_filters_intersect_test() {
+        assert!(topic_filters_intersect("a/b", "a/b"));
+        assert!(topic_filters_intersect("a/#", "a/b"));
+        assert!(topic_filters_intersect("#", "a/b"));
+        assert!(topic_filters_intersect("a/+", "+/b"));
+        assert!(topic_filters_intersect("a", "a/#"));
+        assert!(topic_filters_intersect("a/b/c", "a/#"));
+        assert!(!topic_filters_intersect("a/+", "a/b/c"));
+        assert!(!topic_filters_intersect("a/b", "a/c"));
+        assert!(!topic_filters_intersect("b/#", "a/b"));
+        assert!(!topic_filters_intersect("a", "a/b"));
+    }
+
+    #[tokio::test]
+    pub async fn topic_filter_covered_test() {
+        assert!(topic_filter_covered("a/b", "a/b"));
+        assert!(topic_filter_covered("a/b", "a/+"));
+        assert!(topic_filter_covered("a/b/c", "a/#"));
+        assert!(topic_filter_covered("a", "a/#"));
+        assert!(topic_filter_covered("a/+", "a/+"));
+        assert!(topic_filter_covered("a/+/c", "#"));
+        assert!(!topic_filter_covered("a/#", "a/+"));
+        assert!(!topic_filter_covered("a/+", "a/b"));
+        assert!(!topic_filter_covered("a/b/c", "a/+"));
+        assert!(!topic_filter_covered("a", "a/+"));
+        assert!(!topic_filter_covered("b/c", "a/#"));
+    }
 }
@@ -0,0 +1,274 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use common_base::config::common::Auth;
+use common_base::error::common::CommonError;
+
+use crate::server::connection::NetworkConnectionType;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Authenticator {
+    // Accepts every client
+    Anonymous,
+    // Client certificate verified by mutual TLS
+    X509,
+    // Identity of a TLS-PSK handshake
+    Psk,
+    // The external authentication service
+    Http,
+    // JWT carried in the CONNECT username or password
+    Jwt,
+    // Username and password checked against the auth storage
+    Password,
+}
+
+impl FromStr for Authenticator {
+    type Err = CommonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "anonymous" => Ok(Authenticator::Anonymous),
+            "x509" => Ok(Authenticator::X509),
+            "psk" => Ok(Authenticator::Psk),
+            "http" => Ok(Authenticator::Http),
+            "jwt" => Ok(Authenticator::Jwt),
+            "password" => Ok(Authenticator::Password),
+            _ => Err(CommonError::CommmonError(format!(
+                "unknown authenticator {}",
+                s
+            ))),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum AuthResult {
+    Allow,
+    Deny,
+    // No opinion on this client, the next authenticator decides
+    Ignore,
+}
+
+impl From<bool> for AuthResult {
+    fn from(allow: bool) -> Self {
+        if allow {
+            AuthResult::Allow
+        } else {
+            AuthResult::Deny
+        }
+    }
+}
+
+// The authenticators of every listener, listeners without their own chain use the default one
+#[derive(Clone, Debug, PartialEq)]
+pub struct AuthChains {
+    default: Vec<Authenticator>,
+    listeners: HashMap<NetworkConnectionType, Vec<Authenticator>>,
+}
+
+impl AuthChains {
+    pub fn new(auth: &Auth) -> Result<Self, CommonError> {
+        let default = if auth.chain.default.is_empty() {
+            legacy_chain(auth)
+        } else {
+            parse_chain(auth, &auth.chain.default)?
+        };
+
+        let mut listeners = HashMap::new();
+        for (listener, names) in [
+            (NetworkConnectionType::Tcp, &auth.chain.tcp),
+            (NetworkConnectionType::Tls, &auth.chain.tls),
+            (NetworkConnectionType::TlsPsk, &auth.chain.tlspsk),
+            (NetworkConnectionType::WebSocket, &auth.chain.websocket),
+            (NetworkConnectionType::WebSockets, &auth.chain.websockets),
+            (NetworkConnectionType::Quic, &auth.chain.quic),
+        ] {
+            if !names.is_empty() {
+                listeners.insert(listener, parse_chain(auth, names)?);
+            }
+        }
+        Ok(AuthChains { default, listeners })
+    }
+
+    pub fn get(&self, listener: &NetworkConnectionType) -> &[Authenticator] {
+        self.listeners
+            .get(listener)
+            .map(|chain| chain.as_slice())
+            .unwrap_or(&self.default)
+    }
+}
+
+// The order used before the chain was configurable: TLS identities, the HTTP service,
+// then the JWT or the password
+fn legacy_chain(auth: &Auth) -> Vec<Authenticator> {
+    let mut chain = vec![Authenticator::X509, Authenticator::Psk];
+    if auth.http.enable {
+        chain.push(Authenticator::Http);
+    }
+    if auth.jwt.enable {
+        chain.push(Authenticator::Jwt);
+    } else {
+        chain.push(Authenticator::Password);
+    }
+    chain
+}
+
+fn parse_chain(auth: &Auth, names: &[String]) -> Result<Vec<Authenticator>, CommonError> {
+    names
+        .iter()
+        .map(|name| {
+            let authenticator = Authenticator::from_str(name)?;
+            let enable = match authenticator {
+                Authenticator::Jwt => auth.jwt.enable,
+                Authenticator::Http => auth.http.enable,
+                _ => true,
+            };
+            if !enable {
+                return Err(CommonError::CommmonError(format!(
+                    "authenticator {} is used in a chain but auth.{}.enable is false",
+                    name, name
+                )));
+            }
+            Ok(authenticator)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod test {
+    use std::net::SocketAddr;
+    use std::sync::{Arc, RwLock};
+
+    use common_base::config::default_mqtt::default_auth;
+    use grpc_clients::poll::ClientPool;
+    use metadata_struct::mqtt::user::MqttUser;
+    use protocol::mqtt::common::{Login, MQTTProtocol};
+
+    use super::{AuthChains, Authenticator};
+    use crate::handler::cache::CacheManager;
+    use crate::security::{build_state, AuthDriver};
+    use crate::server::connection::{NetworkConnection, NetworkConnectionType};
+
+    #[test]
+    pub fn legacy_chain_test() {
+        let mut auth = default_auth();
+        let chains = AuthChains::new(&auth).unwrap();
+        for listener in [NetworkConnectionType::Tcp, NetworkConnectionType::Quic] {
+            assert_eq!(
+                chains.get(&listener),
+                [
+                    Authenticator::X509,
+                    Authenticator::Psk,
+                    Authenticator::Password
+                ]
+            );
+        }
+
+        auth.jwt.enable = true;
+        auth.http.enable = true;
+        let chains = AuthChains::new(&auth).unwrap();
+        assert_eq!(
+            chains.get(&NetworkConnectionType::Tcp),
+            [
+                Authenticator::X509,
+                Authenticator::Psk,
+                Authenticator::Http,
+                Authenticator::Jwt
+            ]
+        );
+    }
+
+    #[tokio::test]
+    pub async fn listener_chain_test() {
+        let mut auth = default_auth();
+        auth.jwt.enable = true;
+        auth.jwt.algorithm = "HS256".to_string();
+        auth.jwt.secret = "robustmq".to_string();
+        auth.chain.default = vec!["jwt".to_string(), "password".to_string()];
+        auth.chain.tcp = vec!["anonymous".to_string()];
+        auth.chain.tls = vec!["x509".to_string()];
+        let chains = AuthChains::new(&auth).unwrap();
+
+        assert_eq!(
+            chains.get(&NetworkConnectionType::Tcp),
+            [Authenticator::Anonymous]
+        );
+        assert_eq!(
+            chains.get(&NetworkConnectionType::Tls),
+            [Authenticator::X509]
+        );
+        assert_eq!(
+            chains.get(&NetworkConnectionType::WebSockets),
+            [Authenticator::Jwt, Authenticator::Password]
+        );
+
+        // a password user logs in on a listener whose chain starts with jwt
+        let client_poll = Arc::new(ClientPool::new(10));
+        let cache_manager = Arc::new(CacheManager::new(client_poll.clone(), "test".to_string()));
+        cache_manager.add_user(MqttUser {
+            username: "lobo".to_string(),
+            password: "pwd123".to_string(),
+            is_superuser: false,
+            hash: Default::default(),
+        });
+        let driver = AuthDriver {
+            cache_manager,
+            client_poll: client_poll.clone(),
+            state: RwLock::new(Arc::new(build_state(client_poll, auth).unwrap())),
+        };
+
+        let addr: SocketAddr = "127.0.0.1:1883".parse().unwrap();
+        let connection = NetworkConnection::new(NetworkConnectionType::WebSockets, addr, None);
+        // the password is not a token, so jwt leaves it to the password authenticator
+        for (password, allow) in [("pwd123", true), ("pwd1234", false)] {
+            let login = Some(Login {
+                username: "lobo".to_string(),
+                password: password.to_string(),
+            });
+            let res = driver
+                .check_login_auth(
+                    1,
+                    "client-1",
+                    &MQTTProtocol::MQTT5,
+                    Some(&connection),
+                    &login,
+                    &None,
+                    &addr,
+                )
+                .await
+                .unwrap();
+            assert_eq!(res, allow);
+        }
+    }
+
+    #[test]
+    pub fn invalid_chain_test() {
+        let mut auth = default_auth();
+        auth.chain.quic = vec!["ldap".to_string()];
+        assert!(AuthChains::new(&auth).is_err());
+
+        // jwt and http need their own settings
+        let mut auth = default_auth();
+        auth.chain.default = vec!["jwt".to_string()];
+        assert!(AuthChains::new(&auth).is_err());
+        let mut auth = default_auth();
+        auth.chain.websocket = vec!["http".to_string()];
+        assert!(AuthChains::new(&auth).is_err());
+        auth.http.enable = true;
+        assert!(AuthChains::new(&auth).is_ok());
+    }
+}
@@ -11,3 +11,407 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+
+use std::cmp::min;
+use std::time::Duration;
+
+use common_base::config::common::AuthHttp;
+use common_base::error::mqtt_broker::MQTTBrokerError;
+use common_base::tools::now_second;
+use dashmap::DashMap;
+use log::warn;
+use metadata_struct::acl::mqtt_acl::MqttAclAction;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+use crate::handler::connection::Connection;
+
+const HTTP_AUTH_DEFAULT_TIMEOUT_MS: u64 = 500;
+const HTTP_AUTH_DEFAULT_POOL_MAX_IDLE: usize = 16;
+// Failed calls are cached briefly so that a service outage does not get a request per packet
+const HTTP_AUTH_FAILURE_CACHE_TTL: u64 = 1;
+const HTTP_AUTH_FAILURE_IGNORE: &str = "ignore";
+// Expired entries are swept once the cache grows past this size
+const HTTP_AUTH_CACHE_GC_SIZE: usize = 10000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HttpAuthResult {
+    Allow,
+    Deny,
+    // The service has no opinion, the next authenticator or the local ACL decides
+    Ignore,
+}
+
+#[derive(Serialize)]
+struct HttpAuthnRequest<'a> {
+    clientid: &'a str,
+    username: &'a str,
+    password: &'a str,
+    peerhost: &'a str,
+    proto_ver: u8,
+}
+
+#[derive(Serialize)]
+struct HttpAuthzRequest<'a> {
+    clientid: &'a str,
+    username: &'a str,
+    peerhost: &'a str,
+    action: &'a str,
+    topic: &'a str,
+}
+
+// {"result": "allow" | "deny" | "ignore"}
+#[derive(Deserialize)]
+struct HttpAuthResponse {
+    result: String,
+}
+
+// Calls out to an external HTTP service to authenticate connections and authorize
+// PUBLISH/SUBSCRIBE. A 204 response allows, a 200 response carries the result in its body,
+// any other status, timeout or unreadable body is a failure and denies unless configured otherwise.
+pub struct HttpAuthClient {
+    client: reqwest::Client,
+    authn_url: String,
+    authz_url: String,
+    cache_ttl: u64,
+    failure_result: HttpAuthResult,
+    // (sha256 of the request, (result, expire time))
+    cache: DashMap<String, (HttpAuthResult, u64)>,
+}
+
+impl HttpAuthClient {
+    pub fn new(config: &AuthHttp) -> Result<Self, MQTTBrokerError> {
+        let timeout_ms = if config.timeout_ms == 0 {
+            HTTP_AUTH_DEFAULT_TIMEOUT_MS
+        } else {
+            config.timeout_ms
+        };
+        let pool_max_idle = if config.pool_max_idle == 0 {
+            HTTP_AUTH_DEFAULT_POOL_MAX_IDLE
+        } else {
+            config.pool_max_idle
+        };
+        let client = reqwest::Client::builder()
+            .timeout(Duration::from_millis(timeout_ms))
+            .pool_max_idle_per_host(pool_max_idle)
+            .build()
+            .map_err(|e| MQTTBrokerError::HttpAuthenticationFailed(e.to_string()))?;
+        let failure_result = if config.failure_action.to_lowercase() == HTTP_AUTH_FAILURE_IGNORE {
+            HttpAuthResult::Ignore
+        } else {
+            HttpAuthResult::Deny
+        };
+        Ok(HttpAuthClient {
+            client,
+            authn_url: config.authn_url.clone(),
+            authz_url: config.authz_url.clone(),
+            cache_ttl: config.cache_ttl,
+            failure_result,
+            cache: DashMap::with_capacity(8),
+        })
+    }
+
+    pub async fn authenticate(
+        &self,
+        client_id: &str,
+        username: &str,
+        password: &str,
+        peerhost: &str,
+        proto_ver: u8,
+    ) -> HttpAuthResult {
+        if self.authn_url.is_empty() {
+            return HttpAuthResult::Ignore;
+        }
+        let request = HttpAuthnRequest {
+            clientid: client_id,
+            username,
+            password,
+            peerhost,
+            proto_ver,
+        };
+        self.call(&self.authn_url, &request).await
+    }
+
+    pub async fn authorize(
+        &self,
+        connection: &Connection,
+        topic: &str,
+        action: &MqttAclAction,
+    ) -> HttpAuthResult {
+        if self.authz_url.is_empty() {
+            return HttpAuthResult::Ignore;
+        }
+        let request = HttpAuthzRequest {
+            clientid: &connection.client_id,
+            username: &connection.login_user,
+            peerhost: &connection.source_ip_addr,
+            action: action_name(action),
+            topic,
+        };
+        self.call(&self.authz_url, &request).await
+    }
+
+    async fn call<T: Serialize>(&self, url: &str, request: &T) -> HttpAuthResult {
+        let body = match serde_json::to_vec(request) {
+            Ok(body) => body,
+            Err(e) => {
+                warn!("HTTP auth request to {} could not be encoded, {}", url, e);
+                return self.failure_result;
+            }
+        };
+
+        let key = if self.cache_ttl > 0 {
+            let mut hasher = Sha256::new();
+            hasher.update(url.as_bytes());
+            hasher.update(&body);
+            let key = format!("{:x}", hasher.finalize());
+            if let Some(result) = self.get_cache(&key) {
+                return result;
+            }
+            Some(key)
+        } else {
+            None
+        };
+
+        let (result, ttl) = match self.request(url, body).await {
+            Ok(result) => (result, self.cache_ttl),
+            Err(e) => {
+                warn!("HTTP auth request to {} failed, {}", url, e);
+                (
+                    self.failure_result,
+                    min(self.cache_ttl, HTTP_AUTH_FAILURE_CACHE_TTL),
+                )
+            }
+        };
+
+        if let Some(key) = key {
+            self.set_cache(key, result, ttl);
+        }
+        result
+    }
+
+    async fn request(&self, url: &str, body: Vec<u8>) -> Result<HttpAuthResult, String> {
+        let response = self
+            .client
+            .post(url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await
+            .map_err(|e| e.to_string())?;
+
+        match response.status() {
+            StatusCode::NO_CONTENT => Ok(HttpAuthResult::Allow),
+            StatusCode::OK => {
+                let data = response
+                    .json::<HttpAuthResponse>()
+                    .await
+                    .map_err(|e| format!("invalid response, {}", e))?;
+                parse_result(&data.result).ok_or_else(|| format!("unknown result {}", data.result))
+            }
+            status => Err(format!("unexpected status {}", status)),
+        }
+    }
+
+    fn get_cache(&self, key: &str) -> Option<HttpAuthResult> {
+        if let Some(data) = self.cache.get(key) {
+            if data.1 > now_second() {
+                return Some(data.0);
+            }
+        }
+        None
+    }
+
+    fn set_cache(&self, key: String, result: HttpAuthResult, ttl: u64) {
+        let now = now_second();
+        if self.cache.len() >= HTTP_AUTH_CACHE_GC_SIZE {
+            self.cache.retain(|_, (_, expire)| *expire > now);
+        }
+        self.cache.insert(key, (result, now + ttl));
+    }
+}
+
+fn parse_result(result: &str) -> Option<HttpAuthResult> {
+    match result.to_lowercase().as_str() {
+        "allow" => Some(HttpAuthResult::Allow),
+        "deny" => Some(HttpAuthResult::Deny),
+        "ignore" => Some(HttpAuthResult::Ignore),
+        _ => None,
+    }
+}
+
+fn action_name(action: &MqttAclAction) -> &'static str {
+    match action {
+        MqttAclAction::Publish => "publish",
+        MqttAclAction::Subscribe => "subscribe",
+        MqttAclAction::Retain => "retain",
+        _ => "all",
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::atomic::{AtomicU64, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use axum::extract::State;
+    use axum::http::StatusCode;
+    use axum::response::{IntoResponse, Response};
+    use axum::routing::post;
+    use axum::{Json, Router};
+    use common_base::config::common::AuthHttp;
+    use metadata_struct::acl::mqtt_acl::MqttAclAction;
+    use serde_json::{json, Value};
+
+    use super::{HttpAuthClient, HttpAuthResult};
+    use crate::handler::connection::{Connection, ConnectionConfig};
+
+    // Decides by username for authentication and by topic for authorization
+    async fn auth_handler(State(hits): State<Arc<AtomicU64>>, Json(req): Json<Value>) -> Response {
+        hits.fetch_add(1, Ordering::SeqCst);
+        let name = req
+            .get("topic")
+            .or_else(|| req.get("username"))
+            .and_then(|v| v.as_str())
+            .unwrap_or_default()
+            .to_string();
+        match name.as_str() {
+            "allow" => Json(json!({"result": "allow"})).into_response(),
+            "deny" => Json(json!({"result": "deny"})).into_response(),
+            "no_content" => StatusCode::NO_CONTENT.into_response(),
+            "error" => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+            "slow" => {
+                tokio::time::sleep(Duration::from_secs(2)).await;
+                Json(json!({"result": "allow"})).into_response()
+            }
+            "unknown" => Json(json!({"result": "maybe"})).into_response(),
+            _ => Json(json!({"result": "ignore"})).into_response(),
+        }
+    }
+
+    fn connection() -> Connection {
+        Connection::new(ConnectionConfig {
+            connect_id: 1,
+            client_id: "c1".to_string(),
+            receive_maximum: 3,
+            max_packet_size: 3,
+            topic_alias_max: 3,
+            request_problem_info: 1,
+            keep_alive: 2,
+            source_ip_addr: "127.0.0.1".to_string(),
+        })
+    }
+
+    async fn start_server() -> (String, Arc<AtomicU64>) {
+        let hits = Arc::new(AtomicU64::new(0));
+        let app = Router::new()
+            .route("/auth", post(auth_handler))
+            .with_state(hits.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            axum::serve(listener, app).await.unwrap();
+        });
+        (format!("http://{}/auth", addr), hits)
+    }
+
+    #[tokio::test]
+    pub async fn http_authenticate_test() {
+        let (url, hits) = start_server().await;
+        let config = AuthHttp {
+            enable: true,
+            authn_url: url,
+            timeout_ms: 500,
+            ..Default::default()
+        };
+        let client = HttpAuthClient::new(&config).unwrap();
+
+        // failed calls deny by default
+        for (username, expect) in [
+            ("allow", HttpAuthResult::Allow),
+            ("deny", HttpAuthResult::Deny),
+            ("no_content", HttpAuthResult::Allow),
+            ("other", HttpAuthResult::Ignore),
+            ("error", HttpAuthResult::Deny),
+            ("unknown", HttpAuthResult::Deny),
+            ("slow", HttpAuthResult::Deny),
+        ] {
+            let res = client
+                .authenticate("c1", username, "pwd", "127.0.0.1", 5)
+                .await;
+            assert_eq!(res, expect);
+        }
+        assert_eq!(hits.load(Ordering::SeqCst), 7);
+
+        // authorization is off without authz_url
+        let connection = connection();
+        let res = client
+            .authorize(&connection, "allow", &MqttAclAction::Publish)
+            .await;
+        assert_eq!(res, HttpAuthResult::Ignore);
+        assert_eq!(hits.load(Ordering::SeqCst), 7);
+
+        // failed calls can be left to the next authenticator instead
+        let config = AuthHttp {
+            failure_action: "ignore".to_string(),
+            ..config
+        };
+        let client = HttpAuthClient::new(&config).unwrap();
+        let res = client
+            .authenticate("c1", "error", "pwd", "127.0.0.1", 5)
+            .await;
+        assert_eq!(res, HttpAuthResult::Ignore);
+        let res = client
+            .authenticate("c1", "deny", "pwd", "127.0.0.1", 5)
+            .await;
+        assert_eq!(res, HttpAuthResult::Deny);
+    }
+
+    #[tokio::test]
+    pub async fn http_authorize_cache_test() {
+        let (url, hits) = start_server().await;
+        let config = AuthHttp {
+            enable: true,
+            authz_url: url,
+            cache_ttl: 60,
+            ..Default::default()
+        };
+        let client = HttpAuthClient::new(&config).unwrap();
+        let connection = connection();
+
+        for _ in 0..3 {
+            let res = client
+                .authorize(&connection, "allow", &MqttAclAction::Publish)
+                .await;
+            assert_eq!(res, HttpAuthResult::Allow);
+        }
+        assert_eq!(hits.load(Ordering::SeqCst), 1);
+
+        // different action is a different request
+        let res = client
+            .authorize(&connection, "allow", &MqttAclAction::Subscribe)
+            .await;
+        assert_eq!(res, HttpAuthResult::Allow);
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+
+        let res = client
+            .authorize(&connection, "deny", &MqttAclAction::Subscribe)
+            .await;
+        assert_eq!(res, HttpAuthResult::Deny);
+
+        // deny and ignore are cached as well
+        for _ in 0..2 {
+            let res = client
+                .authorize(&connection, "deny", &MqttAclAction::Subscribe)
+                .await;
+            assert_eq!(res, HttpAuthResult::Deny);
+            let res = client
+                .authorize(&connection, "other", &MqttAclAction::Publish)
+                .await;
+            assert_eq!(res, HttpAuthResult::Ignore);
+        }
+        assert_eq!(hits.load(Ordering::SeqCst), 4);
+    }
+}
@@ -11,3 +11,452 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+
+use std::fs;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use axum::async_trait;
+use common_base::config::common::AuthJwt;
+use common_base::error::mqtt_broker::MQTTBrokerError;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use log::debug;
+use protocol::mqtt::common::Login;
+use serde::Deserialize;
+use serde_json::{Map, Value};
+
+use super::Authentication;
+use crate::handler::cache::CacheManager;
+use crate::security::acl::ConnectionAcl;
+
+pub const JWT_FROM_USERNAME: &str = "username";
+const DEFAULT_USERNAME_CLAIM: &str = "sub";
+
+// Optional claim that limits the topics of the connection, e.g.
+// {"acl": {"publish": ["device/1/up"], "subscribe": ["device/1/down/#"]}}
+#[derive(Deserialize)]
+struct JwtAclClaim {
+    #[serde(default)]
+    publish: Vec<String>,
+    #[serde(default)]
+    subscribe: Vec<String>,
+}
+
+// Keys and validation rules are loaded once from the config, tokens are checked against them on every login
+pub struct JwtDecoder {
+    from_username: bool,
+    username_claim: String,
+    // (kid, key), the key id is only set for keys loaded from a JWKS file
+    keys: Vec<(Option<String>, DecodingKey)>,
+    validation: Validation,
+}
+
+impl JwtDecoder {
+    pub fn new(config: &AuthJwt) -> Result<Self, MQTTBrokerError> {
+        let algorithm = Algorithm::from_str(&config.algorithm).map_err(|e| {
+            MQTTBrokerError::JwtAuthenticationFailed(format!(
+                "unsupported algorithm {}, {}",
+                config.algorithm, e
+            ))
+        })?;
+        if !matches!(
+            algorithm,
+            Algorithm::HS256 | Algorithm::RS256 | Algorithm::ES256
+        ) {
+            return Err(MQTTBrokerError::JwtAuthenticationFailed(format!(
+                "unsupported algorithm {}",
+                config.algorithm
+            )));
+        }
+
+        if algorithm == Algorithm::HS256 && config.secret.is_empty() {
+            return Err(MQTTBrokerError::JwtAuthenticationFailed(
+                "HS256 requires a non-empty secret".to_string(),
+            ));
+        }
+
+        let mut validation = Validation::new(algorithm);
+        validation.validate_nbf = true;
+        if !config.issuer.is_empty() {
+            validation.set_issuer(&[&config.issuer]);
+        }
+        if config.audience.is_empty() {
+            validation.validate_aud = false;
+        } else {
+            validation.set_audience(&[&config.audience]);
+        }
+
+        let username_claim = if config.username_claim.is_empty() {
+            DEFAULT_USERNAME_CLAIM.to_string()
+        } else {
+            config.username_claim.clone()
+        };
+
+        Ok(JwtDecoder {
+            from_username: config.from == JWT_FROM_USERNAME,
+            username_claim,
+            keys: load_decoding_keys(config, algorithm)?,
+            validation,
+        })
+    }
+
+    pub fn token<'a>(&self, login: &'a Login) -> &'a str {
+        if self.from_username {
+            &login.username
+        } else {
+            &login.password
+        }
+    }
+
+    // Passwords that are not shaped like a JWT are left to the other authenticators
+    pub fn is_token(&self, token: &str) -> bool {
+        decode_header(token).is_ok()
+    }
+
+    // Returns the ACL claim of a valid token issued to the username
+    fn decode(
+        &self,
+        token: &str,
+        username: &str,
+    ) -> Result<Option<ConnectionAcl>, MQTTBrokerError> {
+        let header = decode_header(token)
+            .map_err(|e| MQTTBrokerError::JwtAuthenticationFailed(e.to_string()))?;
+        let key = self
+            .keys
+            .iter()
+            .find(|(kid, _)| kid.is_none() || *kid == header.kid)
+            .map(|(_, key)| key)
+            .ok_or_else(|| {
+                MQTTBrokerError::JwtAuthenticationFailed(format!(
+                    "no key matches kid {:?}",
+                    header.kid
+                ))
+            })?;
+
+        let mut claims = decode::<Map<String, Value>>(token, key, &self.validation)
+            .map_err(|e| MQTTBrokerError::JwtAuthenticationFailed(e.to_string()))?
+            .claims;
+
+        // A token carried in the username field has no separate username to bind to
+        if !self.from_username
+            && claims.get(&self.username_claim).and_then(Value::as_str) != Some(username)
+        {
+            return Err(MQTTBrokerError::JwtAuthenticationFailed(format!(
+                "claim {} does not match the username",
+                self.username_claim
+            )));
+        }
+
+        let Some(acl) = claims.remove("acl") else {
+            return Ok(None);
+        };
+        let acl: JwtAclClaim = serde_json::from_value(acl)
+            .map_err(|e| MQTTBrokerError::JwtAuthenticationFailed(e.to_string()))?;
+        Ok(Some(ConnectionAcl {
+            publish: acl.publish,
+            subscribe: acl.subscribe,
+        }))
+    }
+}
+
+fn load_decoding_keys(
+    config: &AuthJwt,
+    algorithm: Algorithm,
+) -> Result<Vec<(Option<String>, DecodingKey)>, MQTTBrokerError> {
+    let read_file = |path: &str| {
+        fs::read(path).map_err(|e| {
+            MQTTBrokerError::JwtAuthenticationFailed(format!("failed to read {}, {}", path, e))
+        })
+    };
+    let key_error = |e: jsonwebtoken::errors::Error| {
+        MQTTBrokerError::JwtAuthenticationFailed(format!("invalid key, {}", e))
+    };
+
+    if algorithm == Algorithm::HS256 {
+        return Ok(vec![(
+            None,
+            DecodingKey::from_secret(config.secret.as_bytes()),
+        )]);
+    }
+
+    if !config.jwks_file.is_empty() {
+        let jwks: JwkSet = serde_json::from_slice(&read_file(&config.jwks_file)?)
+            .map_err(|e| MQTTBrokerError::JwtAuthenticationFailed(e.to_string()))?;
+        let mut keys = Vec::new();
+        for jwk in jwks.keys.iter() {
+            keys.push((
+                jwk.common.key_id.clone(),
+                DecodingKey::from_jwk(jwk).map_err(key_error)?,
+            ));
+        }
+        return Ok(keys);
+    }
+
+    let pem = read_file(&config.public_key)?;
+    let key = if algorithm == Algorithm::RS256 {
+        DecodingKey::from_rsa_pem(&pem).map_err(key_error)?
+    } else {
+        DecodingKey::from_ec_pem(&pem).map_err(key_error)?
+    };
+    Ok(vec![(None, key)])
+}
+
+pub struct Jwt {
+    connect_id: u64,
+    token: String,
+    username: String,
+    decoder: Arc<JwtDecoder>,
+    cache_manager: Arc<CacheManager>,
+}
+
+impl Jwt {
+    pub fn new(
+        connect_id: u64,
+        token: String,
+        username: String,
+        decoder: Arc<JwtDecoder>,
+        cache_manager: Arc<CacheManager>,
+    ) -> Self {
+        Jwt {
+            connect_id,
+            token,
+            username,
+            decoder,
+            cache_manager,
+        }
+    }
+}
+
+#[async_trait]
+impl Authentication for Jwt {
+    async fn apply(&self) -> Result<bool, MQTTBrokerError> {
+        match self.decoder.decode(&self.token, &self.username) {
+            Ok(acl) => {
+                if let Some(acl) = acl {
+                    self.cache_manager
+                        .acl_metadata
+                        .add_connection_acl(self.connect_id, acl);
+                }
+                Ok(true)
+            }
+            Err(e) => {
+                debug!("JWT login of connection {} failed, {}", self.connect_id, e);
+                Ok(false)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use common_base::config::common::AuthJwt;
+    use common_base::tools::now_second;
+    use grpc_clients::poll::ClientPool;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+
+    use super::{Jwt, JwtDecoder};
+    use crate::handler::cache::CacheManager;
+    use crate::security::acl::ConnectionAcl;
+    use crate::security::login::Authentication;
+
+    fn token(claims: serde_json::Value) -> String {
+        encode(
+            &Header::default(),
+            &claims,
+            &EncodingKey::from_secret("secret".as_bytes()),
+        )
+        .unwrap()
+    }
+
+    #[tokio::test]
+    pub async fn jwt_hs256_test() {
+        let config = AuthJwt {
+            enable: true,
+            algorithm: "HS256".to_string(),
+            secret: "secret".to_string(),
+            issuer: "robustmq".to_string(),
+            audience: "device".to_string(),
+            ..Default::default()
+        };
+        let decoder = Arc::new(JwtDecoder::new(&config).unwrap());
+        let client_poll = Arc::new(ClientPool::new(1));
+        let cache_manager = Arc::new(CacheManager::new(client_poll, "test".to_string()));
+        let now = now_second();
+        let user = "device-1".to_string();
+
+        let valid =
+            token(json!({"sub": "device-1", "iss": "robustmq", "aud": "device", "exp": now + 600}));
+        assert!(decoder.is_token(&valid));
+        let jwt = Jwt::new(
+            1,
+            valid.clone(),
+            user.clone(),
+            decoder.clone(),
+            cache_manager.clone(),
+        );
+        assert!(jwt.apply().await.unwrap());
+        assert!(cache_manager.acl_metadata.connection_acl.is_empty());
+
+        // the token must be issued to the username of the connection
+        let jwt = Jwt::new(
+            1,
+            valid,
+            "device-2".to_string(),
+            decoder.clone(),
+            cache_manager.clone(),
+        );
+        assert!(!jwt.apply().await.unwrap());
+
+        let no_subject = token(json!({"iss": "robustmq", "aud": "device", "exp": now + 600}));
+        let jwt = Jwt::new(
+            1,
+            no_subject,
+            user.clone(),
+            decoder.clone(),
+            cache_manager.clone(),
+        );
+        assert!(!jwt.apply().await.unwrap());
+
+        let expired =
+            token(json!({"sub": "device-1", "iss": "robustmq", "aud": "device", "exp": now - 600}));
+        let jwt = Jwt::new(
+            1,
+            expired,
+            user.clone(),
+            decoder.clone(),
+            cache_manager.clone(),
+        );
+        assert!(!jwt.apply().await.unwrap());
+
+        let not_before = token(
+            json!({"sub": "device-1", "iss": "robustmq", "aud": "device", "exp": now + 600, "nbf": now + 300}),
+        );
+        let jwt = Jwt::new(
+            1,
+            not_before,
+            user.clone(),
+            decoder.clone(),
+            cache_manager.clone(),
+        );
+        assert!(!jwt.apply().await.unwrap());
+
+        let wrong_issuer =
+            token(json!({"sub": "device-1", "iss": "other", "aud": "device", "exp": now + 600}));
+        let jwt = Jwt::new(
+            1,
+            wrong_issuer,
+            user.clone(),
+            decoder.clone(),
+            cache_manager.clone(),
+        );
+        assert!(!jwt.apply().await.unwrap());
+
+        let wrong_audience =
+            token(json!({"sub": "device-1", "iss": "robustmq", "aud": "other", "exp": now + 600}));
+        let jwt = Jwt::new(
+            1,
+            wrong_audience,
+            user.clone(),
+            decoder.clone(),
+            cache_manager.clone(),
+        );
+        assert!(!jwt.apply().await.unwrap());
+
+        // plain passwords are not tokens, they are left to the next authenticator
+        assert!(!decoder.is_token("abc"));
+        assert!(!decoder.is_token(""));
+    }
+
+    #[tokio::test]
+    pub async fn jwt_username_claim_test() {
+        let config = AuthJwt {
+            enable: true,
+            algorithm: "HS256".to_string(),
+            secret: "secret".to_string(),
+            username_claim: "client".to_string(),
+            ..Default::default()
+        };
+        let decoder = Arc::new(JwtDecoder::new(&config).unwrap());
+        let client_poll = Arc::new(ClientPool::new(1));
+        let cache_manager = Arc::new(CacheManager::new(client_poll, "test".to_string()));
+
+        let claims = token(json!({"client": "device-1", "exp": now_second() + 600}));
+        let jwt = Jwt::new(
+            1,
+            claims.clone(),
+            "device-1".to_string(),
+            decoder.clone(),
+            cache_manager.clone(),
+        );
+        assert!(jwt.apply().await.unwrap());
+
+        let jwt = Jwt::new(1, claims, "sub".to_string(), decoder, cache_manager);
+        assert!(!jwt.apply().await.unwrap());
+    }
+
+    #[tokio::test]
+    pub async fn jwt_acl_claim_test() {
+        let config = AuthJwt {
+            enable: true,
+            algorithm: "HS256".to_string(),
+            secret: "secret".to_string(),
+            ..Default::default()
+        };
+        let decoder = Arc::new(JwtDecoder::new(&config).unwrap());
+        let client_poll = Arc::new(ClientPool::new(1));
+        let cache_manager = Arc::new(CacheManager::new(client_poll, "test".to_string()));
+
+        let acl = token(json!({
+            "sub": "device-1",
+            "exp": now_second() + 600,
+            "acl": {"publish": ["device/1/up"], "subscribe": ["device/1/down/#"]}
+        }));
+        let jwt = Jwt::new(
+            2,
+            acl,
+            "device-1".to_string(),
+            decoder,
+            cache_manager.clone(),
+        );
+        assert!(jwt.apply().await.unwrap());
+        assert_eq!(
+            cache_manager
+                .acl_metadata
+                .connection_acl
+                .get(&2)
+                .unwrap()
+                .clone(),
+            ConnectionAcl {
+                publish: vec!["device/1/up".to_string()],
+                subscribe: vec!["device/1/down/#".to_string()],
+            }
+        );
+    }
+
+    #[tokio::test]
+    pub async fn jwt_config_test() {
+        let config = AuthJwt {
+            algorithm: "HS512".to_string(),
+            ..Default::default()
+        };
+        assert!(JwtDecoder::new(&config).is_err());
+
+        // an empty secret would accept tokens signed with an empty key
+        let config = AuthJwt {
+            algorithm: "HS256".to_string(),
+            ..Default::default()
+        };
+        assert!(JwtDecoder::new(&config).is_err());
+
+        let config = AuthJwt {
+            algorithm: "RS256".to_string(),
+            public_key: "/not/exist.pem".to_string(),
+            ..Default::default()
+        };
+        assert!(JwtDecoder::new(&config).is_err());
+    }
+}
@@ -13,14 +13,20 @@
 // limitations under the License.
 
 use std::net::SocketAddr;
+use std::sync::Arc;
 
 use axum::async_trait;
+use bytes::Bytes;
 use common_base::error::mqtt_broker::MQTTBrokerError;
+use common_base::tools::now_second;
+
+use crate::handler::cache::CacheManager;
 
 pub mod http;
 pub mod jwt;
 pub mod plaintext;
 pub mod psk;
+pub mod scram;
 pub mod x509;
 
 #[async_trait]
@@ -28,16 +34,91 @@ pub trait Authentication {
     async fn apply(&self) -> Result<bool, MQTTBrokerError>;
 }
 
-pub fn is_ip_blacklist(_: &SocketAddr) -> bool {
-    false
+pub enum EnhancedAuthStep {
+    // The exchange needs another round trip, the data is sent to the client in an AUTH packet
+    Continue(Bytes),
+    // The exchange is complete, the data is sent to the client in CONNACK or AUTH
+    Success {
+        username: String,
+        data: Option<Bytes>,
+    },
+}
+
+// MQTT 5 enhanced authentication mechanism, one instance per authentication exchange
+#[async_trait]
+pub trait EnhancedAuthentication {
+    async fn apply(&mut self, data: Option<Bytes>) -> Result<EnhancedAuthStep, MQTTBrokerError>;
+}
+
+// Checked when the connection is accepted, before any TLS handshake or packet decoding
+pub fn is_ip_blacklist(cache_manager: &Arc<CacheManager>, addr: &SocketAddr) -> bool {
+    let now = now_second();
+    cache_manager
+        .acl_metadata
+        .get_blacklist_ip(&addr.ip())
+        .iter()
+        .any(|raw| raw.end_time > now)
 }
 
 #[cfg(test)]
 mod test {
+    use std::sync::Arc;
+
+    use common_base::tools::now_second;
+    use grpc_clients::poll::ClientPool;
+    use metadata_struct::acl::mqtt_blacklist::{MqttAclBlackList, MqttAclBlackListType};
+
     use super::is_ip_blacklist;
+    use crate::handler::cache::CacheManager;
 
     #[tokio::test]
     pub async fn is_ip_blacklist_test() {
-        assert!(!is_ip_blacklist(&"127.0.0.1:1000".parse().unwrap()))
+        let client_poll = Arc::new(ClientPool::new(1));
+        let cache_manager = Arc::new(CacheManager::new(client_poll, "test".to_string()));
+        assert!(!is_ip_blacklist(
+            &cache_manager,
+            &"127.0.0.1:1000".parse().unwrap()
+        ));
+
+        cache_manager.add_blacklist(MqttAclBlackList {
+            blacklist_type: MqttAclBlackListType::IPCIDR,
+            resource_name: "192.168.1.0/24".to_string(),
+            end_time: now_second() + 100,
+            desc: "".to_string(),
+        });
+        cache_manager.add_blacklist(MqttAclBlackList {
+            blacklist_type: MqttAclBlackListType::Ip,
+            resource_name: "2001:db8::1".to_string(),
+            end_time: now_second() + 100,
+            desc: "".to_string(),
+        });
+        cache_manager.add_blacklist(MqttAclBlackList {
+            blacklist_type: MqttAclBlackListType::Ip,
+            resource_name: "10.0.0.1".to_string(),
+            end_time: now_second() - 1,
+            desc: "".to_string(),
+        });
+
+        assert!(!is_ip_blacklist(
+            &cache_manager,
+            &"127.0.0.1:1000".parse().unwrap()
+        ));
+        assert!(is_ip_blacklist(
+            &cache_manager,
+            &"192.168.1.20:1000".parse().unwrap()
+        ));
+        assert!(is_ip_blacklist(
+            &cache_manager,
+            &"[2001:db8::1]:1000".parse().unwrap()
+        ));
+        assert!(!is_ip_blacklist(
+            &cache_manager,
+            &"[2001:db8::2]:1000".parse().unwrap()
+        ));
+        // expired entries no longer block
+        assert!(!is_ip_blacklist(
+            &cache_manager,
+            &"10.0.0.1:1000".parse().unwrap()
+        ));
     }
 }
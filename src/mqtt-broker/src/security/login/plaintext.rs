@@ -39,10 +39,15 @@ impl Plaintext {
 #[async_trait]
 impl Authentication for Plaintext {
     async fn apply(&self) -> Result<bool, MQTTBrokerError> {
-        if let Some(user) = self.cache_manager.user_info.get(&self.username) {
-            return Ok(user.password == self.password);
-        }
-        return Err(MQTTBrokerError::UserDoesNotExist);
+        // clone so the cache guard is not held while hashing
+        let user = match self.cache_manager.user_info.get(&self.username) {
+            Some(user) => user.clone(),
+            None => return Err(MQTTBrokerError::UserDoesNotExist),
+        };
+        let password = self.password.clone();
+        tokio::task::spawn_blocking(move || user.verify_password(&password))
+            .await
+            .map_err(|e| MQTTBrokerError::PasswordVerificationFailed(e.to_string()))
     }
 }
 
@@ -52,7 +57,7 @@ mod test {
 
     use common_base::config::broker_mqtt::BrokerMQTTConfig;
     use grpc_clients::poll::ClientPool;
-    use metadata_struct::mqtt::user::MqttUser;
+    use metadata_struct::mqtt::user::{HashAlgorithm, MqttUser, MqttUserHash};
     use protocol::mqtt::common::Login;
 
     use super::Plaintext;
@@ -76,6 +81,7 @@ mod test {
             username: username.clone(),
             password: password.clone(),
             is_superuser: true,
+            hash: Default::default(),
         };
         cache_manager.add_user(user);
 
@@ -94,5 +100,21 @@ mod test {
         let pt = Plaintext::new(login.username, login.password, cache_manager.clone());
         let res = pt.apply().await.unwrap();
         assert!(!res);
+
+        let mut user = MqttUser {
+            username: "hashed".to_string(),
+            password: password.clone(),
+            is_superuser: false,
+            hash: Default::default(),
+        };
+        user.hash_password(&MqttUserHash {
+            algorithm: HashAlgorithm::Sha256,
+            ..Default::default()
+        })
+        .unwrap();
+        cache_manager.add_user(user);
+        let pt = Plaintext::new("hashed".to_string(), password, cache_manager.clone());
+        let res = pt.apply().await.unwrap();
+        assert!(res);
     }
 }
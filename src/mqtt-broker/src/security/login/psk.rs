@@ -11,3 +11,201 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+
+use std::fs;
+use std::sync::Arc;
+
+use axum::async_trait;
+use common_base::error::common::CommonError;
+use common_base::error::mqtt_broker::MQTTBrokerError;
+use dashmap::DashMap;
+use grpc_clients::placement::kv::call::placement_get;
+use grpc_clients::poll::ClientPool;
+use protocol::mqtt::common::Login;
+use protocol::placement_center::placement_center_kv::GetRequest;
+
+use super::Authentication;
+
+// Placement center KV key that holds the PSK list of a cluster, in the same format as the PSK file
+pub fn psk_placement_key(cluster_name: &str) -> String {
+    format!("/mqtt/psk/{}", cluster_name)
+}
+
+// Pre-shared keys of the TLS-PSK listener, looked up by the identity the client sends in the handshake
+#[derive(Default)]
+pub struct PskStore {
+    // (identity, key)
+    file_keys: DashMap<String, Vec<u8>>,
+    placement_keys: DashMap<String, Vec<u8>>,
+}
+
+impl PskStore {
+    pub fn new() -> Self {
+        PskStore::default()
+    }
+
+    // Keys from the placement center take precedence over the ones from the file
+    pub fn get(&self, identity: &str) -> Option<Vec<u8>> {
+        if let Some(key) = self.placement_keys.get(identity) {
+            return Some(key.clone());
+        }
+        self.file_keys.get(identity).map(|key| key.clone())
+    }
+
+    pub fn load_file(&self, path: &str) -> Result<usize, MQTTBrokerError> {
+        let content = fs::read_to_string(path).map_err(|e| {
+            MQTTBrokerError::PskAuthenticationFailed(format!("failed to read {}, {}", path, e))
+        })?;
+        replace_keys(&self.file_keys, parse_psk_list(&content)?);
+        Ok(self.file_keys.len())
+    }
+
+    pub async fn load_placement(
+        &self,
+        client_poll: Arc<ClientPool>,
+        addrs: Vec<String>,
+        cluster_name: &str,
+    ) -> Result<usize, CommonError> {
+        let request = GetRequest {
+            key: psk_placement_key(cluster_name),
+        };
+        let reply = placement_get(client_poll, addrs, request).await?;
+        replace_keys(&self.placement_keys, parse_psk_list(&reply.value)?);
+        Ok(self.placement_keys.len())
+    }
+}
+
+fn replace_keys(keys: &DashMap<String, Vec<u8>>, list: Vec<(String, Vec<u8>)>) {
+    let identities: Vec<String> = list.iter().map(|(identity, _)| identity.clone()).collect();
+    for (identity, key) in list {
+        keys.insert(identity, key);
+    }
+    keys.retain(|identity, _| identities.contains(identity));
+}
+
+// One "identity:hex-key" pair per line, empty lines and lines starting with # are skipped
+pub fn parse_psk_list(content: &str) -> Result<Vec<(String, Vec<u8>)>, MQTTBrokerError> {
+    let mut list = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let invalid = || {
+            MQTTBrokerError::PskAuthenticationFailed(format!(
+                "line {} is not a valid identity:hex-key pair",
+                index + 1
+            ))
+        };
+        let (identity, key) = line.rsplit_once(':').ok_or_else(invalid)?;
+        if identity.is_empty() {
+            return Err(invalid());
+        }
+        list.push((identity.to_string(), decode_hex(key).ok_or_else(invalid)?));
+    }
+    Ok(list)
+}
+
+fn decode_hex(data: &str) -> Option<Vec<u8>> {
+    if data.is_empty() {
+        return None;
+    }
+    data.as_bytes()
+        .chunks(2)
+        .map(|pair| {
+            if pair.len() != 2 || !pair.iter().all(u8::is_ascii_hexdigit) {
+                return None;
+            }
+            u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok()
+        })
+        .collect()
+}
+
+// The PSK identity is the username of the connection, so ACLs keyed by user apply to it
+pub fn apply_psk_identity(identity: &str, login: &Option<Login>) -> Option<Login> {
+    let password = if let Some(info) = login {
+        info.password.clone()
+    } else {
+        "".to_string()
+    };
+    Some(Login {
+        username: identity.to_string(),
+        password,
+    })
+}
+
+// The identity was proven by the key exchange of the TLS-PSK handshake
+pub struct Psk {
+    identity: Option<String>,
+}
+
+impl Psk {
+    pub fn new(identity: Option<String>) -> Self {
+        Psk { identity }
+    }
+}
+
+#[async_trait]
+impl Authentication for Psk {
+    async fn apply(&self) -> Result<bool, MQTTBrokerError> {
+        Ok(self.identity.is_some())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use protocol::mqtt::common::Login;
+
+    use super::{apply_psk_identity, parse_psk_list, replace_keys, Psk, PskStore};
+    use crate::security::login::Authentication;
+
+    #[tokio::test]
+    pub async fn parse_psk_list_test() {
+        let content = "# devices\ndevice-1:0a1B2c\n\n  device:2:ff00  \n";
+        let list = parse_psk_list(content).unwrap();
+        assert_eq!(
+            list,
+            vec![
+                ("device-1".to_string(), vec![0x0a, 0x1b, 0x2c]),
+                ("device:2".to_string(), vec![0xff, 0x00]),
+            ]
+        );
+
+        assert!(parse_psk_list("device-1").is_err());
+        assert!(parse_psk_list(":0a").is_err());
+        assert!(parse_psk_list("device-1:0a1").is_err());
+        assert!(parse_psk_list("device-1:zz").is_err());
+        assert!(parse_psk_list("device-1:").is_err());
+        assert!(parse_psk_list("device-1:+1").is_err());
+    }
+
+    #[tokio::test]
+    pub async fn psk_store_test() {
+        let store = PskStore::new();
+        replace_keys(&store.file_keys, parse_psk_list("a:01\nb:02").unwrap());
+        replace_keys(&store.placement_keys, parse_psk_list("b:03").unwrap());
+        assert_eq!(store.get("a"), Some(vec![0x01]));
+        assert_eq!(store.get("b"), Some(vec![0x03]));
+        assert_eq!(store.get("c"), None);
+
+        // identities missing from a reload are removed
+        replace_keys(&store.placement_keys, parse_psk_list("c:04").unwrap());
+        assert_eq!(store.get("b"), Some(vec![0x02]));
+        assert_eq!(store.get("c"), Some(vec![0x04]));
+
+        let login = Some(Login {
+            username: "u1".to_string(),
+            password: "p1".to_string(),
+        });
+        let login = apply_psk_identity("device-1", &login).unwrap();
+        assert_eq!(login.username, "device-1");
+        assert_eq!(login.password, "p1");
+        assert_eq!(apply_psk_identity("device-1", &None).unwrap().password, "");
+
+        assert!(Psk::new(Some("device-1".to_string()))
+            .apply()
+            .await
+            .unwrap());
+        assert!(!Psk::new(None).apply().await.unwrap());
+    }
+}
@@ -0,0 +1,472 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use axum::async_trait;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use common_base::error::mqtt_broker::MQTTBrokerError;
+use hmac::{Hmac, Mac};
+use log::warn;
+use metadata_struct::mqtt::user::{HashAlgorithm, MqttUser};
+use pbkdf2::pbkdf2_hmac;
+use rand::distributions::Alphanumeric;
+use rand::{thread_rng, Rng, RngCore};
+use sha2::{Digest, Sha256};
+
+use super::{EnhancedAuthStep, EnhancedAuthentication};
+use crate::handler::cache::CacheManager;
+use crate::security::AuthStorageAdapter;
+
+pub const SCRAM_SHA_256: &str = "SCRAM-SHA-256";
+
+const SCRAM_ITERATIONS: u32 = 4096;
+const SCRAM_NONCE_LEN: usize = 24;
+const SCRAM_SALT_LEN: usize = 16;
+
+type HmacSha256 = Hmac<Sha256>;
+
+enum ScramState {
+    ClientFirst,
+    ClientFinal {
+        username: String,
+        gs2_header: String,
+        nonce: String,
+        // client-first-message-bare + "," + server-first-message
+        auth_message_prefix: String,
+        stored_key: Vec<u8>,
+        server_key: Vec<u8>,
+    },
+    Finished,
+}
+
+// SCRAM-SHA-256 (RFC 5802, RFC 7677) without channel binding, the credentials
+// are derived from the passwords in the user store.
+pub struct ScramSha256 {
+    cache_manager: Arc<CacheManager>,
+    driver: Arc<dyn AuthStorageAdapter + Send + 'static + Sync>,
+    server_nonce: String,
+    salt: Vec<u8>,
+    state: ScramState,
+}
+
+impl ScramSha256 {
+    pub fn new(
+        cache_manager: Arc<CacheManager>,
+        driver: Arc<dyn AuthStorageAdapter + Send + 'static + Sync>,
+    ) -> Self {
+        let server_nonce = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(SCRAM_NONCE_LEN)
+            .map(char::from)
+            .collect();
+        let mut salt = vec![0u8; SCRAM_SALT_LEN];
+        thread_rng().fill_bytes(&mut salt);
+        ScramSha256 {
+            cache_manager,
+            driver,
+            server_nonce,
+            salt,
+            state: ScramState::ClientFirst,
+        }
+    }
+
+    async fn get_user(&self, username: &str) -> Result<MqttUser, MQTTBrokerError> {
+        if let Some(user) = self.cache_manager.user_info.get(username) {
+            return Ok(user.clone());
+        }
+        match self.driver.get_user(username.to_owned()).await {
+            Ok(Some(user)) => {
+                self.cache_manager.add_user(user.clone());
+                Ok(user)
+            }
+            Ok(None) => Err(MQTTBrokerError::UserDoesNotExist),
+            Err(e) => Err(MQTTBrokerError::EnhancedAuthenticationFailed(e.to_string())),
+        }
+    }
+
+    async fn client_first(&mut self, message: &str) -> Result<EnhancedAuthStep, MQTTBrokerError> {
+        let (gs2_header, client_first_bare) = split_gs2_header(message)?;
+        let username = decode_sasl_name(attribute(client_first_bare, 'n')?);
+        let client_nonce = attribute(client_first_bare, 'r')?;
+
+        let user = match self.get_user(&username).await {
+            Ok(user) => Some(user),
+            Err(MQTTBrokerError::UserDoesNotExist) => None,
+            Err(e) => return Err(e),
+        };
+        // the SCRAM credentials are derived from a plain password, the key of a pbkdf2 user
+        // already is the SaltedPassword under its own salt and iterations
+        let credentials = user.and_then(|user| match user.hash.algorithm {
+            HashAlgorithm::Plain => Some((
+                salted_password(user.password.as_bytes(), &self.salt),
+                self.salt.clone(),
+                SCRAM_ITERATIONS,
+            )),
+            HashAlgorithm::Pbkdf2 => user.scram_credentials(),
+            _ => {
+                warn!(
+                    "SCRAM-SHA-256 requires a plain or pbkdf2 password, user {} has neither",
+                    username
+                );
+                None
+            }
+        });
+        // Unknown users go through the same exchange with credentials nobody knows and fail
+        // at the proof, so the server first message does not tell which usernames exist
+        let (salted_password, salt, iterations) = credentials.unwrap_or_else(|| {
+            let mut salted_password = vec![0u8; 32];
+            thread_rng().fill_bytes(&mut salted_password);
+            (salted_password, self.salt.clone(), SCRAM_ITERATIONS)
+        });
+        let client_key = hmac_sha256(&salted_password, b"Client Key");
+        let stored_key = Sha256::digest(client_key).to_vec();
+        let server_key = hmac_sha256(&salted_password, b"Server Key");
+
+        let nonce = format!("{}{}", client_nonce, self.server_nonce);
+        let server_first = format!("r={},s={},i={}", nonce, STANDARD.encode(&salt), iterations);
+
+        self.state = ScramState::ClientFinal {
+            username,
+            gs2_header: gs2_header.to_owned(),
+            nonce,
+            auth_message_prefix: format!("{},{}", client_first_bare, server_first),
+            stored_key,
+            server_key,
+        };
+        Ok(EnhancedAuthStep::Continue(Bytes::from(server_first)))
+    }
+}
+
+#[async_trait]
+impl EnhancedAuthentication for ScramSha256 {
+    async fn apply(&mut self, data: Option<Bytes>) -> Result<EnhancedAuthStep, MQTTBrokerError> {
+        let data = if let Some(data) = data {
+            data
+        } else {
+            return Err(MQTTBrokerError::EnhancedAuthenticationFailed(
+                "authentication data cannot be empty".to_string(),
+            ));
+        };
+        let message = String::from_utf8(data.to_vec())?;
+
+        match std::mem::replace(&mut self.state, ScramState::Finished) {
+            ScramState::ClientFirst => self.client_first(&message).await,
+            ScramState::ClientFinal {
+                username,
+                gs2_header,
+                nonce,
+                auth_message_prefix,
+                stored_key,
+                server_key,
+            } => {
+                let (client_final_without_proof, proof) = message
+                    .rsplit_once(",p=")
+                    .ok_or_else(|| scram_error("client proof is missing"))?;
+
+                let channel_binding = STANDARD
+                    .decode(attribute(client_final_without_proof, 'c')?)
+                    .map_err(|e| scram_error(&e.to_string()))?;
+                if channel_binding != gs2_header.as_bytes() {
+                    return Err(scram_error("channel binding does not match"));
+                }
+
+                if attribute(client_final_without_proof, 'r')? != nonce {
+                    return Err(scram_error("nonce does not match"));
+                }
+
+                let auth_message =
+                    format!("{},{}", auth_message_prefix, client_final_without_proof);
+                let client_signature = hmac_sha256(&stored_key, auth_message.as_bytes());
+                let proof = STANDARD
+                    .decode(proof)
+                    .map_err(|e| scram_error(&e.to_string()))?;
+                if proof.len() != client_signature.len() {
+                    return Err(scram_error("client proof is invalid"));
+                }
+
+                let client_key: Vec<u8> = proof
+                    .iter()
+                    .zip(client_signature.iter())
+                    .map(|(a, b)| a ^ b)
+                    .collect();
+                if Sha256::digest(client_key).as_slice() != stored_key.as_slice() {
+                    return Err(scram_error("client proof is invalid"));
+                }
+
+                let server_signature = hmac_sha256(&server_key, auth_message.as_bytes());
+                let server_final = format!("v={}", STANDARD.encode(server_signature));
+                Ok(EnhancedAuthStep::Success {
+                    username,
+                    data: Some(Bytes::from(server_final)),
+                })
+            }
+            ScramState::Finished => Err(scram_error("authentication exchange is finished")),
+        }
+    }
+}
+
+fn scram_error(reason: &str) -> MQTTBrokerError {
+    MQTTBrokerError::EnhancedAuthenticationFailed(reason.to_string())
+}
+
+// Returns (gs2-header, client-first-message-bare), channel binding is not supported
+fn split_gs2_header(message: &str) -> Result<(&str, &str), MQTTBrokerError> {
+    let cbind_end = message
+        .find(',')
+        .ok_or_else(|| scram_error("gs2 header is malformed"))?;
+    let authzid_end = message[cbind_end + 1..]
+        .find(',')
+        .map(|index| cbind_end + 1 + index)
+        .ok_or_else(|| scram_error("gs2 header is malformed"))?;
+
+    let cbind_flag = &message[..cbind_end];
+    if cbind_flag != "n" && cbind_flag != "y" {
+        return Err(scram_error("channel binding is not supported"));
+    }
+    Ok((&message[..authzid_end + 1], &message[authzid_end + 1..]))
+}
+
+fn attribute(message: &str, name: char) -> Result<&str, MQTTBrokerError> {
+    for item in message.split(',') {
+        if let Some(value) = item.strip_prefix(name) {
+            if let Some(value) = value.strip_prefix('=') {
+                return Ok(value);
+            }
+        }
+    }
+    Err(scram_error(&format!("attribute {} is missing", name)))
+}
+
+fn decode_sasl_name(name: &str) -> String {
+    name.replace("=2C", ",").replace("=3D", "=")
+}
+
+fn salted_password(password: &[u8], salt: &[u8]) -> Vec<u8> {
+    let mut salted_password = vec![0u8; 32];
+    pbkdf2_hmac::<Sha256>(password, salt, SCRAM_ITERATIONS, &mut salted_password);
+    salted_password
+}
+
+fn hmac_sha256(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).unwrap();
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use axum::async_trait;
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+    use bytes::Bytes;
+    use common_base::config::broker_mqtt::BrokerMQTTConfig;
+    use common_base::error::common::CommonError;
+    use dashmap::DashMap;
+    use grpc_clients::poll::ClientPool;
+    use metadata_struct::acl::mqtt_acl::MqttAcl;
+    use metadata_struct::acl::mqtt_blacklist::MqttAclBlackList;
+    use metadata_struct::mqtt::user::{HashAlgorithm, MqttUser, MqttUserHash};
+    use pbkdf2::pbkdf2_hmac;
+    use sha2::{Digest, Sha256};
+
+    use super::{attribute, hmac_sha256, ScramSha256};
+    use crate::handler::cache::CacheManager;
+    use crate::security::login::{EnhancedAuthStep, EnhancedAuthentication};
+    use crate::security::AuthStorageAdapter;
+
+    // Users only come from the cache in these tests
+    struct EmptyUserStorage;
+
+    #[async_trait]
+    impl AuthStorageAdapter for EmptyUserStorage {
+        async fn read_all_user(&self) -> Result<DashMap<String, MqttUser>, CommonError> {
+            Ok(DashMap::new())
+        }
+
+        async fn read_all_acl(&self) -> Result<Vec<MqttAcl>, CommonError> {
+            Ok(Vec::new())
+        }
+
+        async fn read_all_blacklist(&self) -> Result<Vec<MqttAclBlackList>, CommonError> {
+            Ok(Vec::new())
+        }
+
+        async fn get_user(&self, _: String) -> Result<Option<MqttUser>, CommonError> {
+            Ok(None)
+        }
+    }
+
+    fn build_scram(password: &str) -> ScramSha256 {
+        let mut scram = build_scram_with_user(MqttUser {
+            username: "user".to_string(),
+            password: password.to_string(),
+            is_superuser: false,
+            hash: Default::default(),
+        });
+
+        // RFC 7677 test vector
+        scram.server_nonce = "%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0".to_string();
+        scram.salt = STANDARD.decode("W22ZaJ0SNY7soEsUEjb6gQ==").unwrap();
+        scram
+    }
+
+    fn build_scram_with_user(user: MqttUser) -> ScramSha256 {
+        let conf = BrokerMQTTConfig {
+            cluster_name: "test".to_string(),
+            ..Default::default()
+        };
+        let client_poll: Arc<ClientPool> = Arc::new(ClientPool::new(100));
+        let cache_manager: Arc<CacheManager> =
+            Arc::new(CacheManager::new(client_poll, conf.cluster_name.clone()));
+        cache_manager.add_user(user);
+        ScramSha256::new(cache_manager, Arc::new(EmptyUserStorage))
+    }
+
+    #[tokio::test]
+    pub async fn scram_sha256_test() {
+        let mut scram = build_scram("pencil");
+
+        let client_first = Bytes::from("n,,n=user,r=rOprNGfwEbeRWgbNEkqO");
+        match scram.apply(Some(client_first)).await.unwrap() {
+            EnhancedAuthStep::Continue(data) => {
+                assert_eq!(
+                    data,
+                    Bytes::from("r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096")
+                );
+            }
+            EnhancedAuthStep::Success { .. } => panic!("scram should continue"),
+        }
+
+        let client_final = Bytes::from("c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,p=dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ=");
+        match scram.apply(Some(client_final)).await.unwrap() {
+            EnhancedAuthStep::Success { username, data } => {
+                assert_eq!(username, "user".to_string());
+                assert_eq!(
+                    data,
+                    Some(Bytes::from(
+                        "v=6rriTRBi23WpRR/wtup+mMhUZUn/dB5nLTJRsjl95G4="
+                    ))
+                );
+            }
+            EnhancedAuthStep::Continue(_) => panic!("scram should finish"),
+        }
+    }
+
+    #[tokio::test]
+    pub async fn scram_sha256_bad_password_test() {
+        let mut scram = build_scram("pencil2");
+
+        let client_first = Bytes::from("n,,n=user,r=rOprNGfwEbeRWgbNEkqO");
+        assert!(scram.apply(Some(client_first)).await.is_ok());
+
+        let client_final = Bytes::from("c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,p=dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ=");
+        assert!(scram.apply(Some(client_final)).await.is_err());
+    }
+
+    #[tokio::test]
+    pub async fn scram_sha256_unknown_user_test() {
+        let mut scram = build_scram("pencil");
+
+        // the server first message looks the same as for an existing user
+        let client_first = Bytes::from("n,,n=nobody,r=rOprNGfwEbeRWgbNEkqO");
+        match scram.apply(Some(client_first)).await.unwrap() {
+            EnhancedAuthStep::Continue(data) => {
+                assert_eq!(
+                    data,
+                    Bytes::from("r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,s=W22ZaJ0SNY7soEsUEjb6gQ==,i=4096")
+                );
+            }
+            EnhancedAuthStep::Success { .. } => panic!("scram should continue"),
+        }
+
+        let client_final = Bytes::from("c=biws,r=rOprNGfwEbeRWgbNEkqO%hvYDpWUa2RaTCAfuxFIlj)hNlF$k0,p=dHzbZapWIk4jUhN+Ute9ytag9zjfMHgsqmmiz7AndVQ=");
+        assert!(scram.apply(Some(client_final)).await.is_err());
+    }
+
+    #[tokio::test]
+    pub async fn scram_sha256_stored_user_test() {
+        // the user as the placement center create_user stores it
+        let mut user = MqttUser {
+            username: "user".to_string(),
+            password: "pencil".to_string(),
+            is_superuser: false,
+            hash: Default::default(),
+        };
+        user.hash_password(&MqttUserHash {
+            algorithm: HashAlgorithm::Pbkdf2,
+            ..Default::default()
+        })
+        .unwrap();
+        let user = serde_json::from_slice::<MqttUser>(&user.encode()).unwrap();
+        let mut scram = build_scram_with_user(user);
+
+        let client_first_bare = "n=user,r=rOprNGfwEbeRWgbNEkqO";
+        let server_first = match scram
+            .apply(Some(Bytes::from(format!("n,,{}", client_first_bare))))
+            .await
+        {
+            Ok(EnhancedAuthStep::Continue(data)) => String::from_utf8(data.to_vec()).unwrap(),
+            _ => panic!("scram should continue"),
+        };
+
+        // the client side of the exchange
+        let salt = STANDARD
+            .decode(attribute(&server_first, 's').unwrap())
+            .unwrap();
+        let iterations: u32 = attribute(&server_first, 'i').unwrap().parse().unwrap();
+        let mut salted_password = [0u8; 32];
+        pbkdf2_hmac::<Sha256>(b"pencil", &salt, iterations, &mut salted_password);
+        let client_key = hmac_sha256(&salted_password, b"Client Key");
+        let stored_key = Sha256::digest(&client_key);
+        let server_key = hmac_sha256(&salted_password, b"Server Key");
+
+        let client_final_without_proof =
+            format!("c=biws,r={}", attribute(&server_first, 'r').unwrap());
+        let auth_message = format!(
+            "{},{},{}",
+            client_first_bare, server_first, client_final_without_proof
+        );
+        let client_signature = hmac_sha256(&stored_key, auth_message.as_bytes());
+        let proof: Vec<u8> = client_key
+            .iter()
+            .zip(client_signature.iter())
+            .map(|(a, b)| a ^ b)
+            .collect();
+        let client_final = format!(
+            "{},p={}",
+            client_final_without_proof,
+            STANDARD.encode(proof)
+        );
+
+        match scram.apply(Some(Bytes::from(client_final))).await.unwrap() {
+            EnhancedAuthStep::Success { username, data } => {
+                assert_eq!(username, "user".to_string());
+                let server_signature = hmac_sha256(&server_key, auth_message.as_bytes());
+                assert_eq!(
+                    data,
+                    Some(Bytes::from(format!(
+                        "v={}",
+                        STANDARD.encode(server_signature)
+                    )))
+                );
+            }
+            EnhancedAuthStep::Continue(_) => panic!("scram should finish"),
+        }
+    }
+}
@@ -11,3 +11,206 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+
+use axum::async_trait;
+use common_base::config::broker_mqtt::Network;
+use common_base::error::mqtt_broker::MQTTBrokerError;
+use protocol::mqtt::common::{Connect, Login};
+use tokio_rustls::rustls::ServerConnection;
+use x509_parser::certificate::X509Certificate;
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::FromDer;
+
+use super::Authentication;
+
+pub const TLS_CLIENT_AUTH_OPTIONAL: &str = "optional";
+pub const TLS_CLIENT_AUTH_REQUIRED: &str = "required";
+pub const CERT_FIELD_CN: &str = "cn";
+pub const CERT_FIELD_SAN: &str = "san";
+
+// Names of a client certificate that passed the mutual TLS verification
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct X509Identity {
+    pub common_name: Option<String>,
+    // DNS, email and URI subject alternative names, in certificate order
+    pub subject_alt_names: Vec<String>,
+}
+
+impl X509Identity {
+    pub fn from_der(der: &[u8]) -> Result<Self, MQTTBrokerError> {
+        let (_, cert) = X509Certificate::from_der(der)
+            .map_err(|e| MQTTBrokerError::X509AuthenticationFailed(e.to_string()))?;
+
+        let common_name = cert
+            .subject()
+            .iter_common_name()
+            .next()
+            .and_then(|cn| cn.as_str().ok())
+            .map(|cn| cn.to_string());
+
+        let mut subject_alt_names = Vec::new();
+        if let Ok(Some(san)) = cert.subject_alternative_name() {
+            for name in san.value.general_names.iter() {
+                match name {
+                    GeneralName::DNSName(name)
+                    | GeneralName::RFC822Name(name)
+                    | GeneralName::URI(name) => subject_alt_names.push(name.to_string()),
+                    _ => {}
+                }
+            }
+        }
+
+        Ok(X509Identity {
+            common_name,
+            subject_alt_names,
+        })
+    }
+
+    pub fn field(&self, field: &str) -> Option<String> {
+        match field {
+            CERT_FIELD_CN => self.common_name.clone(),
+            CERT_FIELD_SAN => self.subject_alt_names.first().cloned(),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, name: &str) -> bool {
+        self.common_name.as_deref() == Some(name)
+            || self.subject_alt_names.iter().any(|san| san == name)
+    }
+}
+
+// Identity of the leaf certificate the client presented during the TLS handshake
+pub fn peer_identity(connection: &ServerConnection) -> Option<X509Identity> {
+    let cert = connection.peer_certificates()?.first()?;
+    X509Identity::from_der(cert).ok()
+}
+
+// Replaces the client ID and username of the CONNECT packet with the certificate fields
+// selected by tls_cert_as_client_id and tls_cert_as_username
+pub fn apply_cert_identity(
+    identity: &X509Identity,
+    network: &Network,
+    connect: &mut Connect,
+    login: &Option<Login>,
+) -> Option<Login> {
+    if let Some(client_id) = identity.field(&network.tls_cert_as_client_id) {
+        connect.client_id = client_id;
+    }
+
+    if let Some(username) = identity.field(&network.tls_cert_as_username) {
+        let password = if let Some(info) = login {
+            info.password.clone()
+        } else {
+            "".to_string()
+        };
+        return Some(Login { username, password });
+    }
+    login.clone()
+}
+
+// The certificate chain was already verified against the CA bundle in the TLS handshake.
+// The certificate only vouches for the username it names (or for a client that sends none),
+// any other username is left to the next authenticator
+pub struct X509 {
+    identity: Option<X509Identity>,
+    username: Option<String>,
+}
+
+impl X509 {
+    pub fn new(identity: Option<X509Identity>, login: &Option<Login>) -> Self {
+        X509 {
+            identity,
+            username: login.as_ref().map(|info| info.username.clone()),
+        }
+    }
+}
+
+#[async_trait]
+impl Authentication for X509 {
+    async fn apply(&self) -> Result<bool, MQTTBrokerError> {
+        let Some(identity) = &self.identity else {
+            return Ok(false);
+        };
+        match self.username.as_deref() {
+            None | Some("") => Ok(true),
+            Some(username) => Ok(identity.contains(username)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use common_base::config::broker_mqtt::Network;
+    use protocol::mqtt::common::{Connect, Login};
+
+    use super::{apply_cert_identity, X509Identity, X509};
+    use crate::security::login::Authentication;
+
+    #[tokio::test]
+    pub async fn cert_identity_test() {
+        let identity = X509Identity {
+            common_name: Some("device-1".to_string()),
+            subject_alt_names: vec!["device-1.example.com".to_string()],
+        };
+        assert_eq!(identity.field("cn"), Some("device-1".to_string()));
+        assert_eq!(
+            identity.field("san"),
+            Some("device-1.example.com".to_string())
+        );
+        assert_eq!(identity.field(""), None);
+
+        let network = Network {
+            tls_cert_as_username: "cn".to_string(),
+            tls_cert_as_client_id: "san".to_string(),
+            ..Default::default()
+        };
+        let mut connect = Connect {
+            keep_alive: 10,
+            client_id: "c1".to_string(),
+            clean_session: true,
+        };
+        let login = Some(Login {
+            username: "u1".to_string(),
+            password: "p1".to_string(),
+        });
+        let login = apply_cert_identity(&identity, &network, &mut connect, &login).unwrap();
+        assert_eq!(connect.client_id, "device-1.example.com");
+        assert_eq!(login.username, "device-1");
+        assert_eq!(login.password, "p1");
+
+        // nothing is mapped by default
+        let mut connect = Connect {
+            keep_alive: 10,
+            client_id: "c1".to_string(),
+            clean_session: true,
+        };
+        let login = apply_cert_identity(&identity, &Network::default(), &mut connect, &None);
+        assert_eq!(connect.client_id, "c1");
+        assert!(login.is_none());
+
+        assert!(X509::new(Some(identity.clone()), &None)
+            .apply()
+            .await
+            .unwrap());
+        assert!(!X509::new(None, &None).apply().await.unwrap());
+
+        // only the names of the certificate are vouched for
+        let login_as = |username: &str| {
+            Some(Login {
+                username: username.to_string(),
+                password: "p1".to_string(),
+            })
+        };
+        let x509 = X509::new(Some(identity.clone()), &login_as("device-1"));
+        assert!(x509.apply().await.unwrap());
+        let x509 = X509::new(Some(identity.clone()), &login_as("device-1.example.com"));
+        assert!(x509.apply().await.unwrap());
+        let x509 = X509::new(Some(identity), &login_as("admin"));
+        assert!(!x509.apply().await.unwrap());
+        assert!(!X509::new(None, &login_as("device-1"))
+            .apply()
+            .await
+            .unwrap());
+    }
+}
@@ -14,34 +14,46 @@
 
 use std::net::SocketAddr;
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 
-use acl::is_allow_acl;
+use acl::{is_allow_acl, is_allow_connection_acl};
 use axum::async_trait;
+use bytes::Bytes;
+use chain::{AuthChains, AuthResult, Authenticator};
 use common_base::config::broker_mqtt::broker_mqtt_conf;
 use common_base::config::common::Auth;
 use common_base::error::common::CommonError;
 use common_base::error::mqtt_broker::MQTTBrokerError;
 use dashmap::DashMap;
 use grpc_clients::poll::ClientPool;
+use login::http::{HttpAuthClient, HttpAuthResult};
+use login::jwt::{Jwt, JwtDecoder};
 use login::plaintext::Plaintext;
-use login::Authentication;
+use login::psk::Psk;
+use login::scram::{ScramSha256, SCRAM_SHA_256};
+use login::x509::X509;
+use login::{Authentication, EnhancedAuthStep, EnhancedAuthentication};
 use metadata_struct::acl::mqtt_acl::{MqttAcl, MqttAclAction};
 use metadata_struct::acl::mqtt_blacklist::MqttAclBlackList;
-use metadata_struct::mqtt::user::MqttUser;
+use metadata_struct::mqtt::user::{MqttUser, MqttUserHash};
 use mysql::MySQLAuthStorageAdapter;
 use placement::PlacementAuthStorageAdapter;
-use protocol::mqtt::common::{ConnectProperties, Login, QoS, Subscribe};
+use postgres::PostgresAuthStorageAdapter;
+use protocol::mqtt::common::{ConnectProperties, Login, MQTTProtocol, QoS, Subscribe};
+use redis::RedisAuthStorageAdapter;
 use storage_adapter::StorageType;
 
 use crate::handler::cache::CacheManager;
 use crate::handler::connection::Connection;
-use crate::subscribe::sub_common::get_sub_topic_id_list;
+use crate::server::connection::{NetworkConnection, NetworkConnectionType};
+use crate::subscribe::sub_common::{decode_share_info, is_share_sub};
 
 pub mod acl;
+pub mod chain;
 pub mod login;
 pub mod mysql;
 pub mod placement;
+pub mod postgres;
 pub mod redis;
 
 #[async_trait]
@@ -55,56 +67,71 @@ pub trait AuthStorageAdapter {
     async fn get_user(&self, username: String) -> Result<Option<MqttUser>, CommonError>;
 }
 
+// Everything built from the auth config, replaced as a whole by update_driver
+struct AuthState {
+    driver: Arc<dyn AuthStorageAdapter + Send + 'static + Sync>,
+    // Set when JWT login is enabled
+    jwt: Option<Arc<JwtDecoder>>,
+    // Set when the HTTP authentication/authorization callout is enabled
+    http: Option<Arc<HttpAuthClient>>,
+    chains: AuthChains,
+}
+
 pub struct AuthDriver {
     cache_manager: Arc<CacheManager>,
     client_poll: Arc<ClientPool>,
-    driver: Arc<dyn AuthStorageAdapter + Send + 'static + Sync>,
+    state: RwLock<Arc<AuthState>>,
 }
 
 impl AuthDriver {
     pub fn new(cache_manager: Arc<CacheManager>, client_poll: Arc<ClientPool>) -> AuthDriver {
         let conf = broker_mqtt_conf();
-        let driver = match build_driver(client_poll.clone(), conf.auth.clone()) {
-            Ok(driver) => driver,
+        let state = match build_state(client_poll.clone(), conf.auth.clone()) {
+            Ok(state) => state,
             Err(e) => {
                 panic!("{}", e.to_string());
             }
         };
         AuthDriver {
             cache_manager,
-            driver,
             client_poll,
+            state: RwLock::new(Arc::new(state)),
         }
     }
 
-    pub fn update_driver(&mut self, auth: Auth) -> Result<(), CommonError> {
-        let driver = match build_driver(self.client_poll.clone(), auth) {
-            Ok(driver) => driver,
-            Err(e) => {
-                return Err(e);
-            }
-        };
-        self.driver = driver;
+    // Connections that are already logged in are not affected, new ones use the new config
+    pub fn update_driver(&self, auth: Auth) -> Result<(), CommonError> {
+        let state = build_state(self.client_poll.clone(), auth)?;
+        *self.state.write().unwrap() = Arc::new(state);
         Ok(())
     }
 
+    fn state(&self) -> Arc<AuthState> {
+        self.state.read().unwrap().clone()
+    }
+
     pub async fn read_all_user(&self) -> Result<DashMap<String, MqttUser>, CommonError> {
-        self.driver.read_all_user().await
+        self.state().driver.read_all_user().await
     }
 
     pub async fn read_all_acl(&self) -> Result<Vec<MqttAcl>, CommonError> {
-        self.driver.read_all_acl().await
+        self.state().driver.read_all_acl().await
     }
 
     pub async fn read_all_blacklist(&self) -> Result<Vec<MqttAclBlackList>, CommonError> {
-        self.driver.read_all_blacklist().await
+        self.state().driver.read_all_blacklist().await
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub async fn check_login_auth(
         &self,
+        connect_id: u64,
+        client_id: &str,
+        protocol: &MQTTProtocol,
+        network_connection: Option<&NetworkConnection>,
         login: &Option<Login>,
         _: &Option<ConnectProperties>,
-        _: &SocketAddr,
+        addr: &SocketAddr,
     ) -> Result<bool, CommonError> {
         let cluster = self.cache_manager.get_cluster_info();
 
@@ -112,15 +139,163 @@ impl AuthDriver {
             return Ok(true);
         }
 
-        if let Some(info) = login {
-            return self
-                .plaintext_check_login(&info.username, &info.password)
-                .await;
+        let state = self.state();
+        let listener = network_connection
+            .map(|connection| connection.connection_type.clone())
+            .unwrap_or(NetworkConnectionType::Tcp);
+
+        // the first allow or deny decides, ignore falls through to the next authenticator
+        for authenticator in state.chains.get(&listener) {
+            let result = match authenticator {
+                Authenticator::Anonymous => AuthResult::Allow,
+                Authenticator::X509 => {
+                    let identity = network_connection.and_then(|conn| conn.peer_cert.clone());
+                    ignore_unless_allowed(X509::new(identity, login).apply().await?)
+                }
+                Authenticator::Psk => {
+                    let identity = network_connection.and_then(|conn| conn.psk_identity.clone());
+                    ignore_unless_allowed(Psk::new(identity).apply().await?)
+                }
+                Authenticator::Http => {
+                    self.http_login(&state, client_id, protocol, login, addr)
+                        .await
+                }
+                Authenticator::Jwt => self.jwt_login(&state, connect_id, login).await?,
+                Authenticator::Password => self.password_login(&state, login).await?,
+            };
+            match result {
+                AuthResult::Allow => return Ok(true),
+                AuthResult::Deny => return Ok(false),
+                AuthResult::Ignore => {}
+            }
         }
 
         Ok(false)
     }
 
+    async fn http_login(
+        &self,
+        state: &AuthState,
+        client_id: &str,
+        protocol: &MQTTProtocol,
+        login: &Option<Login>,
+        addr: &SocketAddr,
+    ) -> AuthResult {
+        let Some(http) = &state.http else {
+            return AuthResult::Ignore;
+        };
+        let (username, password) = if let Some(info) = login {
+            (info.username.as_str(), info.password.as_str())
+        } else {
+            ("", "")
+        };
+        match http
+            .authenticate(
+                client_id,
+                username,
+                password,
+                &addr.ip().to_string(),
+                protocol.clone().into(),
+            )
+            .await
+        {
+            HttpAuthResult::Allow => AuthResult::Allow,
+            HttpAuthResult::Deny => AuthResult::Deny,
+            HttpAuthResult::Ignore => AuthResult::Ignore,
+        }
+    }
+
+    // Clients without a token are left to the next authenticator, a bad token is denied
+    async fn jwt_login(
+        &self,
+        state: &AuthState,
+        connect_id: u64,
+        login: &Option<Login>,
+    ) -> Result<AuthResult, CommonError> {
+        let (Some(info), Some(decoder)) = (login, &state.jwt) else {
+            return Ok(AuthResult::Ignore);
+        };
+        let token = decoder.token(info);
+        if !decoder.is_token(token) {
+            return Ok(AuthResult::Ignore);
+        }
+        let jwt = Jwt::new(
+            connect_id,
+            token.to_owned(),
+            info.username.clone(),
+            decoder.clone(),
+            self.cache_manager.clone(),
+        );
+        Ok(jwt.apply().await?.into())
+    }
+
+    // SCRAM checks the password of the auth storage, so it is only offered on the listeners
+    // whose chain contains the password authenticator
+    pub fn is_support_auth_method(
+        &self,
+        method: &str,
+        network_connection: Option<&NetworkConnection>,
+    ) -> bool {
+        let listener = network_connection
+            .map(|connection| connection.connection_type.clone())
+            .unwrap_or(NetworkConnectionType::Tcp);
+        method == SCRAM_SHA_256
+            && self
+                .state()
+                .chains
+                .get(&listener)
+                .contains(&Authenticator::Password)
+    }
+
+    // Runs one step of an enhanced authentication exchange. `restart` is set for the
+    // first AUTH data of a CONNECT or a re-authentication, which discards any previous state.
+    pub async fn enhanced_auth(
+        &self,
+        connect_id: u64,
+        method: &str,
+        data: Option<Bytes>,
+        restart: bool,
+    ) -> Result<EnhancedAuthStep, CommonError> {
+        let mut mechanism = if restart {
+            self.cache_manager.enhanced_auth_info.remove(&connect_id);
+            self.build_enhanced_auth(method)?
+        } else if let Some((_, mechanism)) =
+            self.cache_manager.enhanced_auth_info.remove(&connect_id)
+        {
+            mechanism
+        } else {
+            return Err(MQTTBrokerError::EnhancedAuthenticationFailed(
+                "no authentication exchange in progress".to_string(),
+            )
+            .into());
+        };
+
+        let step = mechanism.apply(data).await?;
+        if let EnhancedAuthStep::Continue(_) = step {
+            self.cache_manager
+                .enhanced_auth_info
+                .insert(connect_id, mechanism);
+        }
+        Ok(step)
+    }
+
+    pub fn remove_enhanced_auth(&self, connect_id: u64) {
+        self.cache_manager.enhanced_auth_info.remove(&connect_id);
+    }
+
+    fn build_enhanced_auth(
+        &self,
+        method: &str,
+    ) -> Result<Box<dyn EnhancedAuthentication + Send + Sync>, CommonError> {
+        if method == SCRAM_SHA_256 {
+            return Ok(Box::new(ScramSha256::new(
+                self.cache_manager.clone(),
+                self.state().driver.clone(),
+            )));
+        }
+        Err(MQTTBrokerError::AuthenticationMethodNotSupported(method.to_owned()).into())
+    }
+
     pub async fn allow_publish(
         &self,
         connection: &Connection,
@@ -128,94 +303,117 @@ impl AuthDriver {
         retain: bool,
         qos: QoS,
     ) -> bool {
+        if !is_allow_connection_acl(
+            &self.cache_manager,
+            connection,
+            topic_name,
+            MqttAclAction::Publish,
+        ) {
+            return false;
+        }
+        let state = self.state();
         is_allow_acl(
             &self.cache_manager,
+            state.http.as_deref(),
             connection,
             topic_name,
             MqttAclAction::Publish,
             retain,
             qos,
         )
+        .await
     }
 
     pub async fn allow_subscribe(&self, connection: &Connection, subscribe: &Subscribe) -> bool {
+        let state = self.state();
         for filter in subscribe.filters.clone() {
-            let topic_list = get_sub_topic_id_list(self.cache_manager.clone(), filter.path).await;
-            for topic in topic_list {
-                if !is_allow_acl(
-                    &self.cache_manager,
-                    connection,
-                    &topic,
-                    MqttAclAction::Publish,
-                    false,
-                    filter.qos,
-                ) {
-                    return false;
-                }
+            let path = if is_share_sub(filter.path.clone()) {
+                let (_, path) = decode_share_info(filter.path.clone());
+                path.strip_prefix('/').unwrap_or(&path).to_owned()
+            } else {
+                filter.path.clone()
+            };
+            if !is_allow_connection_acl(
+                &self.cache_manager,
+                connection,
+                &path,
+                MqttAclAction::Subscribe,
+            ) {
+                return false;
+            }
+
+            // rules and the external service are matched against the topic filter itself,
+            // so they apply before any topic exists
+            if !is_allow_acl(
+                &self.cache_manager,
+                state.http.as_deref(),
+                connection,
+                &path,
+                MqttAclAction::Subscribe,
+                false,
+                filter.qos,
+            )
+            .await
+            {
+                return false;
             }
         }
         true
     }
 
-    async fn plaintext_check_login(
+    // Unknown users are left to the next authenticator, a wrong password is denied
+    async fn password_login(
         &self,
-        username: &str,
-        password: &str,
-    ) -> Result<bool, CommonError> {
+        state: &AuthState,
+        login: &Option<Login>,
+    ) -> Result<AuthResult, CommonError> {
+        let Some(info) = login else {
+            return Ok(AuthResult::Ignore);
+        };
         let plaintext = Plaintext::new(
-            username.to_owned(),
-            password.to_owned(),
+            info.username.clone(),
+            info.password.clone(),
             self.cache_manager.clone(),
         );
         match plaintext.apply().await {
-            Ok(flag) => {
-                if flag {
-                    return Ok(true);
-                }
-            }
-            Err(e) => {
-                // If the user does not exist, try to get the user information from the storage layer
-                if e.to_string() == MQTTBrokerError::UserDoesNotExist.to_string() {
-                    return self.try_get_check_user_by_driver(username).await;
-                }
-                return Err(e.into());
+            Ok(flag) => Ok(flag.into()),
+            // If the user does not exist, try to get the user information from the storage layer
+            Err(MQTTBrokerError::UserDoesNotExist) => {
+                self.try_get_check_user_by_driver(state, &info.username, &info.password)
+                    .await
             }
+            Err(e) => Err(e.into()),
         }
-
-        Ok(false)
     }
 
-    async fn try_get_check_user_by_driver(&self, username: &str) -> Result<bool, CommonError> {
-        match self.driver.get_user(username.to_owned()).await {
-            Ok(Some(user)) => {
-                self.cache_manager.add_user(user.clone());
-                let plaintext = Plaintext::new(
-                    user.username.clone(),
-                    user.password.clone(),
-                    self.cache_manager.clone(),
-                );
-                match plaintext.apply().await {
-                    Ok(flag) => {
-                        if flag {
-                            return Ok(true);
-                        }
-                    }
-                    Err(e) => {
-                        return Err(e.into());
-                    }
-                }
-            }
-            Ok(None) => {
-                return Ok(false);
-            }
-            Err(e) => {
-                return Err(e);
-            }
-        }
-        Ok(false)
+    async fn try_get_check_user_by_driver(
+        &self,
+        state: &AuthState,
+        username: &str,
+        password: &str,
+    ) -> Result<AuthResult, CommonError> {
+        let Some(user) = state.driver.get_user(username.to_owned()).await? else {
+            return Ok(AuthResult::Ignore);
+        };
+        self.cache_manager.add_user(user);
+        let plaintext = Plaintext::new(
+            username.to_owned(),
+            password.to_owned(),
+            self.cache_manager.clone(),
+        );
+        Ok(plaintext.apply().await?.into())
     }
 }
 
+fn build_state(client_poll: Arc<ClientPool>, auth: Auth) -> Result<AuthState, CommonError> {
+    Ok(AuthState {
+        jwt: build_jwt_decoder(&auth)?,
+        http: build_http_auth(&auth)?,
+        chains: AuthChains::new(&auth)?,
+        driver: build_driver(client_poll, auth)?,
+    })
+}
+
 pub fn build_driver(
     client_poll: Arc<ClientPool>,
     auth: Auth,
@@ -228,13 +426,55 @@ pub fn build_driver(
     }
 
     if matches!(storage_type, StorageType::Mysql) {
-        let driver = MySQLAuthStorageAdapter::new(auth.mysql_addr.clone());
+        let driver = MySQLAuthStorageAdapter::new(
+            auth.mysql_addr.clone(),
+            MqttUserHash::from_conf(&auth.password_hash)?,
+        );
+        return Ok(Arc::new(driver));
+    }
+
+    if matches!(storage_type, StorageType::Postgres) {
+        let driver = PostgresAuthStorageAdapter::new(
+            auth.postgres_addr.clone(),
+            MqttUserHash::from_conf(&auth.password_hash)?,
+        );
+        return Ok(Arc::new(driver));
+    }
+
+    if matches!(storage_type, StorageType::Redis) {
+        let driver = RedisAuthStorageAdapter::new(
+            &auth.redis,
+            MqttUserHash::from_conf(&auth.password_hash)?,
+        );
         return Ok(Arc::new(driver));
     }
 
     Err(CommonError::UnavailableStorageType)
 }
 
+fn build_jwt_decoder(auth: &Auth) -> Result<Option<Arc<JwtDecoder>>, CommonError> {
+    if !auth.jwt.enable {
+        return Ok(None);
+    }
+    Ok(Some(Arc::new(JwtDecoder::new(&auth.jwt)?)))
+}
+
+fn build_http_auth(auth: &Auth) -> Result<Option<Arc<HttpAuthClient>>, CommonError> {
+    if !auth.http.enable {
+        return Ok(None);
+    }
+    Ok(Some(Arc::new(HttpAuthClient::new(&auth.http)?)))
+}
+
+// TLS identities only vouch for a client that has one, otherwise the next authenticator decides
+fn ignore_unless_allowed(allow: bool) -> AuthResult {
+    if allow {
+        AuthResult::Allow
+    } else {
+        AuthResult::Ignore
+    }
+}
+
 pub fn authentication_acl() -> bool {
     false
 }
@@ -11,3 +11,536 @@
 // WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
 // See the License for the specific language governing permissions and
 // limitations under the License.
+
+use axum::async_trait;
+use common_base::config::common::AuthRedis;
+use common_base::error::common::CommonError;
+use common_base::tools::now_second;
+use dashmap::DashMap;
+use metadata_struct::acl::mqtt_acl::{
+    MqttAcl, MqttAclAction, MqttAclPermission, MqttAclResourceType,
+};
+use metadata_struct::acl::mqtt_blacklist::{MqttAclBlackList, MqttAclBlackListType};
+use metadata_struct::mqtt::user::{MqttUser, MqttUserHash};
+use redis::aio::MultiplexedConnection;
+use redis::{AsyncCommands, Client};
+
+use super::AuthStorageAdapter;
+use crate::handler::constant::WILDCARD_RESOURCE;
+
+const DEFAULT_USER_KEY: &str = "mqtt_user:${username}";
+const DEFAULT_ACL_KEY: &str = "mqtt_acl:${resource_type}:${resource_name}";
+const DEFAULT_BLACKLIST_KEY: &str = "mqtt_blacklist:${blacklist_type}:${resource_name}";
+
+pub struct RedisAuthStorageAdapter {
+    client: Client,
+    user_key: KeyTemplate,
+    acl_key: KeyTemplate,
+    blacklist_key: KeyTemplate,
+    // salt field of the user hash is filled in per user
+    password_hash: MqttUserHash,
+}
+
+impl RedisAuthStorageAdapter {
+    pub fn new(conf: &AuthRedis, password_hash: MqttUserHash) -> Self {
+        let client = match Client::open(conf.addr.as_str()) {
+            Ok(data) => data,
+            Err(e) => {
+                panic!("{}", e.to_string());
+            }
+        };
+        RedisAuthStorageAdapter {
+            client,
+            user_key: KeyTemplate::new(&conf.user_key, DEFAULT_USER_KEY),
+            acl_key: KeyTemplate::new(&conf.acl_key, DEFAULT_ACL_KEY),
+            blacklist_key: KeyTemplate::new(&conf.blacklist_key, DEFAULT_BLACKLIST_KEY),
+            password_hash,
+        }
+    }
+
+    async fn conn(&self) -> Result<MultiplexedConnection, CommonError> {
+        self.client
+            .get_multiplexed_async_connection()
+            .await
+            .map_err(|e| CommonError::CommmonError(e.to_string()))
+    }
+
+    async fn scan_keys(
+        &self,
+        conn: &mut MultiplexedConnection,
+        template: &KeyTemplate,
+    ) -> Result<Vec<String>, CommonError> {
+        let mut iter = conn
+            .scan_match::<_, String>(template.pattern())
+            .await
+            .map_err(|e| CommonError::CommmonError(e.to_string()))?;
+        let mut keys = Vec::new();
+        while let Some(key) = iter.next_item().await {
+            keys.push(key);
+        }
+        Ok(keys)
+    }
+}
+
+#[async_trait]
+impl AuthStorageAdapter for RedisAuthStorageAdapter {
+    async fn read_all_user(&self) -> Result<DashMap<String, MqttUser>, CommonError> {
+        let mut conn = self.conn().await?;
+        let results = DashMap::with_capacity(2);
+        for key in self.scan_keys(&mut conn, &self.user_key).await? {
+            let Some(values) = self.user_key.parse(&key) else {
+                continue;
+            };
+            let [username] = values.as_slice() else {
+                continue;
+            };
+            let fields: Vec<(String, String)> = conn
+                .hgetall(&key)
+                .await
+                .map_err(|e| CommonError::CommmonError(e.to_string()))?;
+            if let Some(user) = user_from_hash(username, fields, &self.password_hash) {
+                results.insert(user.username.clone(), user);
+            }
+        }
+        Ok(results)
+    }
+
+    async fn get_user(&self, username: String) -> Result<Option<MqttUser>, CommonError> {
+        let mut conn = self.conn().await?;
+        let key = self.user_key.format(&[&username]);
+        let fields: Vec<(String, String)> = conn
+            .hgetall(&key)
+            .await
+            .map_err(|e| CommonError::CommmonError(e.to_string()))?;
+        Ok(user_from_hash(&username, fields, &self.password_hash))
+    }
+
+    async fn read_all_acl(&self) -> Result<Vec<MqttAcl>, CommonError> {
+        let mut conn = self.conn().await?;
+        let mut results = Vec::new();
+        for key in self.scan_keys(&mut conn, &self.acl_key).await? {
+            let Some(values) = self.acl_key.parse(&key) else {
+                continue;
+            };
+            let [resource_type, resource_name] = values.as_slice() else {
+                continue;
+            };
+            let Some(resource_type) = parse_acl_resource_type(resource_type) else {
+                continue;
+            };
+            let fields: Vec<(String, String)> = conn
+                .hgetall(&key)
+                .await
+                .map_err(|e| CommonError::CommmonError(e.to_string()))?;
+            for (topic, rule) in fields {
+                if let Some(acl) = parse_acl_rule(&resource_type, resource_name, topic, &rule) {
+                    results.push(acl);
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    async fn read_all_blacklist(&self) -> Result<Vec<MqttAclBlackList>, CommonError> {
+        let mut conn = self.conn().await?;
+        let mut results = Vec::new();
+        for key in self.scan_keys(&mut conn, &self.blacklist_key).await? {
+            let Some(values) = self.blacklist_key.parse(&key) else {
+                continue;
+            };
+            let [blacklist_type, resource_name] = values.as_slice() else {
+                continue;
+            };
+            let Some(blacklist_type) = parse_blacklist_type(blacklist_type) else {
+                continue;
+            };
+            let (desc, ttl): (Option<String>, i64) = redis::pipe()
+                .get(&key)
+                .ttl(&key)
+                .query_async(&mut conn)
+                .await
+                .map_err(|e| CommonError::CommmonError(e.to_string()))?;
+            // -2: expired between SCAN and GET, -1: never expires
+            let end_time = match ttl {
+                -2 => continue,
+                -1 => u64::MAX,
+                ttl => now_second() + ttl as u64,
+            };
+            results.push(MqttAclBlackList {
+                blacklist_type,
+                resource_name: resource_name.clone(),
+                end_time,
+                desc: desc.unwrap_or_default(),
+            });
+        }
+        Ok(results)
+    }
+}
+
+// A key with ${name} placeholders, e.g. "mqtt_acl:${resource_type}:${resource_name}"
+struct KeyTemplate {
+    // literal text around the placeholders, always one more than the placeholders
+    literals: Vec<String>,
+}
+
+impl KeyTemplate {
+    fn new(template: &str, default: &str) -> Self {
+        let template = if template.is_empty() {
+            default
+        } else {
+            template
+        };
+        let mut literals = Vec::new();
+        let mut rest = template;
+        while let Some(start) = rest.find("${") {
+            let Some(end) = rest[start..].find('}') else {
+                break;
+            };
+            literals.push(rest[..start].to_string());
+            rest = &rest[start + end + 1..];
+        }
+        literals.push(rest.to_string());
+        KeyTemplate { literals }
+    }
+
+    fn format(&self, values: &[&str]) -> String {
+        let mut key = self.literals[0].clone();
+        for (i, literal) in self.literals[1..].iter().enumerate() {
+            key.push_str(values.get(i).copied().unwrap_or_default());
+            key.push_str(literal);
+        }
+        key
+    }
+
+    // SCAN pattern matching every key of the template
+    fn pattern(&self) -> String {
+        self.literals
+            .iter()
+            .map(|literal| escape_glob(literal))
+            .collect::<Vec<String>>()
+            .join("*")
+    }
+
+    // Extract the placeholder values, all but the last stop at the first following literal
+    fn parse(&self, key: &str) -> Option<Vec<String>> {
+        let mut rest = key.strip_prefix(self.literals[0].as_str())?;
+        let last = self.literals.len() - 1;
+        let mut values = Vec::with_capacity(last);
+        for (i, literal) in self.literals[1..].iter().enumerate() {
+            if i + 1 == last {
+                values.push(rest.strip_suffix(literal.as_str())?.to_string());
+                return Some(values);
+            }
+            // adjacent placeholders cannot be told apart
+            if literal.is_empty() {
+                return None;
+            }
+            let end = rest.find(literal.as_str())?;
+            values.push(rest[..end].to_string());
+            rest = &rest[end + literal.len()..];
+        }
+        Some(values)
+    }
+}
+
+fn escape_glob(literal: &str) -> String {
+    let mut result = String::with_capacity(literal.len());
+    for c in literal.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            result.push('\\');
+        }
+        result.push(c);
+    }
+    result
+}
+
+fn user_from_hash(
+    username: &str,
+    fields: Vec<(String, String)>,
+    password_hash: &MqttUserHash,
+) -> Option<MqttUser> {
+    if fields.is_empty() {
+        return None;
+    }
+    let mut user = MqttUser {
+        username: username.to_string(),
+        password: "".to_string(),
+        is_superuser: false,
+        hash: password_hash.clone(),
+    };
+    for (field, value) in fields {
+        match field.as_str() {
+            "password" => user.password = value,
+            "salt" => user.hash.salt = value,
+            "is_superuser" => user.is_superuser = value == "1" || value == "true",
+            _ => {}
+        }
+    }
+    Some(user)
+}
+
+fn parse_acl_resource_type(value: &str) -> Option<MqttAclResourceType> {
+    match value.to_lowercase().as_str() {
+        "user" | "username" => Some(MqttAclResourceType::User),
+        "clientid" | "client_id" => Some(MqttAclResourceType::ClientId),
+        _ => None,
+    }
+}
+
+fn parse_acl_action(value: &str) -> Option<MqttAclAction> {
+    match value.to_lowercase().as_str() {
+        "all" => Some(MqttAclAction::All),
+        "subscribe" => Some(MqttAclAction::Subscribe),
+        "publish" => Some(MqttAclAction::Publish),
+        "pubsub" => Some(MqttAclAction::PubSub),
+        "retain" => Some(MqttAclAction::Retain),
+        "qos" => Some(MqttAclAction::Qos),
+        _ => None,
+    }
+}
+
+// Hash value of one topic: "action[,allow|deny[,ip[,priority]]]",
+// permission defaults to allow, ip to "*" and priority to 0
+fn parse_acl_rule(
+    resource_type: &MqttAclResourceType,
+    resource_name: &str,
+    topic: String,
+    rule: &str,
+) -> Option<MqttAcl> {
+    let mut parts = rule.splitn(4, ',').map(|part| part.trim());
+    let action = parse_acl_action(parts.next()?)?;
+    let permission = match parts.next().map(|part| part.to_lowercase()).as_deref() {
+        None | Some("") | Some("allow") => MqttAclPermission::Allow,
+        Some("deny") => MqttAclPermission::Deny,
+        Some(_) => return None,
+    };
+    let ip = match parts.next() {
+        None | Some("") => WILDCARD_RESOURCE.to_string(),
+        Some(ip) => ip.to_string(),
+    };
+    let priority = match parts.next() {
+        None | Some("") => 0,
+        Some(priority) => priority.parse::<u32>().ok()?,
+    };
+    Some(MqttAcl {
+        resource_type: resource_type.clone(),
+        resource_name: resource_name.to_string(),
+        topic,
+        ip,
+        action,
+        permission,
+        priority,
+    })
+}
+
+fn parse_blacklist_type(value: &str) -> Option<MqttAclBlackListType> {
+    match value.to_lowercase().as_str() {
+        "clientid" | "client_id" => Some(MqttAclBlackListType::ClientId),
+        "user" | "username" => Some(MqttAclBlackListType::User),
+        "ip" => Some(MqttAclBlackListType::Ip),
+        "clientidmatch" | "client_id_match" => Some(MqttAclBlackListType::ClientIdMatch),
+        "usermatch" | "user_match" => Some(MqttAclBlackListType::UserMatch),
+        "ipcidr" | "ip_cidr" => Some(MqttAclBlackListType::IPCIDR),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use common_base::config::common::AuthRedis;
+    use metadata_struct::acl::mqtt_acl::{MqttAclAction, MqttAclPermission, MqttAclResourceType};
+    use metadata_struct::acl::mqtt_blacklist::MqttAclBlackListType;
+    use metadata_struct::mqtt::user::{HashAlgorithm, MqttUserHash};
+    use redis::AsyncCommands;
+
+    use super::{parse_acl_rule, KeyTemplate, RedisAuthStorageAdapter, DEFAULT_ACL_KEY};
+    use crate::security::AuthStorageAdapter;
+
+    #[tokio::test]
+    async fn key_template_test() {
+        let template = KeyTemplate::new("", DEFAULT_ACL_KEY);
+        assert_eq!(template.pattern(), "mqtt_acl:*:*");
+        let key = template.format(&["user", "robustmq"]);
+        assert_eq!(key, "mqtt_acl:user:robustmq");
+        assert_eq!(
+            template.parse(&key),
+            Some(vec!["user".to_string(), "robustmq".to_string()])
+        );
+        assert_eq!(
+            template.parse("mqtt_acl:ip:fd00::1"),
+            Some(vec!["ip".to_string(), "fd00::1".to_string()])
+        );
+        assert_eq!(template.parse("other:user:robustmq"), None);
+
+        let template = KeyTemplate::new("device[${username}]:auth", DEFAULT_ACL_KEY);
+        assert_eq!(template.pattern(), "device\\[*\\]:auth");
+        assert_eq!(
+            template.parse("device[d1]:auth"),
+            Some(vec!["d1".to_string()])
+        );
+        assert_eq!(template.parse("device[d1]:other"), None);
+    }
+
+    #[tokio::test]
+    async fn parse_acl_rule_test() {
+        let acl = parse_acl_rule(
+            &MqttAclResourceType::User,
+            "robustmq",
+            "t/#".to_string(),
+            "publish",
+        )
+        .unwrap();
+        assert_eq!(acl.action, MqttAclAction::Publish);
+        assert_eq!(acl.permission, MqttAclPermission::Allow);
+        assert_eq!(acl.ip, "*");
+
+        let acl = parse_acl_rule(
+            &MqttAclResourceType::ClientId,
+            "c1",
+            "t/1".to_string(),
+            "subscribe, deny, 10.0.0.1, 5",
+        )
+        .unwrap();
+        assert_eq!(acl.action, MqttAclAction::Subscribe);
+        assert_eq!(acl.permission, MqttAclPermission::Deny);
+        assert_eq!(acl.ip, "10.0.0.1");
+        assert_eq!(acl.priority, 5);
+
+        assert!(parse_acl_rule(&MqttAclResourceType::User, "u", "t".to_string(), "read").is_none());
+        assert!(parse_acl_rule(
+            &MqttAclResourceType::User,
+            "u",
+            "t".to_string(),
+            "all,maybe"
+        )
+        .is_none());
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn read_all_user_test() {
+        let conf = conf();
+        init_data(&conf).await;
+        let auth_redis = RedisAuthStorageAdapter::new(&conf, MqttUserHash::default());
+        let result = auth_redis.read_all_user().await;
+        assert!(result.is_ok());
+        let res = result.unwrap();
+        let user = res.get("robustmq").unwrap();
+        assert_eq!(user.password, "robustmq@2024");
+        assert!(user.is_superuser);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn get_user_test() {
+        let conf = conf();
+        init_data(&conf).await;
+        let auth_redis = RedisAuthStorageAdapter::new(&conf, MqttUserHash::default());
+        let user = auth_redis
+            .get_user("robustmq".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.password, "robustmq@2024");
+        assert!(auth_redis
+            .get_user("not_exist".to_string())
+            .await
+            .unwrap()
+            .is_none());
+
+        let password_hash = MqttUserHash {
+            algorithm: HashAlgorithm::Sha256,
+            ..Default::default()
+        };
+        let auth_redis = RedisAuthStorageAdapter::new(&conf, password_hash);
+        let user = auth_redis
+            .get_user("salted".to_string())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(user.hash.salt, "salt");
+        assert!(user.verify_password("pwd123"));
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn read_all_acl_test() {
+        let conf = conf();
+        init_data(&conf).await;
+        let auth_redis = RedisAuthStorageAdapter::new(&conf, MqttUserHash::default());
+        let acls = auth_redis.read_all_acl().await.unwrap();
+        let acl = acls
+            .iter()
+            .find(|acl| acl.resource_name == "robustmq" && acl.topic == "test/deny")
+            .unwrap();
+        assert_eq!(acl.resource_type, MqttAclResourceType::User);
+        assert_eq!(acl.action, MqttAclAction::Publish);
+        assert_eq!(acl.permission, MqttAclPermission::Deny);
+    }
+
+    #[tokio::test]
+    #[ignore]
+    async fn read_all_blacklist_test() {
+        let conf = conf();
+        init_data(&conf).await;
+        let auth_redis = RedisAuthStorageAdapter::new(&conf, MqttUserHash::default());
+        let blacklists = auth_redis.read_all_blacklist().await.unwrap();
+        let blacklist = blacklists
+            .iter()
+            .find(|raw| raw.resource_name == "10.0.0.0/8")
+            .unwrap();
+        assert_eq!(blacklist.blacklist_type, MqttAclBlackListType::IPCIDR);
+        assert_eq!(blacklist.desc, "internal");
+        let blacklist = blacklists
+            .iter()
+            .find(|raw| raw.resource_name == "bad_client")
+            .unwrap();
+        assert_eq!(blacklist.blacklist_type, MqttAclBlackListType::ClientId);
+        assert!(blacklist.end_time < u64::MAX);
+    }
+
+    fn conf() -> AuthRedis {
+        AuthRedis {
+            addr: "redis://127.0.0.1:6379/0".to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn init_data(conf: &AuthRedis) {
+        let client = redis::Client::open(conf.addr.as_str()).unwrap();
+        let mut conn = client.get_multiplexed_async_connection().await.unwrap();
+        let _: () = conn
+            .hset_multiple(
+                "mqtt_user:robustmq",
+                &[("password", "robustmq@2024"), ("is_superuser", "1")],
+            )
+            .await
+            .unwrap();
+        // sha256("salt" + "pwd123")
+        let _: () = conn
+            .hset_multiple(
+                "mqtt_user:salted",
+                &[
+                    (
+                        "password",
+                        "1c76576362c925b9522842884f30dcb824ea61ce9be4bd104331352911d5c4a2",
+                    ),
+                    ("salt", "salt"),
+                ],
+            )
+            .await
+            .unwrap();
+        let _: () = conn
+            .hset("mqtt_acl:user:robustmq", "test/deny", "publish,deny")
+            .await
+            .unwrap();
+        let _: () = conn
+            .set("mqtt_blacklist:ipcidr:10.0.0.0/8", "internal")
+            .await
+            .unwrap();
+        let _: () = conn
+            .set_ex("mqtt_blacklist:clientid:bad_client", "flapping", 3600)
+            .await
+            .unwrap();
+    }
+}
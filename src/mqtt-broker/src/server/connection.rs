@@ -16,17 +16,22 @@ use std::fmt;
 use std::net::SocketAddr;
 use std::sync::atomic::AtomicU64;
 
-use log::error;
+use log::debug;
 use protocol::mqtt::common::MQTTProtocol;
 use tokio::sync::mpsc;
+
+use crate::security::login::x509::X509Identity;
+
 static CONNECTION_ID_BUILD: AtomicU64 = AtomicU64::new(1);
 
-#[derive(Clone, PartialEq, PartialOrd)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash, PartialOrd)]
 pub enum NetworkConnectionType {
     Tcp,
     Tls,
+    TlsPsk,
     WebSocket,
     WebSockets,
+    Quic,
 }
 
 impl fmt::Display for NetworkConnectionType {
@@ -37,8 +42,10 @@ impl fmt::Display for NetworkConnectionType {
             match self {
                 NetworkConnectionType::Tcp => "tcp",
                 NetworkConnectionType::Tls => "tls",
+                NetworkConnectionType::TlsPsk => "tlspsk",
                 NetworkConnectionType::WebSocket => "websocket",
                 NetworkConnectionType::WebSockets => "websockets",
+                NetworkConnectionType::Quic => "quic",
             }
         )
     }
@@ -51,6 +58,10 @@ pub struct NetworkConnection {
     pub protocol: Option<MQTTProtocol>,
     pub addr: SocketAddr,
     pub connection_stop_sx: Option<mpsc::Sender<bool>>,
+    // Client certificate verified by mutual TLS
+    pub peer_cert: Option<X509Identity>,
+    // Identity the client used in the TLS-PSK handshake
+    pub psk_identity: Option<String>,
 }
 
 impl NetworkConnection {
@@ -66,6 +77,8 @@ impl NetworkConnection {
             protocol: None,
             addr,
             connection_stop_sx,
+            peer_cert: None,
+            psk_identity: None,
         }
     }
 
@@ -101,6 +114,16 @@ impl NetworkConnection {
     pub fn is_tcp(&self) -> bool {
         self.connection_type == NetworkConnectionType::Tcp
             || self.connection_type == NetworkConnectionType::Tls
+            || self.connection_type == NetworkConnectionType::TlsPsk
+    }
+
+    pub fn is_quic(&self) -> bool {
+        self.connection_type == NetworkConnectionType::Quic
+    }
+
+    // Connections written through write_tcp_frame, everything except WebSocket
+    pub fn is_stream(&self) -> bool {
+        self.is_tcp() || self.is_quic()
     }
 
     pub async fn stop_connection(&self) {
@@ -108,7 +131,8 @@ impl NetworkConnection {
             match sx.send(true).await {
                 Ok(_) => {}
                 Err(e) => {
-                    error!("{}", e);
+                    // the read loop has already exited, e.g. the client closed the stream
+                    debug!("{}", e);
                 }
             }
         }
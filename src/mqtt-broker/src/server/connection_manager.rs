@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -40,7 +41,15 @@ pub struct ConnectionManager {
             MqttCodec,
         >,
     >,
+    tcp_psk_write_list: DashMap<
+        u64,
+        FramedWrite<
+            tokio::io::WriteHalf<tokio_openssl::SslStream<tokio::net::TcpStream>>,
+            MqttCodec,
+        >,
+    >,
     websocket_write_list: DashMap<u64, SplitSink<WebSocket, Message>>,
+    quic_write_list: DashMap<u64, FramedWrite<quinn::SendStream, MqttCodec>>,
     cache_manager: Arc<CacheManager>,
 }
 
@@ -49,13 +58,17 @@ impl ConnectionManager {
         let connections = DashMap::with_capacity(64);
         let tcp_write_list = DashMap::with_capacity(64);
         let tcp_tls_write_list = DashMap::with_capacity(64);
+        let tcp_psk_write_list = DashMap::with_capacity(64);
         let websocket_write_list = DashMap::with_capacity(64);
+        let quic_write_list = DashMap::with_capacity(64);
         ConnectionManager {
             connections,
             tcp_write_list,
             tcp_tls_write_list,
+            tcp_psk_write_list,
             cache_manager,
             websocket_write_list,
+            quic_write_list,
         }
     }
 
@@ -84,10 +97,42 @@ impl ConnectionManager {
         self.tcp_tls_write_list.insert(connection_id, write);
     }
 
+    pub fn add_tcp_psk_write(
+        &self,
+        connection_id: u64,
+        write: FramedWrite<
+            tokio::io::WriteHalf<tokio_openssl::SslStream<tokio::net::TcpStream>>,
+            MqttCodec,
+        >,
+    ) {
+        self.tcp_psk_write_list.insert(connection_id, write);
+    }
+
     pub fn add_websocket_write(&self, connection_id: u64, write: SplitSink<WebSocket, Message>) {
         self.websocket_write_list.insert(connection_id, write);
     }
 
+    pub fn add_quic_write(
+        &self,
+        connection_id: u64,
+        write: FramedWrite<quinn::SendStream, MqttCodec>,
+    ) {
+        self.quic_write_list.insert(connection_id, write);
+    }
+
+    // QUIC connections may migrate to a new client address (e.g. network handover)
+    pub fn update_connect_addr(&self, connect_id: u64, addr: SocketAddr) {
+        if let Some(mut connec) = self.connections.get_mut(&connect_id) {
+            if connec.addr != addr {
+                info!(
+                    "connection [{}] migrated from {} to {}",
+                    connect_id, connec.addr, addr
+                );
+                connec.addr = addr;
+            }
+        }
+    }
+
     pub async fn close_all_connect(&self) {
         for (connect_id, _) in self.connections.clone() {
             self.close_connect(connect_id).await;
@@ -123,6 +168,30 @@ impl ConnectionManager {
             }
         }
 
+        if let Some((id, mut stream)) = self.tcp_psk_write_list.remove(&connection_id) {
+            match stream.close().await {
+                Ok(_) => {
+                    info!(
+                        "server closes the tcp connection actively, connection id [{}]",
+                        id
+                    );
+                }
+                Err(e) => error!("{}", e),
+            }
+        }
+
+        if let Some((id, mut stream)) = self.quic_write_list.remove(&connection_id) {
+            match stream.close().await {
+                Ok(_) => {
+                    info!(
+                        "server closes the quic connection actively, connection id [{}]",
+                        id
+                    );
+                }
+                Err(e) => error!("{}", e),
+            }
+        }
+
         if let Some((id, mut stream)) = self.websocket_write_list.remove(&connection_id) {
             match stream.close().await {
                 Ok(_) => {
@@ -194,11 +263,17 @@ impl ConnectionManager {
     ) -> Result<(), CommonError> {
         debug!("response packet:{resp:?},connection_id:{connection_id}");
 
-        // write tls stream
+        // write tls/quic stream
         if let Some(connection) = self.get_connect(connection_id) {
             if connection.connection_type == NetworkConnectionType::Tls {
                 return self.write_tcp_tls_frame(connection_id, resp).await;
             }
+            if connection.connection_type == NetworkConnectionType::TlsPsk {
+                return self.write_tcp_psk_frame(connection_id, resp).await;
+            }
+            if connection.connection_type == NetworkConnectionType::Quic {
+                return self.write_quic_frame(connection_id, resp).await;
+            }
         }
 
         let mut times = 0;
@@ -302,6 +377,112 @@ impl ConnectionManager {
         Ok(())
     }
 
+    async fn write_tcp_psk_frame(
+        &self,
+        connection_id: u64,
+        resp: MqttPacketWrapper,
+    ) -> Result<(), CommonError> {
+        let mut times = 0;
+        let cluster = self.cache_manager.get_cluster_info();
+        loop {
+            match self.tcp_psk_write_list.try_get_mut(&connection_id) {
+                dashmap::try_result::TryResult::Present(mut da) => {
+                    match da.send(resp.clone()).await {
+                        Ok(_) => {
+                            break;
+                        }
+                        Err(e) => {
+                            if times > cluster.network.response_max_try_mut_times {
+                                return Err(CommonError::CommmonError(format!(
+                                    "Failed to write data to the mqtt tcp client, error message: {e:?}"
+                                )));
+                            }
+                        }
+                    }
+                }
+                dashmap::try_result::TryResult::Absent => {
+                    if times > cluster.network.response_max_try_mut_times {
+                        return Err(CommonError::CommmonError(
+                            format!(
+                                "[write_frame]Connection management could not obtain an available tcp connection. Connection ID: {},len:{}",
+                                connection_id,
+                                self.tcp_psk_write_list.len()
+                            )
+                        ));
+                    }
+                }
+                dashmap::try_result::TryResult::Locked => {
+                    if times > cluster.network.response_max_try_mut_times {
+                        return Err(CommonError::CommmonError(
+                            format!(
+                                "[write_frame]Connection management failed to get tcp connection variable reference, connection ID: {}",connection_id
+                            )
+                        ));
+                    }
+                }
+            }
+            times += 1;
+            sleep(Duration::from_millis(
+                cluster.network.response_try_mut_sleep_time_ms,
+            ))
+            .await
+        }
+        Ok(())
+    }
+
+    async fn write_quic_frame(
+        &self,
+        connection_id: u64,
+        resp: MqttPacketWrapper,
+    ) -> Result<(), CommonError> {
+        let mut times = 0;
+        let cluster = self.cache_manager.get_cluster_info();
+        loop {
+            match self.quic_write_list.try_get_mut(&connection_id) {
+                dashmap::try_result::TryResult::Present(mut da) => {
+                    match da.send(resp.clone()).await {
+                        Ok(_) => {
+                            break;
+                        }
+                        Err(e) => {
+                            if times > cluster.network.response_max_try_mut_times {
+                                return Err(CommonError::CommmonError(format!(
+                                    "Failed to write data to the mqtt quic client, error message: {e:?}"
+                                )));
+                            }
+                        }
+                    }
+                }
+                dashmap::try_result::TryResult::Absent => {
+                    if times > cluster.network.response_max_try_mut_times {
+                        return Err(CommonError::CommmonError(
+                            format!(
+                                "[write_frame]Connection management could not obtain an available quic connection. Connection ID: {},len:{}",
+                                connection_id,
+                                self.quic_write_list.len()
+                            )
+                        ));
+                    }
+                }
+                dashmap::try_result::TryResult::Locked => {
+                    if times > cluster.network.response_max_try_mut_times {
+                        return Err(CommonError::CommmonError(
+                            format!(
+                                "[write_frame]Connection management failed to get quic connection variable reference, connection ID: {}",connection_id
+                            )
+                        ));
+                    }
+                }
+            }
+            times += 1;
+            sleep(Duration::from_millis(
+                cluster.network.response_try_mut_sleep_time_ms,
+            ))
+            .await
+        }
+        Ok(())
+    }
+
     pub fn tcp_connect_num_check(&self) -> bool {
         let cluster = self.cache_manager.get_cluster_info();
         if self.connections.len() >= cluster.network.tcp_max_connection_num as usize {
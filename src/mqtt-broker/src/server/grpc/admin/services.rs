@@ -15,25 +15,112 @@
 use std::sync::Arc;
 
 use common_base::config::broker_mqtt::broker_mqtt_conf;
+use grpc_clients::mqtt::admin::call::disconnect_client;
 use grpc_clients::poll::ClientPool;
+use metadata_struct::acl::mqtt_acl::MqttAcl;
+use metadata_struct::acl::mqtt_blacklist::{MqttAclBlackList, MqttAclBlackListType};
+use metadata_struct::mqtt::cluster::MqttClusterDynamicConfig;
+use metadata_struct::mqtt::session::MqttSession;
+use metadata_struct::mqtt::user::MqttUser;
 use protocol::broker_mqtt::broker_mqtt_admin::mqtt_broker_admin_service_server::MqttBrokerAdminService;
-use protocol::broker_mqtt::broker_mqtt_admin::{ClusterStatusReply, ClusterStatusRequest};
+use protocol::broker_mqtt::broker_mqtt_admin::{
+    AlarmRaw, ClusterStatusReply, ClusterStatusRequest, ConnectionRaw, CreateAclReply,
+    CreateAclRequest, CreateBlacklistReply, CreateBlacklistRequest, CreateUserReply,
+    CreateUserRequest, DeleteAclReply, DeleteAclRequest, DeleteBlacklistReply,
+    DeleteBlacklistRequest, DeleteSessionReply, DeleteSessionRequest, DeleteUserReply,
+    DeleteUserRequest, DisconnectClientReply, DisconnectClientRequest, GetClusterConfigReply,
+    GetClusterConfigRequest, ListAclReply, ListAclRequest, ListAlarmReply, ListAlarmRequest,
+    ListBlacklistReply, ListBlacklistRequest, ListConnectionReply, ListConnectionRequest,
+    ListSessionReply, ListSessionRequest, ListSlowSubscribeReply, ListSlowSubscribeRequest,
+    ListSubscribeReply, ListSubscribeRequest, ListUserReply, ListUserRequest,
+    SetClusterConfigReply, SetClusterConfigRequest, SlowSubscribeRaw, SubscribeRaw,
+};
+use protocol::broker_mqtt::broker_mqtt_placement::{
+    MqttBrokerUpdateCacheActionType, MqttBrokerUpdateCacheResourceType,
+};
+use storage_adapter::storage::StorageAdapter;
+use tokio::sync::Mutex;
 use tonic::{Request, Response, Status};
 
+use crate::handler::cache::{update_remote_cache, CacheManager};
+use crate::handler::connection::{kick_connection, Connection};
+use crate::handler::session::clear_local_session;
+use crate::server::connection_manager::ConnectionManager;
+use crate::storage::acl::AclStorage;
+use crate::storage::blacklist::BlackListStorage;
 use crate::storage::cluster::ClusterStorage;
+use crate::storage::session::SessionStorage;
+use crate::storage::user::UserStorage;
+use crate::subscribe::subscribe_manager::SubscribeManager;
+use crate::subscribe::subscriber::SubscribeData;
 
-pub struct GrpcAdminServices {
+pub struct GrpcAdminServices<S> {
+    cache_manager: Arc<CacheManager>,
     client_poll: Arc<ClientPool>,
+    connection_manager: Arc<ConnectionManager>,
+    subscribe_manager: Arc<SubscribeManager>,
+    message_storage_adapter: Arc<S>,
+    // Held while a cluster config change is checked and stored
+    cluster_config_lock: Mutex<()>,
 }
 
-impl GrpcAdminServices {
-    pub fn new(client_poll: Arc<ClientPool>) -> Self {
-        GrpcAdminServices { client_poll }
+impl<S> GrpcAdminServices<S> {
+    pub fn new(
+        cache_manager: Arc<CacheManager>,
+        client_poll: Arc<ClientPool>,
+        connection_manager: Arc<ConnectionManager>,
+        subscribe_manager: Arc<SubscribeManager>,
+        message_storage_adapter: Arc<S>,
+    ) -> Self {
+        GrpcAdminServices {
+            cache_manager,
+            client_poll,
+            connection_manager,
+            subscribe_manager,
+            message_storage_adapter,
+            cluster_config_lock: Mutex::new(()),
+        }
+    }
+
+    // The client is not connected to this broker, ask the broker holding its session
+    async fn forward_disconnect(&self, client_id: String) -> Result<(), Status> {
+        let session_storage = SessionStorage::new(self.client_poll.clone());
+        let session = session_storage
+            .get_session(client_id.clone())
+            .await
+            .map_err(|e| Status::cancelled(e.to_string()))?;
+        let not_connected = || Status::not_found(format!("client {} is not connected", client_id));
+        let broker_id = match session.and_then(|session| session.broker_id) {
+            Some(broker_id) if broker_id != broker_mqtt_conf().broker_id => broker_id,
+            _ => return Err(not_connected()),
+        };
+
+        let cluster_storage = ClusterStorage::new(self.client_poll.clone());
+        let nodes = cluster_storage
+            .node_list()
+            .await
+            .map_err(|e| Status::cancelled(e.to_string()))?;
+        let Some(node) = nodes.into_iter().find(|node| node.node_id == broker_id) else {
+            return Err(not_connected());
+        };
+        disconnect_client(
+            self.client_poll.clone(),
+            vec![node.node_inner_addr],
+            DisconnectClientRequest {
+                client_id: client_id.clone(),
+            },
+        )
+        .await
+        .map_err(|e| Status::cancelled(e.to_string()))?;
+        Ok(())
     }
 }
 
 #[tonic::async_trait]
-impl MqttBrokerAdminService for GrpcAdminServices {
+impl<S> MqttBrokerAdminService for GrpcAdminServices<S>
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
     async fn cluster_status(
         &self,
         _: Request<ClusterStatusRequest>,
@@ -56,4 +143,511 @@ impl MqttBrokerAdminService for GrpcAdminServices {
         reply.nodes = broker_node_list;
         return Ok(Response::new(reply));
     }
+
+    async fn list_connection(
+        &self,
+        _: Request<ListConnectionRequest>,
+    ) -> Result<Response<ListConnectionReply>, Status> {
+        let mut connections: Vec<ConnectionRaw> = self
+            .cache_manager
+            .connection_info
+            .iter()
+            .map(|connection| connection_raw(&self.connection_manager, &connection))
+            .collect();
+        connections.sort_by(|a, b| a.client_id.cmp(&b.client_id));
+        return Ok(Response::new(ListConnectionReply { connections }));
+    }
+
+    async fn list_subscribe(
+        &self,
+        request: Request<ListSubscribeRequest>,
+    ) -> Result<Response<ListSubscribeReply>, Status> {
+        let req = request.into_inner();
+        let mut subscribes = Vec::new();
+        for client in self.cache_manager.subscribe_filter.iter() {
+            if !req.client_id.is_empty() && *client.key() != req.client_id {
+                continue;
+            }
+            for subscribe in client.value().iter() {
+                subscribes.push(subscribe_raw(client.key(), subscribe.value()));
+            }
+        }
+        subscribes.sort_by(|a, b| {
+            a.client_id
+                .cmp(&b.client_id)
+                .then_with(|| a.path.cmp(&b.path))
+        });
+        return Ok(Response::new(ListSubscribeReply { subscribes }));
+    }
+
+    async fn disconnect_client(
+        &self,
+        request: Request<DisconnectClientRequest>,
+    ) -> Result<Response<DisconnectClientReply>, Status> {
+        let client_id = request.into_inner().client_id;
+        match self.cache_manager.get_connect_id(&client_id) {
+            Some(connect_id) => {
+                if let Err(e) = kick_connection(
+                    &client_id,
+                    connect_id,
+                    &self.cache_manager,
+                    &self.client_poll,
+                    &self.connection_manager,
+                    &self.subscribe_manager,
+                )
+                .await
+                {
+                    return Err(Status::cancelled(e.to_string()));
+                }
+            }
+            None => self.forward_disconnect(client_id).await?,
+        }
+        return Ok(Response::new(DisconnectClientReply::default()));
+    }
+
+    async fn list_session(
+        &self,
+        _: Request<ListSessionRequest>,
+    ) -> Result<Response<ListSessionReply>, Status> {
+        let mut sessions: Vec<MqttSession> = self
+            .cache_manager
+            .session_info
+            .iter()
+            .map(|session| session.clone())
+            .collect();
+        sessions.sort_by(|a, b| a.client_id.cmp(&b.client_id));
+        let sessions = sessions.iter().map(|session| session.encode()).collect();
+        return Ok(Response::new(ListSessionReply { sessions }));
+    }
+
+    async fn delete_session(
+        &self,
+        request: Request<DeleteSessionRequest>,
+    ) -> Result<Response<DeleteSessionReply>, Status> {
+        let client_id = request.into_inner().client_id;
+        if !self.cache_manager.session_info.contains_key(&client_id) {
+            return Err(Status::not_found(format!(
+                "session {} does not exist",
+                client_id
+            )));
+        }
+
+        if let Some(connect_id) = self.cache_manager.get_connect_id(&client_id) {
+            if let Err(e) = kick_connection(
+                &client_id,
+                connect_id,
+                &self.cache_manager,
+                &self.client_poll,
+                &self.connection_manager,
+                &self.subscribe_manager,
+            )
+            .await
+            {
+                return Err(Status::cancelled(e.to_string()));
+            }
+        }
+
+        let session_storage = SessionStorage::new(self.client_poll.clone());
+        if let Err(e) = session_storage.delete_session(client_id.clone()).await {
+            return Err(Status::cancelled(e.to_string()));
+        }
+
+        clear_local_session(
+            &self.cache_manager,
+            &self.subscribe_manager,
+            &self.message_storage_adapter,
+            &client_id,
+        )
+        .await;
+        return Ok(Response::new(DeleteSessionReply::default()));
+    }
+
+    async fn list_user(
+        &self,
+        _: Request<ListUserRequest>,
+    ) -> Result<Response<ListUserReply>, Status> {
+        let user_storage = UserStorage::new(self.client_poll.clone());
+        let mut users: Vec<MqttUser> = match user_storage.user_list().await {
+            Ok(data) => data.into_iter().map(|(_, user)| user).collect(),
+            Err(e) => {
+                return Err(Status::cancelled(e.to_string()));
+            }
+        };
+        users.sort_by(|a, b| a.username.cmp(&b.username));
+        let users = users.iter().map(|user| user.encode()).collect();
+        return Ok(Response::new(ListUserReply { users }));
+    }
+
+    async fn create_user(
+        &self,
+        request: Request<CreateUserRequest>,
+    ) -> Result<Response<CreateUserReply>, Status> {
+        let req = request.into_inner();
+        if req.username.is_empty() {
+            return Err(Status::invalid_argument(
+                "Username cannot be empty".to_string(),
+            ));
+        }
+        let user_storage = UserStorage::new(self.client_poll.clone());
+        let user = MqttUser {
+            username: req.username.clone(),
+            password: req.password,
+            is_superuser: req.is_superuser,
+            hash: Default::default(),
+        };
+        if let Err(e) = user_storage.save_user(user).await {
+            return Err(Status::cancelled(e.to_string()));
+        }
+
+        // the placement center hashes the password, cache the user as it was stored
+        match user_storage.get_user(req.username).await {
+            Ok(Some(user)) => {
+                self.cache_manager.add_user(user.clone());
+                update_remote_cache(
+                    &self.client_poll,
+                    MqttBrokerUpdateCacheActionType::Add,
+                    MqttBrokerUpdateCacheResourceType::User,
+                    user.encode(),
+                )
+                .await;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                return Err(Status::cancelled(e.to_string()));
+            }
+        }
+        return Ok(Response::new(CreateUserReply::default()));
+    }
+
+    async fn delete_user(
+        &self,
+        request: Request<DeleteUserRequest>,
+    ) -> Result<Response<DeleteUserReply>, Status> {
+        let req = request.into_inner();
+        let user_storage = UserStorage::new(self.client_poll.clone());
+        if let Err(e) = user_storage.delete_user(req.username.clone()).await {
+            return Err(Status::cancelled(e.to_string()));
+        }
+
+        self.cache_manager.user_info.remove(&req.username);
+        let user = MqttUser {
+            username: req.username,
+            password: String::new(),
+            is_superuser: false,
+            hash: Default::default(),
+        };
+        update_remote_cache(
+            &self.client_poll,
+            MqttBrokerUpdateCacheActionType::Delete,
+            MqttBrokerUpdateCacheResourceType::User,
+            user.encode(),
+        )
+        .await;
+        return Ok(Response::new(DeleteUserReply::default()));
+    }
+
+    async fn list_acl(&self, _: Request<ListAclRequest>) -> Result<Response<ListAclReply>, Status> {
+        let acl_storage = AclStorage::new(self.client_poll.clone());
+        let mut acls = Vec::new();
+        match acl_storage.list_acl().await {
+            Ok(data) => {
+                for acl in data {
+                    match acl.encode() {
+                        Ok(raw) => acls.push(raw),
+                        Err(e) => {
+                            return Err(Status::cancelled(e.to_string()));
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                return Err(Status::cancelled(e.to_string()));
+            }
+        }
+        return Ok(Response::new(ListAclReply { acls }));
+    }
+
+    async fn create_acl(
+        &self,
+        request: Request<CreateAclRequest>,
+    ) -> Result<Response<CreateAclReply>, Status> {
+        let req = request.into_inner();
+        let acl = match serde_json::from_slice::<MqttAcl>(&req.acl) {
+            Ok(acl) => acl,
+            Err(e) => {
+                return Err(Status::invalid_argument(e.to_string()));
+            }
+        };
+        let acl_storage = AclStorage::new(self.client_poll.clone());
+        if let Err(e) = acl_storage.save_acl(acl.clone()).await {
+            return Err(Status::cancelled(e.to_string()));
+        }
+
+        self.cache_manager.add_acl(acl);
+        update_remote_cache(
+            &self.client_poll,
+            MqttBrokerUpdateCacheActionType::Add,
+            MqttBrokerUpdateCacheResourceType::Acl,
+            req.acl,
+        )
+        .await;
+        return Ok(Response::new(CreateAclReply::default()));
+    }
+
+    async fn delete_acl(
+        &self,
+        request: Request<DeleteAclRequest>,
+    ) -> Result<Response<DeleteAclReply>, Status> {
+        let req = request.into_inner();
+        let acl = match serde_json::from_slice::<MqttAcl>(&req.acl) {
+            Ok(acl) => acl,
+            Err(e) => {
+                return Err(Status::invalid_argument(e.to_string()));
+            }
+        };
+        let acl_storage = AclStorage::new(self.client_poll.clone());
+        if let Err(e) = acl_storage.delete_acl(acl.clone()).await {
+            return Err(Status::cancelled(e.to_string()));
+        }
+
+        self.cache_manager.remove_acl(&acl);
+        update_remote_cache(
+            &self.client_poll,
+            MqttBrokerUpdateCacheActionType::Delete,
+            MqttBrokerUpdateCacheResourceType::Acl,
+            req.acl,
+        )
+        .await;
+        return Ok(Response::new(DeleteAclReply::default()));
+    }
+
+    async fn list_blacklist(
+        &self,
+        _: Request<ListBlacklistRequest>,
+    ) -> Result<Response<ListBlacklistReply>, Status> {
+        let blacklist_storage = BlackListStorage::new(self.client_poll.clone());
+        let mut blacklists = Vec::new();
+        match blacklist_storage.list_blacklist().await {
+            Ok(data) => {
+                for blacklist in data {
+                    match blacklist.encode() {
+                        Ok(raw) => blacklists.push(raw),
+                        Err(e) => {
+                            return Err(Status::cancelled(e.to_string()));
+                        }
+                    }
+                }
+            }
+            Err(e) => {
+                return Err(Status::cancelled(e.to_string()));
+            }
+        }
+        return Ok(Response::new(ListBlacklistReply { blacklists }));
+    }
+
+    async fn create_blacklist(
+        &self,
+        request: Request<CreateBlacklistRequest>,
+    ) -> Result<Response<CreateBlacklistReply>, Status> {
+        let req = request.into_inner();
+        let blacklist = match serde_json::from_slice::<MqttAclBlackList>(&req.blacklist) {
+            Ok(blacklist) => blacklist,
+            Err(e) => {
+                return Err(Status::invalid_argument(e.to_string()));
+            }
+        };
+        let blacklist_storage = BlackListStorage::new(self.client_poll.clone());
+        if let Err(e) = blacklist_storage.save_blacklist(blacklist.clone()).await {
+            return Err(Status::cancelled(e.to_string()));
+        }
+
+        self.cache_manager.add_blacklist(blacklist);
+        update_remote_cache(
+            &self.client_poll,
+            MqttBrokerUpdateCacheActionType::Add,
+            MqttBrokerUpdateCacheResourceType::Blacklist,
+            req.blacklist,
+        )
+        .await;
+        return Ok(Response::new(CreateBlacklistReply::default()));
+    }
+
+    async fn delete_blacklist(
+        &self,
+        request: Request<DeleteBlacklistRequest>,
+    ) -> Result<Response<DeleteBlacklistReply>, Status> {
+        let req = request.into_inner();
+        let blacklist_type = match req.blacklist_type.parse::<MqttAclBlackListType>() {
+            Ok(blacklist_type) => blacklist_type,
+            Err(e) => {
+                return Err(Status::invalid_argument(e.to_string()));
+            }
+        };
+        let blacklist = MqttAclBlackList {
+            blacklist_type,
+            resource_name: req.resource_name,
+            end_time: 0,
+            desc: String::new(),
+        };
+        let blacklist_storage = BlackListStorage::new(self.client_poll.clone());
+        if let Err(e) = blacklist_storage.delete_blacklist(blacklist.clone()).await {
+            return Err(Status::cancelled(e.to_string()));
+        }
+
+        self.cache_manager.remove_blacklist(&blacklist);
+        let data = match blacklist.encode() {
+            Ok(data) => data,
+            Err(e) => {
+                return Err(Status::cancelled(e.to_string()));
+            }
+        };
+        update_remote_cache(
+            &self.client_poll,
+            MqttBrokerUpdateCacheActionType::Delete,
+            MqttBrokerUpdateCacheResourceType::Blacklist,
+            data,
+        )
+        .await;
+        return Ok(Response::new(DeleteBlacklistReply::default()));
+    }
+
+    async fn get_cluster_config(
+        &self,
+        _: Request<GetClusterConfigRequest>,
+    ) -> Result<Response<GetClusterConfigReply>, Status> {
+        let config = self.cache_manager.get_cluster_info().encode();
+        return Ok(Response::new(GetClusterConfigReply { config }));
+    }
+
+    // The config is a read-merge-set of the caller, so it must carry the version it read.
+    // Changes made through this broker are serialized, the version check catches the others.
+    async fn set_cluster_config(
+        &self,
+        request: Request<SetClusterConfigRequest>,
+    ) -> Result<Response<SetClusterConfigReply>, Status> {
+        let req = request.into_inner();
+        let mut cluster = match serde_json::from_slice::<MqttClusterDynamicConfig>(&req.config) {
+            Ok(cluster) => cluster,
+            Err(e) => {
+                return Err(Status::invalid_argument(e.to_string()));
+            }
+        };
+        if let Err(e) = cluster.validate() {
+            return Err(Status::invalid_argument(e.to_string()));
+        }
+
+        let _guard = self.cluster_config_lock.lock().await;
+        let cluster_name = self.cache_manager.cluster_name.clone();
+        let cluster_storage = ClusterStorage::new(self.client_poll.clone());
+        let current_version = match cluster_storage
+            .get_cluster_config(cluster_name.clone())
+            .await
+        {
+            Ok(Some(current)) => current.version,
+            Ok(None) => self.cache_manager.get_cluster_info().version,
+            Err(e) => {
+                return Err(Status::cancelled(e.to_string()));
+            }
+        };
+        if cluster.version != current_version {
+            return Err(Status::aborted(format!(
+                "cluster config version {} is stale, the current version is {}, read the config again",
+                cluster.version, current_version
+            )));
+        }
+        cluster.version += 1;
+
+        if let Err(e) = cluster_storage
+            .set_cluster_config(cluster_name, cluster.clone())
+            .await
+        {
+            return Err(Status::cancelled(e.to_string()));
+        }
+
+        let data = cluster.encode();
+        self.cache_manager.set_cluster_info(cluster);
+        update_remote_cache(
+            &self.client_poll,
+            MqttBrokerUpdateCacheActionType::Add,
+            MqttBrokerUpdateCacheResourceType::ClusterConfig,
+            data,
+        )
+        .await;
+        return Ok(Response::new(SetClusterConfigReply::default()));
+    }
+
+    async fn list_slow_subscribe(
+        &self,
+        _: Request<ListSlowSubscribeRequest>,
+    ) -> Result<Response<ListSlowSubscribeReply>, Status> {
+        let slow_subscribes = self
+            .cache_manager
+            .slow_subscribe_records
+            .list()
+            .into_iter()
+            .map(|slow| SlowSubscribeRaw {
+                client_id: slow.client_id,
+                topic: slow.topic,
+                time_ms: slow.time_ms as u64,
+                node_info: slow.node_info,
+                create_time: slow.create_time as u64,
+            })
+            .collect();
+        return Ok(Response::new(ListSlowSubscribeReply { slow_subscribes }));
+    }
+
+    async fn list_alarm(
+        &self,
+        _: Request<ListAlarmRequest>,
+    ) -> Result<Response<ListAlarmReply>, Status> {
+        let alarms = self
+            .cache_manager
+            .alarm_records
+            .list()
+            .into_iter()
+            .map(|alarm| AlarmRaw {
+                name: alarm.name,
+                message: alarm.message,
+                node_info: alarm.node_info,
+                create_time: alarm.create_time as u64,
+            })
+            .collect();
+        return Ok(Response::new(ListAlarmReply { alarms }));
+    }
+}
+
+fn connection_raw(
+    connection_manager: &Arc<ConnectionManager>,
+    connection: &Connection,
+) -> ConnectionRaw {
+    let network = connection_manager.get_connect(connection.connect_id);
+    ConnectionRaw {
+        connect_id: connection.connect_id,
+        client_id: connection.client_id.clone(),
+        username: connection.login_user.clone(),
+        source_ip_addr: connection.source_ip_addr.clone(),
+        network: network
+            .as_ref()
+            .map(|network| network.connection_type.to_string())
+            .unwrap_or_default(),
+        protocol_version: network
+            .and_then(|network| network.protocol)
+            .map(|protocol| u8::from(protocol) as u32)
+            .unwrap_or_default(),
+        keep_alive: connection.keep_alive as u32,
+        create_time: connection.create_time,
+        inflight: connection.get_send_qos_message() as i64,
+    }
+}
+
+fn subscribe_raw(client_id: &str, subscribe: &SubscribeData) -> SubscribeRaw {
+    SubscribeRaw {
+        client_id: client_id.to_string(),
+        path: subscribe.filter.path.clone(),
+        protocol_version: u8::from(subscribe.protocol.clone()) as u32,
+        qos: u8::from(subscribe.filter.qos) as u32,
+        no_local: subscribe.filter.nolocal,
+        preserve_retain: subscribe.filter.preserve_retain,
+        retain_handling: u8::from(subscribe.filter.retain_forward_rule.clone()) as u32,
+    }
 }
@@ -19,19 +19,24 @@ use log::debug;
 use metadata_struct::mqtt::lastwill::LastWillData;
 use protocol::broker_mqtt::broker_mqtt_placement::mqtt_broker_placement_service_server::MqttBrokerPlacementService;
 use protocol::broker_mqtt::broker_mqtt_placement::{
-    DeleteSessionReply, DeleteSessionRequest, SendLastWillMessageReply, SendLastWillMessageRequest,
-    UpdateCacheReply, UpdateCacheRequest,
+    DeleteSessionReply, DeleteSessionRequest, NotifyTopicMessageReply, NotifyTopicMessageRequest,
+    SendLastWillMessageReply, SendLastWillMessageRequest, TakeoverSessionReply,
+    TakeoverSessionRequest, UpdateCacheReply, UpdateCacheRequest,
 };
 use storage_adapter::storage::StorageAdapter;
 use tonic::{Request, Response, Status};
 
 use crate::handler::cache::{update_cache_metadata, CacheManager};
 use crate::handler::lastwill::send_last_will_message;
+use crate::handler::session::clear_local_session;
+use crate::handler::takeover::release_session;
+use crate::server::connection_manager::ConnectionManager;
 use crate::subscribe::subscribe_manager::SubscribeManager;
 
 pub struct GrpcPlacementServices<S> {
     cache_manager: Arc<CacheManager>,
     subscribe_manager: Arc<SubscribeManager>,
+    connection_manager: Arc<ConnectionManager>,
     client_poll: Arc<ClientPool>,
     message_storage_adapter: Arc<S>,
 }
@@ -40,12 +45,14 @@ impl<S> GrpcPlacementServices<S> {
     pub fn new(
         metadata_cache: Arc<CacheManager>,
         subscribe_manager: Arc<SubscribeManager>,
+        connection_manager: Arc<ConnectionManager>,
         client_poll: Arc<ClientPool>,
         message_storage_adapter: Arc<S>,
     ) -> Self {
         GrpcPlacementServices {
             cache_manager: metadata_cache,
             subscribe_manager,
+            connection_manager,
             client_poll,
             message_storage_adapter,
         }
@@ -62,7 +69,7 @@ where
         request: Request<UpdateCacheRequest>,
     ) -> Result<Response<UpdateCacheReply>, Status> {
         let req = request.into_inner();
-        update_cache_metadata(req);
+        update_cache_metadata(&self.cache_manager, req);
         return Ok(Response::new(UpdateCacheReply::default()));
     }
 
@@ -80,8 +87,13 @@ where
             return Err(Status::cancelled("Client ID cannot be empty".to_string()));
         }
         for client_id in req.client_id {
-            self.cache_manager.remove_session(&client_id);
-            self.subscribe_manager.stop_push_by_client_id(&client_id);
+            clear_local_session(
+                &self.cache_manager,
+                &self.subscribe_manager,
+                &self.message_storage_adapter,
+                &client_id,
+            )
+            .await;
         }
 
         return Ok(Response::new(DeleteSessionReply::default()));
@@ -121,4 +133,45 @@ where
             }
         }
     }
+    async fn notify_topic_message(
+        &self,
+        request: Request<NotifyTopicMessageRequest>,
+    ) -> Result<Response<NotifyTopicMessageReply>, Status> {
+        let req = request.into_inner();
+        if self.cache_manager.cluster_name != req.cluster_name {
+            return Err(Status::cancelled("Cluster name does not match".to_string()));
+        }
+
+        for topic_id in req.topic_id {
+            self.cache_manager.push_notify.notify_local(&topic_id);
+        }
+        return Ok(Response::new(NotifyTopicMessageReply::default()));
+    }
+    async fn takeover_session(
+        &self,
+        request: Request<TakeoverSessionRequest>,
+    ) -> Result<Response<TakeoverSessionReply>, Status> {
+        let req = request.into_inner();
+        debug!(
+            "Received request to take over the session of client {}, connect id: {}",
+            req.client_id, req.connect_id
+        );
+        if self.cache_manager.cluster_name != req.cluster_name {
+            return Err(Status::cancelled("Cluster name does not match".to_string()));
+        }
+
+        let inflight = release_session(
+            &req.client_id,
+            req.connect_id,
+            &self.cache_manager,
+            &self.connection_manager,
+            &self.subscribe_manager,
+        )
+        .await;
+
+        match serde_json::to_vec(&inflight) {
+            Ok(data) => Ok(Response::new(TakeoverSessionReply { inflight: data })),
+            Err(e) => Err(Status::internal(e.to_string())),
+        }
+    }
 }
@@ -24,6 +24,7 @@ use tonic::transport::Server;
 
 use super::placement::GrpcPlacementServices;
 use crate::handler::cache::CacheManager;
+use crate::server::connection_manager::ConnectionManager;
 use crate::server::grpc::admin::services::GrpcAdminServices;
 use crate::subscribe::subscribe_manager::SubscribeManager;
 
@@ -31,6 +32,7 @@ pub struct GrpcServer<S> {
     port: u32,
     metadata_cache: Arc<CacheManager>,
     subscribe_manager: Arc<SubscribeManager>,
+    connection_manager: Arc<ConnectionManager>,
     client_poll: Arc<ClientPool>,
     message_storage_adapter: Arc<S>,
 }
@@ -43,6 +45,7 @@ where
         port: u32,
         metadata_cache: Arc<CacheManager>,
         subscribe_manager: Arc<SubscribeManager>,
+        connection_manager: Arc<ConnectionManager>,
         client_poll: Arc<ClientPool>,
         message_storage_adapter: Arc<S>,
     ) -> Self {
@@ -50,6 +53,7 @@ where
             port,
             metadata_cache,
             subscribe_manager,
+            connection_manager,
             client_poll,
             message_storage_adapter,
         }
@@ -60,10 +64,17 @@ where
         let placement_handler = GrpcPlacementServices::new(
             self.metadata_cache.clone(),
             self.subscribe_manager.clone(),
+            self.connection_manager.clone(),
             self.client_poll.clone(),
             self.message_storage_adapter.clone(),
         );
-        let admin_handler = GrpcAdminServices::new(self.client_poll.clone());
+        let admin_handler = GrpcAdminServices::new(
+            self.metadata_cache.clone(),
+            self.client_poll.clone(),
+            self.connection_manager.clone(),
+            self.subscribe_manager.clone(),
+            self.message_storage_adapter.clone(),
+        );
         Server::builder()
             .add_service(MqttBrokerPlacementServiceServer::new(placement_handler))
             .add_service(MqttBrokerAdminServiceServer::new(admin_handler))
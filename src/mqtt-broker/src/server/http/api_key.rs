@@ -0,0 +1,164 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::extract::{Request, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use common_base::config::broker_mqtt::{broker_mqtt_conf, HttpApiKey};
+use common_base::http_response::error_response;
+
+// The API key the request was made with, added to the request extensions
+#[derive(Clone, Debug, PartialEq)]
+pub struct ApiKeyIdentity {
+    pub name: String,
+    pub principal: String,
+}
+
+// Route group an API key has to be granted, matched against the scopes of the key
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ApiKeyScope {
+    Publish,
+    Admin,
+}
+
+impl ApiKeyScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ApiKeyScope::Publish => "publish",
+            ApiKeyScope::Admin => "admin",
+        }
+    }
+}
+
+// Rejects requests without a known API key with 401, and keys without the scope of the
+// route group with 403
+pub async fn api_key_auth(
+    State(scope): State<ApiKeyScope>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let conf = broker_mqtt_conf();
+    let Some(api_key) = find_api_key(&conf.http_api.api_keys, request.headers()) else {
+        return (
+            StatusCode::UNAUTHORIZED,
+            error_response("missing or unknown API key".to_string()),
+        )
+            .into_response();
+    };
+    if !has_scope(api_key, scope) {
+        return (
+            StatusCode::FORBIDDEN,
+            error_response(format!(
+                "API key {} does not have the {} scope",
+                api_key.name,
+                scope.as_str()
+            )),
+        )
+            .into_response();
+    }
+    request.extensions_mut().insert(ApiKeyIdentity {
+        name: api_key.name.clone(),
+        principal: api_key.principal.clone(),
+    });
+    next.run(request).await
+}
+
+fn has_scope(api_key: &HttpApiKey, scope: ApiKeyScope) -> bool {
+    api_key
+        .scopes
+        .iter()
+        .any(|name| name.eq_ignore_ascii_case(scope.as_str()))
+}
+
+// The key sent as "Authorization: Bearer <key>", None when it is missing or unknown
+pub fn find_api_key<'a>(api_keys: &'a [HttpApiKey], headers: &HeaderMap) -> Option<&'a HttpApiKey> {
+    let key = headers
+        .get(AUTHORIZATION)?
+        .to_str()
+        .ok()?
+        .strip_prefix("Bearer ")?
+        .trim();
+    api_keys.iter().find(|api_key| {
+        !api_key.key.is_empty() && constant_time_eq(api_key.key.as_bytes(), key.as_bytes())
+    })
+}
+
+// Looks at every byte, so the time taken does not tell how much of a key matched
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    if a.len() != b.len() {
+        return false;
+    }
+    a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod test {
+    use axum::http::header::AUTHORIZATION;
+    use axum::http::HeaderMap;
+    use common_base::config::broker_mqtt::HttpApiKey;
+
+    use super::{find_api_key, has_scope, ApiKeyScope};
+
+    #[test]
+    pub fn find_api_key_test() {
+        let api_keys = vec![
+            HttpApiKey {
+                name: "backend".to_string(),
+                key: "k1".to_string(),
+                principal: "svc-backend".to_string(),
+                scopes: vec!["publish".to_string()],
+            },
+            HttpApiKey {
+                name: "disabled".to_string(),
+                key: "".to_string(),
+                ..Default::default()
+            },
+        ];
+        let headers = |value: &str| {
+            let mut headers = HeaderMap::new();
+            headers.insert(AUTHORIZATION, value.parse().unwrap());
+            headers
+        };
+
+        assert_eq!(
+            find_api_key(&api_keys, &headers("Bearer k1")).map(|api_key| api_key.name.as_str()),
+            Some("backend")
+        );
+        assert!(find_api_key(&api_keys, &headers("Bearer k2")).is_none());
+        assert!(find_api_key(&api_keys, &headers("Bearer k11")).is_none());
+        assert!(find_api_key(&api_keys, &headers("Basic k1")).is_none());
+        assert!(find_api_key(&api_keys, &headers("Bearer ")).is_none());
+        assert!(find_api_key(&api_keys, &HeaderMap::new()).is_none());
+        assert!(find_api_key(&[], &headers("Bearer k1")).is_none());
+    }
+
+    #[test]
+    pub fn has_scope_test() {
+        let mut api_key = HttpApiKey {
+            name: "backend".to_string(),
+            key: "k1".to_string(),
+            scopes: vec!["Publish".to_string()],
+            ..Default::default()
+        };
+        assert!(has_scope(&api_key, ApiKeyScope::Publish));
+        assert!(!has_scope(&api_key, ApiKeyScope::Admin));
+
+        api_key.scopes = vec![];
+        assert!(!has_scope(&api_key, ApiKeyScope::Publish));
+        api_key.scopes = vec!["publish".to_string(), "admin".to_string()];
+        assert!(has_scope(&api_key, ApiKeyScope::Admin));
+    }
+}
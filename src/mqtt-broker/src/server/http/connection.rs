@@ -12,11 +12,229 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use axum::extract::State;
-use common_base::http_response::success_response;
+use std::net::SocketAddr;
 
+use axum::extract::{Path, Query, State};
+use common_base::http_response::{error_response, success_response};
+use serde::{Deserialize, Serialize};
+use storage_adapter::storage::StorageAdapter;
+
+use super::page::paginate;
 use super::server::HttpServerState;
+use crate::handler::connection::{kick_connection, Connection};
+use crate::subscribe::offline_queue::OfflineQueue;
+
+#[derive(Deserialize, Debug, Default)]
+pub struct ConnectionListQuery {
+    pub page: Option<usize>,
+    pub limit: Option<usize>,
+    // Matches client IDs containing this value
+    pub client_id: Option<String>,
+    pub username: Option<String>,
+    pub ip: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct ConnectionInfo {
+    pub connect_id: u64,
+    pub client_id: String,
+    pub username: String,
+    pub ip: String,
+    // tcp, tls, tlspsk, websocket, websockets or quic
+    pub network: String,
+    pub protocol_version: Option<u8>,
+    pub keep_alive: u16,
+    pub create_time: u64,
+    // QoS 1 and 2 messages sent to the client and not acknowledged yet
+    pub inflight: isize,
+    // QoS 1 and 2 messages received from the client and not acknowledged yet
+    pub receive_inflight: isize,
+    // Messages waiting in the offline queue of the session
+    pub queue_depth: u64,
+}
+
+pub async fn connection_list<S>(
+    State(state): State<HttpServerState<S>>,
+    Query(query): Query<ConnectionListQuery>,
+) -> String
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    let mut connections: Vec<Connection> = state
+        .cache_manager
+        .connection_info
+        .iter()
+        .filter(|connection| match_connection(connection, &query))
+        .map(|connection| connection.clone())
+        .collect();
+    connections.sort_by(|a, b| a.client_id.cmp(&b.client_id));
+
+    let page = paginate(connections, query.page, query.limit);
+    let mut data = Vec::with_capacity(page.data.len());
+    for connection in page.data.iter() {
+        data.push(connection_info(&state, connection).await);
+    }
+    success_response(page.with_data(data))
+}
+
+pub async fn connection_detail<S>(
+    State(state): State<HttpServerState<S>>,
+    Path(client_id): Path<String>,
+) -> String
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    let Some(connection) = state
+        .cache_manager
+        .get_connect_id(&client_id)
+        .and_then(|connect_id| state.cache_manager.get_connection(connect_id))
+    else {
+        return error_response(format!("client {} is not connected", client_id));
+    };
+    success_response(connection_info(&state, &connection).await)
+}
+
+pub async fn connection_kick<S>(
+    State(state): State<HttpServerState<S>>,
+    Path(client_id): Path<String>,
+) -> String
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    let Some(connect_id) = state.cache_manager.get_connect_id(&client_id) else {
+        return error_response(format!("client {} is not connected", client_id));
+    };
+    match kick_connection(
+        &client_id,
+        connect_id,
+        &state.cache_manager,
+        &state.client_poll,
+        &state.connection_manager,
+        &state.subscribe_manager,
+    )
+    .await
+    {
+        Ok(()) => success_response("success"),
+        Err(e) => error_response(e.to_string()),
+    }
+}
+
+fn match_connection(connection: &Connection, query: &ConnectionListQuery) -> bool {
+    if let Some(client_id) = &query.client_id {
+        if !connection.client_id.contains(client_id.as_str()) {
+            return false;
+        }
+    }
+    if let Some(username) = &query.username {
+        if connection.login_user != *username {
+            return false;
+        }
+    }
+    if let Some(ip) = &query.ip {
+        if connection_ip(&connection.source_ip_addr) != *ip {
+            return false;
+        }
+    }
+    true
+}
+
+// The source address is stored with its port
+fn connection_ip(source_ip_addr: &str) -> String {
+    match source_ip_addr.parse::<SocketAddr>() {
+        Ok(addr) => addr.ip().to_string(),
+        Err(_) => source_ip_addr.to_string(),
+    }
+}
+
+async fn connection_info<S>(state: &HttpServerState<S>, connection: &Connection) -> ConnectionInfo
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    let network = state.connection_manager.get_connect(connection.connect_id);
+    let queue_depth = match OfflineQueue::new(
+        connection.client_id.clone(),
+        state.message_storage_adapter.clone(),
+    )
+    .get_meta()
+    .await
+    {
+        Ok(meta) => meta.len(),
+        Err(_) => 0,
+    };
+    ConnectionInfo {
+        connect_id: connection.connect_id,
+        client_id: connection.client_id.clone(),
+        username: connection.login_user.clone(),
+        ip: connection_ip(&connection.source_ip_addr),
+        network: network
+            .as_ref()
+            .map(|network| network.connection_type.to_string())
+            .unwrap_or_default(),
+        protocol_version: network
+            .and_then(|network| network.protocol)
+            .map(|protocol| protocol.into()),
+        keep_alive: connection.keep_alive,
+        create_time: connection.create_time,
+        inflight: connection.get_send_qos_message(),
+        receive_inflight: connection.get_recv_qos_message(),
+        queue_depth,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{connection_ip, match_connection, ConnectionListQuery};
+    use crate::handler::connection::Connection;
+
+    #[test]
+    fn connection_ip_test() {
+        assert_eq!(connection_ip("127.0.0.1:1883"), "127.0.0.1");
+        assert_eq!(connection_ip("[::1]:1883"), "::1");
+        assert_eq!(connection_ip("10.0.0.1"), "10.0.0.1");
+    }
+
+    #[test]
+    fn match_connection_test() {
+        let connection = Connection {
+            client_id: "sensor-001".to_string(),
+            login_user: "device".to_string(),
+            source_ip_addr: "10.0.0.1:50000".to_string(),
+            ..Default::default()
+        };
 
-pub async fn connection_list(State(_): State<HttpServerState>) -> String {
-    success_response("data")
+        assert!(match_connection(
+            &connection,
+            &ConnectionListQuery::default()
+        ));
+        assert!(match_connection(
+            &connection,
+            &ConnectionListQuery {
+                client_id: Some("sensor".to_string()),
+                username: Some("device".to_string()),
+                ip: Some("10.0.0.1".to_string()),
+                ..Default::default()
+            }
+        ));
+        assert!(!match_connection(
+            &connection,
+            &ConnectionListQuery {
+                client_id: Some("gateway".to_string()),
+                ..Default::default()
+            }
+        ));
+        assert!(!match_connection(
+            &connection,
+            &ConnectionListQuery {
+                username: Some("admin".to_string()),
+                ..Default::default()
+            }
+        ));
+        assert!(!match_connection(
+            &connection,
+            &ConnectionListQuery {
+                ip: Some("10.0.0.2".to_string()),
+                ..Default::default()
+            }
+        ));
+    }
 }
@@ -12,7 +12,12 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+mod api_key;
 mod connection;
+mod page;
 mod prometheus;
 mod publish;
 pub mod server;
+mod session;
+mod subscribe;
+mod topic;
@@ -0,0 +1,86 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use serde::Serialize;
+
+const DEFAULT_PAGE_LIMIT: usize = 100;
+const MAX_PAGE_LIMIT: usize = 1000;
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct Page<T> {
+    pub total: usize,
+    pub page: usize,
+    pub limit: usize,
+    pub data: Vec<T>,
+}
+
+impl<T> Page<T> {
+    pub fn with_data<U>(&self, data: Vec<U>) -> Page<U> {
+        Page {
+            total: self.total,
+            page: self.page,
+            limit: self.limit,
+            data,
+        }
+    }
+}
+
+// Pages start at 1, the limit defaults to 100 and is capped at 1000
+pub fn paginate<T>(items: Vec<T>, page: Option<usize>, limit: Option<usize>) -> Page<T> {
+    let page = page.unwrap_or(1).max(1);
+    let limit = limit.unwrap_or(DEFAULT_PAGE_LIMIT).clamp(1, MAX_PAGE_LIMIT);
+    let total = items.len();
+    let data = items
+        .into_iter()
+        .skip((page - 1).saturating_mul(limit))
+        .take(limit)
+        .collect();
+    Page {
+        total,
+        page,
+        limit,
+        data,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::paginate;
+
+    #[test]
+    fn paginate_test() {
+        let items: Vec<u32> = (0..250).collect();
+
+        let page = paginate(items.clone(), None, None);
+        assert_eq!(page.total, 250);
+        assert_eq!(page.page, 1);
+        assert_eq!(page.limit, 100);
+        assert_eq!(page.data, (0..100).collect::<Vec<u32>>());
+
+        let page = paginate(items.clone(), Some(3), Some(100));
+        assert_eq!(page.data, (200..250).collect::<Vec<u32>>());
+
+        let page = paginate(items.clone(), Some(4), Some(100));
+        assert!(page.data.is_empty());
+
+        let page = paginate(items.clone(), Some(0), Some(0));
+        assert_eq!(page.page, 1);
+        assert_eq!(page.limit, 1);
+        assert_eq!(page.data, vec![0]);
+
+        let page = paginate(items, Some(usize::MAX), Some(5000));
+        assert_eq!(page.limit, 1000);
+        assert!(page.data.is_empty());
+    }
+}
@@ -12,11 +12,282 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use axum::extract::State;
-use common_base::http_response::success_response;
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
 
+use axum::extract::{ConnectInfo, State};
+use axum::{Extension, Json};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use bytes::Bytes;
+use common_base::error::common::CommonError;
+use common_base::error::mqtt_broker::MQTTBrokerError;
+use common_base::http_response::{error_response, success_response};
+use protocol::mqtt::common::{qos, MQTTPacket, MQTTProtocol, Publish, PublishProperties};
+use serde::{Deserialize, Serialize};
+use storage_adapter::storage::StorageAdapter;
+
+use super::api_key::ApiKeyIdentity;
 use super::server::HttpServerState;
+use crate::handler::cache::CacheManager;
+use crate::handler::connection::{Connection, ConnectionConfig};
+use crate::handler::publish::{publish_message, PublishError};
+
+// Network connection ids start at 1, so HTTP publishes never pick up the state of a real connection
+const HTTP_PUBLISH_CONNECT_ID: u64 = 0;
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct HttpPublishRequest {
+    pub topic: String,
+    pub payload: String,
+    // "plain" or "base64", empty is plain
+    #[serde(default)]
+    pub encoding: String,
+    #[serde(default)]
+    pub qos: u8,
+    #[serde(default)]
+    pub retain: bool,
+    #[serde(default)]
+    pub user_properties: BTreeMap<String, String>,
+    #[serde(default)]
+    pub content_type: Option<String>,
+    // Seconds, the cluster default applies when not set
+    #[serde(default)]
+    pub message_expiry_interval: Option<u32>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct HttpPublishResult {
+    pub topic: String,
+    pub success: bool,
+    // The reason the message was rejected, empty on success
+    pub message: String,
+}
+
+pub async fn http_publish<S>(
+    State(state): State<HttpServerState<S>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Extension(api_key): Extension<ApiKeyIdentity>,
+    Json(request): Json<HttpPublishRequest>,
+) -> String
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    match http_publish_message(&state, &api_key, &addr, request).await {
+        Ok(()) => success_response("success"),
+        Err(e) => error_response(e.to_string()),
+    }
+}
+
+// Messages are published one by one, a rejected message does not stop the rest
+pub async fn http_publish_bulk<S>(
+    State(state): State<HttpServerState<S>>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    Extension(api_key): Extension<ApiKeyIdentity>,
+    Json(requests): Json<Vec<HttpPublishRequest>>,
+) -> String
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    let mut results = Vec::with_capacity(requests.len());
+    for request in requests {
+        let topic = request.topic.clone();
+        let result = match http_publish_message(&state, &api_key, &addr, request).await {
+            Ok(()) => HttpPublishResult {
+                topic,
+                success: true,
+                message: "".to_string(),
+            },
+            Err(e) => HttpPublishResult {
+                topic,
+                success: false,
+                message: e.to_string(),
+            },
+        };
+        results.push(result);
+    }
+    success_response(results)
+}
+
+// Goes through the same pipeline as a PUBLISH packet of an MQTT 5 client logged in as the
+// principal of the API key
+async fn http_publish_message<S>(
+    state: &HttpServerState<S>,
+    api_key: &ApiKeyIdentity,
+    addr: &SocketAddr,
+    request: HttpPublishRequest,
+) -> Result<(), CommonError>
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    if api_key.principal.is_empty() {
+        return Err(MQTTBrokerError::HttpPublishFailed(format!(
+            "API key {} has no principal to publish as",
+            api_key.name
+        ))
+        .into());
+    }
+    let (publish, publish_properties) = build_publish(request)?;
+    let connection = build_http_connection(&state.cache_manager, api_key, addr);
+
+    match publish_message(
+        &MQTTProtocol::MQTT5,
+        &state.cache_manager,
+        &state.client_poll,
+        &state.message_storage_adapter,
+        &state.auth_driver,
+        &connection,
+        &publish,
+        &publish_properties,
+    )
+    .await
+    {
+        Ok(_) => Ok(()),
+        Err(PublishError::Rejected(packet)) => {
+            Err(MQTTBrokerError::HttpPublishFailed(packet_reason(&packet)).into())
+        }
+        Err(PublishError::NotAuthorized(topic_name)) => Err(MQTTBrokerError::HttpPublishFailed(
+            format!("not authorized to publish to {}", topic_name),
+        )
+        .into()),
+        Err(PublishError::TopicNameInvalid(e))
+        | Err(PublishError::QuotaExceeded(e))
+        | Err(PublishError::Failed(e)) => Err(e),
+    }
+}
+
+fn build_publish(
+    request: HttpPublishRequest,
+) -> Result<(Publish, Option<PublishProperties>), CommonError> {
+    let payload = match request.encoding.as_str() {
+        "" | "plain" => Bytes::from(request.payload),
+        "base64" => match STANDARD.decode(request.payload) {
+            Ok(data) => Bytes::from(data),
+            Err(e) => {
+                return Err(MQTTBrokerError::HttpPublishFailed(format!(
+                    "payload is not valid base64, {}",
+                    e
+                ))
+                .into());
+            }
+        },
+        encoding => {
+            return Err(MQTTBrokerError::HttpPublishFailed(format!(
+                "unknown payload encoding {}",
+                encoding
+            ))
+            .into());
+        }
+    };
+    let Some(qos) = qos(request.qos) else {
+        return Err(
+            MQTTBrokerError::HttpPublishFailed(format!("invalid qos {}", request.qos)).into(),
+        );
+    };
+
+    let publish = Publish {
+        dup: false,
+        qos,
+        pkid: 0,
+        retain: request.retain,
+        topic: Bytes::from(request.topic),
+        payload,
+    };
+    let publish_properties = PublishProperties {
+        message_expiry_interval: request.message_expiry_interval,
+        user_properties: request.user_properties.into_iter().collect(),
+        content_type: request.content_type,
+        ..Default::default()
+    };
+    Ok((publish, Some(publish_properties)))
+}
+
+fn build_http_connection(
+    cache_manager: &Arc<CacheManager>,
+    api_key: &ApiKeyIdentity,
+    addr: &SocketAddr,
+) -> Connection {
+    let cluster = cache_manager.get_cluster_info();
+    let config = ConnectionConfig {
+        connect_id: HTTP_PUBLISH_CONNECT_ID,
+        client_id: format!("http_api_{}", api_key.name),
+        receive_maximum: cluster.protocol.receive_max,
+        max_packet_size: cluster.protocol.max_packet_size,
+        topic_alias_max: 0,
+        request_problem_info: 1,
+        keep_alive: 0,
+        source_ip_addr: addr.ip().to_string(),
+    };
+    let mut connection = Connection::new(config);
+    connection.login_success(api_key.principal.clone());
+    connection
+}
+
+// The reason string of a failed PUBACK/PUBREC, or the reason code when there is none
+fn packet_reason(packet: &MQTTPacket) -> String {
+    match packet {
+        MQTTPacket::PubAck(ack, properties) => properties
+            .as_ref()
+            .and_then(|properties| properties.reason_string.clone())
+            .unwrap_or_else(|| format!("{:?}", ack.reason)),
+        MQTTPacket::PubRec(rec, properties) => properties
+            .as_ref()
+            .and_then(|properties| properties.reason_string.clone())
+            .unwrap_or_else(|| format!("{:?}", rec.reason)),
+        MQTTPacket::Disconnect(disconnect, _) => format!("{:?}", disconnect.reason_code),
+        packet => format!("{:?}", packet),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::collections::BTreeMap;
+
+    use protocol::mqtt::common::QoS;
+
+    use super::{build_publish, HttpPublishRequest};
+
+    fn request(payload: &str, encoding: &str) -> HttpPublishRequest {
+        HttpPublishRequest {
+            topic: "t/1".to_string(),
+            payload: payload.to_string(),
+            encoding: encoding.to_string(),
+            qos: 1,
+            retain: true,
+            user_properties: BTreeMap::new(),
+            content_type: None,
+            message_expiry_interval: None,
+        }
+    }
+
+    #[test]
+    pub fn build_publish_test() {
+        let mut req = request("hello", "");
+        req.user_properties
+            .insert("k1".to_string(), "v1".to_string());
+        req.content_type = Some("text/plain".to_string());
+        req.message_expiry_interval = Some(60);
+        let (publish, properties) = build_publish(req).unwrap();
+        assert_eq!(publish.topic, "t/1");
+        assert_eq!(publish.payload, "hello");
+        assert_eq!(publish.qos, QoS::AtLeastOnce);
+        assert!(publish.retain);
+        let properties = properties.unwrap();
+        assert_eq!(
+            properties.user_properties,
+            vec![("k1".to_string(), "v1".to_string())]
+        );
+        assert_eq!(properties.content_type, Some("text/plain".to_string()));
+        assert_eq!(properties.message_expiry_interval, Some(60));
+
+        let (publish, _) = build_publish(request("aGVsbG8=", "base64")).unwrap();
+        assert_eq!(publish.payload, "hello");
 
-pub async fn http_publish(State(_): State<HttpServerState>) -> String {
-    success_response("data")
+        assert!(build_publish(request("not base64!", "base64")).is_err());
+        assert!(build_publish(request("hello", "hex")).is_err());
+        let mut req = request("hello", "plain");
+        req.qos = 3;
+        assert!(build_publish(req).is_err());
+    }
 }
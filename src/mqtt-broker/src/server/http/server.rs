@@ -13,31 +13,76 @@
 // limitations under the License.
 
 use std::net::SocketAddr;
+use std::sync::Arc;
 
-use axum::routing::get;
+use axum::middleware;
+use axum::routing::{delete, get, post};
 use axum::Router;
 use common_base::config::broker_mqtt::broker_mqtt_conf;
 use common_base::error::common::CommonError;
+use grpc_clients::poll::ClientPool;
 use log::info;
+use storage_adapter::storage::StorageAdapter;
 
-use super::connection::connection_list;
+use super::api_key::{api_key_auth, ApiKeyScope};
+use super::connection::{connection_detail, connection_kick, connection_list};
 use super::prometheus::metrics;
-use super::publish::http_publish;
+use super::publish::{http_publish, http_publish_bulk};
+use super::session::{session_delete, session_list};
+use super::subscribe::subscribe_list;
+use super::topic::topic_list;
+use crate::handler::cache::CacheManager;
+use crate::security::AuthDriver;
+use crate::server::connection_manager::ConnectionManager;
+use crate::subscribe::subscribe_manager::SubscribeManager;
 
 pub const ROUTE_PUBLISTH: &str = "/publish";
+pub const ROUTE_PUBLISTH_BULK: &str = "/publish/bulk";
 pub const ROUTE_CONNECTION: &str = "/connection";
+pub const ROUTE_CONNECTION_DETAIL: &str = "/connection/:client_id";
+pub const ROUTE_SESSION: &str = "/session";
+pub const ROUTE_SESSION_DETAIL: &str = "/session/:client_id";
+pub const ROUTE_SUBSCRIBE: &str = "/subscribe";
+pub const ROUTE_TOPIC: &str = "/topic";
 pub const ROUTE_METRICS: &str = "/metrics";
 
 #[derive(Clone)]
-pub struct HttpServerState {}
+pub struct HttpServerState<S> {
+    pub cache_manager: Arc<CacheManager>,
+    pub client_poll: Arc<ClientPool>,
+    pub message_storage_adapter: Arc<S>,
+    pub auth_driver: Arc<AuthDriver>,
+    pub connection_manager: Arc<ConnectionManager>,
+    pub subscribe_manager: Arc<SubscribeManager>,
+}
 
-impl HttpServerState {
-    pub fn new() -> Self {
-        Self {}
+impl<S> HttpServerState<S>
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    pub fn new(
+        cache_manager: Arc<CacheManager>,
+        client_poll: Arc<ClientPool>,
+        message_storage_adapter: Arc<S>,
+        auth_driver: Arc<AuthDriver>,
+        connection_manager: Arc<ConnectionManager>,
+        subscribe_manager: Arc<SubscribeManager>,
+    ) -> Self {
+        Self {
+            cache_manager,
+            client_poll,
+            message_storage_adapter,
+            auth_driver,
+            connection_manager,
+            subscribe_manager,
+        }
     }
 }
 
-pub async fn start_http_server(state: HttpServerState) -> Result<(), CommonError> {
+pub async fn start_http_server<S>(state: HttpServerState<S>) -> Result<(), CommonError>
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
     let config = broker_mqtt_conf();
     let ip: SocketAddr = format!("0.0.0.0:{}", config.http_port).parse()?;
     let app = routes_v1(state);
@@ -46,16 +91,44 @@ pub async fn start_http_server(state: HttpServerState) -> Result<(), CommonError
         "Broker HTTP Server start success. bind addr:{}",
         config.http_port
     );
-    axum::serve(listener, app).await?;
+    axum::serve(
+        listener,
+        app.into_make_service_with_connect_info::<SocketAddr>(),
+    )
+    .await?;
     Ok(())
 }
 
-fn routes_v1(state: HttpServerState) -> Router {
-    let meta = Router::new()
-        .route(ROUTE_PUBLISTH, get(http_publish))
+fn routes_v1<S>(state: HttpServerState<S>) -> Router
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    // Publishing and management require an API key with the scope of the group
+    let publish = Router::new()
+        .route(ROUTE_PUBLISTH, post(http_publish))
+        .route(ROUTE_PUBLISTH_BULK, post(http_publish_bulk))
+        .route_layer(middleware::from_fn_with_state(
+            ApiKeyScope::Publish,
+            api_key_auth,
+        ));
+
+    let admin = Router::new()
         .route(ROUTE_CONNECTION, get(connection_list))
-        .route(ROUTE_METRICS, get(metrics));
+        .route(
+            ROUTE_CONNECTION_DETAIL,
+            get(connection_detail).delete(connection_kick),
+        )
+        .route(ROUTE_SESSION, get(session_list))
+        .route(ROUTE_SESSION_DETAIL, delete(session_delete))
+        .route(ROUTE_SUBSCRIBE, get(subscribe_list))
+        .route(ROUTE_TOPIC, get(topic_list))
+        .route_layer(middleware::from_fn_with_state(
+            ApiKeyScope::Admin,
+            api_key_auth,
+        ));
+
+    let meta = Router::new().route(ROUTE_METRICS, get(metrics));
 
-    let app = Router::new().merge(meta);
+    let app = Router::new().merge(publish).merge(admin).merge(meta);
     app.with_state(state)
 }
@@ -0,0 +1,144 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::extract::{Path, Query, State};
+use common_base::http_response::{error_response, success_response};
+use metadata_struct::mqtt::session::MqttSession;
+use serde::Deserialize;
+use storage_adapter::storage::StorageAdapter;
+
+use super::page::paginate;
+use super::server::HttpServerState;
+use crate::handler::connection::kick_connection;
+use crate::handler::session::clear_local_session;
+use crate::storage::session::SessionStorage;
+
+#[derive(Deserialize, Debug, Default)]
+pub struct SessionListQuery {
+    pub page: Option<usize>,
+    pub limit: Option<usize>,
+    // Matches client IDs containing this value
+    pub client_id: Option<String>,
+    pub connected: Option<bool>,
+}
+
+pub async fn session_list<S>(
+    State(state): State<HttpServerState<S>>,
+    Query(query): Query<SessionListQuery>,
+) -> String
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    let mut sessions: Vec<MqttSession> = state
+        .cache_manager
+        .session_info
+        .iter()
+        .filter(|session| match_session(session, &query))
+        .map(|session| session.clone())
+        .collect();
+    sessions.sort_by(|a, b| a.client_id.cmp(&b.client_id));
+    success_response(paginate(sessions, query.page, query.limit))
+}
+
+// Disconnects the client if it is online, then removes the persisted session
+pub async fn session_delete<S>(
+    State(state): State<HttpServerState<S>>,
+    Path(client_id): Path<String>,
+) -> String
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    if !state.cache_manager.session_info.contains_key(&client_id) {
+        return error_response(format!("session {} does not exist", client_id));
+    }
+
+    if let Some(connect_id) = state.cache_manager.get_connect_id(&client_id) {
+        if let Err(e) = kick_connection(
+            &client_id,
+            connect_id,
+            &state.cache_manager,
+            &state.client_poll,
+            &state.connection_manager,
+            &state.subscribe_manager,
+        )
+        .await
+        {
+            return error_response(e.to_string());
+        }
+    }
+
+    let session_storage = SessionStorage::new(state.client_poll.clone());
+    if let Err(e) = session_storage.delete_session(client_id.clone()).await {
+        return error_response(e.to_string());
+    }
+
+    clear_local_session(
+        &state.cache_manager,
+        &state.subscribe_manager,
+        &state.message_storage_adapter,
+        &client_id,
+    )
+    .await;
+    success_response("success")
+}
+
+fn match_session(session: &MqttSession, query: &SessionListQuery) -> bool {
+    if let Some(client_id) = &query.client_id {
+        if !session.client_id.contains(client_id.as_str()) {
+            return false;
+        }
+    }
+    if let Some(connected) = query.connected {
+        if session.connection_id.is_some() != connected {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod test {
+    use metadata_struct::mqtt::session::MqttSession;
+
+    use super::{match_session, SessionListQuery};
+
+    #[test]
+    fn match_session_test() {
+        let mut session = MqttSession::new("sensor-001".to_string(), 3600, false, None);
+
+        assert!(match_session(&session, &SessionListQuery::default()));
+        let connected = SessionListQuery {
+            connected: Some(true),
+            ..Default::default()
+        };
+        assert!(!match_session(&session, &connected));
+
+        session.connection_id = Some(1);
+        assert!(match_session(&session, &connected));
+        assert!(match_session(
+            &session,
+            &SessionListQuery {
+                client_id: Some("sensor".to_string()),
+                ..Default::default()
+            }
+        ));
+        assert!(!match_session(
+            &session,
+            &SessionListQuery {
+                client_id: Some("gateway".to_string()),
+                ..Default::default()
+            }
+        ));
+    }
+}
@@ -0,0 +1,120 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::extract::{Query, State};
+use common_base::http_response::success_response;
+use serde::{Deserialize, Serialize};
+use storage_adapter::storage::StorageAdapter;
+
+use super::page::paginate;
+use super::server::HttpServerState;
+use crate::subscribe::subscriber::SubscribeData;
+
+#[derive(Deserialize, Debug, Default)]
+pub struct SubscribeListQuery {
+    pub page: Option<usize>,
+    pub limit: Option<usize>,
+    pub client_id: Option<String>,
+    // Exact topic filter, including the $share prefix of shared subscriptions
+    pub filter: Option<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct SubscribeInfo {
+    pub client_id: String,
+    pub filter: String,
+    pub protocol_version: u8,
+    pub qos: u8,
+    pub no_local: bool,
+    pub retain_as_published: bool,
+    pub retain_handling: u8,
+}
+
+pub async fn subscribe_list<S>(
+    State(state): State<HttpServerState<S>>,
+    Query(query): Query<SubscribeListQuery>,
+) -> String
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    let mut subscribes = Vec::new();
+    for client in state.cache_manager.subscribe_filter.iter() {
+        if let Some(client_id) = &query.client_id {
+            if client.key() != client_id {
+                continue;
+            }
+        }
+        for subscribe in client.value().iter() {
+            if let Some(filter) = &query.filter {
+                if subscribe.key() != filter {
+                    continue;
+                }
+            }
+            subscribes.push(subscribe_info(client.key(), subscribe.value()));
+        }
+    }
+    subscribes.sort_by(|a, b| {
+        a.client_id
+            .cmp(&b.client_id)
+            .then_with(|| a.filter.cmp(&b.filter))
+    });
+    success_response(paginate(subscribes, query.page, query.limit))
+}
+
+fn subscribe_info(client_id: &str, subscribe: &SubscribeData) -> SubscribeInfo {
+    SubscribeInfo {
+        client_id: client_id.to_string(),
+        filter: subscribe.filter.path.clone(),
+        protocol_version: subscribe.protocol.clone().into(),
+        qos: subscribe.filter.qos.into(),
+        no_local: subscribe.filter.nolocal,
+        retain_as_published: subscribe.filter.preserve_retain,
+        retain_handling: subscribe.filter.retain_forward_rule.clone().into(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use protocol::mqtt::common::{Filter, MQTTProtocol, QoS, RetainForwardRule};
+
+    use super::{subscribe_info, SubscribeInfo};
+    use crate::subscribe::subscriber::SubscribeData;
+
+    #[test]
+    fn subscribe_info_test() {
+        let subscribe = SubscribeData {
+            protocol: MQTTProtocol::MQTT5,
+            filter: Filter {
+                path: "sensor/+/temperature".to_string(),
+                qos: QoS::AtLeastOnce,
+                nolocal: true,
+                preserve_retain: false,
+                retain_forward_rule: RetainForwardRule::OnNewSubscribe,
+            },
+            subscribe_properties: None,
+        };
+        assert_eq!(
+            subscribe_info("sensor-001", &subscribe),
+            SubscribeInfo {
+                client_id: "sensor-001".to_string(),
+                filter: "sensor/+/temperature".to_string(),
+                protocol_version: 5,
+                qos: 1,
+                no_local: true,
+                retain_as_published: false,
+                retain_handling: 1,
+            }
+        );
+    }
+}
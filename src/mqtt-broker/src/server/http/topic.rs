@@ -0,0 +1,142 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use axum::extract::{Query, State};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use common_base::http_response::success_response;
+use metadata_struct::mqtt::message::MqttMessage;
+use metadata_struct::mqtt::topic::MqttTopic;
+use serde::{Deserialize, Serialize};
+use storage_adapter::storage::StorageAdapter;
+
+use super::page::paginate;
+use super::server::HttpServerState;
+
+#[derive(Deserialize, Debug, Default)]
+pub struct TopicListQuery {
+    pub page: Option<usize>,
+    pub limit: Option<usize>,
+    // Matches topic names containing this value
+    pub topic_name: Option<String>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct TopicInfo {
+    pub topic_id: String,
+    pub topic_name: String,
+    pub retain_message: Option<RetainMessageInfo>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct RetainMessageInfo {
+    pub client_id: String,
+    pub qos: u8,
+    pub payload: String,
+    // plain for UTF-8 payloads, base64 otherwise
+    pub encoding: String,
+    pub create_time: u64,
+    pub expired_at: Option<u64>,
+}
+
+pub async fn topic_list<S>(
+    State(state): State<HttpServerState<S>>,
+    Query(query): Query<TopicListQuery>,
+) -> String
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    let mut topics: Vec<TopicInfo> = state
+        .cache_manager
+        .topic_info
+        .iter()
+        .filter(|topic| match &query.topic_name {
+            Some(topic_name) => topic.topic_name.contains(topic_name.as_str()),
+            None => true,
+        })
+        .map(|topic| topic_info(&topic))
+        .collect();
+    topics.sort_by(|a, b| a.topic_name.cmp(&b.topic_name));
+    success_response(paginate(topics, query.page, query.limit))
+}
+
+fn topic_info(topic: &MqttTopic) -> TopicInfo {
+    TopicInfo {
+        topic_id: topic.topic_id.clone(),
+        topic_name: topic.topic_name.clone(),
+        retain_message: retain_message_info(topic),
+    }
+}
+
+// A deleted retained message is cached as an empty value
+fn retain_message_info(topic: &MqttTopic) -> Option<RetainMessageInfo> {
+    let data = topic.retain_message.as_ref()?;
+    if data.is_empty() {
+        return None;
+    }
+    let message: MqttMessage = serde_json::from_slice(data).ok()?;
+    let (payload, encoding) = match std::str::from_utf8(&message.payload) {
+        Ok(payload) => (payload.to_string(), "plain"),
+        Err(_) => (STANDARD.encode(&message.payload), "base64"),
+    };
+    Some(RetainMessageInfo {
+        client_id: message.client_id,
+        qos: message.qos.into(),
+        payload,
+        encoding: encoding.to_string(),
+        create_time: message.create_time,
+        expired_at: topic.retain_message_expired_at,
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::Bytes;
+    use metadata_struct::mqtt::message::MqttMessage;
+    use metadata_struct::mqtt::topic::MqttTopic;
+    use protocol::mqtt::common::QoS;
+
+    use super::retain_message_info;
+
+    #[test]
+    fn retain_message_info_test() {
+        let mut topic = MqttTopic::new("1".to_string(), "sensor/1".to_string());
+        assert!(retain_message_info(&topic).is_none());
+
+        topic.retain_message = Some(Vec::new());
+        assert!(retain_message_info(&topic).is_none());
+
+        let mut message = MqttMessage {
+            client_id: "sensor-001".to_string(),
+            qos: QoS::AtLeastOnce,
+            payload: Bytes::from("21.5"),
+            create_time: 100,
+            ..Default::default()
+        };
+        topic.retain_message = Some(message.encode());
+        topic.retain_message_expired_at = Some(200);
+        let info = retain_message_info(&topic).unwrap();
+        assert_eq!(info.client_id, "sensor-001");
+        assert_eq!(info.qos, 1);
+        assert_eq!(info.payload, "21.5");
+        assert_eq!(info.encoding, "plain");
+        assert_eq!(info.expired_at, Some(200));
+
+        message.payload = Bytes::from(vec![0xff, 0x00]);
+        topic.retain_message = Some(message.encode());
+        let info = retain_message_info(&topic).unwrap();
+        assert_eq!(info.payload, "/wA=");
+        assert_eq!(info.encoding, "base64");
+    }
+}
@@ -0,0 +1,226 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use futures_util::StreamExt;
+use log::{debug, error, info};
+use protocol::mqtt::codec::MqttCodec;
+use quinn::Endpoint;
+use tokio::select;
+use tokio::sync::mpsc::{Receiver, Sender};
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::sleep;
+use tokio_util::codec::{FramedRead, FramedWrite};
+
+use crate::handler::cache::CacheManager;
+use crate::handler::connection::release_unlogged_connection;
+use crate::handler::validator::establish_connection_check;
+use crate::observability::metrics::packets::{
+    record_received_error_metrics, record_received_metrics,
+};
+use crate::observability::slow::request::try_record_total_request_ms;
+use crate::security::login::is_ip_blacklist;
+use crate::server::connection::{NetworkConnection, NetworkConnectionType};
+use crate::server::connection_manager::ConnectionManager;
+use crate::server::packet::RequestPackage;
+
+pub(crate) async fn acceptor_quic_process(
+    accept_thread_num: usize,
+    endpoint: Endpoint,
+    stop_sx: broadcast::Sender<bool>,
+    connection_manager: Arc<ConnectionManager>,
+    request_queue_sx: Sender<RequestPackage>,
+    cache_manager: Arc<CacheManager>,
+) {
+    for index in 1..=accept_thread_num {
+        let endpoint = endpoint.clone();
+        let connection_manager = connection_manager.clone();
+        let mut stop_rx = stop_sx.subscribe();
+        let raw_request_queue_sx = request_queue_sx.clone();
+        let cache_manager = cache_manager.clone();
+        tokio::spawn(async move {
+            debug!("QUIC Server acceptor thread {} start successfully.", index);
+            loop {
+                select! {
+                    val = stop_rx.recv() =>{
+                        if let Ok(flag) = val {
+                            if flag {
+                                endpoint.close(0u32.into(), b"server stop");
+                                debug!("QUIC Server acceptor thread {} stopped successfully.",index);
+                                break;
+                            }
+                        }
+                    }
+                    val = endpoint.accept()=>{
+                        if let Some(incoming) = val {
+                            if is_ip_blacklist(&cache_manager, &incoming.remote_address()) {
+                                info!("quic connection from blacklisted IP {:?} is rejected",incoming.remote_address());
+                                incoming.refuse();
+                                continue;
+                            }
+                            // The handshake must not block the acceptor, so each connection is set up in its own task
+                            let connection_manager = connection_manager.clone();
+                            let request_queue_sx = raw_request_queue_sx.clone();
+                            let cache_manager = cache_manager.clone();
+                            tokio::spawn(async move {
+                                quic_establish_connection(incoming, connection_manager, request_queue_sx, cache_manager).await;
+                            });
+                        } else {
+                            debug!("QUIC Server endpoint is closed, acceptor thread {} exits.", index);
+                            break;
+                        }
+                    }
+                };
+            }
+        });
+    }
+}
+
+async fn quic_establish_connection(
+    incoming: quinn::Incoming,
+    connection_manager: Arc<ConnectionManager>,
+    request_queue_sx: Sender<RequestPackage>,
+    cache_manager: Arc<CacheManager>,
+) {
+    let quic_connection = match incoming.await {
+        Ok(conn) => conn,
+        Err(e) => {
+            error!("QUIC handshake failed with error message :{:?}", e);
+            return;
+        }
+    };
+    let addr = quic_connection.remote_address();
+    info!("accept quic connection:{:?}", addr);
+
+    // MQTT over QUIC carries the whole session on the first bidirectional stream opened by the client
+    let (w_stream, r_stream) = match quic_connection.accept_bi().await {
+        Ok(stream) => stream,
+        Err(e) => {
+            error!(
+                "QUIC connection {:?} failed to open stream with error message :{:?}",
+                addr, e
+            );
+            return;
+        }
+    };
+
+    let codec = MqttCodec::new(None);
+    let read_frame_stream = FramedRead::new(r_stream, codec.clone());
+    let mut write_frame_stream = FramedWrite::new(w_stream, codec.clone());
+
+    if !establish_connection_check(
+        &addr,
+        &connection_manager,
+        &cache_manager,
+        &NetworkConnectionType::Quic,
+        &mut write_frame_stream,
+    )
+    .await
+    {
+        return;
+    }
+
+    let (connection_stop_sx, connection_stop_rx) = mpsc::channel::<bool>(1);
+    let connection = NetworkConnection::new(
+        NetworkConnectionType::Quic,
+        addr,
+        Some(connection_stop_sx.clone()),
+    );
+    connection_manager.add_connection(connection.clone());
+    connection_manager.add_quic_write(connection.connection_id, write_frame_stream);
+
+    read_quic_frame_process(
+        read_frame_stream,
+        quic_connection,
+        connection,
+        connection_manager,
+        request_queue_sx,
+        connection_stop_rx,
+        cache_manager,
+    );
+}
+
+fn read_quic_frame_process(
+    mut read_frame_stream: FramedRead<quinn::RecvStream, MqttCodec>,
+    quic_connection: quinn::Connection,
+    mut connection: NetworkConnection,
+    connection_manager: Arc<ConnectionManager>,
+    request_queue_sx: Sender<RequestPackage>,
+    mut connection_stop_rx: Receiver<bool>,
+    cache_manager: Arc<CacheManager>,
+) {
+    let network_type = NetworkConnectionType::Quic;
+    tokio::spawn(async move {
+        loop {
+            select! {
+                val = connection_stop_rx.recv() =>{
+                    if let Some(flag) = val{
+                        if flag {
+                            debug!("QUIC connection 【{}】 acceptor thread stopped successfully.",connection.connection_id);
+                            break;
+                        }
+                    }
+                }
+                val = read_frame_stream.next()=>{
+                    if let Some(pkg) = val {
+                        match pkg {
+                            Ok(pack) => {
+                                // The client address changes when the connection migrates, the session stays the same
+                                let addr = quic_connection.remote_address();
+                                if addr != connection.addr {
+                                    connection_manager.update_connect_addr(connection.connection_id, addr);
+                                    connection.addr = addr;
+                                }
+
+                                record_received_metrics(&connection, &pack, &network_type);
+                                debug!("revc quic packet:{:?}", pack);
+                                let package =
+                                    RequestPackage::new(connection.connection_id, connection.addr, pack);
+                                match request_queue_sx.send(package.clone()).await {
+                                    Ok(_) => {
+                                        try_record_total_request_ms(cache_manager.clone(),package.clone());
+                                    }
+                                    Err(err) => error!("Failed to write data to the request queue, error message: {:?}",err),
+                                }
+
+                                if let Some(wait) = cache_manager.rate_limiter.read_pause_time(connection.connection_id) {
+                                    // MQTT 3 clients over the publish rate limits are throttled by pausing the reads
+                                    sleep(wait).await;
+                                }
+                            }
+                            Err(e) => {
+                                record_received_error_metrics(network_type.clone());
+                                debug!("QUIC connection parsing packet format error message :{:?}",e)
+                            }
+                        }
+                    } else {
+                        debug!("QUIC connection 【{}】 closed by the client.",connection.connection_id);
+                        release_unlogged_connection(connection.connection_id, &cache_manager, &connection_manager).await;
+                        break;
+                    }
+                }
+            }
+        }
+
+        // Give the client a moment to receive the last packets (e.g. DISCONNECT) before closing
+        select! {
+            _ = quic_connection.closed() => {}
+            _ = sleep(Duration::from_secs(3)) => {
+                quic_connection.close(0u32.into(), b"connection closed");
+            }
+        }
+    });
+}
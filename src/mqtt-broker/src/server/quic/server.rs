@@ -0,0 +1,138 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::{AddrParseError, SocketAddr};
+use std::path::Path;
+use std::sync::Arc;
+use std::time::Duration;
+
+use common_base::config::broker_mqtt::broker_mqtt_conf;
+use common_base::error::common::CommonError;
+use grpc_clients::poll::ClientPool;
+use log::info;
+use quinn::crypto::rustls::QuicServerConfig;
+use quinn::{Endpoint, ServerConfig, TransportConfig};
+use storage_adapter::storage::StorageAdapter;
+use tokio::sync::{broadcast, mpsc};
+use tokio_rustls::rustls;
+
+use crate::handler::cache::CacheManager;
+use crate::handler::command::Command;
+use crate::security::AuthDriver;
+use crate::server::connection_manager::ConnectionManager;
+use crate::server::packet::{RequestPackage, ResponsePackage};
+use crate::server::quic::quic_server::acceptor_quic_process;
+use crate::server::tcp::handler::handler_process;
+use crate::server::tcp::response::response_process;
+use crate::server::tcp::tls_server::{load_certs, load_key};
+use crate::subscribe::subscribe_manager::SubscribeManager;
+
+pub const MQTT_QUIC_ALPN: &[u8] = b"mqtt";
+
+pub async fn start_quic_server<S>(
+    sucscribe_manager: Arc<SubscribeManager>,
+    cache_manager: Arc<CacheManager>,
+    connection_manager: Arc<ConnectionManager>,
+    message_storage_adapter: Arc<S>,
+    client_poll: Arc<ClientPool>,
+    stop_sx: broadcast::Sender<bool>,
+    auth_driver: Arc<AuthDriver>,
+) -> Result<(), CommonError>
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    let conf = broker_mqtt_conf();
+    let command = Command::new(
+        cache_manager.clone(),
+        message_storage_adapter.clone(),
+        sucscribe_manager.clone(),
+        client_poll.clone(),
+        connection_manager.clone(),
+        auth_driver.clone(),
+    );
+
+    let server_config = build_quic_server_config().map_err(CommonError::CommmonError)?;
+
+    let port = conf.network.quic_port;
+    let addr: SocketAddr = format!("0.0.0.0:{}", port)
+        .parse()
+        .map_err(|e: AddrParseError| CommonError::CommmonError(e.to_string()))?;
+    let endpoint = Endpoint::server(server_config, addr)
+        .map_err(|e| CommonError::CommmonError(e.to_string()))?;
+
+    let (request_queue_sx, request_queue_rx) = mpsc::channel::<RequestPackage>(1000);
+    let (response_queue_sx, response_queue_rx) = mpsc::channel::<ResponsePackage>(1000);
+
+    acceptor_quic_process(
+        conf.tcp_thread.accept_thread_num,
+        endpoint,
+        stop_sx.clone(),
+        connection_manager.clone(),
+        request_queue_sx,
+        cache_manager.clone(),
+    )
+    .await;
+
+    handler_process(
+        conf.tcp_thread.handler_thread_num,
+        request_queue_rx,
+        connection_manager.clone(),
+        response_queue_sx,
+        stop_sx.clone(),
+        command,
+    )
+    .await;
+
+    response_process(
+        conf.tcp_thread.response_thread_num,
+        connection_manager,
+        cache_manager,
+        sucscribe_manager,
+        response_queue_rx,
+        client_poll,
+        stop_sx,
+    )
+    .await;
+
+    info!("MQTT QUIC Server started successfully, listening port: {port}");
+    Ok(())
+}
+
+fn build_quic_server_config() -> Result<ServerConfig, String> {
+    let conf = broker_mqtt_conf();
+    let certs = load_certs(Path::new(&conf.network.tls_cert)).map_err(|e| e.to_string())?;
+    let key = load_key(Path::new(&conf.network.tls_key)).map_err(|e| e.to_string())?;
+
+    // QUIC only runs on TLS 1.3
+    let mut tls_config = rustls::ServerConfig::builder_with_provider(Arc::new(
+        rustls::crypto::ring::default_provider(),
+    ))
+    .with_protocol_versions(&[&rustls::version::TLS13])
+    .map_err(|e| e.to_string())?
+    .with_no_client_auth()
+    .with_single_cert(certs, key)
+    .map_err(|e| e.to_string())?;
+    tls_config.alpn_protocols = vec![MQTT_QUIC_ALPN.to_vec()];
+
+    let quic_tls_config = QuicServerConfig::try_from(tls_config).map_err(|e| e.to_string())?;
+    let mut server_config = ServerConfig::with_crypto(Arc::new(quic_tls_config));
+
+    // Connection migration keeps the session alive when the client switches networks
+    server_config.migration(true);
+
+    let mut transport_config = TransportConfig::default();
+    transport_config.keep_alive_interval(Some(Duration::from_secs(10)));
+    server_config.transport_config(Arc::new(transport_config));
+    Ok(server_config)
+}
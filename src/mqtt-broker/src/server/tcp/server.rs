@@ -30,6 +30,9 @@ use crate::server::packet::{RequestPackage, ResponsePackage};
 use crate::server::tcp::handler::handler_process;
 use crate::server::tcp::response::response_process;
 use crate::server::tcp::tcp_server::acceptor_process;
+use crate::server::tcp::tls_psk_server::{
+    acceptor_tls_psk_process, build_psk_acceptor, load_psk_store,
+};
 use crate::server::tcp::tls_server::acceptor_tls_process;
 use crate::subscribe::subscribe_manager::SubscribeManager;
 
@@ -72,15 +75,28 @@ pub async fn start_tcp_server<S>(
     server.start(conf.network.tcp_port).await;
 
     let mut server = TcpServer::<S>::new(
-        command,
+        command.clone(),
         proc_config,
         stop_sx.clone(),
-        connection_manager,
+        connection_manager.clone(),
         sucscribe_manager.clone(),
-        cache_manager,
-        client_poll,
+        cache_manager.clone(),
+        client_poll.clone(),
     );
     server.start_tls(conf.network.tcps_port).await;
+
+    if conf.network.tcps_psk_port > 0 {
+        let mut server = TcpServer::<S>::new(
+            command,
+            proc_config,
+            stop_sx.clone(),
+            connection_manager,
+            sucscribe_manager.clone(),
+            cache_manager,
+            client_poll,
+        );
+        server.start_tls_psk(conf.network.tcps_psk_port).await;
+    }
 }
 
 // U: codec: encoder + decoder
@@ -199,6 +215,7 @@ where
             self.network_connection_type.clone(),
             self.connection_manager.clone(),
             request_queue_sx,
+            self.cache_manager.clone(),
         )
         .await;
 
@@ -225,4 +242,58 @@ where
         self.network_connection_type = NetworkConnectionType::Tls;
         info!("MQTT TCP TLS Server started successfully, listening port: {port}");
     }
+
+    pub async fn start_tls_psk(&mut self, port: u32) {
+        let listener = match TcpListener::bind(format!("0.0.0.0:{}", port)).await {
+            Ok(tl) => tl,
+            Err(e) => {
+                panic!("{}", e.to_string());
+            }
+        };
+        let psk_store = load_psk_store(self.client_poll.clone(), self.stop_sx.clone());
+        let psk_acceptor = match build_psk_acceptor(psk_store) {
+            Ok(acceptor) => acceptor,
+            Err(e) => {
+                panic!("{}", e);
+            }
+        };
+        let (request_queue_sx, request_queue_rx) = mpsc::channel::<RequestPackage>(1000);
+        let (response_queue_sx, response_queue_rx) = mpsc::channel::<ResponsePackage>(1000);
+
+        let arc_listener = Arc::new(listener);
+
+        acceptor_tls_psk_process(
+            self.accept_thread_num,
+            arc_listener.clone(),
+            self.stop_sx.clone(),
+            self.connection_manager.clone(),
+            request_queue_sx,
+            self.cache_manager.clone(),
+            Arc::new(psk_acceptor),
+        )
+        .await;
+
+        handler_process(
+            self.handler_process_num,
+            request_queue_rx,
+            self.connection_manager.clone(),
+            response_queue_sx,
+            self.stop_sx.clone(),
+            self.command.clone(),
+        )
+        .await;
+
+        response_process(
+            self.response_process_num,
+            self.connection_manager.clone(),
+            self.cache_manager.clone(),
+            self.subscribe_manager.clone(),
+            response_queue_rx,
+            self.client_poll.clone(),
+            self.stop_sx.clone(),
+        )
+        .await;
+        self.network_connection_type = NetworkConnectionType::TlsPsk;
+        info!("MQTT TCP TLS-PSK Server started successfully, listening port: {port}");
+    }
 }
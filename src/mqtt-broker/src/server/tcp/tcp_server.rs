@@ -26,11 +26,13 @@ use tokio::{io, select};
 use tokio_util::codec::{FramedRead, FramedWrite};
 
 use crate::handler::cache::CacheManager;
+use crate::handler::connection::release_unlogged_connection;
 use crate::handler::validator::tcp_establish_connection_check;
 use crate::observability::metrics::packets::{
     record_received_error_metrics, record_received_metrics,
 };
 use crate::observability::slow::request::try_record_total_request_ms;
+use crate::security::login::is_ip_blacklist;
 use crate::server::connection::{NetworkConnection, NetworkConnectionType};
 use crate::server::connection_manager::ConnectionManager;
 use crate::server::packet::RequestPackage;
@@ -67,13 +69,17 @@ pub(crate) async fn acceptor_process(
                         match val{
                             Ok((stream, addr)) => {
                                 info!("accept tcp connection:{:?}",addr);
+                                if is_ip_blacklist(&cache_manager, &addr) {
+                                    info!("tcp connection from blacklisted IP {:?} is rejected",addr);
+                                    continue;
+                                }
 
                                 let (r_stream, w_stream) = io::split(stream);
                                 let codec = MqttCodec::new(None);
                                 let read_frame_stream = FramedRead::new(r_stream, codec.clone());
                                 let mut  write_frame_stream = FramedWrite::new(w_stream, codec.clone());
 
-                                if !tcp_establish_connection_check(&addr,&connection_manager,&mut write_frame_stream).await{
+                                if !tcp_establish_connection_check(&addr,&connection_manager,&cache_manager,&mut write_frame_stream).await{
                                     continue;
                                 }
 
@@ -86,7 +92,7 @@ pub(crate) async fn acceptor_process(
                                 connection_manager.add_connection(connection.clone());
                                 connection_manager.add_tcp_write(connection.connection_id, write_frame_stream);
 
-                                read_frame_process(read_frame_stream,connection,raw_request_queue_sx.clone(),connection_stop_rx,network_type.clone(),cache_manager.clone());
+                                read_frame_process(read_frame_stream,connection,raw_request_queue_sx.clone(),connection_stop_rx,network_type.clone(),cache_manager.clone(),connection_manager.clone());
                             }
                             Err(e) => {
                                 error!("TCP accept failed to create connection with error message :{:?}",e);
@@ -106,6 +112,7 @@ fn read_frame_process(
     mut connection_stop_rx: Receiver<bool>,
     network_type: NetworkConnectionType,
     cache_manager: Arc<CacheManager>,
+    connection_manager: Arc<ConnectionManager>,
 ) {
     tokio::spawn(async move {
         loop {
@@ -134,14 +141,21 @@ fn read_frame_process(
                                     }
                                     Err(err) => error!("Failed to write data to the request queue, error message: {:?}",err),
                                 }
+
+                                if let Some(wait) = cache_manager.rate_limiter.read_pause_time(connection.connection_id) {
+                                    // MQTT 3 clients over the publish rate limits are throttled by pausing the reads
+                                    sleep(wait).await;
+                                }
                             }
                             Err(e) => {
                                 record_received_error_metrics(network_type.clone());
                                 debug!("TCP connection parsing packet format error message :{:?}",e)
                             }
                         }
-                    }else {
-                        sleep(Duration::from_millis(10)).await;
+                    } else {
+                        debug!("TCP connection 【{}】 closed by the client.",connection.connection_id);
+                        release_unlogged_connection(connection.connection_id, &cache_manager, &connection_manager).await;
+                        break;
                     }
                 }
             }
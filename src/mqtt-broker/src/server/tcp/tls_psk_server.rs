@@ -0,0 +1,262 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::net::SocketAddr;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+use common_base::config::broker_mqtt::broker_mqtt_conf;
+use grpc_clients::poll::ClientPool;
+use log::{debug, error, info, warn};
+use openssl::ssl::{Ssl, SslAcceptor, SslMethod, SslVersion};
+use protocol::mqtt::codec::MqttCodec;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::{broadcast, mpsc};
+use tokio::time::{sleep, timeout};
+use tokio_openssl::SslStream;
+use tokio_util::codec::{FramedRead, FramedWrite};
+
+use crate::handler::cache::CacheManager;
+use crate::handler::validator::establish_connection_check;
+use crate::security::login::is_ip_blacklist;
+use crate::security::login::psk::PskStore;
+use crate::server::connection::{NetworkConnection, NetworkConnectionType};
+use crate::server::connection_manager::ConnectionManager;
+use crate::server::packet::RequestPackage;
+use crate::server::tcp::tls_server::read_tls_frame_process;
+
+// TLS 1.2 PSK cipher suites, the listener has no certificate so TLS 1.3 is not offered
+const PSK_CIPHER_LIST: &str = "PSK-AES256-GCM-SHA384:PSK-AES128-GCM-SHA256:PSK-CHACHA20-POLY1305:PSK-AES256-CBC-SHA384:PSK-AES128-CBC-SHA256";
+// How often the PSK list is read again from the placement center
+const PSK_PLACEMENT_REFRESH_SECS: u64 = 30;
+// How long a client may take to finish the TLS-PSK handshake
+const PSK_HANDSHAKE_TIMEOUT_SECS: u64 = 10;
+
+pub(crate) fn build_psk_acceptor(psk_store: Arc<PskStore>) -> Result<SslAcceptor, String> {
+    let mut builder =
+        SslAcceptor::mozilla_intermediate_v5(SslMethod::tls_server()).map_err(|e| e.to_string())?;
+    builder
+        .set_max_proto_version(Some(SslVersion::TLS1_2))
+        .map_err(|e| e.to_string())?;
+    builder
+        .set_cipher_list(PSK_CIPHER_LIST)
+        .map_err(|e| e.to_string())?;
+    builder.set_psk_server_callback(move |_, identity, psk| {
+        let key = identity
+            .and_then(|identity| std::str::from_utf8(identity).ok())
+            .and_then(|identity| psk_store.get(identity));
+        match key {
+            Some(key) if key.len() <= psk.len() => {
+                psk[..key.len()].copy_from_slice(&key);
+                Ok(key.len())
+            }
+            // an empty key fails the handshake
+            _ => Ok(0),
+        }
+    });
+    Ok(builder.build())
+}
+
+// Loads the PSK file, and keeps the list from the placement center up to date when enabled
+pub(crate) fn load_psk_store(
+    client_poll: Arc<ClientPool>,
+    stop_sx: broadcast::Sender<bool>,
+) -> Arc<PskStore> {
+    let conf = broker_mqtt_conf();
+    let psk_store = Arc::new(PskStore::new());
+    if !conf.network.tls_psk_file.is_empty() {
+        match psk_store.load_file(&conf.network.tls_psk_file) {
+            Ok(num) => info!(
+                "Loaded {} PSK identities from {}",
+                num, conf.network.tls_psk_file
+            ),
+            Err(e) => {
+                panic!("{}", e.to_string());
+            }
+        }
+    }
+
+    if conf.network.tls_psk_from_placement {
+        let store = psk_store.clone();
+        let mut stop_rx = stop_sx.subscribe();
+        tokio::spawn(async move {
+            loop {
+                if let Err(e) = store
+                    .load_placement(
+                        client_poll.clone(),
+                        conf.placement_center.clone(),
+                        &conf.cluster_name,
+                    )
+                    .await
+                {
+                    warn!(
+                        "Failed to load the PSK list from the placement center, error message: {}",
+                        e
+                    );
+                }
+                select! {
+                    val = stop_rx.recv() => {
+                        if matches!(val, Ok(true) | Err(RecvError::Closed)) {
+                            break;
+                        }
+                    }
+                    _ = sleep(Duration::from_secs(PSK_PLACEMENT_REFRESH_SECS)) => {}
+                }
+            }
+        });
+    }
+    psk_store
+}
+
+pub(crate) async fn acceptor_tls_psk_process(
+    accept_thread_num: usize,
+    listener_arc: Arc<TcpListener>,
+    stop_sx: broadcast::Sender<bool>,
+    connection_manager: Arc<ConnectionManager>,
+    request_queue_sx: Sender<RequestPackage>,
+    cache_manager: Arc<CacheManager>,
+    psk_acceptor: Arc<SslAcceptor>,
+) {
+    for index in 1..=accept_thread_num {
+        let listener = listener_arc.clone();
+        let connection_manager = connection_manager.clone();
+        let mut stop_rx = stop_sx.subscribe();
+        let raw_request_queue_sx = request_queue_sx.clone();
+        let psk_acceptor = psk_acceptor.clone();
+        let cache_manager = cache_manager.clone();
+        tokio::spawn(async move {
+            debug!(
+                "TCP TLS-PSK Server acceptor thread {} start successfully.",
+                index
+            );
+            loop {
+                select! {
+                    val = stop_rx.recv() =>{
+                        if let Ok(flag) = val {
+                            if flag {
+                                debug!("TCP TLS-PSK Server acceptor thread {} stopped successfully.",index);
+                                break;
+                            }
+                        }
+                    }
+                    val = listener.accept()=>{
+                        match val{
+                            Ok((stream, addr)) => {
+                                info!("accept tcp tls-psk connection:{:?}",addr);
+                                if is_ip_blacklist(&cache_manager, &addr) {
+                                    info!("tcp tls-psk connection from blacklisted IP {:?} is rejected",addr);
+                                    continue;
+                                }
+                                tokio::spawn(tls_psk_handshake_process(
+                                    stream,
+                                    addr,
+                                    psk_acceptor.clone(),
+                                    connection_manager.clone(),
+                                    cache_manager.clone(),
+                                    raw_request_queue_sx.clone(),
+                                ));
+                            }
+                            Err(e) => {
+                                error!("TCP accept failed to create connection with error message :{:?}",e);
+                            }
+                        }
+                    }
+                };
+            }
+        });
+    }
+}
+
+// Runs in its own task so a slow or silent client can't hold up the acceptor thread
+async fn tls_psk_handshake_process(
+    stream: TcpStream,
+    addr: SocketAddr,
+    psk_acceptor: Arc<SslAcceptor>,
+    connection_manager: Arc<ConnectionManager>,
+    cache_manager: Arc<CacheManager>,
+    request_queue_sx: Sender<RequestPackage>,
+) {
+    let mut stream =
+        match Ssl::new(psk_acceptor.context()).and_then(|ssl| SslStream::new(ssl, stream)) {
+            Ok(stream) => stream,
+            Err(e) => {
+                error!("TLS-PSK Accepter failed to create the session with error message :{e:?}");
+                return;
+            }
+        };
+    match timeout(
+        Duration::from_secs(PSK_HANDSHAKE_TIMEOUT_SECS),
+        Pin::new(&mut stream).accept(),
+    )
+    .await
+    {
+        Ok(Ok(())) => {}
+        Ok(Err(e)) => {
+            error!("TLS-PSK Accepter failed to read Stream with error message :{e:?}");
+            return;
+        }
+        Err(_) => {
+            warn!(
+                "TLS-PSK handshake from {:?} did not finish within {}s, the connection is closed",
+                addr, PSK_HANDSHAKE_TIMEOUT_SECS
+            );
+            return;
+        }
+    }
+    let psk_identity = stream
+        .ssl()
+        .psk_identity()
+        .map(|identity| String::from_utf8_lossy(identity).to_string());
+
+    let (r_stream, w_stream) = tokio::io::split(stream);
+    let codec = MqttCodec::new(None);
+    let read_frame_stream = FramedRead::new(r_stream, codec.clone());
+    let mut write_frame_stream = FramedWrite::new(w_stream, codec.clone());
+
+    if !establish_connection_check(
+        &addr,
+        &connection_manager,
+        &cache_manager,
+        &NetworkConnectionType::TlsPsk,
+        &mut write_frame_stream,
+    )
+    .await
+    {
+        return;
+    }
+
+    let (connection_stop_sx, connection_stop_rx) = mpsc::channel::<bool>(1);
+    let mut connection = NetworkConnection::new(
+        NetworkConnectionType::TlsPsk,
+        addr,
+        Some(connection_stop_sx.clone()),
+    );
+    connection.psk_identity = psk_identity;
+    connection_manager.add_connection(connection.clone());
+    connection_manager.add_tcp_psk_write(connection.connection_id, write_frame_stream);
+
+    read_tls_frame_process(
+        read_frame_stream,
+        connection,
+        request_queue_sx,
+        connection_stop_rx,
+        NetworkConnectionType::TlsPsk,
+        cache_manager,
+        connection_manager,
+    );
+}
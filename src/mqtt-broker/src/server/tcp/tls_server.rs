@@ -18,25 +18,35 @@ use std::path::Path;
 use std::sync::Arc;
 use std::time::Duration;
 
-use common_base::config::broker_mqtt::broker_mqtt_conf;
+use common_base::config::broker_mqtt::{broker_mqtt_conf, Network};
 use futures_util::StreamExt;
 use log::{debug, error, info};
 use protocol::mqtt::codec::MqttCodec;
-use rustls_pemfile::{certs, private_key};
+use rustls_pemfile::{certs, crls, private_key};
+use tokio::io::{AsyncRead, AsyncWrite};
 use tokio::net::TcpListener;
 use tokio::select;
 use tokio::sync::mpsc::{Receiver, Sender};
 use tokio::sync::{broadcast, mpsc};
 use tokio::time::sleep;
-use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
-use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::rustls::pki_types::{
+    CertificateDer, CertificateRevocationListDer, PrivateKeyDer,
+};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{RootCertStore, ServerConfig};
 use tokio_rustls::TlsAcceptor;
 use tokio_util::codec::{FramedRead, FramedWrite};
 
-use crate::handler::validator::tcp_tls_establish_connection_check;
+use crate::handler::cache::CacheManager;
+use crate::handler::connection::release_unlogged_connection;
+use crate::handler::validator::establish_connection_check;
 use crate::observability::metrics::packets::{
     record_received_error_metrics, record_received_metrics,
 };
+use crate::security::login::is_ip_blacklist;
+use crate::security::login::x509::{
+    peer_identity, TLS_CLIENT_AUTH_OPTIONAL, TLS_CLIENT_AUTH_REQUIRED,
+};
 use crate::server::connection::{NetworkConnection, NetworkConnectionType};
 use crate::server::connection_manager::ConnectionManager;
 use crate::server::packet::RequestPackage;
@@ -54,6 +64,43 @@ pub(crate) fn load_key(path: &Path) -> io::Result<PrivateKeyDer<'static>> {
         ))
 }
 
+pub(crate) fn load_crls(path: &Path) -> io::Result<Vec<CertificateRevocationListDer<'static>>> {
+    crls(&mut BufReader::new(File::open(path)?)).collect()
+}
+
+// TLS config of the TCP and websocket listeners, client certificates are requested
+// when a CA bundle is configured and tls_client_auth is optional or required
+pub(crate) fn build_tls_server_config(network: &Network) -> Result<ServerConfig, String> {
+    let certs = load_certs(Path::new(&network.tls_cert)).map_err(|e| e.to_string())?;
+    let key = load_key(Path::new(&network.tls_key)).map_err(|e| e.to_string())?;
+
+    let client_auth = network.tls_client_auth.as_str();
+    let builder = if network.tls_ca_cert.is_empty()
+        || (client_auth != TLS_CLIENT_AUTH_OPTIONAL && client_auth != TLS_CLIENT_AUTH_REQUIRED)
+    {
+        ServerConfig::builder().with_no_client_auth()
+    } else {
+        let mut roots = RootCertStore::empty();
+        for cert in load_certs(Path::new(&network.tls_ca_cert)).map_err(|e| e.to_string())? {
+            roots.add(cert).map_err(|e| e.to_string())?;
+        }
+
+        let mut verifier = WebPkiClientVerifier::builder(Arc::new(roots));
+        if !network.tls_crl.is_empty() {
+            verifier = verifier
+                .with_crls(load_crls(Path::new(&network.tls_crl)).map_err(|e| e.to_string())?);
+        }
+        if client_auth == TLS_CLIENT_AUTH_OPTIONAL {
+            verifier = verifier.allow_unauthenticated();
+        }
+        ServerConfig::builder()
+            .with_client_cert_verifier(verifier.build().map_err(|e| e.to_string())?)
+    };
+    builder
+        .with_single_cert(certs, key)
+        .map_err(|e| e.to_string())
+}
+
 pub(crate) async fn acceptor_tls_process(
     accept_thread_num: usize,
     listener_arc: Arc<TcpListener>,
@@ -61,30 +108,14 @@ pub(crate) async fn acceptor_tls_process(
     network_connection_type: NetworkConnectionType,
     connection_manager: Arc<ConnectionManager>,
     request_queue_sx: Sender<RequestPackage>,
+    cache_manager: Arc<CacheManager>,
 ) {
     let conf = broker_mqtt_conf();
 
-    let certs = match load_certs(Path::new(&conf.network.tls_cert)) {
-        Ok(data) => data,
-        Err(e) => {
-            panic!("{}", e.to_string());
-        }
-    };
-
-    let key = match load_key(Path::new(&conf.network.tls_key)) {
-        Ok(data) => data,
-        Err(e) => {
-            panic!("{}", e.to_string());
-        }
-    };
-
-    let config = match ServerConfig::builder()
-        .with_no_client_auth()
-        .with_single_cert(certs, key)
-    {
+    let config = match build_tls_server_config(&conf.network) {
         Ok(data) => data,
         Err(e) => {
-            panic!("{}", e.to_string());
+            panic!("{}", e);
         }
     };
     let tls_acceptor = TlsAcceptor::from(Arc::new(config));
@@ -96,6 +127,7 @@ pub(crate) async fn acceptor_tls_process(
         let raw_request_queue_sx = request_queue_sx.clone();
         let raw_tls_acceptor = tls_acceptor.clone();
         let network_type = network_connection_type.clone();
+        let cache_manager = cache_manager.clone();
         tokio::spawn(async move {
             debug!("TCP Server acceptor thread {} start successfully.", index);
             loop {
@@ -112,6 +144,10 @@ pub(crate) async fn acceptor_tls_process(
                         match val{
                             Ok((stream, addr)) => {
                                 info!("accept tcp tls connection:{:?}",addr);
+                                if is_ip_blacklist(&cache_manager, &addr) {
+                                    info!("tcp tls connection from blacklisted IP {:?} is rejected",addr);
+                                    continue;
+                                }
                                 let stream = match raw_tls_acceptor.accept(stream).await{
                                     Ok(da) => da,
                                     Err(e) => {
@@ -119,25 +155,27 @@ pub(crate) async fn acceptor_tls_process(
                                         continue;
                                     }
                                 };
+                                let peer_cert = peer_identity(stream.get_ref().1);
                                 let (r_stream, w_stream) = tokio::io::split(stream);
                                 let codec = MqttCodec::new(None);
                                 let read_frame_stream = FramedRead::new(r_stream, codec.clone());
                                 let mut  write_frame_stream = FramedWrite::new(w_stream, codec.clone());
 
-                                if !tcp_tls_establish_connection_check(&addr,&connection_manager,&mut write_frame_stream).await{
+                                if !establish_connection_check(&addr,&connection_manager,&cache_manager,&NetworkConnectionType::Tls,&mut write_frame_stream).await{
                                     continue;
                                 }
 
                                 let (connection_stop_sx, connection_stop_rx) = mpsc::channel::<bool>(1);
-                                let connection = NetworkConnection::new(
+                                let mut connection = NetworkConnection::new(
                                     crate::server::connection::NetworkConnectionType::Tls,
                                     addr,
                                     Some(connection_stop_sx.clone())
                                 );
+                                connection.peer_cert = peer_cert;
                                 connection_manager.add_connection(connection.clone());
                                 connection_manager.add_tcp_tls_write(connection.connection_id, write_frame_stream);
 
-                                read_tls_frame_process(read_frame_stream,connection,raw_request_queue_sx.clone(),connection_stop_rx, network_type.clone());
+                                read_tls_frame_process(read_frame_stream,connection,raw_request_queue_sx.clone(),connection_stop_rx, network_type.clone(),cache_manager.clone(),connection_manager.clone());
                             }
                             Err(e) => {
                                 error!("TCP accept failed to create connection with error message :{:?}",e);
@@ -150,16 +188,18 @@ pub(crate) async fn acceptor_tls_process(
     }
 }
 
-pub(crate) fn read_tls_frame_process(
-    mut read_frame_stream: FramedRead<
-        tokio::io::ReadHalf<tokio_rustls::server::TlsStream<tokio::net::TcpStream>>,
-        MqttCodec,
-    >,
+// Shared by the TLS and TLS-PSK listeners
+pub(crate) fn read_tls_frame_process<T>(
+    mut read_frame_stream: FramedRead<tokio::io::ReadHalf<T>, MqttCodec>,
     connection: NetworkConnection,
     request_queue_sx: Sender<RequestPackage>,
     mut connection_stop_rx: Receiver<bool>,
     network_type: NetworkConnectionType,
-) {
+    cache_manager: Arc<CacheManager>,
+    connection_manager: Arc<ConnectionManager>,
+) where
+    T: AsyncRead + AsyncWrite + Send + 'static,
+{
     tokio::spawn(async move {
         loop {
             select! {
@@ -184,6 +224,11 @@ pub(crate) fn read_tls_frame_process(
                                     }
                                     Err(err) => error!("Failed to write data to the request queue, error message: {:?}",err),
                                 }
+
+                                if let Some(wait) = cache_manager.rate_limiter.read_pause_time(connection.connection_id) {
+                                    // MQTT 3 clients over the publish rate limits are throttled by pausing the reads
+                                    sleep(wait).await;
+                                }
                             }
                             Err(e) => {
                                 record_received_error_metrics(network_type.clone());
@@ -191,7 +236,9 @@ pub(crate) fn read_tls_frame_process(
                             }
                         }
                     } else {
-                        sleep(Duration::from_millis(10)).await;
+                        debug!("TCP connection 【{}】 closed by the client.",connection.connection_id);
+                        release_unlogged_connection(connection.connection_id, &cache_manager, &connection_manager).await;
+                        break;
                     }
                 }
             }
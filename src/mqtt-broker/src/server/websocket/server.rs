@@ -12,34 +12,47 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::io;
 use std::net::SocketAddr;
-use std::path::PathBuf;
 use std::sync::Arc;
+use std::task::{Context, Poll};
 
 use axum::extract::ws::{Message, WebSocket};
 use axum::extract::{ConnectInfo, State, WebSocketUpgrade};
-use axum::response::Response;
+use axum::http::{Request, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::routing::get;
-use axum::Router;
+use axum::{Extension, Router};
 use axum_extra::headers::UserAgent;
 use axum_extra::TypedHeader;
+use axum_server::accept::Accept;
 use axum_server::tls_rustls::RustlsConfig;
 use bytes::{BufMut, BytesMut};
 use common_base::config::broker_mqtt::broker_mqtt_conf;
+use futures_util::future::BoxFuture;
 use futures_util::stream::StreamExt;
 use grpc_clients::poll::ClientPool;
 use log::{debug, error, info};
 use protocol::mqtt::codec::{MqttCodec, MqttPacketWrapper};
 use protocol::mqtt::common::{MQTTPacket, MQTTProtocol};
 use storage_adapter::storage::StorageAdapter;
+use tokio::net::TcpStream;
 use tokio::select;
 use tokio::sync::broadcast::{self};
+use tokio::time::sleep;
+use tokio_rustls::server::TlsStream;
+use tower::Service;
 
 use crate::handler::cache::CacheManager;
 use crate::handler::command::Command;
+use crate::handler::connection::release_unlogged_connection;
+use crate::handler::flow_control::is_connection_rate_exceeded;
+use crate::security::login::is_ip_blacklist;
+use crate::security::login::x509::{peer_identity, X509Identity};
 use crate::security::AuthDriver;
-use crate::server::connection::NetworkConnection;
+use crate::server::connection::{NetworkConnection, NetworkConnectionType};
 use crate::server::connection_manager::ConnectionManager;
+use crate::server::tcp::tls_server::build_tls_server_config;
 use crate::subscribe::subscribe_manager::SubscribeManager;
 
 pub const ROUTE_ROOT: &str = "/mqtt";
@@ -53,6 +66,7 @@ pub struct WebSocketServerState<S> {
     stop_sx: broadcast::Sender<bool>,
     connection_manager: Arc<ConnectionManager>,
     auth_driver: Arc<AuthDriver>,
+    network_type: NetworkConnectionType,
 }
 
 impl<S> WebSocketServerState<S>
@@ -76,6 +90,7 @@ where
             client_poll,
             auth_driver,
             stop_sx,
+            network_type: NetworkConnectionType::WebSocket,
         }
     }
 }
@@ -88,12 +103,14 @@ where
     let ip: SocketAddr = format!("0.0.0.0:{}", config.network.websocket_port)
         .parse()
         .unwrap();
+    let cache_manager = state.cache_manager.clone();
     let app = routes_v1(state);
     info!(
         "Broker WebSocket Server start success. port:{}",
         config.network.websocket_port
     );
     match axum_server::bind(ip)
+        .map(|acceptor| BlacklistAcceptor::new(acceptor, cache_manager))
         .serve(app.into_make_service_with_connect_info::<SocketAddr>())
         .await
     {
@@ -110,25 +127,28 @@ where
     let ip: SocketAddr = format!("0.0.0.0:{}", config.network.websockets_port)
         .parse()
         .unwrap();
+    let state = WebSocketServerState {
+        network_type: NetworkConnectionType::WebSockets,
+        ..state
+    };
+    let cache_manager = state.cache_manager.clone();
     let app = routes_v1(state);
 
-    let tls_config = match RustlsConfig::from_pem_file(
-        PathBuf::from(config.network.tls_cert.clone()),
-        PathBuf::from(config.network.tls_key.clone()),
-    )
-    .await
-    {
+    let mut server_config = match build_tls_server_config(&config.network) {
         Ok(cf) => cf,
         Err(e) => {
-            panic!("{}", e.to_string());
+            panic!("{}", e);
         }
     };
+    server_config.alpn_protocols = vec![b"http/1.1".to_vec()];
+    let tls_config = RustlsConfig::from_config(Arc::new(server_config));
 
     info!(
         "Broker WebSocket TLS Server start success. port:{}",
         config.network.websockets_port
     );
     match axum_server::bind_rustls(ip, tls_config)
+        .map(|acceptor| PeerCertAcceptor::new(BlacklistAcceptor::new(acceptor, cache_manager)))
         .serve(app.into_make_service_with_connect_info::<SocketAddr>())
         .await
     {
@@ -137,6 +157,113 @@ where
     }
 }
 
+// Rejects the connections from blacklisted IPs before the TLS handshake and the websocket upgrade
+#[derive(Clone)]
+struct BlacklistAcceptor<A> {
+    inner: A,
+    cache_manager: Arc<CacheManager>,
+}
+
+impl<A> BlacklistAcceptor<A> {
+    fn new(inner: A, cache_manager: Arc<CacheManager>) -> Self {
+        BlacklistAcceptor {
+            inner,
+            cache_manager,
+        }
+    }
+}
+
+impl<A, S> Accept<TcpStream, S> for BlacklistAcceptor<A>
+where
+    A: Accept<TcpStream, S>,
+    A::Stream: 'static,
+    A::Service: 'static,
+    A::Future: Send + 'static,
+{
+    type Stream = A::Stream;
+    type Service = A::Service;
+    type Future = BoxFuture<'static, io::Result<(Self::Stream, Self::Service)>>;
+
+    fn accept(&self, stream: TcpStream, service: S) -> Self::Future {
+        if let Ok(addr) = stream.peer_addr() {
+            if is_ip_blacklist(&self.cache_manager, &addr) {
+                info!("websocket connection from blacklisted IP {addr} is rejected");
+                return Box::pin(async move {
+                    Err(io::Error::new(
+                        io::ErrorKind::PermissionDenied,
+                        format!("IP {} is blacklisted", addr.ip()),
+                    ))
+                });
+            }
+        }
+        Box::pin(self.inner.accept(stream, service))
+    }
+}
+
+// Passes the client certificate verified in the TLS handshake to the websocket handler
+#[derive(Clone)]
+struct PeerCertAcceptor<A> {
+    inner: A,
+}
+
+impl<A> PeerCertAcceptor<A> {
+    fn new(inner: A) -> Self {
+        PeerCertAcceptor { inner }
+    }
+}
+
+impl<A, S> Accept<TcpStream, S> for PeerCertAcceptor<A>
+where
+    A: Accept<TcpStream, S, Stream = TlsStream<TcpStream>>,
+    A::Service: Send + 'static,
+    A::Future: Send + 'static,
+{
+    type Stream = A::Stream;
+    type Service = PeerCertService<A::Service>;
+    type Future = BoxFuture<'static, io::Result<(Self::Stream, Self::Service)>>;
+
+    fn accept(&self, stream: TcpStream, service: S) -> Self::Future {
+        let accept = self.inner.accept(stream, service);
+        Box::pin(async move {
+            let (stream, service) = accept.await?;
+            let peer_cert = peer_identity(stream.get_ref().1);
+            Ok((
+                stream,
+                PeerCertService {
+                    inner: service,
+                    peer_cert,
+                },
+            ))
+        })
+    }
+}
+
+#[derive(Clone)]
+struct PeerCertService<S> {
+    inner: S,
+    peer_cert: Option<X509Identity>,
+}
+
+impl<S, B> Service<Request<B>> for PeerCertService<S>
+where
+    S: Service<Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request<B>) -> Self::Future {
+        if let Some(peer_cert) = &self.peer_cert {
+            req.extensions_mut().insert(peer_cert.clone());
+        }
+        self.inner.call(req)
+    }
+}
+
 fn routes_v1<S>(state: WebSocketServerState<S>) -> Router
 where
     S: StorageAdapter + Sync + Send + 'static + Clone,
@@ -152,6 +279,7 @@ async fn ws_handler<S>(
     State(state): State<WebSocketServerState<S>>,
     user_agent: Option<TypedHeader<UserAgent>>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    peer_cert: Option<Extension<X509Identity>>,
 ) -> Response
 where
     S: StorageAdapter + Sync + Send + 'static + Clone,
@@ -162,6 +290,14 @@ where
         String::from("Unknown Source")
     };
     info!("`{user_agent}` at {addr} connected.");
+
+    if is_connection_rate_exceeded(&state.cache_manager, &state.network_type) {
+        error!(
+            "websocket connection failed to establish from IP: {addr}, connection rate exceeded"
+        );
+        return StatusCode::TOO_MANY_REQUESTS.into_response();
+    }
+
     let command = Command::new(
         state.cache_manager.clone(),
         state.message_storage_adapter.clone(),
@@ -171,14 +307,17 @@ where
         state.auth_driver.clone(),
     );
     let codec = MqttCodec::new(None);
+    let peer_cert = peer_cert.map(|Extension(peer_cert)| peer_cert);
     ws.protocols(["mqtt", "mqttv3.1"])
         .on_upgrade(move |socket| {
             handle_socket(
                 socket,
                 addr,
+                peer_cert,
                 command,
                 codec,
                 state.connection_manager.clone(),
+                state.cache_manager.clone(),
                 state.stop_sx.clone(),
             )
         })
@@ -187,9 +326,11 @@ where
 async fn handle_socket<S>(
     socket: WebSocket,
     addr: SocketAddr,
+    peer_cert: Option<X509Identity>,
     mut command: Command<S>,
     mut codec: MqttCodec,
     connection_manager: Arc<ConnectionManager>,
+    cache_manager: Arc<CacheManager>,
     stop_sx: broadcast::Sender<bool>,
 ) where
     S: StorageAdapter + Sync + Send + 'static + Clone,
@@ -200,6 +341,7 @@ async fn handle_socket<S>(
         addr,
         None,
     );
+    tcp_connection.peer_cert = peer_cert;
 
     connection_manager.add_websocket_write(tcp_connection.connection_id, sender);
     connection_manager.add_connection(tcp_connection.clone());
@@ -267,6 +409,11 @@ async fn handle_socket<S>(
                                     error!("Websocket failed to parse MQTT protocol packet with error message :{e:?}");
                                 }
                             }
+
+                            if let Some(wait) = cache_manager.rate_limiter.read_pause_time(tcp_connection.connection_id) {
+                                // MQTT 3 clients over the publish rate limits are throttled by pausing the reads
+                                sleep(wait).await;
+                            }
                         }
                         Ok(Message::Text(data)) => {
                             debug!(
@@ -301,9 +448,17 @@ async fn handle_socket<S>(
                         },
                     }
                 } else {
-                    debug!("Web socket server receives an empty request packet");
+                    debug!("Web socket connection 【{}】 closed by the client.", tcp_connection.connection_id);
+                    break;
                 }
             }
         }
     }
+
+    release_unlogged_connection(
+        tcp_connection.connection_id,
+        &cache_manager,
+        &connection_manager,
+    )
+    .await;
 }
@@ -16,10 +16,12 @@ use std::sync::Arc;
 
 use common_base::config::broker_mqtt::broker_mqtt_conf;
 use common_base::error::common::CommonError;
-use grpc_clients::placement::mqtt::call::list_blacklist;
+use grpc_clients::placement::mqtt::call::{create_blacklist, delete_blacklist, list_blacklist};
 use grpc_clients::poll::ClientPool;
 use metadata_struct::acl::mqtt_blacklist::MqttAclBlackList;
-use protocol::placement_center::placement_center_mqtt::ListBlacklistRequest;
+use protocol::placement_center::placement_center_mqtt::{
+    CreateBlacklistRequest, DeleteBlacklistRequest, ListBlacklistRequest,
+};
 
 pub struct BlackListStorage {
     client_poll: Arc<ClientPool>,
@@ -52,4 +54,41 @@ impl BlackListStorage {
             Err(e) => Err(e),
         }
     }
+
+    pub async fn save_blacklist(&self, blacklist: MqttAclBlackList) -> Result<(), CommonError> {
+        let config = broker_mqtt_conf();
+        let request = CreateBlacklistRequest {
+            cluster_name: config.cluster_name.clone(),
+            blacklist: blacklist.encode()?,
+        };
+        match create_blacklist(
+            self.client_poll.clone(),
+            config.placement_center.clone(),
+            request,
+        )
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub async fn delete_blacklist(&self, blacklist: MqttAclBlackList) -> Result<(), CommonError> {
+        let config = broker_mqtt_conf();
+        let request = DeleteBlacklistRequest {
+            cluster_name: config.cluster_name.clone(),
+            blacklist_type: blacklist.blacklist_type.to_string(),
+            resource_name: blacklist.resource_name,
+        };
+        match delete_blacklist(
+            self.client_poll.clone(),
+            config.placement_center.clone(),
+            request,
+        )
+        .await
+        {
+            Ok(_) => Ok(()),
+            Err(e) => Err(e),
+        }
+    }
 }
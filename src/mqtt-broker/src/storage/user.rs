@@ -156,6 +156,7 @@ mod tests {
             username: username.clone(),
             password: password.clone(),
             is_superuser,
+            hash: Default::default(),
         };
         user_storage.save_user(user_info).await.unwrap();
 
@@ -165,7 +166,8 @@ mod tests {
             .unwrap()
             .unwrap();
         assert_eq!(result.username, username);
-        assert_eq!(result.password, password);
+        assert_ne!(result.password, password);
+        assert!(result.verify_password(&password));
         assert_eq!(result.is_superuser, is_superuser);
 
         let result = user_storage.user_list().await.unwrap();
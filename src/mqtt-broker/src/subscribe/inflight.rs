@@ -0,0 +1,489 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::sync::Arc;
+use std::time::Duration;
+
+use common_base::error::common::CommonError;
+use common_base::tools::now_second;
+use log::error;
+use metadata_struct::adapter::record::Record;
+use metadata_struct::mqtt::message::MqttMessage;
+use protocol::mqtt::common::{
+    MQTTPacket, MQTTProtocol, PubRel, PubRelReason, Publish, PublishProperties, QoS,
+};
+use serde::{Deserialize, Serialize};
+use storage_adapter::storage::StorageAdapter;
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::{self, Receiver};
+use tokio::sync::Mutex;
+use tokio::time::sleep;
+
+use super::sub_common::publish_message_to_client;
+use super::sub_exclusive::build_exclusive_publish;
+use super::subscriber::Subscriber;
+use crate::handler::cache::{CacheManager, QosAckPackageData, QosAckPackageType, QosAckPacketInfo};
+use crate::handler::connection::Connection;
+use crate::server::connection_manager::ConnectionManager;
+use crate::server::packet::ResponsePackage;
+
+const INFLIGHT_MESSAGE_KEY_PREFIX: &str = "/inflight_message/";
+
+// The acknowledgement the broker is waiting for
+#[derive(Clone, Serialize, Deserialize, PartialEq, Debug)]
+pub enum InflightStage {
+    WaitPubAck,
+    WaitPubRec,
+    WaitPubComp,
+}
+
+// Outbound QoS1/QoS2 message that has not been acknowledged by the client
+#[derive(Clone, Serialize, Deserialize)]
+pub struct InflightMessage {
+    pub pkid: u16,
+    pub qos: QoS,
+    pub stage: InflightStage,
+    pub subscriber: Subscriber,
+    pub message: MqttMessage,
+}
+
+impl InflightMessage {
+    pub fn new(pkid: u16, qos: QoS, subscriber: Subscriber, message: MqttMessage) -> Self {
+        let stage = if qos == QoS::ExactlyOnce {
+            InflightStage::WaitPubRec
+        } else {
+            InflightStage::WaitPubAck
+        };
+        InflightMessage {
+            pkid,
+            qos,
+            stage,
+            subscriber,
+            message,
+        }
+    }
+
+    pub fn build_publish(&self, dup: bool) -> (Publish, PublishProperties) {
+        let mut sub_ids = Vec::new();
+        if let Some(id) = self.subscriber.subscription_identifier {
+            sub_ids.push(id);
+        }
+        let (mut publish, properties) =
+            build_exclusive_publish(&self.subscriber, self.qos, self.message.clone(), &sub_ids);
+        publish.pkid = self.pkid;
+        publish.dup = dup;
+        (publish, properties)
+    }
+}
+
+// Inflight messages of a session, persisted through the storage adapter in the order they were sent
+#[derive(Clone)]
+pub struct InflightStore<S> {
+    client_id: String,
+    storage_adapter: Arc<S>,
+    lock: Arc<Mutex<()>>,
+}
+
+impl<S> InflightStore<S>
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    pub fn new(
+        cache_manager: &Arc<CacheManager>,
+        client_id: &str,
+        storage_adapter: Arc<S>,
+    ) -> Self {
+        InflightStore {
+            client_id: client_id.to_owned(),
+            storage_adapter,
+            lock: cache_manager.get_inflight_message_lock(client_id),
+        }
+    }
+
+    pub async fn list(&self) -> Result<Vec<InflightMessage>, CommonError> {
+        let _lock = self.lock.lock().await;
+        let mut results = Vec::new();
+        for pkid in self.get_index().await? {
+            if let Some(record) = self.storage_adapter.get(self.record_key(pkid)).await? {
+                results.push(serde_json::from_slice(&record.data)?);
+            }
+        }
+        Ok(results)
+    }
+
+    pub async fn save(&self, message: &InflightMessage) -> Result<(), CommonError> {
+        let _lock = self.lock.lock().await;
+        self.storage_adapter
+            .set(
+                self.record_key(message.pkid),
+                Record::build_b(serde_json::to_vec(message)?),
+            )
+            .await?;
+
+        let mut index = self.get_index().await?;
+        if !index.contains(&message.pkid) {
+            index.push(message.pkid);
+            self.save_index(&index).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn remove(&self, pkid: u16) -> Result<(), CommonError> {
+        let _lock = self.lock.lock().await;
+        let mut index = self.get_index().await?;
+        index.retain(|id| *id != pkid);
+        self.save_index(&index).await?;
+        self.storage_adapter.delete(self.record_key(pkid)).await
+    }
+
+    pub async fn clear(&self) -> Result<(), CommonError> {
+        let _lock = self.lock.lock().await;
+        for pkid in self.get_index().await? {
+            self.storage_adapter.delete(self.record_key(pkid)).await?;
+        }
+        self.storage_adapter.delete(self.index_key()).await
+    }
+
+    async fn get_index(&self) -> Result<Vec<u16>, CommonError> {
+        match self.storage_adapter.get(self.index_key()).await? {
+            Some(record) => Ok(serde_json::from_slice(&record.data)?),
+            None => Ok(Vec::new()),
+        }
+    }
+
+    async fn save_index(&self, index: &[u16]) -> Result<(), CommonError> {
+        self.storage_adapter
+            .set(
+                self.index_key(),
+                Record::build_b(serde_json::to_vec(index)?),
+            )
+            .await
+    }
+
+    fn index_key(&self) -> String {
+        format!("{}{}/index", INFLIGHT_MESSAGE_KEY_PREFIX, self.client_id)
+    }
+
+    fn record_key(&self, pkid: u16) -> String {
+        format!(
+            "{}{}/record/{}",
+            INFLIGHT_MESSAGE_KEY_PREFIX, self.client_id, pkid
+        )
+    }
+}
+
+// Waits until the client's Receive Maximum allows one more QoS1/QoS2 message to be sent.
+// Returns None when the client goes offline.
+pub async fn acquire_send_window(
+    cache_manager: &Arc<CacheManager>,
+    client_id: &str,
+) -> Option<Connection> {
+    loop {
+        let connect_id = cache_manager.get_connect_id(client_id)?;
+        let connection = cache_manager.get_connection(connect_id)?;
+        if connection.try_send_qos_message_incr() {
+            return Some(connection);
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+}
+
+// Sends the inflight message on the connection and waits for the acknowledgements, the message is
+// removed from the store once the flow is complete. If the connection is closed in between, the
+// message stays in the store and is retransmitted when the session is resumed.
+// The send window slot taken by acquire_send_window is released here.
+pub async fn complete_inflight_message<S>(
+    cache_manager: Arc<CacheManager>,
+    connection_manager: Arc<ConnectionManager>,
+    store: InflightStore<S>,
+    connection: Connection,
+    mut message: InflightMessage,
+    publish: Option<(Publish, PublishProperties)>,
+) where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    let client_id = message.subscriber.client_id.clone();
+    let pkid = message.pkid;
+
+    let (ack_sx, ack_rx) = broadcast::channel(1);
+    cache_manager.add_ack_packet(
+        &client_id,
+        pkid,
+        QosAckPacketInfo {
+            sx: ack_sx,
+            create_time: now_second(),
+        },
+    );
+
+    match run_inflight_flow(
+        &cache_manager,
+        &connection_manager,
+        &store,
+        &connection,
+        &mut message,
+        publish,
+        ack_rx,
+    )
+    .await
+    {
+        Ok(true) => {
+            if let Err(e) = store.remove(pkid).await {
+                error!(
+                    "Failed to remove inflight message of client [{}], pkid {}, error message :{}",
+                    client_id, pkid, e
+                );
+            }
+        }
+        Ok(false) => {}
+        Err(e) => {
+            error!(
+                "Failed to deliver inflight message of client [{}], pkid {}, error message :{}",
+                client_id, pkid, e
+            );
+        }
+    }
+
+    cache_manager.remove_ack_packet(&client_id, pkid);
+    cache_manager.remove_pkid_info(&client_id, pkid);
+    connection.send_qos_message_decr();
+}
+
+// Returns true when the flow is complete, false when the connection is closed before
+async fn run_inflight_flow<S>(
+    cache_manager: &Arc<CacheManager>,
+    connection_manager: &Arc<ConnectionManager>,
+    store: &InflightStore<S>,
+    connection: &Connection,
+    message: &mut InflightMessage,
+    publish: Option<(Publish, PublishProperties)>,
+    mut ack_rx: Receiver<QosAckPackageData>,
+) -> Result<bool, CommonError>
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    let client_id = message.subscriber.client_id.clone();
+    let connect_id = connection.connect_id;
+
+    if message.stage != InflightStage::WaitPubComp {
+        let (publish, properties) = publish.unwrap_or_else(|| message.build_publish(true));
+        // Packets larger than the Maximum Packet Size of the client are discarded
+        if publish.payload.len() > connection.max_packet_size as usize {
+            return Ok(true);
+        }
+
+        let properties = match connection_manager.get_connect_protocol(connect_id) {
+            Some(protocol) if MQTTProtocol::is_mqtt5(&protocol) => Some(properties),
+            _ => None,
+        };
+        send_packet(
+            connection_manager,
+            connect_id,
+            MQTTPacket::Publish(publish, properties),
+        )
+        .await?;
+
+        let expect = if message.qos == QoS::AtLeastOnce {
+            QosAckPackageType::PubAck
+        } else {
+            QosAckPackageType::PubRec
+        };
+        if !wait_inflight_ack(cache_manager, &client_id, connect_id, &mut ack_rx, expect).await {
+            return Ok(false);
+        }
+        if message.qos == QoS::AtLeastOnce {
+            return Ok(true);
+        }
+
+        message.stage = InflightStage::WaitPubComp;
+        store.save(message).await?;
+    }
+
+    let pubrel = PubRel {
+        pkid: message.pkid,
+        reason: Some(PubRelReason::Success),
+    };
+    send_packet(
+        connection_manager,
+        connect_id,
+        MQTTPacket::PubRel(pubrel, None),
+    )
+    .await?;
+    Ok(wait_inflight_ack(
+        cache_manager,
+        &client_id,
+        connect_id,
+        &mut ack_rx,
+        QosAckPackageType::PubComp,
+    )
+    .await)
+}
+
+async fn send_packet(
+    connection_manager: &Arc<ConnectionManager>,
+    connect_id: u64,
+    packet: MQTTPacket,
+) -> Result<(), CommonError> {
+    let resp = ResponsePackage {
+        connection_id: connect_id,
+        packet,
+    };
+    publish_message_to_client(resp, connection_manager).await
+}
+
+// Returns false when the connection is closed before the acknowledgement arrives
+async fn wait_inflight_ack(
+    cache_manager: &Arc<CacheManager>,
+    client_id: &str,
+    connect_id: u64,
+    ack_rx: &mut Receiver<QosAckPackageData>,
+    expect: QosAckPackageType,
+) -> bool {
+    loop {
+        select! {
+            val = ack_rx.recv() => {
+                match val {
+                    Ok(data) => {
+                        if data.ack_type == expect {
+                            return true;
+                        }
+                    }
+                    Err(RecvError::Lagged(_)) => {}
+                    Err(RecvError::Closed) => return false,
+                }
+            }
+            _ = sleep(Duration::from_secs(1)) => {
+                if cache_manager.get_connect_id(client_id) != Some(connect_id) {
+                    return false;
+                }
+            }
+        }
+    }
+}
+
+// Retransmits the unacknowledged messages of a resumed session in their original order. PUBLISH
+// packets are resent with the DUP flag set, PUBREL is resent for messages that already received PUBREC.
+// Returns false when the client goes offline before all messages are retransmitted.
+pub async fn resend_inflight_message<S>(
+    cache_manager: &Arc<CacheManager>,
+    connection_manager: &Arc<ConnectionManager>,
+    storage_adapter: &Arc<S>,
+    client_id: &str,
+) -> Result<bool, CommonError>
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    let store = InflightStore::new(cache_manager, client_id, storage_adapter.clone());
+    for message in store.list().await? {
+        let connection = match acquire_send_window(cache_manager, client_id).await {
+            Some(connection) => connection,
+            None => return Ok(false),
+        };
+        cache_manager.reserve_pkid(client_id, message.pkid);
+        tokio::spawn(complete_inflight_message(
+            cache_manager.clone(),
+            connection_manager.clone(),
+            store.clone(),
+            connection,
+            message,
+            None,
+        ));
+    }
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use grpc_clients::poll::ClientPool;
+    use metadata_struct::mqtt::message::MqttMessage;
+    use protocol::mqtt::common::QoS;
+    use storage_adapter::memory::MemoryStorageAdapter;
+
+    use super::{InflightMessage, InflightStage, InflightStore};
+    use crate::handler::cache::CacheManager;
+    use crate::subscribe::subscriber::Subscriber;
+
+    fn build_message(pkid: u16, qos: QoS) -> InflightMessage {
+        let subscriber = Subscriber {
+            client_id: "client-1".to_string(),
+            topic_name: "a/b".to_string(),
+            qos,
+            subscription_identifier: Some(3),
+            ..Default::default()
+        };
+        let message = MqttMessage {
+            payload: Bytes::from("test"),
+            ..Default::default()
+        };
+        InflightMessage::new(pkid, qos, subscriber, message)
+    }
+
+    #[tokio::test]
+    async fn inflight_store_test() {
+        let client_poll = Arc::new(ClientPool::new(1));
+        let cache_manager = Arc::new(CacheManager::new(client_poll, "test".to_string()));
+        let storage_adapter = Arc::new(MemoryStorageAdapter::new());
+        let store = InflightStore::new(&cache_manager, "client-1", storage_adapter);
+
+        store
+            .save(&build_message(5, QoS::AtLeastOnce))
+            .await
+            .unwrap();
+        store
+            .save(&build_message(2, QoS::ExactlyOnce))
+            .await
+            .unwrap();
+        store
+            .save(&build_message(9, QoS::AtLeastOnce))
+            .await
+            .unwrap();
+
+        let mut message = build_message(2, QoS::ExactlyOnce);
+        assert_eq!(message.stage, InflightStage::WaitPubRec);
+        message.stage = InflightStage::WaitPubComp;
+        store.save(&message).await.unwrap();
+
+        // messages are kept in the order they were sent
+        let list = store.list().await.unwrap();
+        let pkids: Vec<u16> = list.iter().map(|msg| msg.pkid).collect();
+        assert_eq!(pkids, vec![5, 2, 9]);
+        assert_eq!(list[1].stage, InflightStage::WaitPubComp);
+
+        store.remove(2).await.unwrap();
+        let pkids: Vec<u16> = store
+            .list()
+            .await
+            .unwrap()
+            .iter()
+            .map(|msg| msg.pkid)
+            .collect();
+        assert_eq!(pkids, vec![5, 9]);
+
+        store.clear().await.unwrap();
+        assert!(store.list().await.unwrap().is_empty());
+    }
+
+    #[test]
+    fn build_publish_test() {
+        let message = build_message(7, QoS::AtLeastOnce);
+        let (publish, properties) = message.build_publish(true);
+        assert!(publish.dup);
+        assert_eq!(publish.pkid, 7);
+        assert_eq!(publish.qos, QoS::AtLeastOnce);
+        assert_eq!(publish.topic, Bytes::from("a/b"));
+        assert_eq!(properties.subscription_identifiers, vec![3]);
+    }
+}
@@ -12,9 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod inflight;
+pub mod offline_queue;
+pub mod push_notify;
+pub mod share_strategy;
 pub mod sub_common;
 pub mod sub_exclusive;
 pub mod sub_share_follower;
 pub mod sub_share_leader;
 pub mod subscribe_manager;
 pub mod subscriber;
+pub mod topic_trie;
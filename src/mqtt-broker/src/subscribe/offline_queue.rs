@@ -0,0 +1,509 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+use std::sync::Arc;
+use std::time::Duration;
+
+use common_base::error::common::CommonError;
+use log::{debug, error, info};
+use metadata_struct::adapter::record::Record;
+use metadata_struct::mqtt::cluster::{MqttClusterDynamicConfigProtocol, OfflineMessageDropPolicy};
+use metadata_struct::mqtt::message::MqttMessage;
+use protocol::mqtt::common::QoS;
+use serde::{Deserialize, Serialize};
+use storage_adapter::storage::StorageAdapter;
+use tokio::sync::broadcast;
+use tokio::time::sleep;
+
+use super::inflight::resend_inflight_message;
+use super::sub_common::min_qos;
+use super::sub_exclusive::exclusive_push_message;
+use super::subscribe_manager::SubscribeManager;
+use super::subscriber::Subscriber;
+use crate::handler::cache::CacheManager;
+use crate::handler::message::is_message_expire;
+use crate::server::connection_manager::ConnectionManager;
+
+const OFFLINE_MESSAGE_KEY_PREFIX: &str = "/offline_message/";
+
+#[derive(Clone, Serialize, Deserialize, Default, Debug, PartialEq)]
+pub struct OfflineQueueMeta {
+    // Sequence of the oldest message in the queue
+    pub start: u64,
+    // Sequence of the next message to be appended
+    pub end: u64,
+    pub bytes: u64,
+}
+
+impl OfflineQueueMeta {
+    pub fn len(&self) -> u64 {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+pub struct OfflineMessage {
+    pub subscriber: Subscriber,
+    pub message: MqttMessage,
+}
+
+// Messages of a persistent session that arrive while the client is offline.
+// The queue is stored through the storage adapter, so it survives a broker restart
+// and can be replayed by whichever broker the client reconnects to.
+pub struct OfflineQueue<S> {
+    client_id: String,
+    storage_adapter: Arc<S>,
+}
+
+impl<S> OfflineQueue<S>
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    pub fn new(client_id: String, storage_adapter: Arc<S>) -> Self {
+        OfflineQueue {
+            client_id,
+            storage_adapter,
+        }
+    }
+
+    // Returns false when the message is dropped because of the queue limits
+    pub async fn push(
+        &self,
+        protocol: &MqttClusterDynamicConfigProtocol,
+        message: &OfflineMessage,
+    ) -> Result<bool, CommonError> {
+        let data = serde_json::to_vec(message)?;
+        let size = data.len() as u64;
+        if protocol.offline_message_max_bytes > 0 && size > protocol.offline_message_max_bytes {
+            return Ok(false);
+        }
+
+        let mut meta = self.get_meta().await?;
+        while is_queue_full(protocol, &meta, size) {
+            match protocol.offline_message_drop_policy {
+                OfflineMessageDropPolicy::DropNewest => return Ok(false),
+                OfflineMessageDropPolicy::DropOldest => self.remove_front(&mut meta).await?,
+            }
+        }
+
+        self.storage_adapter
+            .set(self.record_key(meta.end), Record::build_b(data))
+            .await?;
+        meta.end += 1;
+        meta.bytes += size;
+        self.save_meta(&meta).await?;
+        Ok(true)
+    }
+
+    // Returns the oldest message and its sequence
+    pub async fn front(&self) -> Result<Option<(u64, OfflineMessage)>, CommonError> {
+        let mut meta = self.get_meta().await?;
+        while !meta.is_empty() {
+            if let Some(record) = self
+                .storage_adapter
+                .get(self.record_key(meta.start))
+                .await?
+            {
+                return Ok(Some((meta.start, serde_json::from_slice(&record.data)?)));
+            }
+            // The record is missing, skip it
+            meta.start += 1;
+            self.save_meta(&meta).await?;
+        }
+        Ok(None)
+    }
+
+    // Removes the oldest message only if it is still the message with the given sequence,
+    // it may have already been dropped by the drop-oldest policy
+    pub async fn pop_front(&self, seq: u64) -> Result<(), CommonError> {
+        let mut meta = self.get_meta().await?;
+        if meta.is_empty() || meta.start != seq {
+            return Ok(());
+        }
+        self.remove_front(&mut meta).await
+    }
+
+    pub async fn clear(&self) -> Result<(), CommonError> {
+        let meta = self.get_meta().await?;
+        for seq in meta.start..meta.end {
+            self.storage_adapter.delete(self.record_key(seq)).await?;
+        }
+        self.storage_adapter.delete(self.meta_key()).await
+    }
+
+    async fn remove_front(&self, meta: &mut OfflineQueueMeta) -> Result<(), CommonError> {
+        let key = self.record_key(meta.start);
+        if let Some(record) = self.storage_adapter.get(key.clone()).await? {
+            meta.bytes = meta.bytes.saturating_sub(record.data.len() as u64);
+            self.storage_adapter.delete(key).await?;
+        }
+        meta.start += 1;
+        if meta.is_empty() {
+            meta.bytes = 0;
+        }
+        self.save_meta(meta).await
+    }
+
+    pub async fn get_meta(&self) -> Result<OfflineQueueMeta, CommonError> {
+        match self.storage_adapter.get(self.meta_key()).await? {
+            Some(record) => Ok(serde_json::from_slice(&record.data)?),
+            None => Ok(OfflineQueueMeta::default()),
+        }
+    }
+
+    async fn save_meta(&self, meta: &OfflineQueueMeta) -> Result<(), CommonError> {
+        self.storage_adapter
+            .set(self.meta_key(), Record::build_b(serde_json::to_vec(meta)?))
+            .await
+    }
+
+    fn meta_key(&self) -> String {
+        format!("{}{}/meta", OFFLINE_MESSAGE_KEY_PREFIX, self.client_id)
+    }
+
+    fn record_key(&self, seq: u64) -> String {
+        format!(
+            "{}{}/record/{}",
+            OFFLINE_MESSAGE_KEY_PREFIX, self.client_id, seq
+        )
+    }
+}
+
+fn is_queue_full(
+    protocol: &MqttClusterDynamicConfigProtocol,
+    meta: &OfflineQueueMeta,
+    size: u64,
+) -> bool {
+    if meta.is_empty() {
+        return false;
+    }
+    (protocol.offline_message_max_num > 0 && meta.len() >= protocol.offline_message_max_num)
+        || (protocol.offline_message_max_bytes > 0
+            && meta.bytes + size > protocol.offline_message_max_bytes)
+}
+
+// Called by the push threads before a message is pushed. Returns true when the
+// message has been consumed, either saved into the offline queue or dropped, and must not
+// be pushed to the client.
+pub async fn try_save_offline_message<S>(
+    cache_manager: &Arc<CacheManager>,
+    subscribe_manager: &Arc<SubscribeManager>,
+    storage_adapter: &Arc<S>,
+    subscriber: &Subscriber,
+    qos: QoS,
+    message: &MqttMessage,
+) -> Result<bool, CommonError>
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    let client_id = &subscriber.client_id;
+    let lock = subscribe_manager.get_offline_queue_lock(client_id);
+    let replaying = lock.lock().await;
+
+    // New messages are queued behind the offline messages that are being replayed to keep the order
+    if cache_manager.get_connect_id(client_id).is_some() && !*replaying {
+        return Ok(false);
+    }
+
+    let session = if let Some(session) = cache_manager.get_session_info(client_id) {
+        session
+    } else {
+        return Ok(true);
+    };
+
+    let protocol = cache_manager.get_cluster_info().protocol;
+    if session.session_expiry == 0 || (qos == QoS::AtMostOnce && !protocol.offline_message_qos0) {
+        return Ok(true);
+    }
+
+    let queue = OfflineQueue::new(client_id.to_owned(), storage_adapter.clone());
+    let offline_message = OfflineMessage {
+        subscriber: subscriber.clone(),
+        message: message.clone(),
+    };
+    if !queue.push(&protocol, &offline_message).await? {
+        debug!(
+            "Offline queue of client [{}] is full, message is discarded",
+            client_id
+        );
+    }
+    Ok(true)
+}
+
+// Marks the offline queue of the client as being replayed, new messages are queued until the replay is done
+pub async fn mark_offline_message_replay(
+    subscribe_manager: &Arc<SubscribeManager>,
+    client_id: &str,
+) {
+    let lock = subscribe_manager.get_offline_queue_lock(client_id);
+    let mut replaying = lock.lock().await;
+    *replaying = true;
+}
+
+pub fn start_offline_message_replay<S>(
+    client_id: String,
+    cache_manager: Arc<CacheManager>,
+    subscribe_manager: Arc<SubscribeManager>,
+    connection_manager: Arc<ConnectionManager>,
+    storage_adapter: Arc<S>,
+) where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    stop_offline_message_replay(&subscribe_manager, &client_id);
+
+    let (stop_sx, mut stop_rx) = broadcast::channel(1);
+    subscribe_manager
+        .offline_replay_thread
+        .insert(client_id.clone(), stop_sx.clone());
+
+    tokio::spawn(async move {
+        let queue = OfflineQueue::new(client_id.clone(), storage_adapter.clone());
+        let lock = subscribe_manager.get_offline_queue_lock(&client_id);
+        let mut replaced = false;
+        let mut resent = false;
+        loop {
+            if let Ok(flag) = stop_rx.try_recv() {
+                if flag {
+                    replaced = true;
+                    break;
+                }
+            }
+
+            // Messages stay in the queue when the client goes offline again
+            if cache_manager.get_connect_id(&client_id).is_none() {
+                break;
+            }
+
+            // Unacknowledged messages of the session are retransmitted before the offline messages
+            if !resent {
+                match resend_inflight_message(
+                    &cache_manager,
+                    &connection_manager,
+                    &storage_adapter,
+                    &client_id,
+                )
+                .await
+                {
+                    Ok(true) => resent = true,
+                    Ok(false) => break,
+                    Err(e) => {
+                        error!(
+                            "Failed to resend inflight message of client [{}], error message :{}",
+                            client_id, e
+                        );
+                        sleep(Duration::from_secs(1)).await;
+                        continue;
+                    }
+                }
+            }
+
+            let mut replaying = lock.lock().await;
+            let (seq, offline_message) = match queue.front().await {
+                Ok(Some(data)) => data,
+                Ok(None) => {
+                    *replaying = false;
+                    break;
+                }
+                Err(e) => {
+                    drop(replaying);
+                    error!(
+                        "Failed to read offline message of client [{}], error message :{}",
+                        client_id, e
+                    );
+                    sleep(Duration::from_secs(1)).await;
+                    continue;
+                }
+            };
+            drop(replaying);
+
+            let OfflineMessage {
+                subscriber,
+                message,
+            } = offline_message;
+            if is_message_expire(&message) {
+                debug!("Offline message expires, is not pushed to the client, and is discarded");
+            } else {
+                let cluster_qos = cache_manager.get_cluster_info().protocol.max_qos;
+                let qos = min_qos(cluster_qos, subscriber.qos);
+                match exclusive_push_message(
+                    &cache_manager,
+                    &connection_manager,
+                    &storage_adapter,
+                    &subscriber,
+                    qos,
+                    message,
+                    &stop_sx,
+                )
+                .await
+                {
+                    Ok(true) => {}
+                    Ok(false) => break,
+                    Err(e) => {
+                        error!(
+                            "Failed to push offline message to client [{}], error message :{}",
+                            client_id, e
+                        );
+                        sleep(Duration::from_secs(1)).await;
+                        continue;
+                    }
+                }
+            }
+
+            let _replaying = lock.lock().await;
+            if let Err(e) = queue.pop_front(seq).await {
+                error!(
+                    "Failed to remove offline message of client [{}], error message :{}",
+                    client_id, e
+                );
+            }
+        }
+
+        if !replaced {
+            subscribe_manager.offline_replay_thread.remove(&client_id);
+        }
+        info!(
+            "Offline message replay thread for client_id [{}] was stopped",
+            client_id
+        );
+    });
+}
+
+pub fn stop_offline_message_replay(subscribe_manager: &Arc<SubscribeManager>, client_id: &str) {
+    if let Some((_, sx)) = subscribe_manager.offline_replay_thread.remove(client_id) {
+        let _ = sx.send(true);
+    }
+}
+
+// Drops the offline messages of the client, e.g. when a new session is created or the session expires
+pub async fn clear_offline_message<S>(
+    subscribe_manager: &Arc<SubscribeManager>,
+    storage_adapter: &Arc<S>,
+    client_id: &str,
+) -> Result<(), CommonError>
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    stop_offline_message_replay(subscribe_manager, client_id);
+    let lock = subscribe_manager.get_offline_queue_lock(client_id);
+    let mut replaying = lock.lock().await;
+    *replaying = false;
+    OfflineQueue::new(client_id.to_owned(), storage_adapter.clone())
+        .clear()
+        .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use bytes::Bytes;
+    use metadata_struct::mqtt::cluster::{
+        MqttClusterDynamicConfig, MqttClusterDynamicConfigProtocol, OfflineMessageDropPolicy,
+    };
+    use metadata_struct::mqtt::message::MqttMessage;
+    use storage_adapter::memory::MemoryStorageAdapter;
+
+    use super::{OfflineMessage, OfflineQueue};
+    use crate::subscribe::subscriber::Subscriber;
+
+    fn build_message(payload: &str) -> OfflineMessage {
+        OfflineMessage {
+            subscriber: Subscriber {
+                client_id: "client-1".to_string(),
+                ..Default::default()
+            },
+            message: MqttMessage {
+                payload: Bytes::from(payload.to_string()),
+                ..Default::default()
+            },
+        }
+    }
+
+    async fn front_payload(queue: &OfflineQueue<MemoryStorageAdapter>) -> Option<(u64, Bytes)> {
+        queue
+            .front()
+            .await
+            .unwrap()
+            .map(|(seq, msg)| (seq, msg.message.payload))
+    }
+
+    #[tokio::test]
+    async fn offline_queue_order_test() {
+        let storage_adapter = Arc::new(MemoryStorageAdapter::new());
+        let queue = OfflineQueue::new("client-1".to_string(), storage_adapter);
+        let protocol = MqttClusterDynamicConfig::new().protocol;
+
+        assert!(front_payload(&queue).await.is_none());
+        for payload in ["m1", "m2", "m3"] {
+            assert!(queue
+                .push(&protocol, &build_message(payload))
+                .await
+                .unwrap());
+        }
+        assert_eq!(queue.get_meta().await.unwrap().len(), 3);
+
+        assert_eq!(front_payload(&queue).await, Some((0, Bytes::from("m1"))));
+        queue.pop_front(0).await.unwrap();
+        // an outdated sequence does not remove the next message
+        queue.pop_front(0).await.unwrap();
+        assert_eq!(front_payload(&queue).await, Some((1, Bytes::from("m2"))));
+
+        queue.clear().await.unwrap();
+        assert_eq!(queue.get_meta().await.unwrap().len(), 0);
+        assert!(front_payload(&queue).await.is_none());
+    }
+
+    #[tokio::test]
+    async fn offline_queue_drop_policy_test() {
+        let storage_adapter = Arc::new(MemoryStorageAdapter::new());
+        let queue = OfflineQueue::new("client-1".to_string(), storage_adapter);
+        let mut protocol = MqttClusterDynamicConfigProtocol {
+            offline_message_max_num: 2,
+            ..Default::default()
+        };
+
+        for payload in ["m1", "m2", "m3"] {
+            assert!(queue
+                .push(&protocol, &build_message(payload))
+                .await
+                .unwrap());
+        }
+        assert_eq!(queue.get_meta().await.unwrap().len(), 2);
+        assert_eq!(front_payload(&queue).await, Some((1, Bytes::from("m2"))));
+
+        protocol.offline_message_drop_policy = OfflineMessageDropPolicy::DropNewest;
+        assert!(!queue.push(&protocol, &build_message("m4")).await.unwrap());
+        assert_eq!(queue.get_meta().await.unwrap().len(), 2);
+        assert_eq!(front_payload(&queue).await, Some((1, Bytes::from("m2"))));
+
+        // byte limit
+        let size = serde_json::to_vec(&build_message("m5")).unwrap().len() as u64;
+        let protocol = MqttClusterDynamicConfigProtocol {
+            offline_message_max_bytes: size * 2,
+            ..Default::default()
+        };
+        queue.clear().await.unwrap();
+        for payload in ["m5", "m6", "m7"] {
+            assert!(queue
+                .push(&protocol, &build_message(payload))
+                .await
+                .unwrap());
+        }
+        assert_eq!(queue.get_meta().await.unwrap().len(), 2);
+        assert_eq!(front_payload(&queue).await, Some((1, Bytes::from("m6"))));
+    }
+}
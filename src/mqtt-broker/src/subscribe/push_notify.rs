@@ -0,0 +1,225 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use common_base::config::broker_mqtt::broker_mqtt_conf;
+use dashmap::DashMap;
+use grpc_clients::mqtt::placement::call::broker_mqtt_notify_topic_message;
+use grpc_clients::poll::ClientPool;
+use log::{debug, error};
+use protocol::broker_mqtt::broker_mqtt_placement::NotifyTopicMessageRequest;
+use tokio::select;
+use tokio::sync::{broadcast, watch, Notify};
+use tokio::time::{interval, sleep};
+
+use crate::handler::cache::CacheManager;
+use crate::storage::cluster::ClusterStorage;
+
+// Upper bound for a parked push task, covers signals lost between broker nodes
+const PUSH_MAX_WAIT_MS: u64 = 5000;
+const REMOTE_NOTIFY_DEBOUNCE_MS: u64 = 20;
+const NODE_LIST_REFRESH_INTERVAL_MS: u64 = 10000;
+
+// Per-topic wakeups for push tasks.
+// Each topic holds a watch channel whose version is bumped on every append,
+// so a push task that is parked on an empty topic wakes up as soon as data arrives.
+#[derive(Clone, Default)]
+pub struct PushNotify {
+    topics: DashMap<String, Arc<watch::Sender<u64>>>,
+    // Topics that received messages and have not yet been announced to the other broker nodes
+    remote_pending: DashMap<String, bool>,
+    // Wakes the remote notify thread when remote_pending becomes non-empty
+    remote_wakeup: Arc<Notify>,
+}
+
+impl PushNotify {
+    pub fn new() -> Self {
+        PushNotify::default()
+    }
+
+    pub fn subscribe(&self, topic_id: &str) -> watch::Receiver<u64> {
+        self.topics
+            .entry(topic_id.to_owned())
+            .or_insert_with(|| Arc::new(watch::channel(0).0))
+            .subscribe()
+    }
+
+    // Called after messages were appended to the topic on this node
+    pub fn notify(&self, topic_id: &str) {
+        self.notify_local(topic_id);
+        if self
+            .remote_pending
+            .insert(topic_id.to_owned(), true)
+            .is_none()
+        {
+            self.remote_wakeup.notify_one();
+        }
+    }
+
+    // Called when another broker node announces new messages for the topic
+    pub fn notify_local(&self, topic_id: &str) {
+        if let Some(sx) = self.topics.get(topic_id) {
+            sx.send_modify(|version| *version = version.wrapping_add(1));
+        }
+    }
+
+    pub fn take_remote_pending(&self) -> Vec<String> {
+        let topic_ids: Vec<String> = self
+            .remote_pending
+            .iter()
+            .map(|raw| raw.key().clone())
+            .collect();
+        for topic_id in topic_ids.iter() {
+            self.remote_pending.remove(topic_id);
+        }
+        topic_ids
+    }
+
+    // Drop the channels of topics that no push task is waiting on
+    pub fn try_gc(&self) {
+        self.topics.retain(|_, sx| sx.receiver_count() > 0);
+    }
+}
+
+// Park the push task until a new message arrives for the topic
+pub async fn wait_topic_message(rx: &mut watch::Receiver<u64>) {
+    select! {
+        val = rx.changed() => {
+            if val.is_err() {
+                sleep(Duration::from_millis(PUSH_MAX_WAIT_MS)).await;
+            }
+        }
+        _ = sleep(Duration::from_millis(PUSH_MAX_WAIT_MS)) => {}
+    }
+}
+
+// Announce the topics that received messages on this node to the other broker nodes,
+// so that push tasks parked there can read the new data immediately.
+// The thread only wakes up when there is something to announce, and batches the topics
+// appended within REMOTE_NOTIFY_DEBOUNCE_MS into one request per node.
+pub async fn start_remote_notify_thread(
+    cache_manager: Arc<CacheManager>,
+    client_poll: Arc<ClientPool>,
+    stop_send: broadcast::Sender<bool>,
+) {
+    let mut stop_rx = stop_send.subscribe();
+    let mut node_addrs = Vec::new();
+    let mut node_refresh = interval(Duration::from_millis(NODE_LIST_REFRESH_INTERVAL_MS));
+    loop {
+        select! {
+            val = stop_rx.recv() => {
+                if let Ok(flag) = val {
+                    if flag {
+                        debug!("{}", "Push notify thread exited successfully");
+                        break;
+                    }
+                }
+            }
+            _ = node_refresh.tick() => {
+                node_addrs = remote_node_addrs(&client_poll).await;
+                cache_manager.push_notify.try_gc();
+                // nodes lost without leaving the cluster are not announced by the other brokers
+                cache_manager.rate_limiter.set_broker_num(node_addrs.len() as u64 + 1);
+            }
+            _ = cache_manager.push_notify.remote_wakeup.notified() => {
+                sleep(Duration::from_millis(REMOTE_NOTIFY_DEBOUNCE_MS)).await;
+                let topic_ids = cache_manager.push_notify.take_remote_pending();
+                if topic_ids.is_empty() {
+                    continue;
+                }
+
+                for addr in node_addrs.iter() {
+                    let request = NotifyTopicMessageRequest {
+                        cluster_name: cache_manager.cluster_name.clone(),
+                        topic_id: topic_ids.clone(),
+                    };
+                    let client_poll = client_poll.clone();
+                    let addr = addr.clone();
+                    tokio::spawn(async move {
+                        if let Err(e) =
+                            broker_mqtt_notify_topic_message(client_poll, vec![addr], request).await
+                        {
+                            error!("Failed to notify broker node of new topic messages, error message :{}", e);
+                        }
+                    });
+                }
+            }
+        }
+    }
+}
+
+pub(crate) async fn remote_node_addrs(client_poll: &Arc<ClientPool>) -> Vec<String> {
+    let cluster_storage = ClusterStorage::new(client_poll.clone());
+    match cluster_storage.node_list().await {
+        Ok(nodes) => nodes
+            .into_iter()
+            .filter(|node| node.node_id != broker_mqtt_conf().broker_id)
+            .map(|node| node.node_inner_addr)
+            .collect(),
+        Err(e) => {
+            error!("{}", e.to_string());
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::timeout;
+
+    use super::PushNotify;
+
+    #[tokio::test]
+    async fn push_notify_wakeup_test() {
+        let notify = PushNotify::new();
+        let mut rx = notify.subscribe("t1");
+
+        // A message appended before waiting must not be missed
+        notify.notify("t1");
+        let res = timeout(Duration::from_millis(100), rx.changed()).await;
+        assert!(res.is_ok());
+
+        // Messages of other topics do not wake the task
+        notify.notify("t2");
+        let res = timeout(Duration::from_millis(100), rx.changed()).await;
+        assert!(res.is_err());
+
+        // The remote notify thread is woken up once for the pending topics
+        let res = timeout(Duration::from_millis(100), notify.remote_wakeup.notified()).await;
+        assert!(res.is_ok());
+        let res = timeout(Duration::from_millis(100), notify.remote_wakeup.notified()).await;
+        assert!(res.is_err());
+
+        let mut pending = notify.take_remote_pending();
+        pending.sort();
+        assert_eq!(pending, vec!["t1".to_string(), "t2".to_string()]);
+        assert!(notify.take_remote_pending().is_empty());
+
+        // Wakeups from other nodes are not announced again
+        notify.notify_local("t1");
+        let res = timeout(Duration::from_millis(100), rx.changed()).await;
+        assert!(res.is_ok());
+        assert!(notify.take_remote_pending().is_empty());
+
+        notify.try_gc();
+        assert_eq!(notify.topics.len(), 1);
+        drop(rx);
+        notify.try_gc();
+        assert!(notify.topics.is_empty());
+    }
+}
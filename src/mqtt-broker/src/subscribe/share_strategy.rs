@@ -0,0 +1,231 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+
+use metadata_struct::mqtt::cluster::ShareSubStrategy;
+use rand::Rng;
+
+use super::sub_common::is_share_follower_client;
+use super::subscriber::Subscriber;
+
+// Chooses the group member that receives the next message of a shared subscription.
+// Members in `excluded` have already failed to receive the current message and are skipped,
+// so that the message is redispatched to another member of the group.
+pub struct ShareSubDispatcher {
+    strategy: ShareSubStrategy,
+    cursor_point: usize,
+    sticky_client_id: Option<String>,
+}
+
+impl ShareSubDispatcher {
+    pub fn new(strategy: ShareSubStrategy) -> Self {
+        ShareSubDispatcher {
+            strategy,
+            cursor_point: 0,
+            sticky_client_id: None,
+        }
+    }
+
+    pub fn set_strategy(&mut self, strategy: ShareSubStrategy) {
+        if self.strategy != strategy {
+            self.strategy = strategy;
+            self.sticky_client_id = None;
+        }
+    }
+
+    pub fn choose<'a>(
+        &mut self,
+        sub_list: &'a [Subscriber],
+        topic_name: &str,
+        publish_client_id: &str,
+        excluded: &HashSet<String>,
+    ) -> Option<&'a Subscriber> {
+        let candidates: Vec<&Subscriber> = sub_list
+            .iter()
+            .filter(|sub| !excluded.contains(&sub.client_id))
+            .collect();
+        if candidates.is_empty() {
+            return None;
+        }
+
+        match self.strategy {
+            ShareSubStrategy::RoundRobin => Some(self.round_robin(&candidates)),
+            ShareSubStrategy::Random => {
+                let index = rand::thread_rng().gen_range(0..candidates.len());
+                Some(candidates[index])
+            }
+            ShareSubStrategy::HashClientId => {
+                Some(candidates[hash_index(publish_client_id, candidates.len())])
+            }
+            ShareSubStrategy::HashTopic => {
+                Some(candidates[hash_index(topic_name, candidates.len())])
+            }
+            ShareSubStrategy::Sticky => {
+                if let Some(client_id) = &self.sticky_client_id {
+                    if let Some(sub) = candidates.iter().find(|sub| sub.client_id == *client_id) {
+                        return Some(sub);
+                    }
+                }
+                let index = rand::thread_rng().gen_range(0..candidates.len());
+                self.sticky_client_id = Some(candidates[index].client_id.clone());
+                Some(candidates[index])
+            }
+            ShareSubStrategy::LocalFirst => {
+                let local: Vec<&Subscriber> = candidates
+                    .iter()
+                    .filter(|sub| !is_share_follower_client(&sub.client_id))
+                    .copied()
+                    .collect();
+                if local.is_empty() {
+                    Some(self.round_robin(&candidates))
+                } else {
+                    Some(self.round_robin(&local))
+                }
+            }
+        }
+    }
+
+    fn round_robin<'a>(&mut self, candidates: &[&'a Subscriber]) -> &'a Subscriber {
+        self.cursor_point = self.cursor_point.wrapping_add(1);
+        candidates[self.cursor_point % candidates.len()]
+    }
+}
+
+fn hash_index(key: &str, len: usize) -> usize {
+    let mut hasher = DefaultHasher::new();
+    key.hash(&mut hasher);
+    (hasher.finish() % len as u64) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use metadata_struct::mqtt::cluster::ShareSubStrategy;
+
+    use super::ShareSubDispatcher;
+    use crate::subscribe::sub_common::SHARE_SUB_FOLLOWER_CLIENT_ID_PREFIX;
+    use crate::subscribe::subscriber::Subscriber;
+
+    fn build_sub_list(client_ids: &[&str]) -> Vec<Subscriber> {
+        client_ids
+            .iter()
+            .map(|client_id| Subscriber {
+                client_id: client_id.to_string(),
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn round_robin_test() {
+        let sub_list = build_sub_list(&["c1", "c2", "c3"]);
+        let mut dispatcher = ShareSubDispatcher::new(ShareSubStrategy::RoundRobin);
+        let excluded = HashSet::new();
+
+        let mut result = HashSet::new();
+        for _ in 0..3 {
+            let sub = dispatcher.choose(&sub_list, "t1", "p1", &excluded).unwrap();
+            result.insert(sub.client_id.clone());
+        }
+        assert_eq!(result.len(), 3);
+    }
+
+    #[test]
+    fn hash_test() {
+        let sub_list = build_sub_list(&["c1", "c2", "c3"]);
+        let excluded = HashSet::new();
+
+        let mut dispatcher = ShareSubDispatcher::new(ShareSubStrategy::HashTopic);
+        let first = dispatcher
+            .choose(&sub_list, "device/1", "p1", &excluded)
+            .unwrap()
+            .client_id
+            .clone();
+        for i in 0..10 {
+            let sub = dispatcher
+                .choose(&sub_list, "device/1", &format!("p{}", i), &excluded)
+                .unwrap();
+            assert_eq!(sub.client_id, first);
+        }
+
+        let mut dispatcher = ShareSubDispatcher::new(ShareSubStrategy::HashClientId);
+        let first = dispatcher
+            .choose(&sub_list, "t1", "p1", &excluded)
+            .unwrap()
+            .client_id
+            .clone();
+        for i in 0..10 {
+            let sub = dispatcher
+                .choose(&sub_list, &format!("t{}", i), "p1", &excluded)
+                .unwrap();
+            assert_eq!(sub.client_id, first);
+        }
+    }
+
+    #[test]
+    fn sticky_and_redispatch_test() {
+        let sub_list = build_sub_list(&["c1", "c2"]);
+        let mut dispatcher = ShareSubDispatcher::new(ShareSubStrategy::Sticky);
+        let mut excluded = HashSet::new();
+
+        let first = dispatcher
+            .choose(&sub_list, "t1", "p1", &excluded)
+            .unwrap()
+            .client_id
+            .clone();
+        for _ in 0..10 {
+            let sub = dispatcher.choose(&sub_list, "t1", "p1", &excluded).unwrap();
+            assert_eq!(sub.client_id, first);
+        }
+
+        // The sticky member fails, the message goes to the other member which becomes sticky
+        excluded.insert(first.clone());
+        let second = dispatcher
+            .choose(&sub_list, "t1", "p1", &excluded)
+            .unwrap()
+            .client_id
+            .clone();
+        assert_ne!(first, second);
+        let sub = dispatcher
+            .choose(&sub_list, "t1", "p1", &HashSet::new())
+            .unwrap();
+        assert_eq!(sub.client_id, second);
+
+        excluded.insert(second);
+        assert!(dispatcher
+            .choose(&sub_list, "t1", "p1", &excluded)
+            .is_none());
+    }
+
+    #[test]
+    fn local_first_test() {
+        let follower = format!("{}1", SHARE_SUB_FOLLOWER_CLIENT_ID_PREFIX);
+        let sub_list = build_sub_list(&["c1", &follower]);
+        let mut dispatcher = ShareSubDispatcher::new(ShareSubStrategy::LocalFirst);
+        let mut excluded = HashSet::new();
+
+        for _ in 0..5 {
+            let sub = dispatcher.choose(&sub_list, "t1", "p1", &excluded).unwrap();
+            assert_eq!(sub.client_id, "c1");
+        }
+
+        excluded.insert("c1".to_string());
+        let sub = dispatcher.choose(&sub_list, "t1", "p1", &excluded).unwrap();
+        assert_eq!(sub.client_id, follower);
+    }
+}
@@ -39,8 +39,11 @@ use crate::observability::metrics::packets::record_sent_metrics;
 use crate::server::connection_manager::ConnectionManager;
 use crate::server::packet::ResponsePackage;
 use crate::storage::message::MessageStorage;
+use crate::subscribe::topic_trie::topic_filter_match;
 
 const SHARE_SUB_PREFIX: &str = "$share";
+// Client ID prefix of the connections that share subscription followers open to the group leader
+pub const SHARE_SUB_FOLLOWER_CLIENT_ID_PREFIX: &str = "robustmq_share_follower_";
 
 pub fn path_contain_sub(_: &str) -> bool {
     true
@@ -65,35 +68,14 @@ pub fn sub_path_validator(sub_path: String) -> bool {
     true
 }
 
-pub fn path_regex_match(topic_name: String, sub_path: String) -> bool {
+pub fn path_match(topic_name: String, sub_path: String) -> bool {
     let path = if is_share_sub(sub_path.clone()) {
         let (_, group_path) = decode_share_info(sub_path);
         group_path
     } else {
         sub_path
     };
-
-    // Path perfect matching
-    if topic_name == path {
-        return true;
-    }
-
-    if path.contains("+") {
-        let sub_regex = path.replace("+", "[^+*/]+");
-        let re = Regex::new(&sub_regex.to_string()).unwrap();
-        return re.is_match(&topic_name);
-    }
-
-    if path.contains("#") {
-        if path.split("/").last().unwrap() != "#" {
-            return false;
-        }
-        let sub_regex = path.replace("#", "[^+#]+");
-        let re = Regex::new(&sub_regex.to_string()).unwrap();
-        return re.is_match(&topic_name);
-    }
-
-    false
+    topic_filter_match(&topic_name, &path)
 }
 
 pub fn min_qos(qos: QoS, sub_qos: QoS) -> QoS {
@@ -107,13 +89,17 @@ pub async fn get_sub_topic_id_list(
     metadata_cache: Arc<CacheManager>,
     sub_path: String,
 ) -> Vec<String> {
-    let mut result = Vec::new();
-    for (topic_id, topic_name) in metadata_cache.topic_id_name.clone() {
-        if path_regex_match(topic_name.clone(), sub_path.clone()) {
-            result.push(topic_id);
-        }
-    }
-    result
+    let path = if is_share_sub(sub_path.clone()) {
+        let (_, group_path) = decode_share_info(sub_path);
+        group_path
+    } else {
+        sub_path
+    };
+    metadata_cache.topic_tree.match_filter(&path)
+}
+
+pub fn is_share_follower_client(client_id: &str) -> bool {
+    client_id.starts_with(SHARE_SUB_FOLLOWER_CLIENT_ID_PREFIX)
 }
 
 pub fn is_share_sub(sub_name: String) -> bool {
@@ -418,7 +404,7 @@ mod tests {
 
     use crate::handler::cache::CacheManager;
     use crate::subscribe::sub_common::{
-        decode_share_info, get_sub_topic_id_list, is_share_sub, min_qos, path_regex_match,
+        decode_share_info, get_sub_topic_id_list, is_share_sub, min_qos, path_match,
         sub_path_validator,
     };
 
@@ -468,55 +454,55 @@ mod tests {
     fn path_regex_match_test() {
         let topic_name = "/loboxu/test".to_string();
         let sub_regex = "/loboxu/#".to_string();
-        assert!(path_regex_match(topic_name, sub_regex));
+        assert!(path_match(topic_name, sub_regex));
 
         let topic_name = "/topic/test".to_string();
         let sub_regex = "/topic/test".to_string();
-        assert!(path_regex_match(topic_name, sub_regex));
+        assert!(path_match(topic_name, sub_regex));
 
         let topic_name = r"/sensor/1/temperature".to_string();
         let sub_regex = r"/sensor/+/temperature".to_string();
-        assert!(path_regex_match(topic_name, sub_regex));
+        assert!(path_match(topic_name, sub_regex));
 
         let topic_name = r"/sensor/1/2/temperature3".to_string();
         let sub_regex = r"/sensor/+/temperature".to_string();
-        assert!(!path_regex_match(topic_name, sub_regex));
+        assert!(!path_match(topic_name, sub_regex));
 
         let topic_name = r"/sensor/temperature3".to_string();
         let sub_regex = r"/sensor/+/temperature".to_string();
-        assert!(!path_regex_match(topic_name, sub_regex));
+        assert!(!path_match(topic_name, sub_regex));
 
         let topic_name = r"/sensor/temperature3".to_string();
         let sub_regex = r"/sensor/+".to_string();
-        assert!(path_regex_match(topic_name, sub_regex));
+        assert!(path_match(topic_name, sub_regex));
 
         let topic_name = r"/sensor/temperature3/tmpq".to_string();
         let sub_regex = r"/sensor/#".to_string();
-        assert!(path_regex_match(topic_name, sub_regex));
+        assert!(path_match(topic_name, sub_regex));
 
         let topic_name = "/topic/test".to_string();
         let sub_regex = "$share/groupname/topic/test".to_string();
-        assert!(path_regex_match(topic_name, sub_regex));
+        assert!(path_match(topic_name, sub_regex));
 
         let topic_name = r"/sensor/1/temperature".to_string();
         let sub_regex = r"$share/groupname/sensor/+/temperature".to_string();
-        assert!(path_regex_match(topic_name, sub_regex));
+        assert!(path_match(topic_name, sub_regex));
 
         let topic_name = r"/sensor/1/2/temperature3".to_string();
         let sub_regex = r"$share/groupname/sensor/+/temperature".to_string();
-        assert!(!path_regex_match(topic_name, sub_regex));
+        assert!(!path_match(topic_name, sub_regex));
 
         let topic_name = r"/sensor/temperature3".to_string();
         let sub_regex = r"$share/groupname/sensor/+/temperature".to_string();
-        assert!(!path_regex_match(topic_name, sub_regex));
+        assert!(!path_match(topic_name, sub_regex));
 
         let topic_name = r"/sensor/temperature3".to_string();
         let sub_regex = r"$share/groupname/sensor/+".to_string();
-        assert!(path_regex_match(topic_name, sub_regex));
+        assert!(path_match(topic_name, sub_regex));
 
         let topic_name = r"/sensor/temperature3/tmpq".to_string();
         let sub_regex = r"$share/groupname/sensor/#".to_string();
-        assert!(path_regex_match(topic_name, sub_regex));
+        assert!(path_match(topic_name, sub_regex));
     }
 
     #[test]
@@ -17,25 +17,29 @@ use std::time::Duration;
 
 use bytes::Bytes;
 use common_base::error::common::CommonError;
-use common_base::tools::now_second;
+use common_base::tools::now_mills;
 use grpc_clients::poll::ClientPool;
 use log::{debug, error, info};
 use metadata_struct::mqtt::message::MqttMessage;
-use protocol::mqtt::common::{MQTTPacket, MQTTProtocol, Publish, PublishProperties, QoS};
+use protocol::mqtt::common::{Publish, PublishProperties, QoS};
 use storage_adapter::storage::StorageAdapter;
+use tokio::select;
 use tokio::sync::broadcast::{self};
 use tokio::time::sleep;
 
-use super::sub_common::{
-    loop_commit_offset, min_qos, publish_message_qos0, publish_message_to_client,
-    qos2_send_publish, qos2_send_pubrel, wait_packet_ack,
+use super::inflight::{
+    acquire_send_window, complete_inflight_message, InflightMessage, InflightStore,
 };
+use super::offline_queue::try_save_offline_message;
+use super::push_notify::wait_topic_message;
+use super::sub_common::{loop_commit_offset, min_qos, publish_message_qos0};
 use super::subscribe_manager::SubscribeManager;
-use crate::handler::cache::{CacheManager, QosAckPackageData, QosAckPackageType, QosAckPacketInfo};
+use super::subscriber::Subscriber;
+use crate::handler::cache::CacheManager;
 use crate::handler::message::is_message_expire;
 use crate::handler::retain::try_send_retain_message;
+use crate::observability::slow::qos::try_record_slow_message;
 use crate::server::connection_manager::ConnectionManager;
-use crate::server::packet::ResponsePackage;
 use crate::storage::message::MessageStorage;
 
 pub struct SubscribeExclusive<S> {
@@ -107,6 +111,7 @@ where
 
             let (sub_thread_stop_sx, mut sub_thread_stop_rx) = broadcast::channel(1);
             let message_storage = self.message_storage.clone();
+            let storage_adapter = self.message_storage.clone();
             let cache_manager = self.cache_manager.clone();
             let connection_manager = self.connection_manager.clone();
             let subscribe_manager = self.subscribe_manager.clone();
@@ -139,27 +144,28 @@ where
                     client_poll.clone(),
                     cache_manager.clone(),
                     connection_manager.clone(),
+                    storage_adapter.clone(),
                     sub_thread_stop_sx.clone(),
                 )
                 .await;
 
-                let mut sub_ids = Vec::new();
-                if let Some(id) = subscriber.subscription_identifier {
-                    sub_ids.push(id);
-                }
+                let mut topic_rx = cache_manager.push_notify.subscribe(&subscriber.topic_id);
 
                 loop {
-                    if let Ok(flag) = sub_thread_stop_rx.try_recv() {
-                        if flag {
-                            info!(
-                                    "Exclusive Push thread for client_id [{}], sub_path: [{}], topic_id [{}] was stopped successfully",
-                                    client_id,
-                                    subscriber.sub_path,
-                                    subscriber.topic_id
-                                );
-                            break;
-                        }
+                    let is_stop = matches!(sub_thread_stop_rx.try_recv(), Ok(true))
+                        || !subscribe_manager
+                            .exclusive_subscribe
+                            .contains_key(&exclusive_key);
+                    if is_stop {
+                        info!(
+                                "Exclusive Push thread for client_id [{}], sub_path: [{}], topic_id [{}] was stopped successfully",
+                                client_id,
+                                subscriber.sub_path,
+                                subscriber.topic_id
+                            );
+                        break;
                     }
+                    topic_rx.borrow_and_update();
                     match message_storage
                         .read_topic_message(
                             subscriber.topic_id.clone(),
@@ -170,7 +176,23 @@ where
                     {
                         Ok(result) => {
                             if result.is_empty() {
-                                sleep(Duration::from_millis(max_wait_ms)).await;
+                                // Park until a new message is written to the topic
+                                select! {
+                                    val = sub_thread_stop_rx.recv() => {
+                                        if let Ok(flag) = val {
+                                            if flag {
+                                                info!(
+                                                    "Exclusive Push thread for client_id [{}], sub_path: [{}], topic_id [{}] was stopped successfully",
+                                                    client_id,
+                                                    subscriber.sub_path,
+                                                    subscriber.topic_id
+                                                );
+                                                break;
+                                            }
+                                        }
+                                    }
+                                    _ = wait_topic_message(&mut topic_rx) => {}
+                                }
                                 continue;
                             }
 
@@ -213,116 +235,62 @@ where
                                     continue;
                                 }
 
-                                let retain = if subscriber.preserve_retain {
-                                    msg.retain
-                                } else {
-                                    false
-                                };
-
-                                let mut publish = Publish {
-                                    dup: false,
+                                // The client is offline or its offline messages are being replayed,
+                                // the message is kept in the offline queue of the client
+                                match try_save_offline_message(
+                                    &cache_manager,
+                                    &subscribe_manager,
+                                    &storage_adapter,
+                                    &subscriber,
                                     qos,
-                                    pkid: 0,
-                                    retain,
-                                    topic: Bytes::from(subscriber.topic_name.clone()),
-                                    payload: msg.payload,
-                                };
-
-                                let properties = PublishProperties {
-                                    payload_format_indicator: msg.format_indicator,
-                                    message_expiry_interval: Some(msg.expiry_interval as u32),
-                                    topic_alias: None,
-                                    response_topic: msg.response_topic,
-                                    correlation_data: msg.correlation_data,
-                                    user_properties: msg.user_properties,
-                                    subscription_identifiers: sub_ids.clone(),
-                                    content_type: msg.content_type,
-                                };
-
-                                match qos {
-                                    QoS::AtMostOnce => {
-                                        publish_message_qos0(
-                                            &cache_manager,
-                                            &client_id,
-                                            &publish,
-                                            &Some(properties),
-                                            &connection_manager,
-                                            &sub_thread_stop_sx,
+                                    &msg,
+                                )
+                                .await
+                                {
+                                    Ok(true) => {
+                                        loop_commit_offset(
+                                            &message_storage,
+                                            &subscriber.topic_id,
+                                            &group_id,
+                                            record.offset,
                                         )
                                         .await;
+                                        continue;
                                     }
-
-                                    QoS::AtLeastOnce => {
-                                        let pkid: u16 = cache_manager.get_pkid(&client_id).await;
-                                        publish.pkid = pkid;
-
-                                        let (wait_puback_sx, _) = broadcast::channel(1);
-                                        cache_manager.add_ack_packet(
-                                            &client_id,
-                                            pkid,
-                                            QosAckPacketInfo {
-                                                sx: wait_puback_sx.clone(),
-                                                create_time: now_second(),
-                                            },
+                                    Ok(false) => {}
+                                    Err(e) => {
+                                        error!(
+                                            "Failed to save offline message of client [{}], error message :{}",
+                                            client_id, e
                                         );
-
-                                        match exclusive_publish_message_qos1(
-                                            &cache_manager,
-                                            &client_id,
-                                            publish,
-                                            &properties,
-                                            pkid,
-                                            &connection_manager,
-                                            &sub_thread_stop_sx,
-                                            &wait_puback_sx,
-                                        )
-                                        .await
-                                        {
-                                            Ok(()) => {
-                                                cache_manager.remove_pkid_info(&client_id, pkid);
-                                                cache_manager.remove_ack_packet(&client_id, pkid);
-                                            }
-                                            Err(e) => {
-                                                error!("{}", e);
-                                            }
-                                        }
+                                        sleep(Duration::from_millis(max_wait_ms)).await;
+                                        break;
                                     }
+                                }
 
-                                    QoS::ExactlyOnce => {
-                                        let pkid: u16 = cache_manager.get_pkid(&client_id).await;
-                                        publish.pkid = pkid;
-
-                                        let (wait_ack_sx, _) = broadcast::channel(1);
-                                        cache_manager.add_ack_packet(
-                                            &client_id,
-                                            pkid,
-                                            QosAckPacketInfo {
-                                                sx: wait_ack_sx.clone(),
-                                                create_time: now_second(),
-                                            },
+                                match exclusive_push_message(
+                                    &cache_manager,
+                                    &connection_manager,
+                                    &storage_adapter,
+                                    &subscriber,
+                                    qos,
+                                    msg,
+                                    &sub_thread_stop_sx,
+                                )
+                                .await
+                                {
+                                    Ok(true) => {}
+                                    // The client went offline, the message is read again and kept in the offline queue
+                                    Ok(false) => break,
+                                    Err(e) => {
+                                        error!(
+                                            "Failed to push message to client [{}], error message :{}",
+                                            client_id, e
                                         );
-                                        match exclusive_publish_message_qos2(
-                                            &cache_manager,
-                                            &client_id,
-                                            &publish,
-                                            &properties,
-                                            pkid,
-                                            &connection_manager,
-                                            &sub_thread_stop_sx,
-                                            &wait_ack_sx,
-                                        )
-                                        .await
-                                        {
-                                            Ok(()) => {
-                                                cache_manager.remove_pkid_info(&client_id, pkid);
-                                                cache_manager.remove_ack_packet(&client_id, pkid);
-                                            }
-                                            Err(e) => {
-                                                error!("{}", e);
-                                            }
-                                        }
+                                        sleep(Duration::from_millis(max_wait_ms)).await;
+                                        break;
                                     }
-                                };
+                                }
 
                                 // commit offset
                                 loop_commit_offset(
@@ -355,153 +323,114 @@ where
     }
 }
 
-// When the subscribed QOS is 1, we need to keep retrying to send the message to the client.
-// To avoid messages that are not successfully pushed to the client. When the client Session expires,
-// the push thread will exit automatically and will not attempt to push again.
-#[allow(clippy::too_many_arguments)]
-pub async fn exclusive_publish_message_qos1(
-    metadata_cache: &Arc<CacheManager>,
-    client_id: &str,
-    mut publish: Publish,
-    publish_properties: &PublishProperties,
-    pkid: u16,
-    connection_manager: &Arc<ConnectionManager>,
-    stop_sx: &broadcast::Sender<bool>,
-    wait_puback_sx: &broadcast::Sender<QosAckPackageData>,
-) -> Result<(), CommonError> {
-    let mut retry_times = 0;
-    loop {
-        if let Ok(flag) = stop_sx.subscribe().try_recv() {
-            if flag {
-                return Ok(());
-            }
-        }
-
-        let connect_id = if let Some(id) = metadata_cache.get_connect_id(client_id) {
-            id
-        } else {
-            sleep(Duration::from_secs(1)).await;
-            continue;
-        };
-
-        if let Some(conn) = metadata_cache.get_connection(connect_id) {
-            if publish.payload.len() > (conn.max_packet_size as usize) {
-                return Ok(());
-            }
-        }
-
-        retry_times += 1;
-        publish.dup = retry_times >= 2;
-
-        let mut contain_properties = false;
-        if let Some(protocol) = connection_manager.get_connect_protocol(connect_id) {
-            if MQTTProtocol::is_mqtt5(&protocol) {
-                contain_properties = true;
-            }
-        }
-
-        let resp = if contain_properties {
-            ResponsePackage {
-                connection_id: connect_id,
-                packet: MQTTPacket::Publish(publish.clone(), Some(publish_properties.clone())),
-            }
-        } else {
-            ResponsePackage {
-                connection_id: connect_id,
-                packet: MQTTPacket::Publish(publish.clone(), None),
-            }
-        };
-
-        match publish_message_to_client(resp.clone(), connection_manager).await {
-            Ok(_) => {
-                if let Some(data) = wait_packet_ack(wait_puback_sx).await {
-                    if data.ack_type == QosAckPackageType::PubAck && data.pkid == pkid {
-                        return Ok(());
-                    }
-                }
-            }
-            Err(e) => {
-                error!(
-                    "Failed to write QOS1 Publish message to response queue, failure message: {}",
-                    e
-                );
-                sleep(Duration::from_secs(1)).await;
-            }
-        }
-    }
+pub fn build_exclusive_publish(
+    subscriber: &Subscriber,
+    qos: QoS,
+    msg: MqttMessage,
+    sub_ids: &[usize],
+) -> (Publish, PublishProperties) {
+    let retain = if subscriber.preserve_retain {
+        msg.retain
+    } else {
+        false
+    };
+
+    let publish = Publish {
+        dup: false,
+        qos,
+        pkid: 0,
+        retain,
+        topic: Bytes::from(subscriber.topic_name.clone()),
+        payload: msg.payload,
+    };
+
+    let properties = PublishProperties {
+        payload_format_indicator: msg.format_indicator,
+        message_expiry_interval: Some(msg.expiry_interval as u32),
+        topic_alias: None,
+        response_topic: msg.response_topic,
+        correlation_data: msg.correlation_data,
+        user_properties: msg.user_properties,
+        subscription_identifiers: sub_ids.to_vec(),
+        content_type: msg.content_type,
+    };
+    (publish, properties)
 }
 
-// send publish message
-// wait pubrec message
-// send pubrel message
-// wait pubcomp message
-#[allow(clippy::too_many_arguments)]
-pub async fn exclusive_publish_message_qos2(
-    metadata_cache: &Arc<CacheManager>,
-    client_id: &str,
-    publish: &Publish,
-    publish_properties: &PublishProperties,
-    pkid: u16,
+// Push the message to the client according to the QoS. QoS1 and QoS2 messages are persisted as
+// inflight messages before they are sent, so they are retransmitted when the session is resumed.
+// Returns false when the client goes offline before the message is sent.
+pub async fn exclusive_push_message<S>(
+    cache_manager: &Arc<CacheManager>,
     connection_manager: &Arc<ConnectionManager>,
+    storage_adapter: &Arc<S>,
+    subscriber: &Subscriber,
+    qos: QoS,
+    msg: MqttMessage,
     stop_sx: &broadcast::Sender<bool>,
-    wait_ack_sx: &broadcast::Sender<QosAckPackageData>,
-) -> Result<(), CommonError> {
-    // 1. send Publish to Client
-    qos2_send_publish(
-        connection_manager,
-        metadata_cache,
-        client_id,
-        publish,
-        &Some(publish_properties.clone()),
-        stop_sx,
-    )
-    .await?;
-
-    // 2. wait PubRec ack
-    loop {
-        if let Ok(flag) = stop_sx.subscribe().try_recv() {
-            if flag {
-                return Ok(());
-            }
-        }
-        if let Some(data) = wait_packet_ack(wait_ack_sx).await {
-            if data.ack_type == QosAckPackageType::PubRec && data.pkid == pkid {
-                break;
-            }
-        } else {
-            qos2_send_publish(
-                connection_manager,
-                metadata_cache,
-                client_id,
-                publish,
-                &Some(publish_properties.clone()),
-                stop_sx,
-            )
-            .await?;
+) -> Result<bool, CommonError>
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    let client_id = &subscriber.client_id;
+    let start_ms = now_mills();
+    let topic = String::from_utf8_lossy(&msg.topic).to_string();
+    if qos == QoS::AtMostOnce {
+        let mut sub_ids = Vec::new();
+        if let Some(id) = subscriber.subscription_identifier {
+            sub_ids.push(id);
         }
-        sleep(Duration::from_millis(1)).await;
+        let (publish, properties) = build_exclusive_publish(subscriber, qos, msg, &sub_ids);
+        publish_message_qos0(
+            cache_manager,
+            client_id,
+            &publish,
+            &Some(properties),
+            connection_manager,
+            stop_sx,
+        )
+        .await;
+        try_record_slow_message(
+            cache_manager,
+            client_id,
+            &topic,
+            now_mills().saturating_sub(start_ms),
+        );
+        return Ok(true);
     }
 
-    // 3. send PubRel to Client
-    qos2_send_pubrel(metadata_cache, client_id, pkid, connection_manager, stop_sx).await;
-
-    // 4. wait pub comp
-    loop {
-        if let Ok(flag) = stop_sx.subscribe().try_recv() {
-            if flag {
-                break;
-            }
-        }
-        if let Some(data) = wait_packet_ack(wait_ack_sx).await {
-            if data.ack_type == QosAckPackageType::PubComp && data.pkid == pkid {
-                break;
-            }
-        } else {
-            qos2_send_pubrel(metadata_cache, client_id, pkid, connection_manager, stop_sx).await;
-        }
-        sleep(Duration::from_millis(1)).await;
+    let connection = match acquire_send_window(cache_manager, client_id).await {
+        Some(connection) => connection,
+        None => return Ok(false),
+    };
+
+    let pkid = cache_manager.get_pkid(client_id).await;
+    let message = InflightMessage::new(pkid, qos, subscriber.clone(), msg);
+    let store = InflightStore::new(cache_manager, client_id, storage_adapter.clone());
+    if let Err(e) = store.save(&message).await {
+        cache_manager.remove_pkid_info(client_id, pkid);
+        connection.send_qos_message_decr();
+        return Err(e);
     }
-    Ok(())
+
+    let publish = message.build_publish(false);
+    complete_inflight_message(
+        cache_manager.clone(),
+        connection_manager.clone(),
+        store,
+        connection,
+        message,
+        Some(publish),
+    )
+    .await;
+    try_record_slow_message(
+        cache_manager,
+        client_id,
+        &topic,
+        now_mills().saturating_sub(start_ms),
+    );
+    Ok(true)
 }
+
 #[cfg(test)]
 mod test {}
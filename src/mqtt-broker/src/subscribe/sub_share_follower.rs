@@ -38,7 +38,7 @@ use tokio_util::codec::{FramedRead, FramedWrite};
 
 use super::sub_common::{
     get_share_sub_leader, publish_message_qos0, publish_message_to_client, qos2_send_publish,
-    qos2_send_pubrel, wait_packet_ack,
+    qos2_send_pubrel, wait_packet_ack, SHARE_SUB_FOLLOWER_CLIENT_ID_PREFIX,
 };
 use super::subscribe_manager::SubscribeManager;
 use crate::handler::cache::{CacheManager, QosAckPackageData, QosAckPackageType, QosAckPacketInfo};
@@ -221,7 +221,8 @@ async fn resub_sub_mqtt5(
     ws.add_write(write_frame_stream);
     let write_stream = Arc::new(ws);
 
-    let follower_sub_leader_client_id = unique_id();
+    let follower_sub_leader_client_id =
+        format!("{}{}", SHARE_SUB_FOLLOWER_CLIENT_ID_PREFIX, unique_id());
     let follower_sub_leader_pkid: u16 = 1;
 
     // Create a connection to GroupName
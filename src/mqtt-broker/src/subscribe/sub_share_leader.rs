@@ -12,6 +12,7 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashSet;
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -26,8 +27,12 @@ use protocol::mqtt::common::{MQTTPacket, MQTTProtocol, Publish, PublishPropertie
 use storage_adapter::storage::StorageAdapter;
 use tokio::select;
 use tokio::sync::broadcast::{self, Sender};
+use tokio::sync::watch;
 use tokio::time::sleep;
 
+use super::offline_queue::try_save_offline_message;
+use super::push_notify::wait_topic_message;
+use super::share_strategy::ShareSubDispatcher;
 use super::sub_common::{
     loop_commit_offset, min_qos, publish_message_qos0, publish_message_to_client,
     qos2_send_publish, qos2_send_pubrel, wait_packet_ack,
@@ -41,6 +46,8 @@ use crate::server::packet::ResponsePackage;
 use crate::storage::message::MessageStorage;
 use crate::subscribe::subscriber::Subscriber;
 
+const SHARE_SUB_WAIT_ACK_TIMEOUT_SEC: u64 = 120;
+
 #[derive(Clone)]
 pub struct SubscribeShareLeader<S> {
     pub subscribe_manager: Arc<SubscribeManager>,
@@ -125,7 +132,7 @@ where
                 .share_leader_push_thread
                 .contains_key(&share_leader_key)
             {
-                self.push_by_round_robin(
+                self.push_by_strategy(
                     share_leader_key.clone(),
                     sub_data.clone(),
                     subscribe_manager,
@@ -135,7 +142,7 @@ where
         }
     }
 
-    async fn push_by_round_robin(
+    async fn push_by_strategy(
         &self,
         share_leader_key: String,
         sub_data: ShareLeaderSubscribeData,
@@ -154,6 +161,7 @@ where
                 self.client_poll.clone(),
                 self.cache_manager.clone(),
                 self.connection_manager.clone(),
+                self.message_storage.clone(),
                 sub_thread_stop_sx.clone(),
             )
             .await;
@@ -166,6 +174,7 @@ where
         let connection_manager = self.connection_manager.clone();
         let cache_manager = self.cache_manager.clone();
         let message_storage = self.message_storage.clone();
+        let storage_adapter = self.message_storage.clone();
 
         tokio::spawn(async move {
             info!(
@@ -176,10 +185,16 @@ where
             let message_storage: MessageStorage<S> = MessageStorage::new(message_storage);
             let group_id = format!("system_sub_{}_{}_{}", group_name, sub_name, topic_id);
 
-            let mut cursor_point = 0;
+            let mut dispatcher = ShareSubDispatcher::new(
+                cache_manager
+                    .get_cluster_info()
+                    .share_sub
+                    .get_strategy(&group_name),
+            );
             let mut sub_list: Vec<Subscriber> =
                 build_share_leader_sub_list(&subscribe_manager, &share_leader_key);
             let mut pre_times = now_second();
+            let mut topic_rx = cache_manager.push_notify.subscribe(&topic_id);
 
             loop {
                 select! {
@@ -194,22 +209,25 @@ where
                             }
                         }
                     }
-                    cursor = read_message_process(
+                    _ = read_message_process(
                         &topic_id,
                         &topic_name,
                         &message_storage,
                         &sub_list,
                         &group_id,
-                        cursor_point,
+                        &mut dispatcher,
                         &connection_manager,
                         &cache_manager,
-                        &sub_thread_stop_sx
+                        &sub_thread_stop_sx,
+                        &mut topic_rx,
+                        &subscribe_manager,
+                        &storage_adapter,
                     ) =>{
-                        cursor_point = cursor;
-
-                        // Refresh the subscriber list of shared subscriptions every second to ensure that new subscribers can get messages in time.
+                        // Refresh the subscriber list and the dispatch strategy of shared subscriptions every second
+                        // to ensure that new subscribers can get messages in time.
                         if now_second() - pre_times >= 1{
                             sub_list = build_share_leader_sub_list(&subscribe_manager, &share_leader_key);
+                            dispatcher.set_strategy(cache_manager.get_cluster_info().share_sub.get_strategy(&group_name));
                             pre_times = now_second();
                         }
                     }
@@ -230,25 +248,32 @@ async fn read_message_process<S>(
     message_storage: &MessageStorage<S>,
     sub_list: &[Subscriber],
     group_id: &str,
-    mut cursor_point: usize,
+    dispatcher: &mut ShareSubDispatcher,
     connection_manager: &Arc<ConnectionManager>,
     cache_manager: &Arc<CacheManager>,
     stop_sx: &Sender<bool>,
-) -> usize
-where
+    topic_rx: &mut watch::Receiver<u64>,
+    subscribe_manager: &Arc<SubscribeManager>,
+    storage_adapter: &Arc<S>,
+) where
     S: StorageAdapter + Sync + Send + 'static + Clone,
 {
     let max_wait_ms: u64 = 500;
+    if sub_list.is_empty() {
+        sleep(Duration::from_millis(max_wait_ms)).await;
+        return;
+    }
     let record_num = calc_record_num(sub_list.len());
 
+    topic_rx.borrow_and_update();
     match message_storage
         .read_topic_message(topic_id.to_owned(), group_id.to_owned(), record_num as u128)
         .await
     {
         Ok(results) => {
             if results.is_empty() {
-                sleep(Duration::from_millis(max_wait_ms)).await;
-                return cursor_point;
+                wait_topic_message(topic_rx).await;
+                return;
             }
 
             for record in results {
@@ -261,7 +286,7 @@ where
                         );
                         loop_commit_offset(message_storage, topic_id, group_id, record.offset)
                             .await;
-                        return cursor_point;
+                        return;
                     }
                 };
 
@@ -271,44 +296,68 @@ where
                     continue;
                 }
 
-                let mut loop_times = 0;
+                // Members that failed to receive the message, it is redispatched to the other members of the group
+                let mut excluded = HashSet::new();
                 loop {
-                    if loop_times > try_loop_times(sub_list.len()) {
-                        error!("Share subscription push message fails, dropping the message, possibly because no subscriber is available");
-                        break;
+                    let subscribe = match dispatcher.choose(
+                        sub_list,
+                        topic_name,
+                        &msg.client_id,
+                        &excluded,
+                    ) {
+                        Some(sub) => sub,
+                        None => {
+                            // No member of the group is online, the message waits for a member with a persistent session
+                            if !save_share_offline_message(
+                                cache_manager,
+                                subscribe_manager,
+                                storage_adapter,
+                                sub_list,
+                                &msg,
+                            )
+                            .await
+                            {
+                                error!("Share subscription push message fails, dropping the message, possibly because no subscriber is available");
+                            }
+                            break;
+                        }
+                    };
+
+                    if !excluded.is_empty() {
+                        debug!(
+                            "Redispatch share subscription message of topic {} to client {}",
+                            topic_name, subscribe.client_id
+                        );
                     }
 
-                    cursor_point = choose_available_sub(cursor_point, sub_list);
-                    println!("cursor_point:{}", cursor_point);
-                    let subscribe = sub_list.get(cursor_point).unwrap();
-
-                    if let Some((publish, properties)) =
-                        build_publish(cache_manager, subscribe, topic_name, &msg)
-                    {
-                        if qos_publish(
-                            publish,
-                            properties,
-                            subscribe,
-                            topic_id,
-                            group_id,
-                            connection_manager,
-                            cache_manager,
-                            stop_sx,
-                            record.offset,
-                            message_storage,
-                        )
-                        .await
+                    if cache_manager.get_connect_id(&subscribe.client_id).is_some() {
+                        if let Some((publish, properties)) =
+                            build_publish(cache_manager, subscribe, topic_name, &msg)
                         {
-                            break;
+                            if qos_publish(
+                                publish,
+                                properties,
+                                subscribe,
+                                topic_id,
+                                group_id,
+                                connection_manager,
+                                cache_manager,
+                                stop_sx,
+                                record.offset,
+                                message_storage,
+                            )
+                            .await
+                            {
+                                break;
+                            }
                         }
                     }
-                    loop_times += 1;
+                    excluded.insert(subscribe.client_id.clone());
                 }
 
                 // commit offset
                 loop_commit_offset(message_storage, topic_id, group_id, record.offset).await;
             }
-            cursor_point
         }
         Err(e) => {
             error!(
@@ -318,22 +367,58 @@ where
                 group_id
             );
             sleep(Duration::from_millis(max_wait_ms)).await;
-            cursor_point
         }
     }
 }
 
-fn try_loop_times(sub_len: usize) -> usize {
-    sub_len * 2
-}
+// Keeps the message in the offline queue of the first offline member with a persistent session.
+// Returns false when no member can hold the message.
+async fn save_share_offline_message<S>(
+    cache_manager: &Arc<CacheManager>,
+    subscribe_manager: &Arc<SubscribeManager>,
+    storage_adapter: &Arc<S>,
+    sub_list: &[Subscriber],
+    msg: &MqttMessage,
+) -> bool
+where
+    S: StorageAdapter + Sync + Send + 'static + Clone,
+{
+    let cluster_qos = cache_manager.get_cluster_info().protocol.max_qos;
+    for subscriber in sub_list {
+        if subscriber.nolocal && subscriber.client_id == msg.client_id {
+            continue;
+        }
 
-fn choose_available_sub(cursor_point: usize, sub_list: &[Subscriber]) -> usize {
-    let current_point = cursor_point + 1;
-    if current_point < sub_list.len() {
-        current_point
-    } else {
-        0
+        let is_persistent = cache_manager
+            .get_session_info(&subscriber.client_id)
+            .is_some_and(|session| session.session_expiry > 0);
+        if !is_persistent {
+            continue;
+        }
+
+        let qos = min_qos(cluster_qos, subscriber.qos);
+        match try_save_offline_message(
+            cache_manager,
+            subscribe_manager,
+            storage_adapter,
+            subscriber,
+            qos,
+            msg,
+        )
+        .await
+        {
+            Ok(true) => return true,
+            // The member came back online in the meantime
+            Ok(false) => {}
+            Err(e) => {
+                error!(
+                    "Failed to save offline message of client [{}], error message :{}",
+                    subscriber.client_id, e
+                );
+            }
+        }
     }
+    false
 }
 
 #[allow(clippy::too_many_arguments)]
@@ -404,6 +489,8 @@ where
                         subscribe.client_id.clone(),
                         e.to_string()
                     );
+                    cache_manager.remove_pkid_info(&subscribe.client_id, pkid);
+                    cache_manager.remove_ack_packet(&subscribe.client_id, pkid);
                     false
                 }
             }
@@ -442,6 +529,8 @@ where
                 Ok(()) => true,
                 Err(e) => {
                     error!("{}", e);
+                    cache_manager.remove_pkid_info(&subscribe.client_id, pkid);
+                    cache_manager.remove_ack_packet(&subscribe.client_id, pkid);
                     false
                 }
             }
@@ -542,7 +631,9 @@ async fn share_leader_publish_message_qos1(
 
     match publish_message_to_client(resp.clone(), connection_manager).await {
         Ok(_) => {
-            if let Some(data) = wait_packet_ack(wait_puback_sx).await {
+            if let Some(data) =
+                wait_member_packet_ack(metadata_cache, client_id, connect_id, wait_puback_sx).await
+            {
                 if data.ack_type == QosAckPackageType::PubAck && data.pkid == pkid {
                     return Ok(());
                 }
@@ -581,6 +672,15 @@ async fn share_leader_publish_message_qos2<S>(
 where
     S: StorageAdapter + Sync + Send + 'static + Clone,
 {
+    let connect_id = if let Some(id) = cache_manager.get_connect_id(client_id) {
+        id
+    } else {
+        return Err(CommonError::CommmonError(format!(
+            "Client [{}] failed to get connect id, no connection available.",
+            client_id
+        )));
+    };
+
     // 1. send Publish to Client
     qos2_send_publish(
         connection_manager,
@@ -599,7 +699,9 @@ where
                 return Ok(());
             }
         }
-        if let Some(data) = wait_packet_ack(wait_ack_sx).await {
+        if let Some(data) =
+            wait_member_packet_ack(cache_manager, client_id, connect_id, wait_ack_sx).await
+        {
             if data.ack_type == QosAckPackageType::PubRec && data.pkid == pkid {
                 // When sending a QOS2 message, as long as the pubrec is received, the offset can be submitted,
                 // the pubrel is sent asynchronously, and the pubcomp is waited for. Push the next message at the same time.
@@ -636,6 +738,33 @@ where
     Ok(())
 }
 
+// Wait for the ack packet of a group member. Returns early when the member disconnects,
+// so that the message can be redispatched to another member without waiting for the timeout.
+async fn wait_member_packet_ack(
+    cache_manager: &Arc<CacheManager>,
+    client_id: &str,
+    connect_id: u64,
+    wait_ack_sx: &broadcast::Sender<QosAckPackageData>,
+) -> Option<QosAckPackageData> {
+    let mut wait_ack_rx = wait_ack_sx.subscribe();
+    let start_time = now_second();
+    loop {
+        select! {
+            val = wait_ack_rx.recv() => {
+                return val.ok();
+            }
+            _ = sleep(Duration::from_secs(1)) => {
+                if cache_manager.get_connect_id(client_id) != Some(connect_id) {
+                    return None;
+                }
+                if now_second() - start_time >= SHARE_SUB_WAIT_ACK_TIMEOUT_SEC {
+                    return None;
+                }
+            }
+        }
+    }
+}
+
 fn build_share_leader_sub_list(
     subscribe_manager: &Arc<SubscribeManager>,
     key: &str,
@@ -650,6 +779,8 @@ fn build_share_leader_sub_list(
     for (_, sub) in sub_list {
         result.push(sub);
     }
+    // Keep a stable member order so that hash based strategies choose the same member
+    result.sort_by(|a, b| a.client_id.cmp(&b.client_id));
     result
 }
 
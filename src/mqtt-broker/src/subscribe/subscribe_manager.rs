@@ -13,20 +13,22 @@
 // limitations under the License.
 
 use std::sync::Arc;
-use std::time::Duration;
 
 use common_base::config::broker_mqtt::broker_mqtt_conf;
 use dashmap::DashMap;
 use grpc_clients::poll::ClientPool;
-use log::{error, info};
+use log::{error, info, warn};
 use protocol::mqtt::common::{Filter, MQTTProtocol, Subscribe, SubscribeProperties};
 use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast::error::RecvError;
 use tokio::sync::broadcast::Sender;
-use tokio::time::sleep;
+use tokio::sync::Mutex;
 
-use super::sub_common::{decode_share_info, get_share_sub_leader, is_share_sub, path_regex_match};
+use super::sub_common::{
+    decode_share_info, get_share_sub_leader, get_sub_topic_id_list, is_share_sub, path_match,
+};
 use crate::handler::cache::CacheManager;
-use crate::subscribe::subscriber::Subscriber;
+use crate::subscribe::subscriber::{SubscribeData, Subscriber};
 
 #[derive(Clone, Serialize, Deserialize)]
 pub struct ShareSubShareSub {
@@ -74,6 +76,12 @@ pub struct SubscribeManager {
 
     // (identifier_id，client_id)
     pub share_follower_identifier_id: DashMap<usize, String>,
+
+    // (client_id, Mutex<is_replaying>), serializes the access to the offline queue of the client
+    pub offline_queue_lock: DashMap<String, Arc<Mutex<bool>>>,
+
+    // (client_id, Sender<bool>)
+    pub offline_replay_thread: DashMap<String, Sender<bool>>,
 }
 
 impl SubscribeManager {
@@ -88,37 +96,83 @@ impl SubscribeManager {
             exclusive_push_thread: DashMap::with_capacity(8),
             share_leader_push_thread: DashMap::with_capacity(8),
             share_follower_resub_thread: DashMap::with_capacity(8),
+            offline_queue_lock: DashMap::with_capacity(8),
+            offline_replay_thread: DashMap::with_capacity(8),
         }
     }
 
+    pub fn get_offline_queue_lock(&self, client_id: &str) -> Arc<Mutex<bool>> {
+        self.offline_queue_lock
+            .entry(client_id.to_owned())
+            .or_insert_with(|| Arc::new(Mutex::new(false)))
+            .clone()
+    }
+
     pub async fn start(&self) {
         info!("Subscribe manager thread started successfully.");
+        let mut new_topic_rx = self.metadata_cache.new_topic_sx.subscribe();
+        // topics loaded before the receiver was created were never announced
+        self.parse_subscribe_by_all_topic().await;
         loop {
-            self.parse_subscribe_by_new_topic().await;
-            sleep(Duration::from_secs(10)).await;
+            match new_topic_rx.recv().await {
+                Ok(topic_name) => self.parse_subscribe_by_new_topic(&topic_name).await,
+                Err(RecvError::Lagged(num)) => {
+                    warn!(
+                        "Subscribe manager missed {} new topic events, rescanning all topics.",
+                        num
+                    );
+                    self.parse_subscribe_by_all_topic().await;
+                }
+                Err(RecvError::Closed) => break,
+            }
         }
     }
 
-    pub async fn parse_subscribe_by_new_topic(&self) {
-        for (topic_name, topic) in self.metadata_cache.topic_info.clone() {
-            for (client_id, sub_list) in self.metadata_cache.subscribe_filter.clone() {
-                for (_, data) in sub_list {
-                    let subscribe = Subscribe {
-                        packet_identifier: 0,
-                        filters: vec![data.filter],
-                    };
-                    let subscribe_properties = data.subscribe_properties;
-                    self.parse_subscribe(
-                        topic_name.clone(),
-                        topic.topic_id.clone(),
-                        client_id.clone(),
-                        data.protocol.clone(),
-                        subscribe,
-                        subscribe_properties,
-                    )
-                    .await;
+    async fn parse_subscribe_by_all_topic(&self) {
+        let topic_names: Vec<String> = self
+            .metadata_cache
+            .topic_info
+            .iter()
+            .map(|raw| raw.key().clone())
+            .collect();
+        for topic_name in topic_names {
+            self.parse_subscribe_by_new_topic(&topic_name).await;
+        }
+    }
+
+    // Bind the existing subscriptions whose filter matches the newly created topic
+    pub async fn parse_subscribe_by_new_topic(&self, topic_name: &str) {
+        let topic_id = if let Some(topic) = self.metadata_cache.topic_info.get(topic_name) {
+            topic.topic_id.clone()
+        } else {
+            return;
+        };
+
+        for (client_id, path) in self.metadata_cache.subscribe_tree.match_topic(topic_name) {
+            let data = if let Some(sub_list) = self.metadata_cache.subscribe_filter.get(&client_id)
+            {
+                if let Some(data) = sub_list.get(&path) {
+                    data.clone()
+                } else {
+                    continue;
                 }
-            }
+            } else {
+                continue;
+            };
+
+            let subscribe = Subscribe {
+                packet_identifier: 0,
+                filters: vec![data.filter],
+            };
+            self.parse_subscribe(
+                topic_name.to_owned(),
+                topic_id.clone(),
+                client_id,
+                data.protocol,
+                subscribe,
+                data.subscribe_properties,
+            )
+            .await;
         }
     }
 
@@ -129,16 +183,30 @@ impl SubscribeManager {
         subscribe: Subscribe,
         subscribe_properties: Option<SubscribeProperties>,
     ) {
-        for (topic_name, topic) in self.metadata_cache.topic_info.clone() {
-            self.parse_subscribe(
-                topic_name,
-                topic.topic_id,
-                client_id.clone(),
-                protocol.clone(),
-                subscribe.clone(),
-                subscribe_properties.clone(),
-            )
-            .await;
+        for filter in subscribe.filters.clone() {
+            let topic_id_list =
+                get_sub_topic_id_list(self.metadata_cache.clone(), filter.path.clone()).await;
+            for topic_id in topic_id_list {
+                let topic_name =
+                    if let Some(name) = self.metadata_cache.topic_name_by_id(topic_id.clone()) {
+                        name
+                    } else {
+                        continue;
+                    };
+                let sub = Subscribe {
+                    packet_identifier: subscribe.packet_identifier,
+                    filters: vec![filter.clone()],
+                };
+                self.parse_subscribe(
+                    topic_name,
+                    topic_id,
+                    client_id.clone(),
+                    protocol.clone(),
+                    sub,
+                    subscribe_properties.clone(),
+                )
+                .await;
+            }
         }
     }
 
@@ -158,6 +226,12 @@ impl SubscribeManager {
             }
         }
 
+        self.stop_share_follower_by_client_id(client_id);
+    }
+
+    // Members of a shared subscription whose leader runs on another broker receive the messages
+    // through a follower connection, which can only forward them while the client is online
+    pub fn stop_share_follower_by_client_id(&self, client_id: &str) {
         for (key, share_sub) in self.share_follower_subscribe.clone() {
             if share_sub.client_id == *client_id {
                 self.share_follower_subscribe.remove(&key);
@@ -165,62 +239,95 @@ impl SubscribeManager {
         }
     }
 
+    // Binds the subscriptions of a resumed session again, they were released on this broker
+    // when the session was taken over or when its previous connection was on another broker
+    pub async fn restore_client_subscribe(&self, client_id: &str) {
+        let sub_list: Vec<SubscribeData> =
+            if let Some(sub_list) = self.metadata_cache.subscribe_filter.get(client_id) {
+                sub_list.iter().map(|raw| raw.value().clone()).collect()
+            } else {
+                return;
+            };
+
+        for data in sub_list {
+            let subscribe = Subscribe {
+                packet_identifier: 0,
+                filters: vec![data.filter],
+            };
+            self.add_subscribe(
+                client_id.to_owned(),
+                data.protocol,
+                subscribe,
+                data.subscribe_properties,
+            )
+            .await;
+        }
+    }
+
     pub fn remove_subscribe(&self, client_id: &str, filter_path: &[String]) {
-        for (topic_name, _) in self.metadata_cache.topic_info.clone() {
-            for path in filter_path {
-                if !path_regex_match(topic_name.clone(), path.clone()) {
-                    continue;
-                }
+        for path in filter_path {
+            let match_path = if is_share_sub(path.clone()) {
+                decode_share_info(path.clone()).1
+            } else {
+                path.clone()
+            };
+            if self
+                .metadata_cache
+                .topic_tree
+                .match_filter(&match_path)
+                .is_empty()
+            {
+                continue;
+            }
 
-                if is_share_sub(path.clone()) {
-                    let (group_name, sub_name) = decode_share_info(path.clone());
-                    // share leader
-                    for (key, data) in self.share_leader_subscribe.clone() {
-                        let mut flag = false;
-                        for (sub_key, share_sub) in data.sub_list {
-                            if share_sub.client_id == *client_id
-                                && (share_sub.group_name.is_some()
-                                    && share_sub.group_name.unwrap() == group_name)
-                                && share_sub.sub_path == sub_name
-                            {
-                                let mut_data = self.share_leader_subscribe.get_mut(&key).unwrap();
-                                mut_data.sub_list.remove(&sub_key);
-                                flag = true;
-                            }
+            if is_share_sub(path.clone()) {
+                let (group_name, sub_name) = decode_share_info(path.clone());
+                // share leader
+                for (key, data) in self.share_leader_subscribe.clone() {
+                    let mut flag = false;
+                    for (sub_key, share_sub) in data.sub_list {
+                        if share_sub.client_id == *client_id
+                            && (share_sub.group_name.is_some()
+                                && share_sub.group_name.unwrap() == group_name)
+                            && share_sub.sub_path == sub_name
+                        {
+                            let mut_data = self.share_leader_subscribe.get_mut(&key).unwrap();
+                            mut_data.sub_list.remove(&sub_key);
+                            flag = true;
                         }
+                    }
 
-                        if flag {
-                            if let Some(sx) = self.share_leader_push_thread.get(&key) {
-                                match sx.send(true) {
-                                    Ok(_) => {}
-                                    Err(e) => error!("{}", e),
-                                }
+                    if flag {
+                        if let Some(sx) = self.share_leader_push_thread.get(&key) {
+                            match sx.send(true) {
+                                Ok(_) => {}
+                                Err(e) => error!("{}", e),
                             }
                         }
                     }
+                }
 
-                    // share follower
-                    for (key, data) in self.share_follower_subscribe.clone() {
-                        if data.client_id == *client_id && data.filter.path == *path {
-                            self.share_follower_subscribe.remove(&key);
-                            if let Some(sx) = self.share_follower_resub_thread.get(&key) {
-                                match sx.send(true) {
-                                    Ok(_) => {}
-                                    Err(e) => error!("{}", e),
-                                }
+                // share follower
+                for (key, data) in self.share_follower_subscribe.clone() {
+                    if data.client_id == *client_id && data.filter.path == *path {
+                        self.share_follower_subscribe.remove(&key);
+                        if let Some(sx) = self.share_follower_resub_thread.get(&key) {
+                            match sx.send(true) {
+                                Ok(_) => {}
+                                Err(e) => error!("{}", e),
                             }
                         }
                     }
-                } else {
-                    for (key, subscriber) in self.exclusive_subscribe.clone() {
-                        if subscriber.client_id == *client_id && subscriber.sub_path == *path {
-                            if let Some(sx) = self.exclusive_push_thread.get(&key) {
-                                match sx.send(true) {
-                                    Ok(_) => {}
-                                    Err(e) => error!("{}", e),
-                                }
-                                self.exclusive_subscribe.remove(&key);
+                }
+            } else {
+                for (key, subscriber) in self.exclusive_subscribe.clone() {
+                    if subscriber.client_id == *client_id && subscriber.sub_path == *path {
+                        if let Some(sx) = self.exclusive_push_thread.get(&key) {
+                            match sx.send(true) {
+                                Ok(_) => {}
+                                Err(e) => error!("{}", e),
                             }
+                            self.exclusive_subscribe.remove(&key);
                         }
                     }
                 }
@@ -248,7 +355,7 @@ impl SubscribeManager {
                 let conf = broker_mqtt_conf();
                 let (group_name, sub_name) = decode_share_info(filter.path.clone());
 
-                if path_regex_match(topic_name.clone(), sub_name.clone()) {
+                if path_match(topic_name.clone(), sub_name.clone()) {
                     match get_share_sub_leader(self.client_poll.clone(), group_name.clone()).await {
                         Ok(reply) => {
                             if reply.broker_id == conf.broker_id {
@@ -394,7 +501,7 @@ impl SubscribeManager {
         sub_identifier: Option<usize>,
         filter: Filter,
     ) {
-        if path_regex_match(topic_name.clone(), filter.path.clone()) {
+        if path_match(topic_name.clone(), filter.path.clone()) {
             let key = self.exclusive_key(&client_id, &filter.path, &topic_id);
             let sub = Subscriber {
                 protocol: protocol.clone(),
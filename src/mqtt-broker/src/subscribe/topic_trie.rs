@@ -0,0 +1,377 @@
+// Copyright 2023 RobustMQ Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::sync::RwLock;
+
+const TOPIC_LEVEL_SEPARATOR: &str = "/";
+const SINGLE_LEVEL_WILDCARD: &str = "+";
+const MULTI_LEVEL_WILDCARD: &str = "#";
+
+#[derive(Clone)]
+struct TrieNode<T> {
+    children: HashMap<String, TrieNode<T>>,
+    // (key, value) of the entries whose path ends at this node
+    values: HashMap<String, T>,
+}
+
+impl<T> TrieNode<T> {
+    fn new() -> Self {
+        TrieNode {
+            children: HashMap::new(),
+            values: HashMap::new(),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.children.is_empty() && self.values.is_empty()
+    }
+}
+
+// Topic level trie. It is used in two directions:
+// 1. paths are topic names, and match_filter finds the topics matched by a subscription filter.
+// 2. paths are subscription filters, and match_topic finds the filters matching a topic name.
+// Following the MQTT spec, a filter starting with a wildcard never matches a topic starting with `$`.
+pub struct TopicTrie<T> {
+    root: RwLock<TrieNode<T>>,
+}
+
+impl<T> Clone for TopicTrie<T>
+where
+    T: Clone,
+{
+    fn clone(&self) -> Self {
+        TopicTrie {
+            root: RwLock::new(self.root.read().unwrap().clone()),
+        }
+    }
+}
+
+impl<T> Default for TopicTrie<T>
+where
+    T: Clone,
+{
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> TopicTrie<T>
+where
+    T: Clone,
+{
+    pub fn new() -> Self {
+        TopicTrie {
+            root: RwLock::new(TrieNode::new()),
+        }
+    }
+
+    pub fn insert(&self, path: &str, key: String, value: T) {
+        let mut root = self.root.write().unwrap();
+        let mut node = &mut *root;
+        for level in path.split(TOPIC_LEVEL_SEPARATOR) {
+            node = node
+                .children
+                .entry(level.to_owned())
+                .or_insert_with(TrieNode::new);
+        }
+        node.values.insert(key, value);
+    }
+
+    pub fn remove(&self, path: &str, key: &str) {
+        let mut root = self.root.write().unwrap();
+        let levels: Vec<&str> = path.split(TOPIC_LEVEL_SEPARATOR).collect();
+        remove_value(&mut root, &levels, key);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.read().unwrap().is_empty()
+    }
+
+    // Stored paths are topic names, returns the values of the topics matched by the filter
+    pub fn match_filter(&self, filter: &str) -> Vec<T> {
+        let root = self.root.read().unwrap();
+        let levels: Vec<&str> = filter.split(TOPIC_LEVEL_SEPARATOR).collect();
+        let mut result = Vec::new();
+        collect_by_filter(&root, &levels, true, &mut result);
+        result
+    }
+
+    // Stored paths are subscription filters, returns the values of the filters matching the topic
+    pub fn match_topic(&self, topic_name: &str) -> Vec<T> {
+        let root = self.root.read().unwrap();
+        let levels: Vec<&str> = topic_name.split(TOPIC_LEVEL_SEPARATOR).collect();
+        let mut result = Vec::new();
+        collect_by_topic(&root, &levels, true, &mut result);
+        result
+    }
+}
+
+fn remove_value<T>(node: &mut TrieNode<T>, levels: &[&str], key: &str) -> bool {
+    if let Some((level, rest)) = levels.split_first() {
+        if let Some(child) = node.children.get_mut(*level) {
+            if remove_value(child, rest, key) {
+                node.children.remove(*level);
+            }
+        }
+    } else {
+        node.values.remove(key);
+    }
+    node.is_empty()
+}
+
+fn collect_all<T: Clone>(node: &TrieNode<T>, is_root: bool, result: &mut Vec<T>) {
+    result.extend(node.values.values().cloned());
+    for (level, child) in node.children.iter() {
+        if is_root && level.starts_with('$') {
+            continue;
+        }
+        collect_all(child, false, result);
+    }
+}
+
+fn collect_by_filter<T: Clone>(
+    node: &TrieNode<T>,
+    levels: &[&str],
+    is_root: bool,
+    result: &mut Vec<T>,
+) {
+    let Some((level, rest)) = levels.split_first() else {
+        result.extend(node.values.values().cloned());
+        return;
+    };
+
+    match *level {
+        // `#` also matches the parent level, `a/#` matches `a`
+        MULTI_LEVEL_WILDCARD => collect_all(node, is_root, result),
+        SINGLE_LEVEL_WILDCARD => {
+            for (name, child) in node.children.iter() {
+                if is_root && name.starts_with('$') {
+                    continue;
+                }
+                collect_by_filter(child, rest, false, result);
+            }
+        }
+        _ => {
+            if let Some(child) = node.children.get(*level) {
+                collect_by_filter(child, rest, false, result);
+            }
+        }
+    }
+}
+
+fn collect_by_topic<T: Clone>(
+    node: &TrieNode<T>,
+    levels: &[&str],
+    is_root: bool,
+    result: &mut Vec<T>,
+) {
+    let is_system_topic = is_root
+        && levels
+            .first()
+            .map(|level| level.starts_with('$'))
+            .unwrap_or(false);
+
+    if !is_system_topic {
+        if let Some(child) = node.children.get(MULTI_LEVEL_WILDCARD) {
+            result.extend(child.values.values().cloned());
+        }
+    }
+
+    let Some((level, rest)) = levels.split_first() else {
+        result.extend(node.values.values().cloned());
+        return;
+    };
+
+    if let Some(child) = node.children.get(*level) {
+        collect_by_topic(child, rest, false, result);
+    }
+
+    if !is_system_topic {
+        if let Some(child) = node.children.get(SINGLE_LEVEL_WILDCARD) {
+            collect_by_topic(child, rest, false, result);
+        }
+    }
+}
+
+// Match a single topic name against a subscription filter level by level
+pub fn topic_filter_match(topic_name: &str, filter: &str) -> bool {
+    let topic_levels: Vec<&str> = topic_name.split(TOPIC_LEVEL_SEPARATOR).collect();
+    let filter_levels: Vec<&str> = filter.split(TOPIC_LEVEL_SEPARATOR).collect();
+
+    if let (Some(topic_level), Some(filter_level)) = (topic_levels.first(), filter_levels.first()) {
+        if topic_level.starts_with('$')
+            && (*filter_level == SINGLE_LEVEL_WILDCARD || *filter_level == MULTI_LEVEL_WILDCARD)
+        {
+            return false;
+        }
+    }
+
+    for (i, filter_level) in filter_levels.iter().enumerate() {
+        if *filter_level == MULTI_LEVEL_WILDCARD {
+            return i == filter_levels.len() - 1;
+        }
+        match topic_levels.get(i) {
+            Some(topic_level) => {
+                if *filter_level != SINGLE_LEVEL_WILDCARD && filter_level != topic_level {
+                    return false;
+                }
+            }
+            None => return false,
+        }
+    }
+    topic_levels.len() == filter_levels.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{topic_filter_match, TopicTrie};
+
+    fn sorted(mut data: Vec<String>) -> Vec<String> {
+        data.sort();
+        data
+    }
+
+    fn build_topic_trie() -> TopicTrie<String> {
+        let trie = TopicTrie::new();
+        for topic in [
+            "sport",
+            "sport/tennis",
+            "sport/tennis/player1",
+            "sport/football/player1",
+            "/sensor/1/temperature",
+            "$SYS/brokers/version",
+        ] {
+            trie.insert(topic, topic.to_string(), topic.to_string());
+        }
+        trie
+    }
+
+    #[test]
+    fn match_filter_test() {
+        let trie = build_topic_trie();
+        assert_eq!(
+            trie.match_filter("sport/tennis"),
+            vec!["sport/tennis".to_string()]
+        );
+        assert_eq!(
+            sorted(trie.match_filter("sport/#")),
+            vec![
+                "sport".to_string(),
+                "sport/football/player1".to_string(),
+                "sport/tennis".to_string(),
+                "sport/tennis/player1".to_string(),
+            ]
+        );
+        assert_eq!(
+            sorted(trie.match_filter("sport/+/player1")),
+            vec![
+                "sport/football/player1".to_string(),
+                "sport/tennis/player1".to_string(),
+            ]
+        );
+        assert_eq!(
+            trie.match_filter("/sensor/+/temperature"),
+            vec!["/sensor/1/temperature".to_string()]
+        );
+        assert!(trie.match_filter("sport/+/player2").is_empty());
+
+        // wildcards at the first level do not match `$` topics
+        assert!(!trie
+            .match_filter("#")
+            .contains(&"$SYS/brokers/version".to_string()));
+        assert!(trie.match_filter("+/brokers/version").is_empty());
+        assert_eq!(
+            trie.match_filter("$SYS/#"),
+            vec!["$SYS/brokers/version".to_string()]
+        );
+        assert_eq!(trie.match_filter("#").len(), 5);
+    }
+
+    #[test]
+    fn match_topic_test() {
+        let trie = TopicTrie::new();
+        for filter in [
+            "sport/#",
+            "sport/+",
+            "sport/tennis/+",
+            "+/tennis/#",
+            "#",
+            "$SYS/#",
+        ] {
+            trie.insert(filter, filter.to_string(), filter.to_string());
+        }
+
+        assert_eq!(
+            sorted(trie.match_topic("sport")),
+            vec!["#".to_string(), "sport/#".to_string()]
+        );
+        assert_eq!(
+            sorted(trie.match_topic("sport/tennis")),
+            vec![
+                "#".to_string(),
+                "+/tennis/#".to_string(),
+                "sport/#".to_string(),
+                "sport/+".to_string(),
+            ]
+        );
+        assert_eq!(
+            sorted(trie.match_topic("sport/tennis/player1")),
+            vec![
+                "#".to_string(),
+                "+/tennis/#".to_string(),
+                "sport/#".to_string(),
+                "sport/tennis/+".to_string(),
+            ]
+        );
+        assert_eq!(trie.match_topic("$SYS/brokers"), vec!["$SYS/#".to_string()]);
+    }
+
+    #[test]
+    fn insert_remove_test() {
+        let trie = TopicTrie::new();
+        trie.insert("a/b", "c1".to_string(), 1);
+        trie.insert("a/b", "c2".to_string(), 2);
+        trie.insert("a/b/c", "c1".to_string(), 3);
+
+        let mut res = trie.match_filter("a/b");
+        res.sort();
+        assert_eq!(res, vec![1, 2]);
+
+        trie.remove("a/b", "c1");
+        assert_eq!(trie.match_filter("a/b"), vec![2]);
+        trie.remove("a/b", "c2");
+        assert!(trie.match_filter("a/b").is_empty());
+        assert_eq!(trie.match_filter("a/#"), vec![3]);
+
+        trie.remove("a/b/c", "c1");
+        assert!(trie.is_empty());
+    }
+
+    #[test]
+    fn topic_filter_match_test() {
+        assert!(topic_filter_match("sport/tennis", "sport/tennis"));
+        assert!(topic_filter_match("sport", "sport/#"));
+        assert!(topic_filter_match("sport/tennis/player1", "sport/#"));
+        assert!(topic_filter_match("sport/tennis", "sport/+"));
+        assert!(!topic_filter_match("sport/tennis/player1", "sport/+"));
+        assert!(!topic_filter_match("sport", "sport/+"));
+        assert!(topic_filter_match("/finance", "+/+"));
+        assert!(topic_filter_match("/finance", "/+"));
+        assert!(!topic_filter_match("/finance", "+"));
+        assert!(!topic_filter_match("$SYS/brokers", "#"));
+        assert!(!topic_filter_match("$SYS/brokers", "+/brokers"));
+        assert!(topic_filter_match("$SYS/brokers", "$SYS/#"));
+    }
+}
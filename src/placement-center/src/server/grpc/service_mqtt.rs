@@ -14,6 +14,8 @@
 
 use std::sync::Arc;
 
+use common_base::config::placement_center::placement_center_conf;
+use metadata_struct::mqtt::user::{MqttUser, MqttUserHash};
 use prost::Message;
 use protocol::placement_center::placement_center_mqtt::mqtt_service_server::MqttService;
 use protocol::placement_center::placement_center_mqtt::{
@@ -136,7 +138,25 @@ impl MqttService for GrpcMqttService {
         &self,
         request: Request<CreateUserRequest>,
     ) -> Result<Response<CreateUserReply>, Status> {
-        let req = request.into_inner();
+        let mut req = request.into_inner();
+
+        // hash before the raft write so every replica stores the same salt
+        let mut user = match serde_json::from_slice::<MqttUser>(&req.content) {
+            Ok(user) => user,
+            Err(e) => {
+                return Err(Status::invalid_argument(e.to_string()));
+            }
+        };
+        let hash = match MqttUserHash::from_conf(&placement_center_conf().password_hash) {
+            Ok(hash) => hash,
+            Err(e) => {
+                return Err(Status::failed_precondition(e.to_string()));
+            }
+        };
+        if let Err(e) = user.hash_password(&hash) {
+            return Err(Status::cancelled(e.to_string()));
+        }
+        req.content = user.encode();
 
         let data = StorageData::new(
             StorageDataType::MQTTCreateUser,
@@ -111,6 +111,7 @@ mod tests {
             username: username.clone(),
             password: "pwd123".to_string(),
             is_superuser: true,
+            hash: Default::default(),
         };
         user_storage.save(&cluster_name, &username, user).unwrap();
 
@@ -119,6 +120,7 @@ mod tests {
             username: username.clone(),
             password: "pwd1231".to_string(),
             is_superuser: true,
+            hash: Default::default(),
         };
         user_storage.save(&cluster_name, &username, user).unwrap();
 
@@ -146,6 +146,8 @@ impl MqttCodec {
                         crate::mqtt::mqttv5::disconnect::read(fixed_header, packet)?;
                     MQTTPacket::Disconnect(disconnect, None)
                 }
+                // AUTH packet is only valid in MQTT V5
+                PacketType::Auth => return Err(Error::InvalidProtocol),
                 _ => unreachable!(),
             };
             return Ok(Some(packet));
@@ -210,6 +212,11 @@ impl MqttCodec {
                         crate::mqtt::mqttv5::disconnect::read(fixed_header, packet)?;
                     MQTTPacket::Disconnect(disconnect, disconnect_properties)
                 }
+                PacketType::Auth => {
+                    let (auth, auth_properties) =
+                        crate::mqtt::mqttv5::auth::read(fixed_header, packet)?;
+                    MQTTPacket::Auth(auth, auth_properties)
+                }
                 _ => unreachable!(),
             };
             return Ok(Some(packet));
@@ -314,6 +321,9 @@ impl MqttCodec {
                         buffer,
                     )?
                 }
+                MQTTPacket::Auth(auth, auth_properties) => {
+                    crate::mqtt::mqttv5::auth::write(&auth, &auth_properties, buffer)?
+                }
             };
         }
         Ok(())
@@ -433,6 +443,9 @@ fn calc_mqtt_packet_len(
                     &mut buffer,
                 )?
             }
+            MQTTPacket::Auth(auth, auth_properties) => {
+                crate::mqtt::mqttv5::auth::write(&auth, &auth_properties, &mut buffer)?
+            }
         };
     }
     Ok(size)
@@ -454,6 +467,7 @@ pub fn parse_mqtt_packet_to_name(packet: MQTTPacket) -> String {
         MQTTPacket::PingReq(_) => "ping",
         MQTTPacket::PingResp(_) => "pong",
         MQTTPacket::Disconnect(_, _) => "disconnect",
+        MQTTPacket::Auth(_, _) => "auth",
     };
     name.to_string()
 }
@@ -89,6 +89,7 @@ pub enum PacketType {
     PingReq,
     PingResp,
     Disconnect,
+    Auth,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq)]
@@ -114,6 +115,7 @@ pub enum MQTTPacket {
     Unsubscribe(Unsubscribe, Option<UnsubscribeProperties>),
     UnsubAck(UnsubAck, Option<UnsubAckProperties>),
     Disconnect(Disconnect, Option<DisconnectProperties>),
+    Auth(Auth, Option<AuthProperties>),
 }
 
 /// Packet type from a byte
@@ -167,6 +169,7 @@ impl FixedHeader {
             12 => Ok(PacketType::PingReq),
             13 => Ok(PacketType::PingResp),
             14 => Ok(PacketType::Disconnect),
+            15 => Ok(PacketType::Auth),
             _ => Err(Error::InvalidPacketType(num)),
         }
     }
@@ -1047,6 +1050,36 @@ pub struct DisconnectProperties {
     pub server_reference: Option<String>,
 }
 
+//--------------------------- Auth packet -------------------------------
+/// Authentication exchange, only valid in MQTT V5
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Auth {
+    pub reason: Option<AuthReason>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum AuthReason {
+    Success,
+    ContinueAuthentication,
+    ReAuthenticate,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct AuthProperties {
+    /// Name of the authentication method
+    pub authentication_method: Option<String>,
+
+    /// Method specific authentication data
+    pub authentication_data: Option<Bytes>,
+
+    /// Human readable reason for the auth exchange
+    pub reason_string: Option<String>,
+
+    /// List of user properties
+    pub user_properties: Vec<(String, String)>,
+}
+
 /// Error during serialization and deserialization
 #[derive(Debug, thiserror::Error)]
 pub enum Error {
@@ -101,6 +101,8 @@ impl codec::Decoder for Mqtt4Codec {
             // MQTT V4 Disconnect packet gets handled in the previous check, this branch gets
             // hit when Disconnect packet has properties which are only valid for MQTT V5
             PacketType::Disconnect => return Err(Error::InvalidProtocol),
+            // AUTH packet is only valid in MQTT V5
+            PacketType::Auth => return Err(Error::InvalidProtocol),
         };
         Ok(Some(packet))
     }
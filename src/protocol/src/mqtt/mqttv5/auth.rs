@@ -12,3 +12,318 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use super::*;
+
+fn len(auth: &Auth, properties: &Option<AuthProperties>) -> usize {
+    let reason = match auth.reason {
+        Some(reason) => reason,
+        None => return 0,
+    };
+
+    if reason == AuthReason::Success && properties.is_none() {
+        return 0;
+    }
+
+    let mut length = 1; // Auth Reason Code
+    if let Some(properties) = &properties {
+        let properties_len = properties::len(properties);
+        let properties_len_len = len_len(properties_len);
+        length += properties_len_len + properties_len;
+    } else {
+        length += 1;
+    }
+    length
+}
+
+pub fn write(
+    auth: &Auth,
+    properties: &Option<AuthProperties>,
+    buffer: &mut BytesMut,
+) -> Result<usize, Error> {
+    buffer.put_u8(0xF0);
+
+    let length = len(auth, properties);
+    let len_len = write_remaining_length(buffer, length)?;
+    if length == 0 {
+        return Ok(1 + len_len);
+    }
+
+    buffer.put_u8(code(auth.reason.unwrap()));
+
+    if let Some(properties) = &properties {
+        properties::write(properties, buffer)?;
+    } else {
+        write_remaining_length(buffer, 0)?;
+    }
+    Ok(1 + len_len + length)
+}
+
+pub fn read(
+    fixed_header: FixedHeader,
+    mut bytes: Bytes,
+) -> Result<(Auth, Option<AuthProperties>), Error> {
+    let packet_type = fixed_header.byte1 >> 4;
+    let flags = fixed_header.byte1 & 0b0000_1111;
+
+    bytes.advance(fixed_header.fixed_header_len);
+
+    if packet_type != PacketType::Auth as u8 {
+        return Err(Error::InvalidPacketType(packet_type));
+    };
+
+    if flags != 0x00 {
+        return Err(Error::MalformedPacket);
+    };
+
+    // The Reason Code and Property Length can be omitted if the Reason Code is 0x00 (Success)
+    // and there are no Properties. In this case the AUTH has a Remaining Length of 0.
+    if fixed_header.remaining_len == 0 {
+        return Ok((
+            Auth {
+                reason: Some(AuthReason::Success),
+            },
+            None,
+        ));
+    }
+
+    let reason_code = read_u8(&mut bytes)?;
+    let auth = Auth {
+        reason: Some(reason(reason_code)?),
+    };
+
+    if fixed_header.remaining_len == 1 {
+        return Ok((auth, None));
+    }
+
+    let properties = properties::read(&mut bytes)?;
+    Ok((auth, properties))
+}
+
+mod properties {
+    use super::*;
+
+    pub fn len(properties: &AuthProperties) -> usize {
+        let mut length = 0;
+
+        if let Some(method) = &properties.authentication_method {
+            length += 1 + 2 + method.len();
+        }
+
+        if let Some(data) = &properties.authentication_data {
+            length += 1 + 2 + data.len();
+        }
+
+        if let Some(reason) = &properties.reason_string {
+            length += 1 + 2 + reason.len();
+        }
+
+        for (key, value) in properties.user_properties.iter() {
+            length += 1 + 2 + key.len() + 2 + value.len();
+        }
+
+        length
+    }
+
+    pub fn write(properties: &AuthProperties, buffer: &mut BytesMut) -> Result<(), Error> {
+        let length = len(properties);
+        write_remaining_length(buffer, length)?;
+
+        if let Some(method) = &properties.authentication_method {
+            buffer.put_u8(PropertyType::AuthenticationMethod as u8);
+            write_mqtt_string(buffer, method);
+        }
+
+        if let Some(data) = &properties.authentication_data {
+            buffer.put_u8(PropertyType::AuthenticationData as u8);
+            write_mqtt_bytes(buffer, data);
+        }
+
+        if let Some(reason) = &properties.reason_string {
+            buffer.put_u8(PropertyType::ReasonString as u8);
+            write_mqtt_string(buffer, reason);
+        }
+
+        for (key, value) in properties.user_properties.iter() {
+            buffer.put_u8(PropertyType::UserProperty as u8);
+            write_mqtt_string(buffer, key);
+            write_mqtt_string(buffer, value);
+        }
+
+        Ok(())
+    }
+
+    pub fn read(bytes: &mut Bytes) -> Result<Option<AuthProperties>, Error> {
+        let (properties_len_len, properties_len) = length(bytes.iter())?;
+
+        bytes.advance(properties_len_len);
+
+        if properties_len == 0 {
+            return Ok(None);
+        }
+
+        let mut authentication_method = None;
+        let mut authentication_data = None;
+        let mut reason_string = None;
+        let mut user_properties = Vec::new();
+
+        let mut cursor = 0;
+        // read until cursor reaches property length. It will skip this loop if properties_len is 0.
+        while cursor < properties_len {
+            let prop = read_u8(bytes)?;
+            cursor += 1;
+
+            match property(prop)? {
+                PropertyType::AuthenticationMethod => {
+                    let method = read_mqtt_string(bytes)?;
+                    cursor += 2 + method.len();
+                    authentication_method = Some(method);
+                }
+                PropertyType::AuthenticationData => {
+                    let data = read_mqtt_bytes(bytes)?;
+                    cursor += 2 + data.len();
+                    authentication_data = Some(data);
+                }
+                PropertyType::ReasonString => {
+                    let reason = read_mqtt_string(bytes)?;
+                    cursor += 2 + reason.len();
+                    reason_string = Some(reason);
+                }
+                PropertyType::UserProperty => {
+                    let key = read_mqtt_string(bytes)?;
+                    let value = read_mqtt_string(bytes)?;
+                    cursor += 2 + key.len() + 2 + value.len();
+                    user_properties.push((key, value));
+                }
+                _ => return Err(Error::InvalidPacketType(prop)),
+            }
+        }
+
+        let properties = AuthProperties {
+            authentication_method,
+            authentication_data,
+            reason_string,
+            user_properties,
+        };
+
+        Ok(Some(properties))
+    }
+}
+
+fn code(reason: AuthReason) -> u8 {
+    match reason {
+        AuthReason::Success => 0x00,
+        AuthReason::ContinueAuthentication => 0x18,
+        AuthReason::ReAuthenticate => 0x19,
+    }
+}
+
+fn reason(code: u8) -> Result<AuthReason, Error> {
+    let v = match code {
+        0x00 => AuthReason::Success,
+        0x18 => AuthReason::ContinueAuthentication,
+        0x19 => AuthReason::ReAuthenticate,
+        other => return Err(Error::InvalidReason(other)),
+    };
+    Ok(v)
+}
+
+#[cfg(test)]
+mod test {
+    use bytes::BytesMut;
+
+    use super::*;
+
+    #[test]
+    fn auth1_parsing_works() {
+        let mut buffer = bytes::BytesMut::new();
+        let packet_bytes = [
+            0xF0, // Packet type
+            0x00, // Remaining length
+        ];
+        let expected = Auth {
+            reason: Some(AuthReason::Success),
+        };
+
+        buffer.extend_from_slice(&packet_bytes[..]);
+
+        let fixed_header = parse_fixed_header(buffer.iter()).unwrap();
+        let auth_bytes = buffer.split_to(fixed_header.frame_length()).freeze();
+        let (auth, properties) = read(fixed_header, auth_bytes).unwrap();
+
+        assert_eq!(auth, expected);
+        assert!(properties.is_none());
+    }
+
+    #[test]
+    fn auth1_encoding_works() {
+        let mut buffer = BytesMut::new();
+        let auth = Auth {
+            reason: Some(AuthReason::Success),
+        };
+        let expected = [
+            0xF0, // Packet type
+            0x00, // Remaining length
+        ];
+
+        write(&auth, &None, &mut buffer).unwrap();
+
+        assert_eq!(&buffer[..], &expected);
+    }
+
+    fn sample2() -> (Auth, Option<AuthProperties>) {
+        let properties = AuthProperties {
+            authentication_method: Some("SCRAM".to_owned()),
+            authentication_data: Some(Bytes::from("data")),
+            reason_string: Some("test".to_owned()),
+            user_properties: vec![("test".to_owned(), "test".to_owned())],
+        };
+
+        (
+            Auth {
+                reason: Some(AuthReason::ContinueAuthentication),
+            },
+            Some(properties),
+        )
+    }
+
+    fn sample_bytes2() -> Vec<u8> {
+        vec![
+            0xF0, // Packet type
+            0x25, // Remaining length
+            0x18, // Auth Reason Code
+            0x23, // Properties length
+            0x15, 0x00, 0x05, 0x53, 0x43, 0x52, 0x41, 0x4d, // Authentication method
+            0x16, 0x00, 0x04, 0x64, 0x61, 0x74, 0x61, // Authentication data
+            0x1F, 0x00, 0x04, 0x74, 0x65, 0x73, 0x74, // Reason string
+            0x26, 0x00, 0x04, 0x74, 0x65, 0x73, 0x74, 0x00, 0x04, 0x74, 0x65, 0x73,
+            0x74, // User properties
+        ]
+    }
+
+    #[test]
+    fn auth2_parsing_works() {
+        let mut buffer = bytes::BytesMut::new();
+        let packet_bytes = sample_bytes2();
+        let expected = sample2();
+
+        buffer.extend_from_slice(&packet_bytes[..]);
+
+        let fixed_header = parse_fixed_header(buffer.iter()).unwrap();
+        let auth_bytes = buffer.split_to(fixed_header.frame_length()).freeze();
+        let auth = read(fixed_header, auth_bytes).unwrap();
+
+        assert_eq!(auth, expected);
+    }
+
+    #[test]
+    fn auth2_encoding_works() {
+        let mut buffer = BytesMut::new();
+
+        let (auth, properties) = sample2();
+        let expected = sample_bytes2();
+
+        write(&auth, &properties, &mut buffer).unwrap();
+
+        assert_eq!(&buffer[..], &expected);
+    }
+}
@@ -16,8 +16,8 @@ use bytes::BytesMut;
 use tokio_util::codec;
 
 use super::{
-    check, connack, connect, disconnect, ping, puback, pubcomp, publish, pubrec, pubrel, suback,
-    subscribe, unsuback, unsubscribe, ConnectReadOutcome, MQTTPacket, PacketType,
+    auth, check, connack, connect, disconnect, ping, puback, pubcomp, publish, pubrec, pubrel,
+    suback, subscribe, unsuback, unsubscribe, ConnectReadOutcome, MQTTPacket, PacketType,
 };
 
 #[derive(Clone, Debug)]
@@ -84,6 +84,9 @@ impl codec::Encoder<MQTTPacket> for Mqtt5Codec {
             MQTTPacket::Disconnect(disconnect, disconnect_properties) => {
                 disconnect::write(&disconnect, &disconnect_properties, buffer)?
             }
+            MQTTPacket::Auth(auth, auth_properties) => {
+                auth::write(&auth, &auth_properties, buffer)?
+            }
         };
         Ok(())
     }
@@ -166,6 +169,10 @@ impl codec::Decoder for Mqtt5Codec {
                 let (disconnect, disconnect_properties) = disconnect::read(fixed_header, packet)?;
                 MQTTPacket::Disconnect(disconnect, disconnect_properties)
             }
+            PacketType::Auth => {
+                let (auth, auth_properties) = auth::read(fixed_header, packet)?;
+                MQTTPacket::Auth(auth, auth_properties)
+            }
         };
         Ok(Some(packet))
     }
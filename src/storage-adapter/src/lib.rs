@@ -19,6 +19,7 @@ pub mod local_rocksdb;
 pub mod memory;
 pub mod mysql;
 pub mod placement;
+pub mod postgres;
 pub mod storage;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
@@ -26,8 +27,10 @@ pub enum StorageType {
     Journal,
     Memory,
     Mysql,
+    Postgres,
     Placement,
     RocksDB,
+    Redis,
 }
 
 impl FromStr for StorageType {
@@ -38,8 +41,10 @@ impl FromStr for StorageType {
             "journal" => Ok(StorageType::Journal),
             "memory" => Ok(StorageType::Memory),
             "mysql" => Ok(StorageType::Mysql),
+            "postgres" => Ok(StorageType::Postgres),
             "placement" => Ok(StorageType::Placement),
             "rocksdb" => Ok(StorageType::RocksDB),
+            "redis" => Ok(StorageType::Redis),
             _ => Err(()),
         }
     }